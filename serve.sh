@@ -1,4 +1,6 @@
 #!/bin/sh -e
 # https://github.com/erer1243/wgpu-0.20-winit-0.30-web-example/blob/master/web.sh
 wasm-pack build --no-typescript --no-pack --target=web --dev
+# Lists res/ so Resources::list and Resources::glob work on the web.
+python3 -c 'import json, pathlib; res = pathlib.Path("res"); files = sorted(p.relative_to(res).as_posix() for p in res.rglob("*") if p.is_file() and p.name != "index.json"); (res / "index.json").write_text(json.dumps(files, indent=1))'
 python3 -m http.server
\ No newline at end of file
@@ -0,0 +1,157 @@
+use glam::Vec2;
+
+use crate::shape::Bounds;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    Left,
+    /// Horizontal centers.
+    Center,
+    Right,
+    Top,
+    /// Vertical centers.
+    Middle,
+    Bottom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+impl Axis {
+    fn index(&self) -> usize {
+        match self {
+            Self::Horizontal => 0,
+            Self::Vertical => 1,
+        }
+    }
+}
+
+/// Returns how far each of `bounds` needs to move to line up with the
+/// matching edge or center of all of them combined.
+pub fn align(bounds: &[Bounds], alignment: Alignment) -> Vec<Vec2> {
+    let Some(all) = bounds.iter().copied().reduce(|a, b| a.union(&b)) else {
+        return Vec::new();
+    };
+    bounds
+        .iter()
+        .map(|b| match alignment {
+            Alignment::Left => Vec2::new(all.min.x - b.min.x, 0.0),
+            Alignment::Center => Vec2::new(all.center().x - b.center().x, 0.0),
+            Alignment::Right => Vec2::new(all.max.x - b.max.x, 0.0),
+            Alignment::Top => Vec2::new(0.0, all.min.y - b.min.y),
+            Alignment::Middle => Vec2::new(0.0, all.center().y - b.center().y),
+            Alignment::Bottom => Vec2::new(0.0, all.max.y - b.max.y),
+        })
+        .collect()
+}
+
+/// Returns how far each of `bounds` needs to move so the gaps between
+/// neighbours along `axis` are all the same. The first and last ones, by
+/// center, stay where they are.
+pub fn distribute(bounds: &[Bounds], axis: Axis) -> Vec<Vec2> {
+    let mut offsets = vec![Vec2::ZERO; bounds.len()];
+    let n = bounds.len();
+    if n < 3 {
+        return offsets;
+    }
+
+    let a = axis.index();
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| bounds[i].center()[a].total_cmp(&bounds[j].center()[a]));
+    let first = bounds[order[0]];
+    let last = bounds[order[n - 1]];
+    let total: f32 = bounds.iter().map(|b| b.size()[a]).sum();
+    let gap = (last.max[a] - first.min[a] - total) / (n - 1) as f32;
+
+    let mut position = first.min[a];
+    for i in order {
+        offsets[i][a] = position - bounds[i].min[a];
+        position += bounds[i].size()[a] + gap;
+    }
+    offsets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(x: f32, y: f32, w: f32, h: f32) -> Bounds {
+        Bounds::new(Vec2::new(x, y), Vec2::new(x + w, y + h))
+    }
+
+    #[test]
+    fn nothing_to_align_or_distribute() {
+        assert!(align(&[], Alignment::Left).is_empty());
+        assert!(distribute(&[], Axis::Horizontal).is_empty());
+    }
+
+    #[test]
+    fn one_item_stays_put() {
+        let one = [bounds(3.0, 4.0, 2.0, 2.0)];
+        for alignment in [
+            Alignment::Left,
+            Alignment::Center,
+            Alignment::Right,
+            Alignment::Top,
+            Alignment::Middle,
+            Alignment::Bottom,
+        ] {
+            assert_eq!(align(&one, alignment), [Vec2::ZERO]);
+        }
+        assert_eq!(distribute(&one, Axis::Vertical), [Vec2::ZERO]);
+    }
+
+    #[test]
+    fn aligns_to_the_combined_bounds() {
+        let items = [bounds(0.0, 0.0, 2.0, 2.0), bounds(4.0, 6.0, 4.0, 4.0)];
+        assert_eq!(
+            align(&items, Alignment::Left),
+            [Vec2::ZERO, Vec2::new(-4.0, 0.0)]
+        );
+        assert_eq!(
+            align(&items, Alignment::Center),
+            [Vec2::new(3.0, 0.0), Vec2::new(-2.0, 0.0)]
+        );
+        assert_eq!(
+            align(&items, Alignment::Right),
+            [Vec2::new(6.0, 0.0), Vec2::ZERO]
+        );
+        assert_eq!(
+            align(&items, Alignment::Top),
+            [Vec2::ZERO, Vec2::new(0.0, -6.0)]
+        );
+        assert_eq!(
+            align(&items, Alignment::Middle),
+            [Vec2::new(0.0, 4.0), Vec2::new(0.0, -3.0)]
+        );
+        assert_eq!(
+            align(&items, Alignment::Bottom),
+            [Vec2::new(0.0, 8.0), Vec2::ZERO]
+        );
+    }
+
+    #[test]
+    fn two_items_are_not_distributed() {
+        let items = [bounds(0.0, 0.0, 1.0, 1.0), bounds(5.0, 0.0, 1.0, 1.0)];
+        assert_eq!(distribute(&items, Axis::Horizontal), [Vec2::ZERO; 2]);
+    }
+
+    #[test]
+    fn evens_out_the_gaps_between_the_outer_items() {
+        // Out of order, and with different widths. The gaps should end up
+        // (12 - 1 - 3 - 2) / 2 = 3 wide.
+        let items = [
+            bounds(10.0, 0.0, 2.0, 1.0),
+            bounds(1.0, 5.0, 3.0, 1.0),
+            bounds(0.0, 0.0, 1.0, 1.0),
+        ];
+        assert_eq!(
+            distribute(&items, Axis::Horizontal),
+            [Vec2::ZERO, Vec2::new(3.0, 0.0), Vec2::ZERO]
+        );
+        assert_eq!(distribute(&items, Axis::Vertical)[1].x, 0.0);
+    }
+}
@@ -0,0 +1,392 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::Window,
+};
+
+use crate::{
+    clock::FrameClock,
+    gpu::Gpu,
+    input::InputState,
+    renderer::Renderer,
+    resources::{
+        camera::{CameraBinding, OrthoCamera},
+        msaa::MultisampleBuffer,
+        Resources,
+    },
+    window::WindowConfig,
+};
+
+/// Something built on the crate's renderer without the drawing app around
+/// it, run with [run_app]. Only [App::render] has to be written.
+pub trait App: 'static {
+    /// Called once the GPU is ready, before anything else.
+    fn init(&mut self, renderer: &mut Renderer, gpu: &Gpu) -> anyhow::Result<()> {
+        let _ = (renderer, gpu);
+        Ok(())
+    }
+
+    /// Called for every window event after `input` has seen it. Returning
+    /// false exits.
+    fn event(&mut self, event: &WindowEvent, input: &InputState) -> bool {
+        let _ = (event, input);
+        true
+    }
+
+    /// Called once a frame before it's drawn. Returning false exits.
+    fn update(&mut self, dt: Duration, input: &InputState) -> bool {
+        let _ = (dt, input);
+        true
+    }
+
+    fn render(&mut self, frame: &mut Frame<'_>);
+
+    /// Called when the app is suspended, after which nothing is updated or
+    /// drawn until [App::resume]. On mobile this can be the last chance to
+    /// save anything, as the app may be closed without being resumed.
+    fn suspend(&mut self) {}
+
+    fn resume(&mut self) {}
+}
+
+/// What an [App] draws a frame with. The pass has already been cleared.
+pub struct Frame<'a> {
+    pub renderer: &'a Renderer,
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    pub pass: wgpu::RenderPass<'a>,
+    /// A camera in logical window pixels, with the origin in the top left.
+    pub screen: &'a CameraBinding,
+    /// The window's size in logical pixels.
+    pub size: glam::Vec2,
+}
+
+/// Everything set up once the window and GPU are ready.
+struct Context {
+    window: Arc<Window>,
+    gpu: Gpu,
+    renderer: Renderer,
+    msaa: Option<MultisampleBuffer>,
+    screen: CameraBinding,
+    /// Only held to keep watching the `<canvas>` for resizes.
+    #[cfg(target_arch = "wasm32")]
+    #[allow(unused)]
+    canvas_observer: Option<crate::window::CanvasObserver>,
+}
+
+impl Context {
+    async fn new(window: Arc<Window>, window_config: &WindowConfig) -> anyhow::Result<Self> {
+        let gpu = Gpu::new(window.clone(), window_config).await?;
+        let format = gpu.format;
+        let renderer = Renderer::load_async(
+            &Resources::app(),
+            format,
+            gpu.sample_count,
+            &gpu.device,
+            &gpu.queue,
+        )
+        .await?;
+        let msaa = (gpu.sample_count > 1).then(|| {
+            MultisampleBuffer::new(
+                gpu.config.width,
+                gpu.config.height,
+                format,
+                gpu.sample_count,
+                &gpu.device,
+            )
+        });
+        let screen = renderer.bind_camera(&Self::screen_camera(&window), &gpu.device);
+        Ok(Self {
+            #[cfg(target_arch = "wasm32")]
+            canvas_observer: crate::window::CanvasObserver::new(window.clone()),
+            window,
+            gpu,
+            renderer,
+            msaa,
+            screen,
+        })
+    }
+
+    fn screen_camera(window: &Window) -> OrthoCamera {
+        let size = window.inner_size().to_logical::<f32>(window.scale_factor());
+        OrthoCamera::new(0.0, size.width, size.height, 0.0)
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.gpu.resize(width, height);
+        if let Some(msaa) = &mut self.msaa {
+            msaa.resize(width, height, &self.gpu.device);
+        }
+        self.screen
+            .update(&Self::screen_camera(&self.window), &self.gpu.queue);
+    }
+
+    /// Draws a frame with `app`. Fails if the frame can't be drawn at all.
+    fn render(&mut self, app: &mut impl App) -> anyhow::Result<()> {
+        // CSS can resize the canvas without winit noticing.
+        #[cfg(target_arch = "wasm32")]
+        if let Some((size, _)) = crate::window::canvas_size(&self.window) {
+            if size.width > 0
+                && size.height > 0
+                && (size.width, size.height) != (self.gpu.config.width, self.gpu.config.height)
+            {
+                self.resize(size.width, size.height);
+            }
+        }
+        let Some(surface) = &self.gpu.surface else {
+            return Ok(());
+        };
+        let Some(frame) = crate::gpu::next_frame(surface, &self.gpu.device, &self.gpu.config)?
+        else {
+            return Ok(());
+        };
+        let view = self.gpu.view(&frame);
+        let mut encoder = self.gpu.device.create_command_encoder(&Default::default());
+        {
+            let load = wgpu::LoadOp::Clear(self.gpu.clear_color);
+            let color = match &self.msaa {
+                Some(msaa) => msaa.attachment(&view, load),
+                None => wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                },
+            };
+            let pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[Some(color)],
+                ..Default::default()
+            });
+            let size = self
+                .window
+                .inner_size()
+                .to_logical::<f32>(self.window.scale_factor());
+            app.render(&mut Frame {
+                renderer: &self.renderer,
+                device: &self.gpu.device,
+                queue: &self.gpu.queue,
+                pass,
+                screen: &self.screen,
+                size: glam::vec2(size.width, size.height),
+            });
+        }
+        self.gpu.queue.submit([encoder.finish()]);
+        frame.present();
+        Ok(())
+    }
+}
+
+/// Creates the window and what draws into it the first time an app is
+/// resumed. Shared by [run_app] and the drawing app, so both fail the same
+/// way: by logging why and exiting.
+pub(crate) struct Launcher<T: 'static> {
+    pub(crate) window_config: WindowConfig,
+    /// Sends what was made back to the event loop, as it's made
+    /// asynchronously on the web.
+    #[cfg(target_arch = "wasm32")]
+    proxy: Option<winit::event_loop::EventLoopProxy<anyhow::Result<T>>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    made: std::marker::PhantomData<T>,
+}
+
+impl<T: 'static> Launcher<T> {
+    pub(crate) fn new(
+        window_config: WindowConfig,
+        #[cfg(target_arch = "wasm32")] event_loop: &EventLoop<anyhow::Result<T>>,
+    ) -> Self {
+        Self {
+            window_config,
+            #[cfg(target_arch = "wasm32")]
+            proxy: Some(event_loop.create_proxy()),
+            #[cfg(not(target_arch = "wasm32"))]
+            made: std::marker::PhantomData,
+        }
+    }
+
+    /// Creates the window and makes what draws into it with `new`. On
+    /// native that's returned once it's ready. On the web it's sent to the
+    /// event loop as a user event, to be passed to [Launcher::started].
+    pub(crate) fn launch<F, Fut>(&mut self, event_loop: &ActiveEventLoop, new: F) -> Option<T>
+    where
+        F: FnOnce(Arc<Window>, WindowConfig) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>> + 'static,
+    {
+        let attributes = match self.window_config.attributes() {
+            Ok(attributes) => attributes,
+            Err(e) => {
+                log::error!("{e}");
+                event_loop.exit();
+                return None;
+            }
+        };
+        let window = match event_loop.create_window(attributes) {
+            Ok(window) => Arc::new(window),
+            Err(e) => {
+                log::error!("Unable to create the window: {e}");
+                event_loop.exit();
+                return None;
+            }
+        };
+        let made = new(window, self.window_config.clone());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        return Self::started(event_loop, pollster::block_on(made));
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            if let Some(proxy) = self.proxy.take() {
+                crate::utils::spawn(async move {
+                    if proxy.send_event(made.await).is_err() {
+                        log::error!("The event loop closed before the app was ready");
+                    }
+                });
+            }
+            None
+        }
+    }
+
+    /// What [Launcher::launch] made, or nothing if it couldn't be made, in
+    /// which case the event loop exits.
+    pub(crate) fn started(event_loop: &ActiveEventLoop, made: anyhow::Result<T>) -> Option<T> {
+        match made {
+            Ok(made) => Some(made),
+            Err(e) => {
+                log::error!("Unable to set up the GPU: {e}");
+                event_loop.exit();
+                None
+            }
+        }
+    }
+}
+
+/// Drives an [App] from winit's event loop.
+struct Runner<A> {
+    app: A,
+    launcher: Launcher<Context>,
+    context: Option<Context>,
+    input: InputState,
+    clock: FrameClock,
+}
+
+impl<A: App> Runner<A> {
+    /// Hands the context to the app once it's ready.
+    fn start(&mut self, event_loop: &ActiveEventLoop, mut context: Context) {
+        if let Err(e) = self.app.init(&mut context.renderer, &context.gpu) {
+            log::error!("Unable to start: {e}");
+            event_loop.exit();
+            return;
+        }
+        context.window.request_redraw();
+        self.context = Some(context);
+    }
+}
+
+impl<A: App> ApplicationHandler<anyhow::Result<Context>> for Runner<A> {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(context) = &mut self.context {
+            if let Err(e) = context.gpu.resume(context.window.clone()) {
+                log::error!("Unable to recreate the surface: {e}");
+                event_loop.exit();
+                return;
+            }
+            self.clock.pause();
+            self.app.resume();
+            context.window.request_redraw();
+            return;
+        }
+        if let Some(context) = self
+            .launcher
+            .launch(event_loop, |window, window_config| async move {
+                Context::new(window, &window_config).await
+            })
+        {
+            self.start(event_loop, context);
+        }
+    }
+
+    fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(context) = &mut self.context {
+            context.gpu.suspend();
+            self.app.suspend();
+        }
+    }
+
+    #[allow(unused_mut)]
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, context: anyhow::Result<Context>) {
+        let Some(mut context) = Launcher::started(event_loop, context) else {
+            return;
+        };
+        // The canvas only has a size once it's on the page.
+        #[cfg(target_arch = "wasm32")]
+        {
+            let size = context.window.inner_size();
+            context.resize(size.width, size.height);
+        }
+        self.start(event_loop, context);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        _window_id: winit::window::WindowId,
+        event: WindowEvent,
+    ) {
+        let Some(context) = &mut self.context else {
+            return;
+        };
+        self.input
+            .handle_event(&event, context.window.scale_factor() as f32);
+        match &event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(size) => context.resize(size.width, size.height),
+            // Nothing's updated while suspended.
+            WindowEvent::RedrawRequested if context.gpu.surface.is_none() => return,
+            WindowEvent::RedrawRequested => {
+                self.clock.tick();
+                self.input.begin_frame();
+                if !self.app.update(self.clock.dt(), &self.input) {
+                    event_loop.exit();
+                    return;
+                }
+                if let Err(e) = context.render(&mut self.app) {
+                    log::error!("{e}");
+                    event_loop.exit();
+                    return;
+                }
+                self.input.end_frame();
+                context.window.request_redraw();
+                return;
+            }
+            _ => {}
+        }
+        if !self.app.event(&event, &self.input) {
+            event_loop.exit();
+        }
+    }
+}
+
+/// Runs `app` in a window created with `window_config` until it exits or
+/// the window is closed.
+pub fn run_app(app: impl App, window_config: WindowConfig) -> anyhow::Result<()> {
+    crate::init_logging();
+
+    let event_loop = EventLoop::with_user_event().build()?;
+    let mut runner = Runner {
+        app,
+        launcher: Launcher::new(
+            window_config,
+            #[cfg(target_arch = "wasm32")]
+            &event_loop,
+        ),
+        context: None,
+        input: InputState::new(),
+        clock: FrameClock::new(),
+    };
+    event_loop.run_app(&mut runner)?;
+    Ok(())
+}
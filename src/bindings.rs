@@ -0,0 +1,394 @@
+use std::{collections::HashMap, fmt};
+
+use serde::{Deserialize, Serialize};
+use winit::keyboard::KeyCode;
+
+use crate::{constraints::ConstraintKind, document::Format, tools::ToolKind};
+
+/// Something the user can do with a shortcut.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Exit,
+    Help,
+    CycleDrawMode,
+    Undo,
+    Redo,
+    /// Saves to [crate::DOCUMENT_PATH]. Does nothing on the web.
+    Save,
+    /// Opens [crate::DOCUMENT_PATH]. Does nothing on the web.
+    Open,
+    /// Does nothing on the web.
+    ExportSvg,
+    ExportPng,
+    Copy,
+    Cut,
+    Paste,
+    Delete,
+    Tool(ToolKind),
+    ToggleSelectMode,
+    Group,
+    Ungroup,
+    ToggleGrid,
+    ZoomToFit,
+    ToggleDetailView,
+    ToggleMinimap,
+    ToggleFollow,
+    RotateViewLeft,
+    RotateViewRight,
+    ResetViewRotation,
+    Constrain(ConstraintKind),
+    ShowStateDiagram,
+    ShowStateDebug,
+    /// Frame rate, frame times and draw counts.
+    TogglePerfOverlay,
+    /// Switches between redrawing every frame and only when something
+    /// changes.
+    ToggleRedrawPolicy,
+    /// Steps through VSync and the lower latency present modes.
+    CyclePresentMode,
+    /// Types text into the drawing at the cursor.
+    AddText,
+    /// Makes every finger draw with the brush instead of two panning and
+    /// zooming.
+    ToggleTouchDrawing,
+    /// Starts recording input, or stops and saves it to
+    /// [crate::INPUT_RECORDING_PATH]. Saves nothing on the web.
+    ToggleInputRecording,
+    /// Plays back [crate::INPUT_RECORDING_PATH]. Does nothing on the web.
+    ReplayInput,
+    /// Starts recording frames to [crate::GIF_RECORDING_PATH], or stops.
+    /// Does nothing on the web.
+    ToggleFrameRecording,
+    /// Shows the egui debug panel. Needs the `debug-ui` feature.
+    ToggleDebugUi,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exit => write!(f, "Exit"),
+            Self::Help => write!(f, "Help"),
+            Self::CycleDrawMode => write!(f, "Draw mode"),
+            Self::Undo => write!(f, "Undo"),
+            Self::Redo => write!(f, "Redo"),
+            Self::Save => write!(f, "Save"),
+            Self::Open => write!(f, "Open"),
+            Self::ExportSvg => write!(f, "Export SVG"),
+            Self::ExportPng => write!(f, "Export PNG"),
+            Self::Copy => write!(f, "Copy"),
+            Self::Cut => write!(f, "Cut"),
+            Self::Paste => write!(f, "Paste"),
+            Self::Delete => write!(f, "Delete"),
+            Self::Tool(tool) => write!(f, "{tool:?}"),
+            Self::ToggleSelectMode => write!(f, "Select mode"),
+            Self::Group => write!(f, "Group"),
+            Self::Ungroup => write!(f, "Ungroup"),
+            Self::ToggleGrid => write!(f, "Grid"),
+            Self::ZoomToFit => write!(f, "Zoom to fit"),
+            Self::ToggleDetailView => write!(f, "Detail view"),
+            Self::ToggleMinimap => write!(f, "Minimap"),
+            Self::ToggleFollow => write!(f, "Follow selection"),
+            Self::RotateViewLeft => write!(f, "Rotate left"),
+            Self::RotateViewRight => write!(f, "Rotate right"),
+            Self::ResetViewRotation => write!(f, "Reset rotation"),
+            Self::Constrain(ConstraintKind::Parallel) => write!(f, "Parallel"),
+            Self::Constrain(ConstraintKind::Perpendicular) => write!(f, "Perpendicular"),
+            Self::Constrain(ConstraintKind::EqualLength) => write!(f, "Equal length"),
+            Self::ShowStateDiagram => write!(f, "Tool state diagram"),
+            Self::ShowStateDebug => write!(f, "Tool state debug info"),
+            Self::TogglePerfOverlay => write!(f, "Performance overlay"),
+            Self::ToggleRedrawPolicy => write!(f, "Continuous redraw"),
+            Self::CyclePresentMode => write!(f, "Present mode"),
+            Self::AddText => write!(f, "Add text"),
+            Self::ToggleTouchDrawing => write!(f, "Multi-touch drawing"),
+            Self::ToggleInputRecording => write!(f, "Record input"),
+            Self::ReplayInput => write!(f, "Replay input"),
+            Self::ToggleFrameRecording => write!(f, "Record GIF"),
+            Self::ToggleDebugUi => write!(f, "Debug panel"),
+        }
+    }
+}
+
+/// A key along with the modifiers that have to be held for it. Modifiers
+/// have to match exactly, so Ctrl+Z and Ctrl+Shift+Z can do different
+/// things.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Chord {
+    pub key: KeyCode,
+    /// Ctrl, or Cmd on macOS.
+    #[serde(default)]
+    pub command: bool,
+    #[serde(default)]
+    pub shift: bool,
+    #[serde(default)]
+    pub alt: bool,
+}
+
+impl Chord {
+    pub fn new(key: KeyCode) -> Self {
+        Self {
+            key,
+            command: false,
+            shift: false,
+            alt: false,
+        }
+    }
+
+    pub fn command(mut self) -> Self {
+        self.command = true;
+        self
+    }
+
+    pub fn shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+
+    pub fn alt(mut self) -> Self {
+        self.alt = true;
+        self
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.command {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        match self.key {
+            KeyCode::BracketLeft => write!(f, "["),
+            KeyCode::BracketRight => write!(f, "]"),
+            KeyCode::Backslash => write!(f, "\\"),
+            KeyCode::Equal => write!(f, "="),
+            KeyCode::Minus => write!(f, "-"),
+            key => {
+                let name = format!("{key:?}");
+                let name = name
+                    .strip_prefix("Key")
+                    .or_else(|| name.strip_prefix("Digit"))
+                    .unwrap_or(&name);
+                write!(f, "{name}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Binding {
+    pub chord: Chord,
+    pub action: Action,
+}
+
+/// A chord that was about to be bound to a second action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conflict {
+    pub chord: Chord,
+    pub existing: Action,
+    pub action: Action,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is bound to both \"{}\" and \"{}\"",
+            self.chord, self.existing, self.action
+        )
+    }
+}
+
+impl std::error::Error for Conflict {}
+
+/// Maps chords to the actions they trigger. A config file replaces the
+/// defaults entirely, so it should list every shortcut that's wanted.
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    /// In the order they were bound, for listing them.
+    bindings: Vec<Binding>,
+    lookup: HashMap<Chord, Action>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use KeyCode::*;
+        let key = Chord::new;
+        Self::new([
+            (key(Escape), Action::Exit),
+            (key(F1), Action::Help),
+            (key(Space), Action::CycleDrawMode),
+            (key(KeyZ).command(), Action::Undo),
+            (key(KeyZ).command().shift(), Action::Redo),
+            (key(KeyS).command(), Action::Save),
+            (key(KeyO).command(), Action::Open),
+            (key(KeyE).command(), Action::ExportSvg),
+            (key(KeyE).command().shift(), Action::ExportPng),
+            (key(KeyC).command(), Action::Copy),
+            (key(KeyX).command(), Action::Cut),
+            (key(KeyV).command(), Action::Paste),
+            (key(Delete), Action::Delete),
+            (key(Backspace), Action::Delete),
+            (key(KeyB), Action::Tool(ToolKind::Brush)),
+            (key(KeyL), Action::Tool(ToolKind::Line)),
+            (key(KeyD), Action::Tool(ToolKind::Measure)),
+            (key(KeyI), Action::Tool(ToolKind::Eyedropper)),
+            (key(KeyV), Action::Tool(ToolKind::Select)),
+            (key(KeyM), Action::ToggleSelectMode),
+            (key(KeyG).command(), Action::Group),
+            (key(KeyG).command().shift(), Action::Ungroup),
+            (key(KeyG), Action::ToggleGrid),
+            (key(KeyF), Action::ZoomToFit),
+            (key(F2), Action::ToggleDetailView),
+            (key(KeyN), Action::ToggleMinimap),
+            (key(KeyT), Action::ToggleFollow),
+            (key(BracketLeft), Action::RotateViewLeft),
+            (key(BracketRight), Action::RotateViewRight),
+            (key(Backslash), Action::ResetViewRotation),
+            (key(KeyP), Action::Constrain(ConstraintKind::Parallel)),
+            (
+                key(KeyP).shift(),
+                Action::Constrain(ConstraintKind::Perpendicular),
+            ),
+            (key(Equal), Action::Constrain(ConstraintKind::EqualLength)),
+            (key(F3), Action::ShowStateDiagram),
+            (key(F4), Action::ShowStateDebug),
+            (key(F6), Action::CyclePresentMode),
+            (key(F7), Action::ToggleRedrawPolicy),
+            (key(F8), Action::TogglePerfOverlay),
+            (key(KeyT).shift(), Action::AddText),
+            (key(KeyW), Action::ToggleTouchDrawing),
+            (key(F9), Action::ToggleInputRecording),
+            (key(F10), Action::ReplayInput),
+            (key(F9).shift(), Action::ToggleFrameRecording),
+            (key(F12), Action::ToggleDebugUi),
+        ])
+    }
+}
+
+impl KeyBindings {
+    /// Binds each chord to its action. If a chord comes up more than once,
+    /// the last one wins.
+    pub fn new(bindings: impl IntoIterator<Item = (Chord, Action)>) -> Self {
+        let mut this = Self {
+            bindings: Vec::new(),
+            lookup: HashMap::new(),
+        };
+        for (chord, action) in bindings {
+            this.bind(chord, action);
+        }
+        this
+    }
+
+    /// Makes `chord` trigger `action`, replacing what it did before.
+    /// Returns the action it used to trigger.
+    pub fn bind(&mut self, chord: Chord, action: Action) -> Option<Action> {
+        let replaced = self.unbind(chord);
+        self.bindings.push(Binding { chord, action });
+        self.lookup.insert(chord, action);
+        replaced
+    }
+
+    /// Makes `chord` trigger `action` unless it already triggers something
+    /// else.
+    pub fn try_bind(&mut self, chord: Chord, action: Action) -> Result<(), Conflict> {
+        match self.action(chord) {
+            Some(existing) if existing != action => Err(Conflict {
+                chord,
+                existing,
+                action,
+            }),
+            Some(_) => Ok(()),
+            None => {
+                self.bind(chord, action);
+                Ok(())
+            }
+        }
+    }
+
+    /// Chords in `bindings` that are bound to more than one action. Each
+    /// one is reported against the first action it was bound to.
+    pub fn conflicts(bindings: &[Binding]) -> Vec<Conflict> {
+        let mut seen = HashMap::new();
+        let mut conflicts = Vec::new();
+        for binding in bindings {
+            match seen.get(&binding.chord) {
+                Some(&existing) if existing != binding.action => conflicts.push(Conflict {
+                    chord: binding.chord,
+                    existing,
+                    action: binding.action,
+                }),
+                Some(_) => {}
+                None => {
+                    seen.insert(binding.chord, binding.action);
+                }
+            }
+        }
+        conflicts
+    }
+
+    pub fn unbind(&mut self, chord: Chord) -> Option<Action> {
+        self.bindings.retain(|binding| binding.chord != chord);
+        self.lookup.remove(&chord)
+    }
+
+    pub fn action(&self, chord: Chord) -> Option<Action> {
+        self.lookup.get(&chord).copied()
+    }
+
+    /// Every chord bound to `action`.
+    pub fn chords(&self, action: Action) -> impl Iterator<Item = Chord> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |binding| binding.action == action)
+            .map(|binding| binding.chord)
+    }
+
+    pub fn bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    pub fn serialize(&self, format: Format) -> anyhow::Result<String> {
+        Ok(match format {
+            Format::Json => serde_json::to_string_pretty(&self.bindings)?,
+            Format::Ron => {
+                ron::ser::to_string_pretty(&self.bindings, ron::ser::PrettyConfig::default())?
+            }
+        })
+    }
+
+    /// Fails if a chord is bound to more than one action, since only one
+    /// of them could ever happen.
+    pub fn parse(data: &str, format: Format) -> anyhow::Result<Self> {
+        let bindings: Vec<Binding> = match format {
+            Format::Json => serde_json::from_str(data)?,
+            Format::Ron => ron::from_str(data)?,
+        };
+        let conflicts = Self::conflicts(&bindings);
+        if !conflicts.is_empty() {
+            let conflicts: Vec<_> = conflicts.iter().map(ToString::to_string).collect();
+            anyhow::bail!("Conflicting shortcuts: {}", conflicts.join("; "));
+        }
+        Ok(Self::new(
+            bindings
+                .into_iter()
+                .map(|binding| (binding.chord, binding.action)),
+        ))
+    }
+
+    /// Loads the bindings in `path`, or the defaults if there's no such
+    /// file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = std::fs::read_to_string(path)?;
+        Self::parse(&data, Format::from_path(path))
+    }
+}
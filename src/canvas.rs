@@ -0,0 +1,3455 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::align::{Alignment, Axis};
+use crate::app::Launcher;
+use crate::bindings::KeyBindings;
+use crate::capabilities::GpuCapabilities;
+use crate::clipboard::{Clipboard, Paste};
+use crate::clock::{FixedTimestep, FrameClock};
+use crate::constraints::{Constraint, ConstraintKind};
+use crate::diagram::StateDiagram;
+use crate::dimension::{Anchor, Dimension, DimensionRenderer};
+use crate::document::{Document, NodeData};
+use crate::export::{FrameCaptures, PendingImage};
+use crate::follow::{CameraFollow, FollowTarget};
+use crate::gpu::{self, fallback_present_mode, Gpu};
+use crate::graph::{GraphPass, GraphSurface, PassOutput, RenderGraph};
+use crate::grid::{Grid, GridSettings};
+use crate::history::{
+    AddConstraint, AddDimension, AddLayer, AddNode, Command, CommandContext, Compound, Group,
+    History, MoveLayer, RemoveLayer, RemoveNode, SetColor, SetFill, SetSdfOp, SetShapeStyle,
+    SetStyle, SetText, SetTransform, Ungroup,
+};
+use crate::input::{ClickCounter, InputState, PointerId};
+use crate::layers::LayerStack;
+use crate::machine::{MachineDefinition, StateMachine};
+use crate::minimap::Minimap;
+use crate::overlay::Overlay;
+use crate::perf::PerfOverlay;
+use crate::plugins::{PluginContext, PluginRegistry, RenderPlugin};
+use crate::post::{self, PostEffect, PostProcessor, Tonemap};
+use crate::renderer::Renderer;
+use crate::replay::{InputRecorder, InputRecording, InputReplayer, RecordedEvent};
+use crate::resources::{
+    cache::AssetCache,
+    camera::{CameraAnimator, CameraArray, CameraBinding, CameraTarget, OrthoCamera},
+    depth::DepthBuffer,
+    font::TextBlock,
+    geometry::DrawMode,
+    msaa::MultisampleBuffer,
+    target::RenderTarget,
+    texture::{Texture, TextureId, TextureRegistry},
+    Resources,
+};
+use crate::scene::{NodeId, Transform2D};
+use crate::shape::{Bounds, Shape};
+use crate::snapping::{snap_angle, Snapper};
+use crate::states::{DrawingState, StateStack};
+use crate::stats::RenderStats;
+use crate::style::{Style, StyleId, StyleSheet};
+use crate::tools::{
+    brush::BrushTool,
+    drag::{Drag, DragPoint, DragTracker},
+    eyedropper::{ColorTarget, EyedropperTool},
+    interaction_machine,
+    line::LineTool,
+    select::{SelectMode, SelectTool},
+    Interaction, InteractionEvent, ToolKind, INTERACTION_MACHINE_PATH,
+};
+use crate::touch::{TouchGesture, TouchTracker};
+use crate::viewport::{ViewRect, Viewport, ViewportFrame};
+use crate::window::WindowConfig;
+use anyhow::Context;
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    keyboard::ModifiersState,
+    window::Window,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::export::FrameCapture;
+#[cfg(target_arch = "wasm32")]
+use crate::{export, utils};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+/// How close to a stroke (in pixels) the cursor needs to be to select it.
+pub const PICK_TOLERANCE: f32 = 4.0;
+/// How close, in pixels, a dimension's end needs to be to a shape's point
+/// to attach to it.
+pub const ANCHOR_TOLERANCE: f32 = 2.0;
+/// How much closer the detail view is than the main view.
+const DETAIL_ZOOM: f32 = 4.0;
+/// How far a view drag turns the canvas per pixel the mouse moves sideways,
+/// in radians.
+const VIEW_DRAG_ROTATION: f32 = 0.005;
+/// How far in logical pixels the wheel has to scroll to double the zoom.
+const WHEEL_ZOOM_PIXELS: f32 = 200.0;
+/// How many viewport cameras share a buffer before each needs its own.
+const VIEW_CAMERA_CAPACITY: u32 = 16;
+/// How far the bracket keys turn the canvas, in radians.
+pub(crate) const VIEW_ROTATION_STEP: f32 = std::f32::consts::PI / 12.0;
+/// Space in pixels left around the drawing by [Canvas::zoom_to_fit].
+const FIT_MARGIN: f32 = 20.0;
+/// Length in pixels of the dashes in the line tool's preview.
+const LINE_PREVIEW_DASH: f32 = 6.0;
+/// Where Ctrl+S saves and Ctrl+O loads the drawing from.
+#[cfg(not(target_arch = "wasm32"))]
+pub const DOCUMENT_PATH: &str = "drawing.ron";
+/// Where Ctrl+E exports the drawing to.
+#[cfg(not(target_arch = "wasm32"))]
+pub const SVG_EXPORT_PATH: &str = "drawing.svg";
+/// Where Ctrl+Shift+E exports a PNG of the drawing to. On the web this is
+/// the name of the downloaded file.
+pub const PNG_EXPORT_PATH: &str = "drawing.png";
+/// Key bindings that replace the defaults, if the file exists.
+#[cfg(not(target_arch = "wasm32"))]
+pub const BINDINGS_PATH: &str = "bindings.ron";
+/// Where input is recorded to and replayed from.
+#[cfg(not(target_arch = "wasm32"))]
+pub const INPUT_RECORDING_PATH: &str = "input.ron";
+/// Where Shift+F9 records a GIF of the window to.
+#[cfg(not(target_arch = "wasm32"))]
+pub const GIF_RECORDING_PATH: &str = "recording.gif";
+/// How many frames Shift+F9 records.
+#[cfg(not(target_arch = "wasm32"))]
+pub const GIF_RECORDING_FRAMES: u32 = 300;
+/// The directory the app keeps its own files in, under the platform's
+/// [data directory](crate::utils::data_dir).
+#[cfg(not(target_arch = "wasm32"))]
+pub const DATA_DIR_NAME: &str = "state-machine";
+/// The file in the app's data directory the drawing is saved to when the
+/// app is suspended, and reopened from if the app was closed before being
+/// resumed.
+#[cfg(not(target_arch = "wasm32"))]
+pub const SESSION_FILE: &str = "session.ron";
+
+pub struct DrawingApp {
+    launcher: Launcher<Canvas>,
+    canvas: Option<Canvas>,
+    states: StateStack,
+    /// Overrides the platform's data directory. See
+    /// [DrawingApp::with_data_dir].
+    #[cfg(not(target_arch = "wasm32"))]
+    data_dir: Option<std::path::PathBuf>,
+}
+
+impl DrawingApp {
+    #[allow(clippy::new_without_default)]
+    pub fn new(
+        #[cfg(target_arch = "wasm32")] event_loop: &EventLoop<anyhow::Result<Canvas>>,
+    ) -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        let bindings = KeyBindings::load(BINDINGS_PATH).unwrap_or_else(|e| {
+            log::error!("Unable to load {BINDINGS_PATH}: {e}");
+            KeyBindings::default()
+        });
+        #[cfg(target_arch = "wasm32")]
+        let bindings = KeyBindings::default();
+        Self {
+            launcher: Launcher::new(
+                WindowConfig::default(),
+                #[cfg(target_arch = "wasm32")]
+                event_loop,
+            ),
+            canvas: None,
+            states: StateStack::new(Box::new(DrawingState::with_bindings(bindings))),
+            #[cfg(not(target_arch = "wasm32"))]
+            data_dir: None,
+        }
+    }
+
+    /// Creates the window with `config` instead of the defaults.
+    pub fn with_window_config(mut self, config: WindowConfig) -> Self {
+        self.launcher.window_config = config;
+        self
+    }
+
+    /// Keeps the app's files, like the session saved when it's suspended,
+    /// in `dir` rather than the platform's data directory. Android apps
+    /// have to set this to their internal data path to save anything.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_data_dir(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.data_dir = Some(dir.into());
+        self
+    }
+
+    /// Takes over the canvas once it's ready.
+    #[allow(unused_mut)]
+    fn start(&mut self, mut canvas: Canvas) {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if let Some(dir) = &self.data_dir {
+                canvas.set_session_path(Some(dir.join(SESSION_FILE)));
+            }
+            canvas.restore_session();
+        }
+        // The canvas only has a size once it's on the page.
+        #[cfg(target_arch = "wasm32")]
+        {
+            canvas.window.request_redraw();
+            canvas.resize(
+                canvas.window.inner_size().width,
+                canvas.window.inner_size().height,
+            );
+        }
+        self.canvas = Some(canvas);
+    }
+}
+
+impl ApplicationHandler<anyhow::Result<Canvas>> for DrawingApp {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(canvas) = &mut self.canvas {
+            if let Err(e) = canvas.resume(canvas.window.clone()) {
+                log::error!("Unable to recreate the surface: {e}");
+                event_loop.exit();
+            }
+            return;
+        }
+        if let Some(canvas) = self
+            .launcher
+            .launch(event_loop, |window, window_config| async move {
+                Canvas::new(window, &window_config).await
+            })
+        {
+            self.start(canvas);
+        }
+    }
+
+    fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(canvas) = &mut self.canvas {
+            canvas.suspend();
+        }
+    }
+
+    fn device_event(
+        &mut self,
+        _event_loop: &ActiveEventLoop,
+        _device_id: winit::event::DeviceId,
+        event: winit::event::DeviceEvent,
+    ) {
+        if let Some(canvas) = &mut self.canvas {
+            canvas.input.handle_device_event(&event);
+            if canvas.view_drag().is_some() {
+                canvas.window.request_redraw();
+            }
+        }
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, canvas: anyhow::Result<Canvas>) {
+        if let Some(canvas) = Launcher::started(event_loop, canvas) {
+            self.start(canvas);
+        }
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        _window_id: winit::window::WindowId,
+        event: WindowEvent,
+    ) {
+        let canvas = match &mut self.canvas {
+            Some(canvas) => canvas,
+            None => return,
+        };
+        if canvas.replayer.is_some() && RecordedEvent::from_event(&event).is_some() {
+            // Pressing anything stops a replay. Other input is ignored so
+            // it doesn't get mixed in.
+            if !is_press(&event) {
+                return;
+            }
+            canvas.stop_replay();
+        }
+        if let Some(recorder) = &mut canvas.recorder {
+            recorder.record(&event);
+        }
+        canvas.input.handle_event(&event, canvas.scale_factor);
+        if canvas.redraw_policy == RedrawPolicy::Reactive
+            && !matches!(event, WindowEvent::RedrawRequested)
+        {
+            canvas.window.request_redraw();
+        }
+
+        match &event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(size) => canvas.resize(size.width, size.height),
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                canvas.set_scale_factor(*scale_factor)
+            }
+            // The input state keeps track of them.
+            WindowEvent::ModifiersChanged(_) => {}
+            // Nothing's updated while suspended.
+            WindowEvent::RedrawRequested if canvas.is_suspended() => {}
+            WindowEvent::RedrawRequested => {
+                let replayed = canvas
+                    .replayer
+                    .as_mut()
+                    .map(|replayer| replayer.due(false))
+                    .unwrap_or_default();
+                for event in replayed {
+                    if !replay(canvas, &mut self.states, &event) {
+                        event_loop.exit();
+                        return;
+                    }
+                }
+                if canvas.replayer.as_ref().is_some_and(|r| r.is_finished()) {
+                    canvas.stop_replay();
+                }
+                canvas.clock.tick();
+                canvas.input.begin_frame();
+                let steps = canvas.fixed_timestep.advance(canvas.clock.dt());
+                let step = canvas.fixed_timestep.step();
+                for _ in 0..steps {
+                    if !self.states.fixed_update(canvas, step) {
+                        event_loop.exit();
+                        return;
+                    }
+                }
+                #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+                for path in canvas.reload_assets() {
+                    if !self.states.asset_changed(canvas, &path) {
+                        event_loop.exit();
+                        return;
+                    }
+                }
+                if !self.states.update(canvas) {
+                    event_loop.exit();
+                    return;
+                }
+                canvas.render(event_loop, &self.states);
+                if let Some(recorder) = &mut canvas.recorder {
+                    recorder.end_frame();
+                }
+                canvas.input.end_frame();
+            }
+            _ => {
+                #[cfg(feature = "debug-ui")]
+                if let Some(debug_ui) = &mut canvas.debug_ui {
+                    if debug_ui.handle_event(&canvas.window, &event) {
+                        return;
+                    }
+                }
+                if !self.states.handle_event(canvas, &event) {
+                    event_loop.exit();
+                }
+            }
+        }
+    }
+}
+
+/// Draws straight into `view`.
+fn view_attachment(
+    view: &wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+) -> wgpu::RenderPassColorAttachment<'_> {
+    wgpu::RenderPassColorAttachment {
+        view,
+        resolve_target: None,
+        ops: wgpu::Operations {
+            load,
+            store: wgpu::StoreOp::Store,
+        },
+    }
+}
+
+/// Whether `event` is a key, button or finger going down.
+fn is_press(event: &WindowEvent) -> bool {
+    match event {
+        WindowEvent::KeyboardInput { event, .. } => event.state.is_pressed(),
+        WindowEvent::MouseInput { state, .. } => state.is_pressed(),
+        WindowEvent::Touch(touch) => touch.phase == winit::event::TouchPhase::Started,
+        _ => false,
+    }
+}
+
+/// Plays back `event` as if it had just happened. Returns `false` once the
+/// app should exit.
+fn replay(canvas: &mut Canvas, states: &mut StateStack, event: &RecordedEvent) -> bool {
+    if let RecordedEvent::Key(key) = event {
+        canvas.input.handle_key(key);
+        return states.handle_key(canvas, key);
+    }
+    let Some(event) = event.to_event() else {
+        return true;
+    };
+    canvas.input.handle_event(&event, canvas.scale_factor);
+    match event {
+        WindowEvent::ModifiersChanged(_) => true,
+        event => states.handle_event(canvas, &event),
+    }
+}
+
+/// A stroke being drawn by one pointer.
+struct Stroke {
+    brush: BrushTool,
+    layer: usize,
+    node: NodeId,
+}
+
+/// When the window gets redrawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RedrawPolicy {
+    /// Every frame, as fast as the display allows.
+    #[default]
+    Continuous,
+    /// Only after input, or while something is animating or asked for a
+    /// redraw with [Canvas::request_redraw]. Saves power while nothing
+    /// changes.
+    Reactive,
+}
+
+/// Moving the view with the mouse while the pointer is locked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewDrag {
+    Pan,
+    Rotate,
+}
+
+/// A text node picked to be edited, with where it is and how big it looks.
+#[derive(Debug, Clone)]
+pub struct TextEdit {
+    pub selection: crate::tools::select::Selection,
+    pub text: String,
+    /// Top left corner of the text in the world.
+    pub position: glam::Vec2,
+    /// How high the text looks in pixels at the current zoom.
+    pub size: f32,
+}
+
+pub struct Canvas {
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    /// `None` while the app is suspended.
+    surface: Option<wgpu::Surface<'static>>,
+    pub(crate) config: wgpu::SurfaceConfiguration,
+    /// What the surface supports, best first.
+    present_modes: Vec<wgpu::PresentMode>,
+    /// Transparent if the window is.
+    clear_color: wgpu::Color,
+    capabilities: GpuCapabilities,
+    /// Drawn into instead of the surface when multisampling.
+    msaa: Option<MultisampleBuffer>,
+    /// Made the first time post effects are set, or up front when drawing
+    /// to an HDR surface.
+    post: Option<PostProcessor>,
+    pub(crate) device: wgpu::Device,
+    pub(crate) queue: wgpu::Queue,
+    grid: Grid,
+    pub(crate) renderer: Renderer,
+    /// Where the font and the interaction machine were loaded from.
+    resources: Resources,
+    assets: AssetCache,
+    /// Reloads resources as they're edited.
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    asset_watcher: Option<crate::hot_reload::AssetWatcher>,
+    #[allow(unused)]
+    pub(crate) window: Arc<Window>,
+    /// Physical pixels per logical pixel. The surface is sized in physical
+    /// pixels and the camera and input in logical ones.
+    scale_factor: f32,
+    /// Only held to keep watching the `<canvas>` for resizes.
+    #[cfg(target_arch = "wasm32")]
+    #[allow(unused)]
+    canvas_observer: Option<crate::window::CanvasObserver>,
+    pub(crate) camera: OrthoCamera,
+    camera_animator: CameraAnimator,
+    follow: Option<CameraFollow>,
+    /// Times each frame, for animating the camera.
+    pub(crate) clock: FrameClock,
+    fixed_timestep: FixedTimestep,
+    redraw_policy: RedrawPolicy,
+    /// Set when something asked for another frame in reactive mode.
+    redraw_requested: bool,
+    /// Shared by the cameras of the viewports and the minimap.
+    view_cameras: CameraArray,
+    camera_binding: CameraBinding,
+    /// Maps logical window pixels straight to the screen, for text and
+    /// menus that don't move with the drawing.
+    screen_binding: CameraBinding,
+    /// Extra views drawn over the main one.
+    viewports: Vec<Viewport>,
+    viewport_frame: ViewportFrame,
+    minimap: Minimap,
+    perf: PerfOverlay,
+    render_stats: RenderStats,
+    plugins: PluginRegistry,
+    /// Taken out while it's drawn, since it changes the canvas.
+    #[cfg(feature = "debug-ui")]
+    debug_ui: Option<crate::debug_ui::DebugUi>,
+    draw_mode: DrawMode,
+    /// Set when layers are ordered with a depth buffer.
+    depth: Option<DepthBuffer>,
+    pub(crate) layers: LayerStack,
+    overlay: Overlay,
+    tool: ToolKind,
+    /// Follows the tool and whether it's being used.
+    interaction: StateMachine<Interaction, InteractionEvent>,
+    select: SelectTool,
+    /// Settings for new strokes. Each pointer drawing has its own copy.
+    brush: BrushTool,
+    line: LineTool,
+    measure: LineTool,
+    eyedropper: EyedropperTool,
+    captures: FrameCaptures,
+    #[cfg(not(target_arch = "wasm32"))]
+    frame_recorder: Option<crate::recording::FrameRecorder>,
+    /// See [Canvas::session_path].
+    #[cfg(not(target_arch = "wasm32"))]
+    session_path: Option<std::path::PathBuf>,
+    /// `None` if the shaders can't be watched.
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    shader_watcher: Option<crate::hot_reload::ShaderWatcher>,
+    /// Why edited shaders didn't compile, by file.
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    shader_errors: Vec<(std::path::PathBuf, String)>,
+    /// [Canvas::shader_errors] drawn over the frame.
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    shader_error_text: Option<TextBlock>,
+    dimensions: DimensionRenderer,
+    brush_color: glam::Vec4,
+    /// The strokes being drawn, one for each pointer drawing.
+    strokes: HashMap<PointerId, Stroke>,
+    /// Every finger draws with the brush instead of two panning and
+    /// zooming.
+    touch_drawing: bool,
+    cursor: glam::Vec2,
+    /// The cursor in logical window pixels.
+    cursor_screen: glam::Vec2,
+    /// The left button drag the tools are following.
+    drag: DragTracker,
+    /// Clicks of the left button, including touches acting as it.
+    clicks: ClickCounter,
+    view_drag: Option<ViewDrag>,
+    recorder: Option<InputRecorder>,
+    replayer: Option<InputReplayer>,
+    /// A text node that was double clicked with the select tool.
+    text_edit: Option<crate::tools::select::Selection>,
+    touches: TouchTracker,
+    snapper: Snapper,
+    input: InputState,
+    history: History,
+    /// [LayerStack::revision] as of the last change [Canvas::history] knows
+    /// about. See [Canvas::forget_history_if_layers_changed].
+    history_revision: u64,
+    clipboard: Clipboard,
+}
+
+impl Canvas {
+    /// Draws into `window`, which was created with `window_config`.
+    pub async fn new(window: Arc<Window>, window_config: &WindowConfig) -> anyhow::Result<Self> {
+        let Gpu {
+            instance,
+            adapter,
+            surface,
+            device,
+            queue,
+            config,
+            format,
+            capabilities,
+            present_modes,
+            sample_count,
+            clear_color,
+        } = Gpu::new(window.clone(), window_config).await?;
+
+        let scale_factor = window.scale_factor() as f32;
+        let camera = OrthoCamera::new(
+            0.0,
+            window.inner_size().width as f32 / scale_factor,
+            window.inner_size().height as f32 / scale_factor,
+            0.0,
+        );
+        let resources = Resources::app();
+        let mut assets = AssetCache::new();
+        let font = assets
+            .font_async(
+                &resources,
+                crate::renderer::DEFAULT_FONT_PATH,
+                crate::renderer::FALLBACK_CHAR,
+                &device,
+                &queue,
+            )
+            .await?;
+        let renderer = Renderer::new(font, format, sample_count, &device)?;
+        let msaa = (sample_count > 1).then(|| {
+            MultisampleBuffer::new(config.width, config.height, format, sample_count, &device)
+        });
+        let camera_binding = renderer.bind_camera(&camera, &device);
+        let screen_binding = renderer.bind_camera(&camera, &device);
+        let view_cameras = renderer
+            .camera_binder
+            .bind_array(&device, VIEW_CAMERA_CAPACITY);
+
+        log::info!("Creating grid pipeline");
+        let grid = Grid::new(
+            &renderer.camera_binder,
+            &renderer.shader,
+            renderer.format(),
+            renderer.sample_count(),
+            &device,
+        )?;
+
+        let mut interaction =
+            match MachineDefinition::load_async(&resources, INTERACTION_MACHINE_PATH).await {
+                Ok(definition) => definition.builder().build(),
+                Err(e) => {
+                    log::warn!("Using the built in tool states: {e:#}");
+                    interaction_machine(ToolKind::default())
+                }
+            };
+        interaction.reset(Interaction::Tool(ToolKind::default()));
+
+        let perf = PerfOverlay::new(&renderer, &device)?;
+        #[cfg(feature = "debug-ui")]
+        let debug_ui = crate::debug_ui::DebugUi::new(&window, format, &device);
+
+        let viewport_frame = ViewportFrame::new(
+            &renderer.shader,
+            renderer.format(),
+            renderer.sample_count(),
+            &device,
+        )?;
+        let minimap = Minimap::new(
+            view_cameras
+                .bind(&camera, &queue)
+                .context("No room for the minimap camera")?,
+            &renderer.geometry_pipeline,
+            &device,
+            &queue,
+        );
+        let layers = renderer.layers(&device);
+        let overlay = renderer.overlay(&device);
+        let dimensions = DimensionRenderer::new(&device);
+
+        // SDR colors have to be tonemapped onto HDR surfaces.
+        let post = post::is_hdr(format)
+            .then(|| {
+                PostProcessor::new(
+                    &renderer.shader,
+                    format,
+                    sample_count,
+                    config.width,
+                    config.height,
+                    &device,
+                )
+            })
+            .transpose()?;
+
+        #[cfg(target_arch = "wasm32")]
+        let canvas_observer = crate::window::CanvasObserver::new(window.clone());
+        #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+        let shader_watcher = {
+            let window = window.clone();
+            crate::hot_reload::ShaderWatcher::new(move || window.request_redraw())
+                .inspect_err(|e| log::warn!("Shaders won't be reloaded: {e}"))
+                .ok()
+        };
+        #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+        let asset_watcher = resources.base_dir().and_then(|dir| {
+            let window = window.clone();
+            crate::hot_reload::AssetWatcher::new(dir, move || window.request_redraw())
+                .inspect_err(|e| log::warn!("Resources won't be reloaded: {e}"))
+                .ok()
+        });
+        Ok(Self {
+            config,
+            present_modes,
+            clear_color,
+            capabilities,
+            msaa,
+            post,
+            instance,
+            adapter,
+            surface,
+            device,
+            queue,
+            window,
+            grid,
+            perf,
+            render_stats: RenderStats::default(),
+            plugins: PluginRegistry::default(),
+            #[cfg(feature = "debug-ui")]
+            debug_ui: Some(debug_ui),
+            renderer,
+            resources,
+            assets,
+            #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+            asset_watcher,
+            scale_factor,
+            #[cfg(target_arch = "wasm32")]
+            canvas_observer,
+            camera,
+            camera_animator: CameraAnimator::default(),
+            follow: None,
+            clock: FrameClock::new(),
+            fixed_timestep: FixedTimestep::default(),
+            redraw_policy: RedrawPolicy::default(),
+            redraw_requested: false,
+            view_cameras,
+            camera_binding,
+            screen_binding,
+            viewports: Vec::new(),
+            viewport_frame,
+            minimap,
+            draw_mode: DrawMode::default(),
+            depth: None,
+            layers,
+            overlay,
+            tool: ToolKind::default(),
+            interaction,
+            select: SelectTool::new(),
+            brush: BrushTool::new(),
+            line: LineTool::new(),
+            measure: LineTool::new(),
+            eyedropper: EyedropperTool::new(),
+            captures: FrameCaptures::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            frame_recorder: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            session_path: crate::utils::data_dir()
+                .map(|dir| dir.join(DATA_DIR_NAME).join(SESSION_FILE)),
+            #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+            shader_watcher,
+            #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+            shader_errors: Vec::new(),
+            #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+            shader_error_text: None,
+            dimensions,
+            brush_color: glam::Vec4::ONE,
+            strokes: HashMap::new(),
+            touch_drawing: false,
+            cursor: glam::Vec2::ZERO,
+            cursor_screen: glam::Vec2::ZERO,
+            drag: DragTracker::default(),
+            clicks: ClickCounter::default(),
+            view_drag: None,
+            recorder: None,
+            replayer: None,
+            text_edit: None,
+            touches: TouchTracker::new(),
+            snapper: Snapper::default(),
+            input: InputState::new(),
+            history: History::default(),
+            history_revision: 0,
+            clipboard: Clipboard::new(),
+        })
+    }
+
+    /// Resizes the surface. `width` and `height` are in physical pixels.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.config.width = width.max(1);
+        self.config.height = height.max(1);
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+        self.resize_targets(self.config.width, self.config.height);
+        self.update_camera_size();
+    }
+
+    /// Resizes the textures that match the surface's size.
+    fn resize_targets(&mut self, width: u32, height: u32) {
+        if let Some(msaa) = &mut self.msaa {
+            msaa.resize(width, height, &self.device);
+        }
+        if let Some(post) = &mut self.post {
+            post.resize(width, height, &self.device);
+        }
+        if let Some(depth) = &mut self.depth {
+            depth.resize(width, height, &self.device);
+        }
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.config.present_mode
+    }
+
+    /// The optional features and limits the device was created with.
+    pub fn capabilities(&self) -> &GpuCapabilities {
+        &self.capabilities
+    }
+
+    /// The present modes the surface supports.
+    pub fn present_modes(&self) -> &[wgpu::PresentMode] {
+        &self.present_modes
+    }
+
+    /// Switches to `mode`, or the closest one the surface supports if it
+    /// doesn't. Returns the mode that ended up being used.
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) -> wgpu::PresentMode {
+        let mode = fallback_present_mode(mode, &self.present_modes);
+        if mode != self.config.present_mode {
+            log::info!("Present mode: {mode:?}");
+            self.config.present_mode = mode;
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.config);
+            }
+        }
+        mode
+    }
+
+    /// Drops the surface and shrinks the textures sized to match it, and
+    /// saves the drawing to [Canvas::session_path], as the app may be
+    /// closed while suspended. Pipelines, layers and textures are kept.
+    /// Nothing is updated or drawn until [Canvas::resume].
+    pub fn suspend(&mut self) {
+        self.surface = None;
+        self.resize_targets(1, 1);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = &self.session_path {
+            let saved = path
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .map_err(anyhow::Error::from)
+                .and_then(|()| self.document().save(path));
+            if let Err(e) = saved {
+                log::error!("Unable to save the session to {}: {e}", path.display());
+            }
+        }
+    }
+
+    /// Recreates the surface for `window` after [Canvas::suspend]. The
+    /// window can be a new one, like when moving to another GPU or monitor,
+    /// as long as it supports the surface's format. Resuming without
+    /// suspending first swaps the surface over to `window`.
+    pub fn resume(&mut self, window: Arc<Window>) -> anyhow::Result<()> {
+        if self.surface.is_some() && Arc::ptr_eq(&self.window, &window) {
+            return Ok(());
+        }
+        self.surface = None;
+        let (surface, present_modes) = gpu::recreate_surface(
+            &self.instance,
+            &self.adapter,
+            window.clone(),
+            &mut self.config,
+        )?;
+        self.surface = Some(surface);
+        self.present_modes = present_modes;
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.canvas_observer = crate::window::CanvasObserver::new(window.clone());
+        }
+        self.window = window;
+        self.scale_factor = self.window.scale_factor() as f32;
+        let size = self.window.inner_size();
+        self.resize(size.width, size.height);
+        self.clock.pause();
+        self.window.request_redraw();
+        // The drawing is still here, so there's nothing to restore.
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = &self.session_path {
+            let _ = std::fs::remove_file(path);
+        }
+        Ok(())
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.surface.is_none()
+    }
+
+    /// Where the drawing is saved when the app is suspended. By default
+    /// that's [SESSION_FILE] in the app's directory under the platform's
+    /// [data directory](crate::utils::data_dir). `None` if there's nowhere to
+    /// save it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn session_path(&self) -> Option<&std::path::Path> {
+        self.session_path.as_deref()
+    }
+
+    /// Saves the session to `path` instead, or nowhere.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_session_path(&mut self, path: Option<std::path::PathBuf>) {
+        self.session_path = path;
+    }
+
+    /// Reopens the drawing saved when the app was last suspended, if it
+    /// was closed before being resumed. The saved drawing is only removed
+    /// once it's been reopened.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn restore_session(&mut self) {
+        let Some(path) = self.session_path.clone() else {
+            return;
+        };
+        if !path.exists() {
+            return;
+        }
+        match Document::load(&path).and_then(|document| self.open_document(&document)) {
+            Ok(()) => {
+                log::info!("Restored the drawing from {}", path.display());
+                let _ = std::fs::remove_file(&path);
+            }
+            Err(e) => log::error!("Unable to restore the session from {}: {e}", path.display()),
+        }
+    }
+
+    /// Steps through VSync, then mailbox and immediate presenting if the
+    /// surface supports them, and back.
+    pub fn cycle_present_mode(&mut self) -> wgpu::PresentMode {
+        use wgpu::PresentMode::*;
+        let cycle: Vec<_> = [Fifo, Mailbox, Immediate]
+            .into_iter()
+            .filter(|mode| self.present_modes.contains(mode))
+            .collect();
+        let next = cycle
+            .iter()
+            .position(|&mode| mode == self.config.present_mode)
+            .map_or(0, |i| (i + 1) % cycle.len());
+        self.set_present_mode(cycle.get(next).copied().unwrap_or(Fifo))
+    }
+
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    /// Called when the window moves to a screen with a different pixel
+    /// density. The window is resized separately.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor as f32;
+        self.update_camera_size();
+    }
+
+    /// Follows the `<canvas>` when CSS resizes it or the device pixel ratio
+    /// changes, which winit doesn't always report.
+    #[cfg(target_arch = "wasm32")]
+    fn follow_canvas_size(&mut self) {
+        let Some((size, scale_factor)) = crate::window::canvas_size(&self.window) else {
+            return;
+        };
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        if scale_factor as f32 != self.scale_factor {
+            self.set_scale_factor(scale_factor);
+        }
+        if (size.width, size.height) != (self.config.width, self.config.height) {
+            self.resize(size.width, size.height);
+        }
+    }
+
+    fn update_camera_size(&mut self) {
+        self.camera.resize(
+            self.config.width as f32 / self.scale_factor,
+            self.config.height as f32 / self.scale_factor,
+        );
+        self.camera_binding.update(&self.camera, &self.queue);
+        let size = self.camera.size();
+        self.screen_binding
+            .update(&OrthoCamera::new(0.0, size.x, size.y, 0.0), &self.queue);
+    }
+
+    /// Draws the canvas with `states` on top.
+    pub fn render(&mut self, event_loop: &ActiveEventLoop, states: &StateStack) {
+        if std::mem::take(&mut self.redraw_requested) || self.is_animating() {
+            self.window.request_redraw();
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        self.follow_canvas_size();
+        let Some(surface) = &self.surface else {
+            return;
+        };
+        let frame = match gpu::next_frame(surface, &self.device, &self.config) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => {
+                self.window.request_redraw();
+                return;
+            }
+            Err(e) => {
+                log::error!("{e}");
+                event_loop.exit();
+                return;
+            }
+        };
+
+        #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+        self.reload_shaders();
+
+        // Everything since the last frame started, which is that frame's
+        // drawing and whatever was uploaded for it.
+        self.render_stats = crate::stats::take();
+        if let Err(e) = self.perf.update(
+            &self.clock,
+            self.render_stats,
+            &self.renderer,
+            &self.device,
+            &self.queue,
+        ) {
+            log::error!("Unable to update the performance overlay: {e}");
+        }
+
+        // Releasing without a press, for one, is expected and changes nothing.
+        if let Err(e) = self.interaction.update(&mut ()) {
+            log::trace!("{e}");
+        }
+        self.update_follow();
+        // The camera animates in fixed steps, so it's drawn between the
+        // last two to keep it smooth.
+        if let Some(camera) = self
+            .camera_animator
+            .interpolate(&self.camera, self.fixed_timestep.alpha())
+        {
+            self.camera_binding.update(&camera, &self.queue);
+        }
+
+        if self.captures.is_pending() {
+            self.device.poll(wgpu::Maintain::Poll);
+            self.captures.poll();
+        }
+        if self.eyedropper.is_picking() {
+            self.apply_picked_color();
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(recorder) = &mut self.frame_recorder {
+            recorder.update(&mut self.captures);
+            // Kept until it's written everything, as dropping it waits.
+            if !recorder.is_recording() && !recorder.is_writing() {
+                self.frame_recorder = None;
+            }
+        }
+
+        self.prepare_styles();
+        if self.depth.is_some() {
+            let (near, far) = self.camera.depth_range();
+            self.layers.update_depths(near, far, &self.queue);
+        }
+        for layer in self.layers.iter_mut() {
+            layer.prepare(&self.device, &self.queue);
+        }
+        {
+            let mut batch = self.overlay.begin(&self.device, &self.queue);
+            match self.tool {
+                ToolKind::Select => self.select.draw_overlay(&mut self.layers, &mut batch),
+                ToolKind::Brush if !self.strokes.contains_key(&PointerId::Mouse) => {
+                    let snap = self
+                        .snapper
+                        .snap(&mut self.layers, self.cursor, None, false, 1.0);
+                    self.snapper.draw_indicator(&snap, &mut batch);
+                }
+                ToolKind::Brush | ToolKind::Eyedropper => {}
+                ToolKind::Line => {
+                    let snap = self.snapper.snap(
+                        &mut self.layers,
+                        self.cursor,
+                        self.line.start(),
+                        self.input.modifiers().shift_key(),
+                        1.0,
+                    );
+                    self.snapper.draw_indicator(&snap, &mut batch);
+                    let ghost = self.brush_color * glam::Vec4::new(1.0, 1.0, 1.0, 0.5);
+                    let dash = LINE_PREVIEW_DASH / self.camera.zoom();
+                    self.line.draw_preview(ghost, dash, &mut batch);
+                }
+                ToolKind::Measure => {
+                    let snap = self.snapper.snap(
+                        &mut self.layers,
+                        self.cursor,
+                        self.measure.start(),
+                        self.input.modifiers().shift_key(),
+                        1.0,
+                    );
+                    self.snapper.draw_indicator(&snap, &mut batch);
+                }
+            }
+            let mut lines = Vec::new();
+            for layer in self.layers.iter_mut().filter(|l| l.is_visible()) {
+                lines.extend(layer.dimension_lines());
+            }
+            lines.extend(self.measure.preview());
+            if let Err(e) = self.dimensions.prepare(
+                lines,
+                self.camera.zoom(),
+                &self.renderer.font,
+                &self.renderer.text_pipeline,
+                &self.device,
+                &self.queue,
+                &mut batch,
+            ) {
+                log::error!("Unable to lay out dimensions: {e}");
+            }
+            if self.draw_mode == DrawMode::Wireframe {
+                let marker_size = 6.0 / self.camera.zoom();
+                for layer in self.layers.visible() {
+                    layer.draw_wireframe(&mut batch, marker_size);
+                }
+            }
+        }
+        if self.draw_mode == DrawMode::Sdf {
+            self.renderer
+                .sdf_pipeline
+                .prepare(&self.layers, &self.device, &self.queue);
+        }
+        if self.minimap.is_visible() {
+            let bounds = self.drawing_bounds();
+            self.minimap.prepare(
+                self.camera.size(),
+                bounds,
+                &self.camera,
+                &self.device,
+                &self.queue,
+            );
+        }
+
+        let view = gpu::frame_view(&frame, self.renderer.format());
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        // Taken out so the plugins can change while the canvas is borrowed.
+        let mut plugins = std::mem::take(&mut self.plugins);
+        {
+            let [background, scene, overlay, hud, after_post] = plugins.stages_mut();
+            // With post effects, everything is drawn for them to read instead.
+            let post = self.post.as_ref().filter(|post| post.is_enabled());
+            let surface = GraphSurface {
+                color: Box::new(|load| match (post, &self.msaa) {
+                    (Some(post), _) => post.scene().color_attachment(load),
+                    (None, Some(msaa)) => msaa.attachment(&view, load),
+                    (None, None) => view_attachment(&view, load),
+                }),
+                depth: self.depth.as_ref(),
+            };
+            let graph = self.render_graph(states, [background, scene, overlay, hud]);
+            if let Err(e) = graph.execute(&mut encoder, &surface) {
+                log::error!("Unable to draw the frame: {e}");
+            }
+            if let Some(post) = post {
+                post.apply(&mut encoder, &view, &self.queue);
+            }
+            let window_surface = GraphSurface {
+                color: Box::new(|load| view_attachment(&view, load)),
+                depth: None,
+            };
+            let after_post = self.plugin_pass("post plugins", after_post);
+            if let Err(e) = after_post.execute(&mut encoder, &window_surface) {
+                log::error!("Unable to draw the frame: {e}");
+            }
+        }
+        self.plugins = plugins;
+
+        #[cfg(feature = "debug-ui")]
+        if let Some(mut debug_ui) = self.debug_ui.take() {
+            debug_ui.render(self, &view, &mut encoder);
+            self.debug_ui = Some(debug_ui);
+        }
+
+        if self.captures.is_requested() {
+            self.captures
+                .capture(&frame.texture, encoder, &self.device, &self.queue);
+        } else {
+            self.queue.submit([encoder.finish()]);
+        }
+        frame.present();
+    }
+
+    /// The passes that draw a frame: the grid, the shapes, the text when
+    /// it's drawn apart from them, the overlay and viewports, and then the
+    /// HUD with `states` on top. `plugins` run after the background, scene,
+    /// overlay and HUD passes.
+    fn render_graph<'a>(
+        &'a self,
+        states: &'a StateStack,
+        plugins: [Vec<&'a mut dyn RenderPlugin>; 4],
+    ) -> RenderGraph<'a> {
+        let [background, scene, overlay, hud] = plugins;
+        let mut graph = RenderGraph::new();
+        graph.add_pass(
+            GraphPass::new("background", PassOutput::Surface, |pass| {
+                self.grid.draw(pass, &self.camera_binding);
+            })
+            .clear(self.clear_color),
+        );
+        graph.extend(self.plugin_pass("background plugins", background));
+
+        // Depth ordered shapes are drawn with all the text on top of them.
+        let depth_tested = self
+            .depth
+            .as_ref()
+            .filter(|_| self.draw_mode != DrawMode::Sdf)
+            .and(self.renderer.geometry_pipeline.depth_tested());
+        match depth_tested {
+            Some(pipelines) => {
+                graph.add_pass(
+                    GraphPass::new("scene", PassOutput::Surface, move |pass| {
+                        for layer in self.layers.visible() {
+                            layer.draw_shapes(
+                                pass,
+                                pipelines,
+                                &self.renderer.textures,
+                                &self.camera_binding,
+                            );
+                        }
+                    })
+                    .with_depth(),
+                );
+                graph.add_pass(GraphPass::new("text", PassOutput::Surface, |pass| {
+                    for layer in self.layers.visible() {
+                        layer.draw_text(pass, &self.renderer.text_pipeline, &self.camera_binding);
+                    }
+                }));
+            }
+            None => graph.add_pass(GraphPass::new("scene", PassOutput::Surface, |pass| {
+                self.draw_scene(pass, &self.camera_binding);
+            })),
+        }
+        graph.extend(self.plugin_pass("scene plugins", scene));
+
+        graph.add_pass(GraphPass::new("overlay", PassOutput::Surface, |pass| {
+            self.overlay
+                .draw(pass, &self.renderer.geometry_pipeline, &self.camera_binding);
+            self.dimensions
+                .draw(pass, &self.renderer.text_pipeline, &self.camera_binding);
+
+            let surface_size = glam::uvec2(self.config.width, self.config.height);
+            for viewport in &self.viewports {
+                if !viewport.begin(pass, self.scale_factor, surface_size) {
+                    continue;
+                }
+                self.viewport_frame.draw_background(pass);
+                self.grid.draw(pass, viewport.binding());
+                self.draw_scene(pass, viewport.binding());
+                self.viewport_frame.draw_border(pass);
+            }
+            let minimap = self.minimap.viewport();
+            if self.minimap.is_visible() && minimap.begin(pass, self.scale_factor, surface_size) {
+                self.viewport_frame.draw_background(pass);
+                self.draw_scene(pass, minimap.binding());
+                self.minimap
+                    .draw_indicator(pass, &self.renderer.geometry_pipeline);
+                self.viewport_frame.draw_border(pass);
+            }
+        }));
+        graph.extend(self.plugin_pass("overlay plugins", overlay));
+
+        graph.add_pass(GraphPass::new("hud", PassOutput::Surface, |pass| {
+            self.perf.draw(pass, &self.renderer, &self.screen_binding);
+            states.render(self, pass, self.fixed_timestep.alpha());
+            #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+            if let Some(text) = &self.shader_error_text {
+                self.draw_screen_text(pass, text);
+            }
+        }));
+        graph.extend(self.plugin_pass("hud plugins", hud));
+        graph
+    }
+
+    /// A graph with a pass that runs `plugins`, if there are any.
+    fn plugin_pass<'a>(
+        &'a self,
+        name: &'static str,
+        mut plugins: Vec<&'a mut dyn RenderPlugin>,
+    ) -> RenderGraph<'a> {
+        let mut graph = RenderGraph::new();
+        if plugins.is_empty() {
+            return graph;
+        }
+        graph.add_pass(GraphPass::encode(
+            name,
+            PassOutput::Surface,
+            move |encoder, surface| {
+                let mut context = PluginContext::new(
+                    encoder,
+                    surface,
+                    &self.device,
+                    &self.queue,
+                    &self.renderer,
+                    &self.camera_binding,
+                    &self.screen_binding,
+                );
+                for plugin in &mut plugins {
+                    plugin.render(&mut context);
+                }
+            },
+        ));
+        graph
+    }
+
+    /// Custom drawing that runs every frame along with the canvas's own.
+    pub fn render_plugins(&mut self) -> &mut PluginRegistry {
+        &mut self.plugins
+    }
+
+    /// Orders layers with a depth buffer instead of drawing them one after
+    /// the other. Text is drawn on top of every layer's shapes in this mode.
+    pub fn set_depth_ordering(&mut self, enabled: bool) -> anyhow::Result<()> {
+        if !enabled {
+            self.depth = None;
+            return Ok(());
+        }
+        if self.renderer.geometry_pipeline.depth_tested().is_none() {
+            self.renderer
+                .geometry_pipeline
+                .enable_depth(DepthBuffer::FORMAT, &self.device)?;
+        }
+        self.depth.get_or_insert_with(|| {
+            DepthBuffer::new(
+                self.config.width,
+                self.config.height,
+                self.renderer.sample_count(),
+                &self.device,
+            )
+        });
+        Ok(())
+    }
+
+    pub fn depth_ordering(&self) -> bool {
+        self.depth.is_some()
+    }
+
+    /// Draws the visible layers the way the draw mode asks for.
+    fn draw_scene(&self, pass: &mut wgpu::RenderPass<'_>, camera_binding: &CameraBinding) {
+        if self.draw_mode == DrawMode::Sdf {
+            self.renderer.sdf_pipeline.draw(pass, camera_binding);
+            for layer in self.layers.visible() {
+                layer.draw_text(pass, &self.renderer.text_pipeline, camera_binding);
+            }
+        } else {
+            self.draw_layers(pass, camera_binding);
+        }
+    }
+
+    fn draw_layers(&self, pass: &mut wgpu::RenderPass<'_>, camera_binding: &CameraBinding) {
+        self.renderer
+            .draw_layers(pass, &self.layers, camera_binding);
+    }
+
+    /// Renders the drawing without any tool overlays or HUD into an
+    /// offscreen texture. The image shows the same part of the canvas as
+    /// the window, scaled to fit `width` x `height`.
+    pub fn render_image(&mut self, width: u32, height: u32) -> anyhow::Result<PendingImage> {
+        anyhow::ensure!(width > 0 && height > 0, "Image size can't be zero");
+        let max_size = self.capabilities.max_texture_size();
+        anyhow::ensure!(
+            width <= max_size && height <= max_size,
+            "Images can't be larger than {max_size} x {max_size}"
+        );
+        let mut camera = OrthoCamera::new(0.0, width as f32, height as f32, 0.0);
+        let view = self.camera.size();
+        let fit = (width as f32 / view.x).min(height as f32 / view.y);
+        camera.set_zoom(self.camera.zoom() * fit);
+        camera.set_rotation(self.camera.rotation());
+        camera.set_center(self.camera.center());
+        self.render_view(width, height, &camera)
+    }
+
+    /// Renders the drawing as seen by `camera` into a `width` x `height`
+    /// image.
+    fn render_view(
+        &mut self,
+        width: u32,
+        height: u32,
+        camera: &OrthoCamera,
+    ) -> anyhow::Result<PendingImage> {
+        let target = self.render_target(width, height);
+        let encoder = self.encode_view(&target, camera);
+        // Exports always use the regular colors. Buffer writes are applied
+        // on the next submit, so the mode can be restored straight away.
+        self.renderer
+            .geometry_pipeline
+            .set_draw_mode(DrawMode::Color, &self.queue);
+        let image = PendingImage::new(target.texture(), encoder, &self.device, &self.queue);
+        self.renderer
+            .geometry_pipeline
+            .set_draw_mode(self.draw_mode, &self.queue);
+        image
+    }
+
+    /// An offscreen target the canvas's pipelines can draw into.
+    pub fn render_target(&self, width: u32, height: u32) -> RenderTarget {
+        RenderTarget::new(
+            width,
+            height,
+            self.renderer.format(),
+            self.renderer.sample_count(),
+            &self.device,
+        )
+    }
+
+    /// Draws the drawing as seen by `camera` into `target`, without any tool
+    /// overlays or HUD, like for a thumbnail. The target can then be
+    /// registered with [TextureRegistry::insert_target] to fill shapes with.
+    pub fn render_to_target(&mut self, target: &RenderTarget, camera: &OrthoCamera) {
+        let encoder = self.encode_view(target, camera);
+        self.renderer
+            .geometry_pipeline
+            .set_draw_mode(DrawMode::Color, &self.queue);
+        self.queue.submit([encoder.finish()]);
+        self.renderer
+            .geometry_pipeline
+            .set_draw_mode(self.draw_mode, &self.queue);
+    }
+
+    fn encode_view(&mut self, target: &RenderTarget, camera: &OrthoCamera) -> wgpu::CommandEncoder {
+        let camera_binding = self.renderer.camera_binder.bind(&self.device, camera);
+
+        self.prepare_styles();
+        for layer in self.layers.iter_mut() {
+            layer.prepare(&self.device, &self.queue);
+        }
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        {
+            let mut pass = target.begin_pass(&mut encoder, Some(wgpu::Color::BLACK));
+            self.draw_layers(&mut pass, &camera_binding);
+        }
+        encoder
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn export_png(
+        &mut self,
+        width: u32,
+        height: u32,
+        path: impl AsRef<std::path::Path>,
+    ) -> anyhow::Result<()> {
+        let image = self.render_image(width, height)?;
+        self.device.poll(wgpu::Maintain::Wait);
+        let png = pollster::block_on(image.png())?;
+        std::fs::write(path, png)?;
+        Ok(())
+    }
+
+    /// Renders the drawing and has the browser download it as `file_name`
+    /// once it's ready.
+    #[cfg(target_arch = "wasm32")]
+    pub fn export_png(&mut self, width: u32, height: u32, file_name: &str) -> anyhow::Result<()> {
+        let image = self.render_image(width, height)?;
+        let file_name = file_name.to_string();
+        utils::spawn(async move {
+            let result = match image.png().await {
+                Ok(png) => export::download(&png, &file_name, "image/png")
+                    .map_err(|e| anyhow::anyhow!("{e:?}")),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                log::error!("Unable to export {file_name}: {e}");
+            }
+        });
+        Ok(())
+    }
+
+    /// Copies the next frame drawn to the window, overlays and all. Fails
+    /// if the surface can't be copied from or isn't 8-bit RGBA or BGRA.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn capture_frame(&mut self) -> FrameCapture {
+        self.request_redraw();
+        self.captures.request()
+    }
+
+    /// Copies the next frame drawn to the window and has the browser
+    /// download it as `file_name`.
+    #[cfg(target_arch = "wasm32")]
+    pub fn capture_frame(&mut self, file_name: &str) {
+        self.request_redraw();
+        let capture = self.captures.request();
+        let file_name = file_name.to_string();
+        utils::spawn(async move {
+            let result = async {
+                let image = capture.image().await?;
+                let mut png = Vec::new();
+                image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+                export::download(&png, &file_name, "image/png")
+                    .map_err(|e| anyhow::anyhow!("{e:?}"))
+            };
+            if let Err(e) = result.await {
+                log::error!("Unable to capture {file_name}: {e}");
+            }
+        });
+    }
+
+    /// Takes the cursor position in logical pixels.
+    pub fn cursor_moved(&mut self, x: f32, y: f32) {
+        self.cursor_screen = glam::vec2(x, y);
+        self.cursor = self.screen_to_world(self.cursor_screen);
+        let Some(drag) = self.drag.moved(self.cursor_point()) else {
+            return;
+        };
+        match self.tool {
+            ToolKind::Brush => self.drag_stroke(PointerId::Mouse, self.cursor),
+            ToolKind::Line if self.line.is_drawing() => {
+                let end = self.snap_cursor(self.line.start()).point;
+                self.line.drag(end);
+            }
+            ToolKind::Measure if self.measure.is_drawing() => {
+                let end = self.snap_cursor(self.measure.start()).point;
+                self.measure.drag(end);
+            }
+            // Wobbles while clicking shouldn't nudge the selection.
+            ToolKind::Select if drag.dragged => {
+                let mut cursor = self.cursor;
+                if let (true, Some(origin)) = (
+                    self.input.modifiers().shift_key(),
+                    self.select.drag_origin(),
+                ) {
+                    cursor = snap_angle(origin, cursor, self.snapper.settings.angle_step);
+                }
+                self.select
+                    .drag(&mut self.layers, cursor, &self.device, &self.queue)
+            }
+            _ => {}
+        }
+    }
+
+    pub fn mouse_pressed(&mut self) {
+        if self.minimap.contains(self.cursor_screen) {
+            let center = self.minimap.viewport().screen_to_world(self.cursor_screen);
+            self.animate_camera(center, self.camera.zoom());
+            return;
+        }
+        let clicks = self
+            .clicks
+            .press(winit::event::MouseButton::Left, self.cursor_screen);
+        self.drag.press(self.cursor_point());
+        self.interact(InteractionEvent::Press);
+        match self.tool {
+            ToolKind::Brush => self.begin_stroke(PointerId::Mouse, self.cursor),
+            ToolKind::Eyedropper => {
+                let target = if self.input.modifiers().shift_key() {
+                    ColorTarget::Fill
+                } else {
+                    ColorTarget::Stroke
+                };
+                self.pick_color(target);
+            }
+            ToolKind::Line => self.begin_line(),
+            ToolKind::Measure => {
+                let start = self.snap_cursor(None).point;
+                self.measure.begin(start);
+            }
+            ToolKind::Select => {
+                self.select.press(
+                    &mut self.layers,
+                    self.cursor,
+                    PICK_TOLERANCE,
+                    self.input.modifiers().shift_key(),
+                );
+                if clicks == 2 {
+                    self.text_edit = self.text_at_cursor();
+                }
+            }
+        }
+    }
+
+    /// The editable text node under the cursor. Text in a group is found
+    /// too, unlike when selecting.
+    fn text_at_cursor(&mut self) -> Option<crate::tools::select::Selection> {
+        let hit = crate::picking::pick(&mut self.layers, self.cursor, PICK_TOLERANCE)?;
+        let layer = self.layers.get(hit.layer)?;
+        let node = layer.scene().get(hit.node)?;
+        (layer.is_editable() && matches!(node.kind, crate::scene::NodeKind::Text(_))).then_some(
+            crate::tools::select::Selection {
+                layer: hit.layer,
+                node: hit.node,
+            },
+        )
+    }
+
+    /// How many clicks the last left button press made. 2 is a double
+    /// click and 3 a triple click.
+    pub fn click_count(&self) -> u32 {
+        self.clicks.count()
+    }
+
+    /// Takes the text node that was double clicked to be edited, if there
+    /// is one.
+    pub fn take_text_edit(&mut self) -> Option<TextEdit> {
+        let selection = self.text_edit.take()?;
+        let layer = self.layers.get_mut(selection.layer)?;
+        let crate::scene::NodeKind::Text(text) = &layer.scene().get(selection.node)?.kind else {
+            return None;
+        };
+        let text = text.clone();
+        let (scale, _, position) = layer
+            .scene_mut()
+            .world_transform(selection.node)
+            .to_scale_angle_translation();
+        Some(TextEdit {
+            selection,
+            text,
+            position,
+            size: scale.y * self.camera.zoom() / self.renderer.font.scale(1.0),
+        })
+    }
+
+    /// Changes what a text node says.
+    pub fn edit_text(
+        &mut self,
+        selection: crate::tools::select::Selection,
+        text: &str,
+    ) -> anyhow::Result<()> {
+        let buffer =
+            self.renderer
+                .text_pipeline
+                .buffer_text(&self.renderer.font, &self.device, text)?;
+        let mut ctx = CommandContext {
+            layers: &mut self.layers,
+            device: &self.device,
+            queue: &self.queue,
+        };
+        let command = SetText::new(selection.layer, selection.node, text.to_string(), buffer);
+        self.history.execute(Box::new(command), &mut ctx)
+    }
+
+    pub fn mouse_released(&mut self) {
+        self.drag.release(self.cursor_point());
+        self.interact(InteractionEvent::Release);
+        match self.tool {
+            ToolKind::Brush => self.end_stroke(PointerId::Mouse, self.cursor),
+            ToolKind::Eyedropper => {}
+            ToolKind::Line => self.end_line(),
+            ToolKind::Measure => self.end_measure(),
+            ToolKind::Select => {
+                if let Some(change) = self.select.release(&mut self.layers) {
+                    self.history.push(Box::new(change));
+                }
+            }
+        }
+    }
+
+    /// Takes a touch at `position` in logical pixels. One finger acts like
+    /// the mouse and two pan and pinch zoom the view.
+    pub fn touch(&mut self, id: u64, phase: winit::event::TouchPhase, position: glam::Vec2) {
+        let pointer = PointerId::Touch(id);
+        // Strokes started before switching tools still finish.
+        if (self.touch_drawing && self.tool == ToolKind::Brush)
+            || self.strokes.contains_key(&pointer)
+        {
+            use winit::event::TouchPhase;
+            let world = self.screen_to_world(position);
+            match phase {
+                TouchPhase::Started => self.begin_stroke(pointer, world),
+                TouchPhase::Moved => self.drag_stroke(pointer, world),
+                TouchPhase::Ended | TouchPhase::Cancelled => self.end_stroke(pointer, world),
+            }
+            return;
+        }
+        match self.touches.handle(id, phase, position) {
+            Some(TouchGesture::Press(p)) => {
+                self.cursor_moved(p.x, p.y);
+                self.mouse_pressed();
+            }
+            Some(TouchGesture::Move(p)) => self.cursor_moved(p.x, p.y),
+            Some(TouchGesture::Release(p)) => {
+                self.cursor_moved(p.x, p.y);
+                self.mouse_released();
+            }
+            Some(TouchGesture::PanZoom { from, to, scale }) => self.pan_zoom(from, to, scale),
+            None => {}
+        }
+    }
+
+    /// Whether every finger draws with the brush, so several people can
+    /// draw at once, instead of two fingers panning and zooming.
+    pub fn set_touch_drawing(&mut self, enabled: bool) {
+        if !enabled {
+            let touches: Vec<_> = self
+                .strokes
+                .keys()
+                .filter(|pointer| matches!(pointer, PointerId::Touch(_)))
+                .copied()
+                .collect();
+            for pointer in touches {
+                self.end_stroke(pointer, self.cursor);
+            }
+        }
+        self.touch_drawing = enabled;
+    }
+
+    pub fn touch_drawing(&self) -> bool {
+        self.touch_drawing
+    }
+
+    /// Zooms in by `scale` and moves the view so the point that was under
+    /// `from` is under `to`. Both are in logical window pixels.
+    pub fn pan_zoom(&mut self, from: glam::Vec2, to: glam::Vec2, scale: f32) {
+        self.camera_animator.stop();
+        let anchor = self.screen_to_world(from);
+        self.camera.set_zoom(self.camera.zoom() * scale);
+        let moved = self.screen_to_world(to);
+        self.camera
+            .set_center(self.camera.center() + anchor - moved);
+        self.camera_binding.update(&self.camera, &self.queue);
+        self.cursor = self.screen_to_world(self.cursor_screen);
+    }
+
+    fn cursor_point(&self) -> DragPoint {
+        DragPoint::new(self.cursor_screen, self.cursor)
+    }
+
+    /// The cursor in the world.
+    pub fn cursor(&self) -> glam::Vec2 {
+        self.cursor
+    }
+
+    /// The cursor in logical window pixels.
+    pub fn cursor_screen(&self) -> glam::Vec2 {
+        self.cursor_screen
+    }
+
+    /// The left button drag in progress, if the button is down.
+    pub fn drag(&self) -> Option<&Drag> {
+        self.drag.drag()
+    }
+
+    /// Queues `event` for the interaction machine, which handles it on the
+    /// next frame.
+    fn interact(&mut self, event: InteractionEvent) {
+        self.interaction.post(event);
+    }
+
+    /// Ctrl, or Cmd on macOS.
+    pub fn command_modifier(&self) -> bool {
+        self.input.command_held()
+    }
+
+    pub fn undo(&mut self) {
+        self.forget_history_if_layers_changed();
+        let mut ctx = CommandContext {
+            layers: &mut self.layers,
+            device: &self.device,
+            queue: &self.queue,
+        };
+        let revision = ctx.layers.revision();
+        if let Err(e) = self.history.undo(&mut ctx) {
+            log::error!("Undo failed: {e}");
+        }
+        self.drop_stale_selection(revision);
+        self.history_revision = self.layers.revision();
+    }
+
+    pub fn redo(&mut self) {
+        self.forget_history_if_layers_changed();
+        let mut ctx = CommandContext {
+            layers: &mut self.layers,
+            device: &self.device,
+            queue: &self.queue,
+        };
+        let revision = ctx.layers.revision();
+        if let Err(e) = self.history.redo(&mut ctx) {
+            log::error!("Redo failed: {e}");
+        }
+        self.drop_stale_selection(revision);
+        self.history_revision = self.layers.revision();
+    }
+
+    /// Deletes everything selected on layers that can be edited, as one
+    /// step of undo history.
+    pub fn delete_selection(&mut self) {
+        let commands: Vec<Box<dyn Command>> = self
+            .selected_roots()
+            .into_iter()
+            .filter(|s| self.layers.get(s.layer).is_some_and(|l| l.is_editable()))
+            .map(|s| Box::new(RemoveNode::new(s.layer, s.node)) as Box<dyn Command>)
+            .collect();
+        if commands.is_empty() {
+            return;
+        }
+        self.select.select(None);
+        let mut ctx = CommandContext {
+            layers: &mut self.layers,
+            device: &self.device,
+            queue: &self.queue,
+        };
+        let command = Box::new(Compound::new("Delete", commands));
+        if let Err(e) = self.history.execute(command, &mut ctx) {
+            log::error!("Unable to delete selection: {e}");
+        }
+    }
+
+    /// The selected nodes that aren't inside other selected nodes, which
+    /// come along with them anyway, ordered by layer.
+    fn selected_roots(&self) -> Vec<crate::tools::select::Selection> {
+        let selections = self.select.selections();
+        let mut roots: Vec<_> = selections
+            .iter()
+            .copied()
+            .filter(|s| {
+                let Some(layer) = self.layers.get(s.layer) else {
+                    return false;
+                };
+                let scene = layer.scene();
+                scene.contains(s.node)
+                    && !selections.iter().any(|other| {
+                        other.layer == s.layer
+                            && other.node != s.node
+                            && scene.is_ancestor_of(other.node, s.node)
+                    })
+            })
+            .collect();
+        roots.sort_by_key(|s| s.layer);
+        roots
+    }
+
+    /// Groups the selected nodes on the primary selection's layer into a
+    /// single node and selects it.
+    pub fn group_selection(&mut self) {
+        let Some(primary) = self.select.selection() else {
+            return;
+        };
+        let Some(layer) = self
+            .layers
+            .get_mut(primary.layer)
+            .filter(|l| l.is_editable())
+        else {
+            return;
+        };
+        let nodes: Vec<NodeId> = self
+            .select
+            .selections()
+            .iter()
+            .filter(|s| s.layer == primary.layer)
+            .map(|s| s.node)
+            .collect();
+        let command = match Group::new(primary.layer, layer, &nodes, &self.device, &self.queue) {
+            Ok(command) => command,
+            Err(e) => {
+                log::error!("Unable to group selection: {e}");
+                return;
+            }
+        };
+        let group = command.group();
+        let mut ctx = CommandContext {
+            layers: &mut self.layers,
+            device: &self.device,
+            queue: &self.queue,
+        };
+        match self.history.execute(Box::new(command), &mut ctx) {
+            Ok(()) => self.select.select(Some(crate::tools::select::Selection {
+                layer: primary.layer,
+                node: group,
+            })),
+            Err(e) => log::error!("Unable to group selection: {e}"),
+        }
+    }
+
+    /// Replaces the selected group with its children, which stay selected.
+    pub fn ungroup_selection(&mut self) {
+        let Some(selection) = self.select.selection() else {
+            return;
+        };
+        let Some(layer) = self.layers.get(selection.layer).filter(|l| l.is_editable()) else {
+            return;
+        };
+        let command = match Ungroup::new(selection.layer, layer, selection.node) {
+            Ok(command) => command,
+            Err(e) => {
+                log::error!("Unable to ungroup selection: {e}");
+                return;
+            }
+        };
+        let members: Vec<_> = command
+            .members()
+            .map(|node| crate::tools::select::Selection {
+                layer: selection.layer,
+                node,
+            })
+            .collect();
+        let mut ctx = CommandContext {
+            layers: &mut self.layers,
+            device: &self.device,
+            queue: &self.queue,
+        };
+        match self.history.execute(Box::new(command), &mut ctx) {
+            Ok(()) => self.select.select_all(members),
+            Err(e) => log::error!("Unable to ungroup selection: {e}"),
+        }
+    }
+
+    /// Lines the selection up with the edges or center of its combined
+    /// bounds.
+    pub fn align_selection(&mut self, alignment: Alignment) {
+        self.arrange_selection("Align", |bounds| crate::align::align(bounds, alignment));
+    }
+
+    /// Spaces the selection out evenly between its outermost nodes.
+    pub fn distribute_selection(&mut self, axis: Axis) {
+        self.arrange_selection("Distribute", |bounds| {
+            crate::align::distribute(bounds, axis)
+        });
+    }
+
+    /// Moves every editable node in the selection by the world space offset
+    /// `offsets` returns for its bounds, as a single undo step.
+    fn arrange_selection(
+        &mut self,
+        name: &str,
+        offsets: impl FnOnce(&[Bounds]) -> Vec<glam::Vec2>,
+    ) {
+        let mut targets = Vec::new();
+        let mut bounds = Vec::new();
+        // A child moves with its parent, so it isn't offset again.
+        for selection in self.selected_roots() {
+            let Some(layer) = self
+                .layers
+                .get_mut(selection.layer)
+                .filter(|l| l.is_editable())
+            else {
+                continue;
+            };
+            if let Some(b) = layer.scene_mut().world_bounds(selection.node) {
+                targets.push(selection);
+                bounds.push(b);
+            }
+        }
+
+        let mut commands: Vec<Box<dyn Command>> = Vec::new();
+        for (selection, offset) in targets.into_iter().zip(offsets(&bounds)) {
+            if offset.length_squared() <= f32::EPSILON {
+                continue;
+            }
+            let Some(layer) = self.layers.get_mut(selection.layer) else {
+                continue;
+            };
+            let scene = layer.scene_mut();
+            let Some(node) = scene.get(selection.node) else {
+                continue;
+            };
+            let before = *node.transform();
+            let parent_world = match node.parent() {
+                Some(parent) => scene.world_transform(parent),
+                None => glam::Affine2::IDENTITY,
+            };
+            commands.push(Box::new(SetTransform {
+                layer: selection.layer,
+                node: selection.node,
+                before,
+                after: Transform2D {
+                    translation: before.translation
+                        + parent_world.inverse().transform_vector2(offset),
+                    ..before
+                },
+            }));
+        }
+
+        let command = Compound::new(name, commands);
+        if command.is_empty() {
+            return;
+        }
+        let mut ctx = CommandContext {
+            layers: &mut self.layers,
+            device: &self.device,
+            queue: &self.queue,
+        };
+        if let Err(e) = self.history.execute(Box::new(command), &mut ctx) {
+            log::error!("Unable to {} selection: {e}", name.to_lowercase());
+        }
+    }
+
+    /// Copies everything selected. Returns whether there was anything to
+    /// copy.
+    pub fn copy_selection(&mut self) -> anyhow::Result<bool> {
+        let selections = self.selected_roots();
+        if selections.is_empty() {
+            return Ok(false);
+        }
+        let nodes = selections
+            .iter()
+            .filter_map(|s| {
+                let layer = self.layers.get_mut(s.layer)?;
+                Some(Clipboard::capture(layer.scene_mut(), s.node))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.clipboard.copy(nodes)?;
+        Ok(true)
+    }
+
+    /// Puts `text` on the system clipboard.
+    pub fn copy_text(&mut self, text: &str) {
+        self.clipboard.copy_text(text);
+    }
+
+    /// The text on the system clipboard. If it has to be read
+    /// asynchronously this returns `None` and it turns up in
+    /// [Canvas::poll_clipboard_text] later.
+    pub fn clipboard_text(&mut self) -> Option<String> {
+        self.clipboard.text()
+    }
+
+    pub fn poll_clipboard_text(&mut self) -> Option<String> {
+        self.clipboard.poll_text()
+    }
+
+    /// Copies everything selected, then deletes it as one step of undo
+    /// history. Nothing is deleted if it couldn't be copied.
+    pub fn cut_selection(&mut self) -> anyhow::Result<()> {
+        if self.copy_selection()? {
+            self.delete_selection();
+        }
+        Ok(())
+    }
+
+    /// Pastes the clipboard onto the active layer and selects the result.
+    pub fn paste(&mut self) {
+        if !self.layers.active().is_editable() {
+            log::info!("Layer \"{}\" can't be edited", self.layers.active().name());
+            return;
+        }
+        if let Some(paste) = self.clipboard.paste() {
+            self.apply_paste(paste);
+        }
+    }
+
+    /// Finishes a paste that was waiting on the system clipboard. Call it
+    /// every frame.
+    pub fn poll_paste(&mut self) {
+        if let Some(paste) = self.clipboard.poll_paste() {
+            if self.layers.active().is_editable() {
+                self.apply_paste(paste);
+            }
+        }
+    }
+
+    fn apply_paste(&mut self, paste: Paste) {
+        let layer_index = self.layers.active_index();
+        let result = match paste {
+            Paste::Svg(svg) => self.import_svg(svg.as_bytes()).map(|node| vec![node]),
+            Paste::Nodes(nodes) => self.paste_nodes(nodes),
+        };
+        match result {
+            Ok(nodes) => self.select.select_all(nodes.into_iter().map(|node| {
+                crate::tools::select::Selection {
+                    layer: layer_index,
+                    node,
+                }
+            })),
+            Err(e) => log::error!("Unable to paste: {e}"),
+        }
+    }
+
+    /// Adds pasted nodes to the active layer, as one step of undo history.
+    fn paste_nodes(&mut self, nodes: Vec<NodeData>) -> anyhow::Result<Vec<NodeId>> {
+        let layer_index = self.layers.active_index();
+        let mut ids = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            let built = node.build(
+                self.layers.active_mut(),
+                None,
+                &self.renderer.font,
+                &self.renderer.text_pipeline,
+                &self.device,
+                &self.queue,
+            );
+            match built {
+                Ok(id) => ids.push(id),
+                Err(e) => {
+                    // All or nothing, like the commands it would have been.
+                    for &id in &ids {
+                        self.layers
+                            .active_mut()
+                            .remove(id, &self.device, &self.queue);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        let commands = ids
+            .iter()
+            .map(|&id| Box::new(AddNode::added(layer_index, id)) as Box<dyn Command>)
+            .collect();
+        self.history
+            .push(Box::new(Compound::new("Paste", commands)));
+        Ok(ids)
+    }
+
+    pub fn set_selection_color(&mut self, color: glam::Vec4) {
+        let Some(selection) = self.select.selection() else {
+            return;
+        };
+        let mut ctx = CommandContext {
+            layers: &mut self.layers,
+            device: &self.device,
+            queue: &self.queue,
+        };
+        let command = Box::new(SetColor::new(selection.layer, selection.node, color));
+        if let Err(e) = self.history.execute(command, &mut ctx) {
+            log::error!("Unable to change color: {e}");
+        }
+    }
+
+    /// Sets the fill of the selected shape. Passing `None` removes it.
+    pub fn set_selection_fill(&mut self, fill: Option<crate::shape::Fill>) {
+        let Some(selection) = self.select.selection() else {
+            return;
+        };
+        let mut ctx = CommandContext {
+            layers: &mut self.layers,
+            device: &self.device,
+            queue: &self.queue,
+        };
+        let command = Box::new(SetFill::new(selection.layer, selection.node, fill));
+        if let Err(e) = self.history.execute(command, &mut ctx) {
+            log::error!("Unable to change fill: {e}");
+        }
+    }
+
+    /// Loads a PNG, JPEG, WebP or KTX2 image from the resource directory so shapes
+    /// can be filled with it. It's named after `path`, so it's reloaded
+    /// when the file changes.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_texture_file(&mut self, path: &str) -> anyhow::Result<TextureId> {
+        let data = self.resources.load_binary(path)?;
+        self.load_texture(path, &data)
+    }
+
+    /// Decodes an image so shapes can be filled with it.
+    pub fn load_texture(&mut self, name: &str, data: &[u8]) -> anyhow::Result<TextureId> {
+        self.renderer
+            .textures
+            .load(name, data, &self.device, &self.queue)
+    }
+
+    /// Where the app's files are loaded from. A loading screen can clone it
+    /// to load more in the background, and draw its progress.
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
+
+    /// Assets loaded so far, like the font, for sharing them instead of
+    /// loading them again.
+    pub fn assets(&mut self) -> &mut AssetCache {
+        &mut self.assets
+    }
+
+    pub fn textures(&self) -> &TextureRegistry {
+        self.renderer.textures()
+    }
+
+    /// The pipelines everything is drawn with, for drawing into the same
+    /// render passes from outside.
+    pub fn renderer(&self) -> &Renderer {
+        &self.renderer
+    }
+
+    /// Fills the selected shape with a texture, one tile per texture pixel.
+    pub fn set_selection_texture(&mut self, texture: TextureId) {
+        let Some(size) = self.renderer.textures.get(texture).map(|t| t.size()) else {
+            return;
+        };
+        let fill = crate::shape::TextureFill::new(texture, size.as_vec2());
+        self.set_selection_fill(Some(crate::shape::Fill::Texture(fill)));
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    pub fn set_grid_settings(&mut self, settings: GridSettings) {
+        self.grid.set_settings(settings, &self.queue);
+    }
+
+    pub fn toggle_grid(&mut self) {
+        self.grid.set_visible(!self.grid.is_visible());
+    }
+
+    /// Sets how the selected shape merges with the one before it in
+    /// [DrawMode::Sdf].
+    pub fn set_selection_sdf_op(&mut self, op: crate::shape::SdfOp) {
+        let Some(selection) = self.select.selection() else {
+            return;
+        };
+        let mut ctx = CommandContext {
+            layers: &mut self.layers,
+            device: &self.device,
+            queue: &self.queue,
+        };
+        let command = Box::new(SetSdfOp::new(selection.layer, selection.node, op));
+        if let Err(e) = self.history.execute(command, &mut ctx) {
+            log::error!("Unable to change SDF operation: {e}");
+        }
+    }
+
+    /// Adds a shared style that shapes can be pointed at with
+    /// [Canvas::set_selection_style]. Returns `None` if there are already
+    /// [style::MAX_STYLES] styles.
+    pub fn add_style(&mut self, style: Style) -> Option<StyleId> {
+        self.layers.styles_mut().add(style)
+    }
+
+    pub fn styles(&self) -> &StyleSheet {
+        self.layers.styles()
+    }
+
+    /// Replaces a style, updating every shape that uses it.
+    pub fn set_style(&mut self, id: StyleId, style: Style) {
+        let mut ctx = CommandContext {
+            layers: &mut self.layers,
+            device: &self.device,
+            queue: &self.queue,
+        };
+        let command = Box::new(SetStyle::new(id, style));
+        if let Err(e) = self.history.execute(command, &mut ctx) {
+            log::error!("Unable to edit style: {e}");
+        }
+    }
+
+    /// Points the selected shapes at a shared style, or back at their own
+    /// color and fill with `None`.
+    pub fn set_selection_style(&mut self, style: Option<StyleId>) {
+        let mut commands: Vec<Box<dyn Command>> = Vec::new();
+        for selection in self.select.selections() {
+            let is_shape = self
+                .layers
+                .get(selection.layer)
+                .and_then(|layer| layer.scene().get(selection.node))
+                .is_some_and(|node| node.shape().is_some());
+            if is_shape {
+                commands.push(Box::new(SetShapeStyle::new(
+                    selection.layer,
+                    selection.node,
+                    style,
+                )));
+            }
+        }
+
+        let command = Compound::new("Change shape style", commands);
+        if command.is_empty() {
+            return;
+        }
+        let mut ctx = CommandContext {
+            layers: &mut self.layers,
+            device: &self.device,
+            queue: &self.queue,
+        };
+        if let Err(e) = self.history.execute(Box::new(command), &mut ctx) {
+            log::error!("Unable to change shape style: {e}");
+        }
+    }
+
+    /// Constrains the primary selection against the other selected line.
+    /// Both need to be lines on the same layer. The primary selection is
+    /// the one that gets adjusted.
+    pub fn constrain_selection(&mut self, kind: ConstraintKind) {
+        let [a, b] = self.select.selections() else {
+            log::info!("Select two lines to constrain");
+            return;
+        };
+        if a.layer != b.layer {
+            log::info!("Only lines on the same layer can be constrained");
+            return;
+        }
+        let Some(layer) = self.layers.get_mut(a.layer) else {
+            return;
+        };
+        let scene = layer.scene_mut();
+        if crate::constraints::line(scene, a.node).is_none()
+            || crate::constraints::line(scene, b.node).is_none()
+        {
+            log::info!("Only straight lines can be constrained");
+            return;
+        }
+
+        let command = AddConstraint::new(a.layer, Constraint::new(kind, a.node, b.node));
+        let mut ctx = CommandContext {
+            layers: &mut self.layers,
+            device: &self.device,
+            queue: &self.queue,
+        };
+        if let Err(e) = self.history.execute(Box::new(command), &mut ctx) {
+            log::error!("Unable to add constraint: {e}");
+        }
+    }
+
+    /// Uploads the styles if they changed.
+    fn prepare_styles(&mut self) {
+        if self.layers.styles_mut().take_dirty() {
+            self.renderer
+                .geometry_pipeline
+                .set_styles(self.layers.styles(), &self.queue);
+        }
+    }
+
+    /// Sets how far apart shapes start to blend in [DrawMode::Sdf].
+    pub fn set_sdf_blend_radius(&mut self, radius: f32) {
+        self.renderer.sdf_pipeline.set_blend_radius(radius);
+    }
+
+    pub fn draw_mode(&self) -> DrawMode {
+        self.draw_mode
+    }
+
+    pub fn set_draw_mode(&mut self, mode: DrawMode) {
+        self.draw_mode = mode;
+        self.renderer
+            .geometry_pipeline
+            .set_draw_mode(mode, &self.queue);
+        log::info!("Draw mode: {mode:?}");
+    }
+
+    /// Switches to the next [DrawMode].
+    pub fn cycle_draw_mode(&mut self) {
+        let mut mode = self.draw_mode;
+        mode.increment();
+        self.set_draw_mode(mode);
+    }
+
+    pub fn set_brush_color(&mut self, color: glam::Vec4) {
+        self.brush_color = color;
+    }
+
+    /// Reads back the color under the cursor from the next frame drawn to
+    /// the window, and applies it to `target` once it arrives. What's picked
+    /// is what's shown, so the grid, overlays and post effects are included.
+    pub fn pick_color(&mut self, target: ColorTarget) {
+        let pixel = (self.cursor_screen * self.scale_factor).as_uvec2();
+        self.request_redraw();
+        let frame = self.captures.request();
+        self.eyedropper.begin(frame, pixel, target);
+    }
+
+    /// The last color picked with the eyedropper.
+    pub fn picked_color(&self) -> Option<glam::Vec4> {
+        self.eyedropper.color()
+    }
+
+    fn apply_picked_color(&mut self) {
+        match self.eyedropper.poll() {
+            Some(Ok((color, target))) => {
+                log::info!("Picked color {color}");
+                match target {
+                    ColorTarget::Stroke => self.set_brush_color(color),
+                    ColorTarget::Fill => {
+                        self.set_selection_fill(Some(crate::shape::Fill::Solid(color)))
+                    }
+                }
+            }
+            Some(Err(e)) => log::error!("Unable to pick a color: {e}"),
+            None => {}
+        }
+    }
+
+    /// What's held down and what changed since the last frame.
+    pub fn input(&self) -> &InputState {
+        &self.input
+    }
+
+    pub fn input_mut(&mut self) -> &mut InputState {
+        &mut self.input
+    }
+
+    /// Starts recording input, dropping any recording in progress.
+    pub fn start_recording(&mut self) {
+        self.recorder = Some(InputRecorder::new(self.scale_factor as f64));
+    }
+
+    /// Stops recording and returns what was recorded. Input since the last
+    /// frame was drawn is left out, which includes whatever stopped the
+    /// recording.
+    pub fn stop_recording(&mut self) -> Option<InputRecording> {
+        self.recorder.take().map(InputRecorder::finish)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Records the next `count` frames drawn to the window to `path`,
+    /// replacing any recording in progress. GIFs play back at `fps` frames a
+    /// second.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn start_frame_recording(
+        &mut self,
+        path: impl Into<std::path::PathBuf>,
+        format: crate::recording::RecordingFormat,
+        count: u32,
+        fps: u32,
+    ) -> anyhow::Result<()> {
+        // Waits for the last recording to finish writing.
+        self.frame_recorder = None;
+        self.frame_recorder = Some(crate::recording::FrameRecorder::new(
+            path, format, count, fps,
+        )?);
+        self.request_redraw();
+        Ok(())
+    }
+
+    /// Stops capturing frames. The ones already captured are still
+    /// written.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn stop_frame_recording(&mut self) {
+        if let Some(recorder) = &mut self.frame_recorder {
+            recorder.stop();
+        }
+    }
+
+    pub fn is_recording_frames(&self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        return self
+            .frame_recorder
+            .as_ref()
+            .is_some_and(|recorder| recorder.is_recording());
+        #[cfg(target_arch = "wasm32")]
+        false
+    }
+
+    /// Plays `recording` back in place of the user's input, which is
+    /// ignored until it's done or a key or button is pressed.
+    pub fn start_replay(&mut self, recording: InputRecording) {
+        if recording.scale_factor != self.scale_factor as f64 {
+            log::warn!(
+                "Input was recorded at scale factor {}, not {}",
+                recording.scale_factor,
+                self.scale_factor
+            );
+        }
+        self.recorder = None;
+        self.drag.cancel();
+        self.replayer = Some(InputReplayer::new(recording));
+    }
+
+    pub fn stop_replay(&mut self) {
+        self.replayer = None;
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replayer.is_some()
+    }
+
+    /// Hides the cursor and keeps it where it is, or in the window if the
+    /// platform can't hold it still. Mouse movement still shows up in
+    /// [InputState::raw_motion].
+    pub fn set_pointer_locked(&mut self, locked: bool) -> anyhow::Result<()> {
+        use winit::window::CursorGrabMode;
+        if locked {
+            self.window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| self.window.set_cursor_grab(CursorGrabMode::Confined))?;
+        } else {
+            self.window.set_cursor_grab(CursorGrabMode::None)?;
+        }
+        self.window.set_cursor_visible(!locked);
+        Ok(())
+    }
+
+    /// Locks the pointer and starts panning or rotating the view with the
+    /// mouse, which can go on for as long as the mouse moves.
+    pub fn begin_view_drag(&mut self, drag: ViewDrag) {
+        if let Err(e) = self.set_pointer_locked(true) {
+            log::warn!("Unable to lock the pointer: {e}");
+        }
+        self.camera_animator.stop();
+        self.view_drag = Some(drag);
+    }
+
+    pub fn end_view_drag(&mut self) {
+        if self.view_drag.take().is_some() {
+            if let Err(e) = self.set_pointer_locked(false) {
+                log::warn!("Unable to unlock the pointer: {e}");
+            }
+        }
+    }
+
+    pub fn view_drag(&self) -> Option<ViewDrag> {
+        self.view_drag
+    }
+
+    /// Moves the view by however far the mouse moved this frame if there's
+    /// a view drag going on.
+    pub fn update_view_drag(&mut self) {
+        let motion = self.input.raw_motion();
+        match self.view_drag {
+            _ if motion == glam::Vec2::ZERO => {}
+            Some(ViewDrag::Pan) => {
+                self.pan_zoom(self.cursor_screen, self.cursor_screen + motion, 1.0)
+            }
+            Some(ViewDrag::Rotate) => self.rotate_view(motion.x * VIEW_DRAG_ROTATION),
+            None => {}
+        }
+    }
+
+    /// Zooms around the cursor by however far the wheel scrolled this
+    /// frame.
+    pub fn zoom_with_wheel(&mut self) {
+        let scroll = self.input.scroll_axis();
+        if scroll != 0.0 {
+            let scale = 2f32.powf(scroll / WHEEL_ZOOM_PIXELS);
+            self.pan_zoom(self.cursor_screen, self.cursor_screen, scale);
+        }
+    }
+
+    pub fn modifiers(&self) -> ModifiersState {
+        self.input.modifiers()
+    }
+
+    /// How long frames are taking. States can animate with
+    /// [FrameClock::dt] in their updates.
+    pub fn clock(&self) -> &FrameClock {
+        &self.clock
+    }
+
+    pub fn redraw_policy(&self) -> RedrawPolicy {
+        self.redraw_policy
+    }
+
+    pub fn set_redraw_policy(&mut self, policy: RedrawPolicy) {
+        self.redraw_policy = policy;
+        self.window.request_redraw();
+    }
+
+    /// Flips between continuous and reactive redrawing.
+    pub fn toggle_redraw_policy(&mut self) {
+        self.set_redraw_policy(match self.redraw_policy {
+            RedrawPolicy::Continuous => RedrawPolicy::Reactive,
+            RedrawPolicy::Reactive => RedrawPolicy::Continuous,
+        });
+    }
+
+    /// Draws another frame after this one even if nothing else would. States
+    /// that animate should call it every update while they do.
+    pub fn request_redraw(&mut self) {
+        self.redraw_requested = true;
+        self.window.request_redraw();
+    }
+
+    /// Whether anything needs the next frame drawn without waiting for
+    /// input. Always true when redrawing continuously.
+    fn is_animating(&self) -> bool {
+        self.redraw_policy == RedrawPolicy::Continuous
+            || self.camera_animator.is_animating()
+            || self.follow.is_some()
+            || self.input.is_scrolling()
+            || self.replayer.is_some()
+            || self.eyedropper.is_picking()
+            || self.captures.is_pending()
+            || self.is_recording_frames()
+            || self.clipboard.is_paste_pending()
+            // Keeps the numbers current.
+            || self.perf.is_visible()
+    }
+
+    pub fn post_effects(&self) -> &[PostEffect] {
+        self.post.as_ref().map_or(&[], |post| post.effects())
+    }
+
+    /// Runs the whole frame, HUD included, through `effects` on its way to
+    /// the window. They can be changed every frame, and an empty list turns
+    /// post-processing off.
+    pub fn set_post_effects(&mut self, effects: Vec<PostEffect>) -> anyhow::Result<()> {
+        if self.post.is_none() && effects.is_empty() {
+            return Ok(());
+        }
+        self.post_processor()?.set_effects(effects);
+        Ok(())
+    }
+
+    /// How the frame is tonemapped. `None` unless drawing to an HDR surface.
+    pub fn tonemap(&self) -> Option<&Tonemap> {
+        self.post.as_ref()?.tonemap()
+    }
+
+    pub fn set_tonemap(&mut self, tonemap: Tonemap) {
+        if let Some(post) = &mut self.post {
+            post.set_tonemap(tonemap);
+        }
+    }
+
+    fn post_processor(&mut self) -> anyhow::Result<&mut PostProcessor> {
+        if self.post.is_none() {
+            self.post = Some(PostProcessor::new(
+                &self.renderer.shader,
+                self.renderer.format(),
+                self.renderer.sample_count(),
+                self.config.width,
+                self.config.height,
+                &self.device,
+            )?);
+        }
+        Ok(self.post.as_mut().unwrap())
+    }
+
+    /// Calls `callback` with the source of the shader at `path` each time
+    /// it's saved, so it can rebuild what uses it. Errors it returns are
+    /// drawn over the frame until it succeeds. Does nothing in release
+    /// builds or on the web.
+    pub fn watch_shader(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        callback: impl FnMut(&str, &wgpu::Device) -> anyhow::Result<()> + 'static,
+    ) -> anyhow::Result<()> {
+        #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+        if let Some(watcher) = &mut self.shader_watcher {
+            watcher.watch(path.as_ref(), Box::new(callback))?;
+        }
+        #[cfg(any(not(debug_assertions), target_arch = "wasm32"))]
+        let _ = (path, callback);
+        Ok(())
+    }
+
+    /// Rebuilds whatever uses the shaders saved since the last frame.
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    fn reload_shaders(&mut self) {
+        let Some(watcher) = &self.shader_watcher else {
+            return;
+        };
+        let changed = watcher.changed();
+        if changed.is_empty() {
+            return;
+        }
+        for path in changed {
+            let result = std::fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|source| self.reload_shader(&path, &source));
+            self.shader_errors.retain(|(errored, _)| *errored != path);
+            match result {
+                Ok(()) => log::info!("Reloaded {}", path.display()),
+                Err(e) => {
+                    log::error!("Unable to reload {}: {e:#}", path.display());
+                    let message = format!("{}: {e:#}", path.display());
+                    self.shader_errors.push((path, message));
+                }
+            }
+        }
+        self.shader_error_text = None;
+        if !self.shader_errors.is_empty() {
+            let errors: Vec<_> = self.shader_errors.iter().map(|(_, e)| e.as_str()).collect();
+            match self.screen_text(&errors.join("\n"), glam::vec2(8.0, 8.0), 14.0) {
+                Ok(text) => self.shader_error_text = Some(text),
+                Err(e) => log::error!("Unable to show shader errors: {e}"),
+            }
+        }
+    }
+
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    fn reload_shader(&mut self, path: &std::path::Path, source: &str) -> anyhow::Result<()> {
+        if let Some(callback) = self
+            .shader_watcher
+            .as_mut()
+            .and_then(|watcher| watcher.user_shader(path))
+        {
+            return callback(source, &self.device);
+        }
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return Ok(());
+        };
+        let source = crate::wgsl::compose(source, crate::hot_reload::snippet)?;
+        if !self.renderer.reload_shader(name, &source, &self.device)? {
+            return Ok(());
+        }
+        // The shared module is used outside the renderer too.
+        if name == "shader.wgsl" {
+            let shader = &self.renderer.shader;
+            self.grid.reload(shader, &self.device)?;
+            self.viewport_frame.reload(shader, &self.device)?;
+            if let Some(post) = &mut self.post {
+                post.reload(shader, &self.device)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reloads the resources changed on disk since the last frame, and
+    /// returns their paths in the resource directory. The font, the
+    /// interaction machine and textures named after their path are
+    /// reloaded.
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    pub(crate) fn reload_assets(&mut self) -> Vec<std::path::PathBuf> {
+        let Some(watcher) = &self.asset_watcher else {
+            return Vec::new();
+        };
+        let changed = watcher.changed();
+        for path in &changed {
+            if let Err(e) = self.reload_asset(path) {
+                log::error!("Unable to reload {}: {e:#}", path.display());
+            }
+        }
+        changed
+    }
+
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    fn reload_asset(&mut self, path: &std::path::Path) -> anyhow::Result<()> {
+        if path == std::path::Path::new(crate::renderer::DEFAULT_FONT_PATH) {
+            let font = crate::resources::font::Font::load(
+                &self.resources,
+                path,
+                crate::renderer::FALLBACK_CHAR,
+                &self.device,
+                &self.queue,
+            )?;
+            let font = self.assets.insert(path, font);
+            self.renderer.set_font(font, &self.device, &self.queue);
+            for layer in self.layers.iter_mut() {
+                layer.rebuild_text(
+                    &self.renderer.font,
+                    &self.renderer.text_pipeline,
+                    &self.device,
+                )?;
+            }
+        } else if path == std::path::Path::new(INTERACTION_MACHINE_PATH) {
+            let mut interaction = MachineDefinition::load(&self.resources, path)?
+                .builder()
+                .build();
+            interaction.reset(Interaction::Tool(self.tool));
+            self.interaction = interaction;
+        } else if let Some(id) = path
+            .to_str()
+            .and_then(|name| self.renderer.textures.find(name))
+        {
+            let data = self.resources.load_binary(path)?;
+            self.renderer
+                .textures
+                .replace(id, &data, &self.device, &self.queue)?;
+        } else {
+            return Ok(());
+        }
+        log::info!("Reloaded {}", path.display());
+        Ok(())
+    }
+
+    /// Shows or hides frame rate, frame times and draw counts.
+    pub fn toggle_perf_overlay(&mut self) {
+        self.perf.set_visible(!self.perf.is_visible());
+    }
+
+    pub fn perf_overlay_visible(&self) -> bool {
+        self.perf.is_visible()
+    }
+
+    /// What the last frame asked of the GPU.
+    pub fn render_stats(&self) -> RenderStats {
+        self.render_stats
+    }
+
+    /// Shows or hides the egui debug panel.
+    #[cfg(feature = "debug-ui")]
+    pub fn toggle_debug_ui(&mut self) {
+        if let Some(debug_ui) = &mut self.debug_ui {
+            debug_ui.set_visible(!debug_ui.is_visible());
+        }
+    }
+
+    /// How often [states::AppState::fixed_update] runs, and how far the
+    /// frame is between steps for interpolating what they move.
+    pub fn fixed_timestep(&self) -> &FixedTimestep {
+        &self.fixed_timestep
+    }
+
+    pub fn set_fixed_step(&mut self, step: std::time::Duration) {
+        self.fixed_timestep.set_step(step);
+    }
+
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    /// Buffers `text` to be drawn at `position` in logical window pixels,
+    /// one line under the other, `size` pixels high.
+    pub fn screen_text(
+        &self,
+        text: &str,
+        position: glam::Vec2,
+        size: f32,
+    ) -> anyhow::Result<TextBlock> {
+        self.renderer.text(text, position, size, &self.device)
+    }
+
+    pub fn draw_screen_text(&self, pass: &mut wgpu::RenderPass<'_>, text: &TextBlock) {
+        self.renderer.draw_text(pass, text, &self.screen_binding);
+    }
+
+    /// How wide one line of `text` is when drawn `size` pixels high, and
+    /// how far down from its top the baseline is.
+    pub fn text_metrics(&self, text: &str, size: f32) -> (f32, f32) {
+        self.renderer.text_metrics(text, size)
+    }
+
+    /// Buffers lines to be drawn in logical window pixels, like underlines
+    /// under text drawn with [Canvas::screen_text].
+    pub fn screen_lines(&self, lines: &[[glam::Vec2; 2]], color: glam::Vec4) -> Overlay {
+        self.renderer.lines(lines, color, &self.device, &self.queue)
+    }
+
+    pub fn draw_screen_lines(&self, pass: &mut wgpu::RenderPass<'_>, lines: &Overlay) {
+        self.renderer
+            .draw_overlay(pass, lines, &self.screen_binding);
+    }
+
+    /// Adds a text node to the active layer with its top left corner at
+    /// `position` in the world, sized to look `size` pixels high at the
+    /// current zoom, and selects it.
+    pub fn add_text(&mut self, text: &str, position: glam::Vec2, size: f32) -> anyhow::Result<()> {
+        if !self.layers.active().is_editable() {
+            anyhow::bail!("Layer \"{}\" can't be edited", self.layers.active().name());
+        }
+        let scale = self.renderer.font.scale(size) / self.camera.zoom();
+        let transform = Transform2D {
+            translation: position,
+            rotation: 0.0,
+            scale: glam::Vec2::splat(scale),
+        };
+        let layer = self.layers.active_index();
+        let node = self.layers.active_mut().add_text(
+            text,
+            transform,
+            None,
+            &self.renderer.font,
+            &self.renderer.text_pipeline,
+            &self.device,
+        )?;
+        self.history.push(Box::new(AddNode::added(layer, node)));
+        self.select
+            .select(Some(crate::tools::select::Selection { layer, node }));
+        Ok(())
+    }
+
+    /// Lays out a diagram of `machine` to be drawn at `origin` in logical
+    /// window pixels.
+    pub fn screen_diagram<S, E, C>(
+        &self,
+        machine: &StateMachine<S, E, C>,
+        origin: glam::Vec2,
+    ) -> anyhow::Result<StateDiagram>
+    where
+        S: Clone + Eq + std::hash::Hash + std::fmt::Debug,
+        E: Clone + Eq + std::hash::Hash + std::fmt::Debug,
+    {
+        StateDiagram::new(
+            machine,
+            origin,
+            &self.renderer.font,
+            &self.renderer.text_pipeline,
+            &self.renderer.geometry_pipeline,
+            &self.device,
+            &self.queue,
+        )
+    }
+
+    pub fn draw_screen_diagram(&self, pass: &mut wgpu::RenderPass<'_>, diagram: &StateDiagram) {
+        diagram.draw(
+            pass,
+            &self.renderer.geometry_pipeline,
+            &self.renderer.text_pipeline,
+            &self.screen_binding,
+        );
+    }
+
+    pub fn interaction(&self) -> &StateMachine<Interaction, InteractionEvent> {
+        &self.interaction
+    }
+
+    pub fn snapper_mut(&mut self) -> &mut Snapper {
+        &mut self.snapper
+    }
+
+    pub fn tool(&self) -> ToolKind {
+        self.tool
+    }
+
+    pub fn set_tool(&mut self, tool: ToolKind) {
+        if self.tool == tool {
+            return;
+        }
+        // Finish whatever the previous tool was doing.
+        self.mouse_released();
+        self.tool = tool;
+        self.interact(InteractionEvent::SetTool(tool));
+    }
+
+    /// Switches to the select tool, flipping between marquee and lasso
+    /// selection if it's already active.
+    pub fn toggle_select_mode(&mut self) {
+        if self.tool == ToolKind::Select {
+            let mode = match self.select.mode() {
+                SelectMode::Marquee => SelectMode::Lasso,
+                SelectMode::Lasso => SelectMode::Marquee,
+            };
+            self.select.set_mode(mode);
+        }
+        self.set_tool(ToolKind::Select);
+        log::info!("Select mode: {:?}", self.select.mode());
+    }
+
+    pub fn selection(&self) -> Option<crate::tools::select::Selection> {
+        self.select.selection()
+    }
+
+    /// Starts a stroke for `pointer` at `at` in the world, finishing any
+    /// it was already drawing.
+    fn begin_stroke(&mut self, pointer: PointerId, at: glam::Vec2) {
+        self.end_stroke(pointer, at);
+        if !self.layers.active().is_editable() {
+            log::info!("Layer \"{}\" can't be edited", self.layers.active().name());
+            return;
+        }
+        let start = self
+            .snapper
+            .snap(&mut self.layers, at, None, false, 1.0)
+            .point;
+        let mut brush = self.brush.clone();
+        brush.begin(start);
+        let node = self.layers.active_mut().add_shape(
+            "Stroke",
+            Shape::polyline(Vec::new(), false, self.brush_color),
+            Transform2D::IDENTITY,
+            None,
+            &self.device,
+            &self.queue,
+        );
+        let layer = self.layers.active_index();
+        self.strokes.insert(pointer, Stroke { brush, layer, node });
+        self.flush_stroke(pointer);
+    }
+
+    fn drag_stroke(&mut self, pointer: PointerId, at: glam::Vec2) {
+        if let Some(stroke) = self.strokes.get_mut(&pointer) {
+            stroke.brush.drag(at);
+            self.flush_stroke(pointer);
+        }
+    }
+
+    fn end_stroke(&mut self, pointer: PointerId, at: glam::Vec2) {
+        let Some(stroke) = self.strokes.get_mut(&pointer) else {
+            return;
+        };
+        stroke.brush.drag(at);
+        let points = stroke.brush.end();
+        self.flush_stroke(pointer);
+        log::debug!("Finished stroke with {} points", points.len());
+        if let Some(Stroke { layer, node, .. }) = self.strokes.remove(&pointer) {
+            self.history.push(Box::new(AddNode::added(layer, node)));
+        }
+    }
+
+    fn begin_line(&mut self) {
+        if !self.layers.active().is_editable() {
+            log::info!("Layer \"{}\" can't be edited", self.layers.active().name());
+            return;
+        }
+        let start = self.snap_cursor(None).point;
+        self.line.begin(start);
+    }
+
+    fn end_line(&mut self) {
+        if !self.line.is_drawing() {
+            return;
+        }
+        let end = self.snap_cursor(self.line.start()).point;
+        self.line.drag(end);
+        let Some(points) = self.line.end() else {
+            return;
+        };
+        let id = self.layers.active_mut().add_shape(
+            "Line",
+            Shape::polyline(points.to_vec(), false, self.brush_color),
+            Transform2D::IDENTITY,
+            None,
+            &self.device,
+            &self.queue,
+        );
+        self.history
+            .push(Box::new(AddNode::added(self.layers.active_index(), id)));
+    }
+
+    /// Adds a dimension between the ends of the measure tool's drag. Ends
+    /// that land on a shape's point stay attached to it.
+    fn end_measure(&mut self) {
+        if !self.measure.is_drawing() {
+            return;
+        }
+        let end = self.snap_cursor(self.measure.start()).point;
+        self.measure.drag(end);
+        let Some([start, end]) = self.measure.end() else {
+            return;
+        };
+        let index = self.layers.active_index();
+        let scene = self.layers.active_mut().scene_mut();
+        let radius = ANCHOR_TOLERANCE / self.camera.zoom();
+        let dimension = Dimension::new(
+            Anchor::at(scene, start, radius),
+            Anchor::at(scene, end, radius),
+        );
+        let mut ctx = CommandContext {
+            layers: &mut self.layers,
+            device: &self.device,
+            queue: &self.queue,
+        };
+        let command = Box::new(AddDimension::new(index, dimension));
+        if let Err(e) = self.history.execute(command, &mut ctx) {
+            log::error!("Unable to add dimension: {e}");
+        }
+    }
+
+    /// Snaps the cursor for the line and measure tools. Once a line is
+    /// started from `origin`, holding shift locks it to the angle
+    /// increments.
+    fn snap_cursor(&mut self, origin: Option<glam::Vec2>) -> crate::snapping::Snap {
+        self.snapper.snap(
+            &mut self.layers,
+            self.cursor,
+            origin,
+            self.input.modifiers().shift_key(),
+            1.0,
+        )
+    }
+
+    /// Appends the points the brush produced since the last flush to the
+    /// stroke's shape so they show up while the user is still dragging.
+    /// Adds the points `pointer`'s stroke has made since the last flush to
+    /// its shape.
+    fn flush_stroke(&mut self, pointer: PointerId) {
+        let Some(stroke) = self.strokes.get_mut(&pointer) else {
+            return;
+        };
+        let points = stroke.brush.drain_new();
+        if points.is_empty() {
+            return;
+        }
+        if let Some(layer) = self.layers.get_mut(stroke.layer) {
+            layer.extend_polyline(stroke.node, points, &self.device, &self.queue);
+        }
+    }
+
+    /// Returns the topmost shape under the cursor.
+    pub fn pick_at_cursor(&mut self) -> Option<crate::picking::Hit> {
+        crate::picking::pick(&mut self.layers, self.cursor, PICK_TOLERANCE)
+    }
+
+    pub fn layers(&self) -> &LayerStack {
+        &self.layers
+    }
+
+    /// Adding, removing or moving layers through this can't be undone and
+    /// clears undo history. [Canvas::add_layer], [Canvas::remove_layer] and
+    /// [Canvas::move_layer] can be.
+    pub fn layers_mut(&mut self) -> &mut LayerStack {
+        &mut self.layers
+    }
+
+    /// Adds a layer above the active one and makes it active.
+    pub fn add_layer(&mut self, name: impl Into<String>) -> usize {
+        self.forget_history_if_layers_changed();
+        let index = self
+            .layers
+            .add(name, &self.renderer.geometry_pipeline, &self.device);
+        self.history.push(Box::new(AddLayer::added(index)));
+        self.history_revision = self.layers.revision();
+        self.select.select(None);
+        index
+    }
+
+    /// Removes the layer at `index` and everything on it. The last layer
+    /// can't be removed.
+    pub fn remove_layer(&mut self, index: usize) -> anyhow::Result<()> {
+        self.execute_layer_command(Box::new(RemoveLayer::new(index)))
+    }
+
+    /// Moves the layer at `from` so it ends up at `to`.
+    pub fn move_layer(&mut self, from: usize, to: usize) -> anyhow::Result<()> {
+        self.execute_layer_command(Box::new(MoveLayer::new(from, to)))
+    }
+
+    fn execute_layer_command(&mut self, command: Box<dyn Command>) -> anyhow::Result<()> {
+        self.forget_history_if_layers_changed();
+        let mut ctx = CommandContext {
+            layers: &mut self.layers,
+            device: &self.device,
+            queue: &self.queue,
+        };
+        let revision = ctx.layers.revision();
+        let result = self.history.execute(command, &mut ctx);
+        self.drop_stale_selection(revision);
+        self.history_revision = self.layers.revision();
+        result
+    }
+
+    /// Undo history refers to layers by index, so it's forgotten once
+    /// layers have been added, removed or moved without it, like through
+    /// [Canvas::layers_mut], rather than undoing changes on the wrong ones.
+    fn forget_history_if_layers_changed(&mut self) {
+        let revision = self.layers.revision();
+        if revision != self.history_revision {
+            log::debug!("Layers changed outside of undo history, clearing it");
+            self.history.clear();
+            self.select.select(None);
+            self.history_revision = revision;
+        }
+    }
+
+    /// Selections point at layers by index, so once layers have been added,
+    /// removed or moved since `revision` they're dropped rather than left on
+    /// whichever layer ends up where theirs was.
+    fn drop_stale_selection(&mut self, revision: u64) {
+        if self.layers.revision() != revision {
+            self.select.select(None);
+        }
+    }
+
+    /// Adds the paths of an SVG file to the active layer as a single group.
+    /// Fails without adding anything if none of them could be imported.
+    pub fn import_svg(&mut self, data: &[u8]) -> anyhow::Result<NodeId> {
+        anyhow::ensure!(
+            self.layers.active().is_editable(),
+            "Layer \"{}\" can't be edited",
+            self.layers.active().name()
+        );
+        let shapes = crate::svg::import(data)?;
+        anyhow::ensure!(!shapes.is_empty(), "No shapes in SVG");
+        let layer_index = self.layers.active_index();
+        let layer = self.layers.active_mut();
+        let group = layer.add_group("SVG", Transform2D::IDENTITY, None);
+        for imported in shapes {
+            layer.add_shape(
+                imported.name,
+                imported.shape,
+                Transform2D::IDENTITY,
+                Some(group),
+                &self.device,
+                &self.queue,
+            );
+        }
+        self.history
+            .push(Box::new(AddNode::added(layer_index, group)));
+        Ok(group)
+    }
+
+    /// Adds an image to the active layer, centered on the cursor at one
+    /// unit per pixel. Images that are already loaded are reused.
+    pub fn import_image(&mut self, name: &str, data: &[u8]) -> anyhow::Result<NodeId> {
+        anyhow::ensure!(
+            self.layers.active().is_editable(),
+            "Layer \"{}\" can't be edited",
+            self.layers.active().name()
+        );
+        let texture = self.image_texture(name, data)?;
+        let size = self
+            .renderer
+            .textures
+            .get(texture)
+            .map(|t| t.size())
+            .unwrap_or_default();
+        let shape = Shape::image(texture, size.as_vec2());
+        let transform = Transform2D {
+            translation: self.cursor,
+            ..Transform2D::IDENTITY
+        };
+        let layer_index = self.layers.active_index();
+        let name = std::path::Path::new(name)
+            .file_name()
+            .map_or_else(|| name.to_string(), |n| n.to_string_lossy().into_owned());
+        let id = self.layers.active_mut().add_shape(
+            name,
+            shape,
+            transform,
+            None,
+            &self.device,
+            &self.queue,
+        );
+        self.history.push(Box::new(AddNode::added(layer_index, id)));
+        self.select.select(Some(crate::tools::select::Selection {
+            layer: layer_index,
+            node: id,
+        }));
+        Ok(id)
+    }
+
+    /// The texture loaded from the image in `data`, loading it as `name`
+    /// if it isn't already.
+    fn image_texture(&mut self, name: &str, data: &[u8]) -> anyhow::Result<TextureId> {
+        match self.renderer.textures.find_source(data) {
+            Some(texture) => Ok(texture),
+            None => {
+                Texture::ensure_fits(data, name, self.capabilities.max_texture_size())?;
+                self.load_texture(name, data)
+            }
+        }
+    }
+
+    /// Imports SVG files and opens anything that isn't an image as a saved
+    /// document. Images fill the selection, or are added as image objects
+    /// if nothing's selected.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open_file(&mut self, path: &std::path::Path) -> anyhow::Result<()> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("svg") => {
+                self.import_svg(&std::fs::read(path)?)?;
+            }
+            Some("png" | "jpg" | "jpeg") => {
+                let name = path.file_name().unwrap_or(path.as_os_str());
+                let name = name.to_string_lossy();
+                let data = std::fs::read(path)?;
+                if self.select.selection().is_some() {
+                    let texture = self.image_texture(&name, &data)?;
+                    self.set_selection_texture(texture);
+                } else {
+                    self.import_image(&name, &data)?;
+                }
+            }
+            _ => self.open_document(&Document::load(path)?)?,
+        }
+        Ok(())
+    }
+
+    pub fn export_svg(&self) -> String {
+        crate::svg::export(
+            &self.layers,
+            &self.renderer.font.info,
+            &self.renderer.textures,
+        )
+    }
+
+    /// Snapshots the drawing so it can be saved.
+    pub fn document(&self) -> Document {
+        Document::capture(&self.layers, &self.camera, &self.renderer.textures)
+    }
+
+    /// Replaces the current drawing. Undo history doesn't carry over.
+    pub fn open_document(&mut self, document: &Document) -> anyhow::Result<()> {
+        self.layers = document.build_layers(
+            &self.renderer.geometry_pipeline,
+            &mut self.renderer.textures,
+            &self.renderer.font,
+            &self.renderer.text_pipeline,
+            &self.device,
+            &self.queue,
+        )?;
+        self.camera_animator.stop();
+        self.follow = None;
+        document.apply_camera(&mut self.camera);
+        self.camera_binding.update(&self.camera, &self.queue);
+        self.history.clear();
+        self.history_revision = self.layers.revision();
+        self.select.select(None);
+        self.line.end();
+        self.measure.end();
+        self.strokes.clear();
+        Ok(())
+    }
+
+    pub fn set_layer_opacity(&mut self, index: usize, opacity: f32) {
+        if let Some(layer) = self.layers.get_mut(index) {
+            layer.set_opacity(opacity, &self.queue);
+        }
+    }
+
+    /// Smoothly moves the camera so `center` ends up in the middle of the
+    /// view at `zoom`.
+    pub fn animate_camera(&mut self, center: glam::Vec2, zoom: f32) {
+        self.camera_animator
+            .animate_to(CameraTarget { center, zoom });
+    }
+
+    /// Moves the camera one fixed step of its animation. Called from
+    /// [states::AppState::fixed_update].
+    pub fn step_camera(&mut self, step: std::time::Duration) {
+        self.camera_animator
+            .update(&mut self.camera, step.as_secs_f32());
+    }
+
+    /// Keeps `target` in view until [Canvas::stop_following] is called.
+    pub fn follow(&mut self, target: FollowTarget) {
+        self.follow = Some(CameraFollow::new(target));
+    }
+
+    pub fn stop_following(&mut self) {
+        self.follow = None;
+    }
+
+    pub fn following(&self) -> Option<&CameraFollow> {
+        self.follow.as_ref()
+    }
+
+    pub fn following_mut(&mut self) -> Option<&mut CameraFollow> {
+        self.follow.as_mut()
+    }
+
+    /// Starts or stops following the selection.
+    pub fn toggle_follow_selection(&mut self) {
+        match self.follow {
+            Some(_) => self.stop_following(),
+            None => self.follow(FollowTarget::Selection),
+        }
+    }
+
+    /// Animates the camera towards the followed target if it has left the
+    /// dead zone.
+    fn update_follow(&mut self) {
+        let Some(follow) = &self.follow else {
+            return;
+        };
+        let Some(position) = follow.target_position(&mut self.layers, self.select.selection())
+        else {
+            return;
+        };
+        // Measured from where the camera is heading so it doesn't keep
+        // restarting the move.
+        let mut camera = self.camera.clone();
+        let zoom = self
+            .camera_animator
+            .target()
+            .map_or(self.camera.zoom(), |target| target.zoom);
+        camera.set_zoom(zoom);
+        camera.set_center(
+            self.camera_animator
+                .target()
+                .map_or(self.camera.center(), |target| target.center),
+        );
+        if let Some(center) = follow.recenter(&camera, position) {
+            self.animate_camera(center, zoom);
+        }
+    }
+
+    /// Adds a view over `rect` of the window and returns its index. The view
+    /// starts out looking at the same place as the main camera.
+    pub fn add_viewport(&mut self, rect: ViewRect) -> usize {
+        // Falls back to a binding of its own once the shared buffer is full.
+        let binding = self
+            .view_cameras
+            .bind(&self.camera, &self.queue)
+            .unwrap_or_else(|| self.renderer.camera_binder.bind(&self.device, &self.camera));
+        let mut viewport = Viewport::new(rect, binding, &self.queue);
+        let center = self.camera.center();
+        let zoom = self.camera.zoom();
+        viewport.update_camera(&self.queue, |camera| {
+            camera.set_zoom(zoom);
+            camera.set_center(center);
+        });
+        self.viewports.push(viewport);
+        self.viewports.len() - 1
+    }
+
+    pub fn remove_viewport(&mut self, index: usize) -> Option<Viewport> {
+        (index < self.viewports.len()).then(|| self.viewports.remove(index))
+    }
+
+    pub fn viewports(&self) -> &[Viewport] {
+        &self.viewports
+    }
+
+    pub fn viewport_mut(&mut self, index: usize) -> Option<&mut Viewport> {
+        self.viewports.get_mut(index)
+    }
+
+    /// Shows or hides a close up of the area around the cursor in the
+    /// bottom right quarter of the window.
+    pub fn toggle_detail_view(&mut self) {
+        if !self.viewports.is_empty() {
+            self.viewports.clear();
+            return;
+        }
+        let size = self.camera.size() * 0.5;
+        let index = self.add_viewport(ViewRect::new(size, size));
+        let center = self.cursor;
+        let zoom = self.camera.zoom() * DETAIL_ZOOM;
+        self.viewports[index].update_camera(&self.queue, |camera| {
+            camera.set_zoom(zoom);
+            camera.set_center(center);
+        });
+    }
+
+    /// World bounds of everything on the visible layers.
+    fn drawing_bounds(&mut self) -> Option<Bounds> {
+        let mut bounds: Option<Bounds> = None;
+        for layer in self.layers.iter_mut().filter(|l| l.is_visible()) {
+            let scene = layer.scene_mut();
+            for id in scene.roots().to_vec() {
+                if let Some(b) = scene.world_bounds(id) {
+                    bounds = Some(bounds.map_or(b, |a| a.union(&b)));
+                }
+            }
+        }
+        bounds
+    }
+
+    /// Shows or hides an overview of the drawing. Clicking it moves the
+    /// camera there.
+    pub fn toggle_minimap(&mut self) {
+        self.minimap.set_visible(!self.minimap.is_visible());
+    }
+
+    /// Turns the canvas clockwise by `delta` radians around the middle of
+    /// the window.
+    pub fn rotate_view(&mut self, delta: f32) {
+        self.set_view_rotation(self.camera.rotation() + delta);
+    }
+
+    pub fn set_view_rotation(&mut self, rotation: f32) {
+        self.camera.set_rotation(rotation);
+        self.camera_binding.update(&self.camera, &self.queue);
+        self.cursor = self.screen_to_world(self.cursor_screen);
+    }
+
+    /// Animates the camera to show everything on the visible layers.
+    pub fn zoom_to_fit(&mut self) {
+        let Some(bounds) = self.drawing_bounds() else {
+            return;
+        };
+        let view = (self.camera.size() - 2.0 * FIT_MARGIN).max(glam::Vec2::ONE);
+        // How big the bounds look once the canvas is turned.
+        let (sin, cos) = self.camera.rotation().sin_cos();
+        let (sin, cos) = (sin.abs(), cos.abs());
+        let size = bounds.size();
+        let size = glam::vec2(cos * size.x + sin * size.y, sin * size.x + cos * size.y);
+        // A single point or line keeps the zoom along its missing axis.
+        let fit = |view: f32, size: f32| {
+            if size > f32::EPSILON {
+                view / size
+            } else {
+                f32::INFINITY
+            }
+        };
+        let zoom = fit(view.x, size.x).min(fit(view.y, size.y));
+        let zoom = if zoom.is_finite() {
+            zoom
+        } else {
+            self.camera.zoom()
+        };
+        self.animate_camera(bounds.center(), zoom);
+    }
+
+    /// Converts a position in logical window pixels to world space.
+    pub fn screen_to_world(&self, screen: glam::Vec2) -> glam::Vec2 {
+        self.camera.screen_to_world(screen)
+    }
+
+    /// Converts a world position to logical window pixels.
+    pub fn world_to_screen(&self, world: glam::Vec2) -> glam::Vec2 {
+        self.camera.world_to_screen(world)
+    }
+}
+
+pub fn run() -> anyhow::Result<()> {
+    run_with(WindowConfig::default())
+}
+
+pub(crate) fn init_logging() {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        env_logger::init();
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        console_log::init_with_level(log::Level::Info).unwrap_throw();
+    }
+}
+
+/// Runs the app in a window created with `window_config`.
+pub fn run_with(window_config: WindowConfig) -> anyhow::Result<()> {
+    init_logging();
+
+    let event_loop = EventLoop::with_user_event().build()?;
+    let mut app = DrawingApp::new(
+        #[cfg(target_arch = "wasm32")]
+        &event_loop,
+    )
+    .with_window_config(window_config);
+    event_loop.run_app(&mut app)?;
+
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn run_web() -> Result<(), wasm_bindgen::JsValue> {
+    console_error_panic_hook::set_once();
+    run().unwrap_throw();
+
+    Ok(())
+}
+
+/// Like [run_web], drawing into the `<canvas>` with the id `canvas_id`.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn run_web_in(canvas_id: &str) -> Result<(), wasm_bindgen::JsValue> {
+    console_error_panic_hook::set_once();
+    run_with(WindowConfig::default().canvas_id(canvas_id)).unwrap_throw();
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+/// Features that are used when the adapter has them, and worked around
+/// when it doesn't.
+pub const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::POLYGON_MODE_LINE
+    .union(wgpu::Features::TIMESTAMP_QUERY)
+    .union(wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_BC)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ETC2)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ASTC);
+
+/// What the device was created with, for picking code paths that need
+/// more than the baseline.
+#[derive(Debug, Clone)]
+pub struct GpuCapabilities {
+    features: wgpu::Features,
+    limits: wgpu::Limits,
+}
+
+impl GpuCapabilities {
+    /// What to ask `adapter` for: the [OPTIONAL_FEATURES] it has, and the
+    /// downlevel limits with texture sizes and alignments raised to what it
+    /// supports. WebGL2 adapters get the even lower WebGL2 limits.
+    pub fn negotiate(adapter: &wgpu::Adapter) -> Self {
+        let supported = adapter.limits();
+        let base = if adapter.get_info().backend == wgpu::Backend::Gl {
+            wgpu::Limits::downlevel_webgl2_defaults()
+        } else {
+            wgpu::Limits::downlevel_defaults()
+        };
+        Self {
+            features: adapter.features() & OPTIONAL_FEATURES,
+            limits: base
+                .using_resolution(supported.clone())
+                .using_alignment(supported),
+        }
+    }
+
+    /// What `device` was actually created with.
+    pub fn granted(device: &wgpu::Device) -> Self {
+        Self {
+            features: device.features(),
+            limits: device.limits(),
+        }
+    }
+
+    pub fn device_descriptor(&self) -> wgpu::DeviceDescriptor<'static> {
+        wgpu::DeviceDescriptor {
+            required_features: self.features,
+            required_limits: self.limits.clone(),
+            ..Default::default()
+        }
+    }
+
+    pub fn features(&self) -> wgpu::Features {
+        self.features
+    }
+
+    pub fn limits(&self) -> &wgpu::Limits {
+        &self.limits
+    }
+
+    pub fn has(&self, features: wgpu::Features) -> bool {
+        self.features.contains(features)
+    }
+
+    /// Whether triangles can be drawn as outlines.
+    pub fn has_line_polygon_mode(&self) -> bool {
+        self.has(wgpu::Features::POLYGON_MODE_LINE)
+    }
+
+    /// Whether GPU time can be measured between passes.
+    pub fn has_timestamps(&self) -> bool {
+        self.has(wgpu::Features::TIMESTAMP_QUERY)
+    }
+
+    /// Whether textures in `format` can be sampled as they are. Compressed
+    /// textures that can't are decompressed when they're loaded.
+    pub fn has_format(&self, format: wgpu::TextureFormat) -> bool {
+        self.has(format.required_features())
+    }
+
+    /// The widest or tallest texture that can be created, like for exports.
+    pub fn max_texture_size(&self) -> u32 {
+        self.limits.max_texture_dimension_2d
+    }
+}
@@ -0,0 +1,335 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    document::{NodeData, FORMAT_VERSION},
+    scene::{NodeId, Scene, Transform2D},
+};
+
+/// How far each successive paste is moved so copies don't sit exactly on
+/// top of each other. Matches the default snapping grid.
+pub const PASTE_OFFSET: Vec2 = Vec2::splat(20.0);
+
+/// What gets put on the system clipboard, as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipboardData {
+    pub version: u32,
+    pub nodes: Vec<NodeData>,
+}
+
+/// Something that can be pasted.
+#[derive(Debug, Clone)]
+pub enum Paste {
+    Nodes(Vec<NodeData>),
+    Svg(String),
+}
+
+/// The system clipboard, so copies can be pasted into other apps and the
+/// other way around.
+pub trait ClipboardBackend {
+    fn set_text(&mut self, text: &str) -> anyhow::Result<()>;
+
+    /// The text on the clipboard. Backends that can only read it
+    /// asynchronously return `None` and hand it to
+    /// [ClipboardBackend::poll_text] once it's read.
+    fn get_text(&mut self) -> anyhow::Result<Option<String>>;
+
+    /// Text from an earlier [ClipboardBackend::get_text] that has been read
+    /// since.
+    fn poll_text(&mut self) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct NativeClipboard(arboard::Clipboard);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl NativeClipboard {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self(arboard::Clipboard::new()?))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ClipboardBackend for NativeClipboard {
+    fn set_text(&mut self, text: &str) -> anyhow::Result<()> {
+        Ok(self.0.set_text(text)?)
+    }
+
+    fn get_text(&mut self) -> anyhow::Result<Option<String>> {
+        Ok(Some(self.0.get_text()?))
+    }
+}
+
+/// The browser's async Clipboard API. Reading asks the browser, which may
+/// ask the user, and the text turns up in a later
+/// [ClipboardBackend::poll_text].
+#[cfg(target_arch = "wasm32")]
+pub struct WebClipboard {
+    sender: futures_channel::mpsc::UnboundedSender<String>,
+    read: futures_channel::mpsc::UnboundedReceiver<String>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl WebClipboard {
+    pub fn new() -> anyhow::Result<Self> {
+        let (sender, read) = futures_channel::mpsc::unbounded();
+        Ok(Self { sender, read })
+    }
+
+    fn clipboard() -> anyhow::Result<web_sys::Clipboard> {
+        let window = web_sys::window().ok_or_else(|| anyhow::anyhow!("No window"))?;
+        Ok(window.navigator().clipboard())
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl ClipboardBackend for WebClipboard {
+    fn set_text(&mut self, text: &str) -> anyhow::Result<()> {
+        let written = wasm_bindgen_futures::JsFuture::from(Self::clipboard()?.write_text(text));
+        crate::utils::spawn(async move {
+            if let Err(e) = written.await {
+                log::warn!("Unable to write to the clipboard: {e:?}");
+            }
+        });
+        Ok(())
+    }
+
+    fn get_text(&mut self) -> anyhow::Result<Option<String>> {
+        let read = wasm_bindgen_futures::JsFuture::from(Self::clipboard()?.read_text());
+        let sender = self.sender.clone();
+        crate::utils::spawn(async move {
+            match read.await {
+                Ok(text) => {
+                    let _ = sender.unbounded_send(text.as_string().unwrap_or_default());
+                }
+                Err(e) => {
+                    log::warn!("Unable to read the clipboard: {e:?}");
+                    // Lets a paste waiting on it fall back to what was
+                    // copied here.
+                    let _ = sender.unbounded_send(String::new());
+                }
+            }
+        });
+        Ok(None)
+    }
+
+    fn poll_text(&mut self) -> Option<String> {
+        self.read.try_recv().ok()
+    }
+}
+
+/// The system clipboard for this platform, if there is one.
+pub fn system_clipboard() -> Option<Box<dyn ClipboardBackend>> {
+    #[cfg(not(target_arch = "wasm32"))]
+    let backend = NativeClipboard::new();
+    #[cfg(target_arch = "wasm32")]
+    let backend = WebClipboard::new();
+    match backend {
+        Ok(backend) => Some(Box::new(backend)),
+        Err(e) => {
+            log::warn!("System clipboard unavailable: {e}");
+            None
+        }
+    }
+}
+
+/// Copied nodes. Copies are mirrored to the system clipboard so they can
+/// be pasted into another window, and SVG markup copied from elsewhere can
+/// be pasted in. Text fields copy and paste plain text through it too.
+pub struct Clipboard {
+    contents: Option<ClipboardData>,
+    /// JSON of `contents`, used to tell whether the system clipboard has
+    /// changed since the last copy.
+    text: String,
+    /// Times `contents` has been pasted since it was copied.
+    pastes: u32,
+    system: Option<Box<dyn ClipboardBackend>>,
+    /// Whether a paste is waiting for the system clipboard to be read.
+    paste_pending: bool,
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        Self::with_backend(system_clipboard())
+    }
+
+    /// Uses `system` instead of the platform's clipboard.
+    pub fn with_backend(system: Option<Box<dyn ClipboardBackend>>) -> Self {
+        Self {
+            contents: None,
+            text: String::new(),
+            pastes: 0,
+            system,
+            paste_pending: false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contents.is_none()
+    }
+
+    /// A node and its descendants, ready to be copied. The node keeps its
+    /// position in the world, so nodes copied out of a group paste where
+    /// they were.
+    pub fn capture(scene: &mut Scene, id: NodeId) -> anyhow::Result<NodeData> {
+        let mut node = NodeData::capture(scene, id)
+            .ok_or_else(|| anyhow::anyhow!("Node {id:?} doesn't exist"))?;
+        node.transform = Transform2D::from_affine(scene.world_transform(id));
+        Ok(node)
+    }
+
+    /// Copies nodes made with [Clipboard::capture].
+    pub fn copy(&mut self, nodes: Vec<NodeData>) -> anyhow::Result<()> {
+        let data = ClipboardData {
+            version: FORMAT_VERSION,
+            nodes,
+        };
+        self.text = serde_json::to_string(&data)?;
+        self.contents = Some(data);
+        self.pastes = 0;
+        let text = self.text.clone();
+        self.copy_text(&text);
+        Ok(())
+    }
+
+    /// Puts plain text on the system clipboard.
+    pub fn copy_text(&mut self, text: &str) {
+        if let Some(system) = &mut self.system {
+            if let Err(e) = system.set_text(text) {
+                log::warn!("Unable to write to the system clipboard: {e}");
+            }
+        }
+    }
+
+    /// The text on the system clipboard. If it has to be read
+    /// asynchronously this returns `None` and it turns up in
+    /// [Clipboard::poll_text] later.
+    pub fn text(&mut self) -> Option<String> {
+        let system = self.system.as_mut()?;
+        system
+            .get_text()
+            .inspect_err(|e| log::debug!("Unable to read the system clipboard: {e}"))
+            .ok()
+            .flatten()
+    }
+
+    /// Text read since an earlier [Clipboard::text] came back empty handed.
+    pub fn poll_text(&mut self) -> Option<String> {
+        self.system.as_mut()?.poll_text()
+    }
+
+    /// Returns what should be pasted next. Each paste of the same nodes is
+    /// offset a little further by [PASTE_OFFSET]. If the system clipboard
+    /// has to be read asynchronously this returns `None` and the paste
+    /// turns up in [Clipboard::poll_paste] later.
+    pub fn paste(&mut self) -> Option<Paste> {
+        let Some(system) = &mut self.system else {
+            return self.paste_copied();
+        };
+        match system.get_text() {
+            Ok(Some(text)) => self.paste_text(text),
+            Ok(None) => {
+                self.paste_pending = true;
+                None
+            }
+            Err(_) => self.paste_copied(),
+        }
+    }
+
+    /// Whether a paste is waiting on the system clipboard.
+    pub fn is_paste_pending(&self) -> bool {
+        self.paste_pending
+    }
+
+    /// A paste that was waiting on the system clipboard, once it's been
+    /// read.
+    pub fn poll_paste(&mut self) -> Option<Paste> {
+        if !self.paste_pending {
+            return None;
+        }
+        let text = self.poll_text()?;
+        self.paste_pending = false;
+        self.paste_text(text)
+    }
+
+    /// Pastes `text` read from the system clipboard, which can be nodes
+    /// copied from another window or SVG markup. Anything else pastes what
+    /// was copied here.
+    fn paste_text(&mut self, text: String) -> Option<Paste> {
+        if text != self.text {
+            match serde_json::from_str::<ClipboardData>(&text) {
+                Ok(data) if data.version <= FORMAT_VERSION => {
+                    self.contents = Some(data);
+                    self.text = text;
+                    self.pastes = 0;
+                }
+                _ if text.contains("<svg") => return Some(Paste::Svg(text)),
+                _ => {}
+            }
+        }
+        self.paste_copied()
+    }
+
+    fn paste_copied(&mut self) -> Option<Paste> {
+        let mut nodes = self.contents.as_ref()?.nodes.clone();
+        self.pastes += 1;
+        for node in &mut nodes {
+            node.transform.translation += PASTE_OFFSET * self.pastes as f32;
+        }
+        Some(Paste::Nodes(nodes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec2;
+
+    use super::*;
+    use crate::scene::NodeKind;
+
+    #[test]
+    fn every_copied_node_pastes_further_along() {
+        let mut scene = Scene::default();
+        let parent = scene.insert(
+            "parent",
+            NodeKind::Group,
+            Transform2D::from_translation(vec2(100.0, 0.0)),
+            None,
+        );
+        let child = scene.insert(
+            "child",
+            NodeKind::Group,
+            Transform2D::from_translation(vec2(1.0, 2.0)),
+            Some(parent),
+        );
+        let other = scene.insert("other", NodeKind::Group, Transform2D::IDENTITY, None);
+
+        let mut clipboard = Clipboard::with_backend(None);
+        let nodes = [child, other]
+            .into_iter()
+            .map(|id| Clipboard::capture(&mut scene, id))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap();
+        clipboard.copy(nodes).unwrap();
+
+        for pastes in 1..=2 {
+            let Some(Paste::Nodes(nodes)) = clipboard.paste() else {
+                panic!("Nothing to paste");
+            };
+            let offset = PASTE_OFFSET * pastes as f32;
+            let names: Vec<_> = nodes.iter().map(|n| n.name.as_str()).collect();
+            assert_eq!(names, ["child", "other"]);
+            // Copied out of its group, where it was in the world.
+            assert_eq!(nodes[0].transform.translation, vec2(101.0, 2.0) + offset);
+            assert_eq!(nodes[1].transform.translation, offset);
+        }
+    }
+}
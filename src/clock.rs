@@ -0,0 +1,246 @@
+use std::{collections::VecDeque, time::Duration};
+
+use web_time::Instant;
+
+/// Frames longer than this are counted as this long, so a stall (like the
+/// window being dragged or the tab being hidden) doesn't make everything
+/// jump.
+pub const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+/// How much each new frame counts towards [FrameStats::average].
+const SMOOTHING: f64 = 0.05;
+/// How many frames [FrameStats::min] and [FrameStats::max] look back over.
+const STATS_WINDOW: usize = 120;
+
+/// Frame times over the last few frames.
+#[derive(Debug, Clone, Default)]
+pub struct FrameStats {
+    average: Duration,
+    recent: VecDeque<Duration>,
+}
+
+impl FrameStats {
+    fn push(&mut self, dt: Duration) {
+        self.average = if self.recent.is_empty() {
+            dt
+        } else {
+            self.average.mul_f64(1.0 - SMOOTHING) + dt.mul_f64(SMOOTHING)
+        };
+        if self.recent.len() == STATS_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(dt);
+    }
+
+    /// Frame time smoothed over roughly the last couple dozen frames.
+    pub fn average(&self) -> Duration {
+        self.average
+    }
+
+    /// Frames per second going by [FrameStats::average].
+    pub fn fps(&self) -> f32 {
+        match self.average.as_secs_f32() {
+            secs if secs > 0.0 => secs.recip(),
+            _ => 0.0,
+        }
+    }
+
+    /// The shortest recent frame.
+    pub fn min(&self) -> Duration {
+        self.recent.iter().min().copied().unwrap_or_default()
+    }
+
+    /// The longest recent frame.
+    pub fn max(&self) -> Duration {
+        self.recent.iter().max().copied().unwrap_or_default()
+    }
+
+    /// The recent frame time that `p` percent of recent frames are at most.
+    pub fn percentile(&self, p: f32) -> Duration {
+        let mut sorted: Vec<_> = self.recent.iter().copied().collect();
+        if sorted.is_empty() {
+            return Duration::ZERO;
+        }
+        sorted.sort_unstable();
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f32).round();
+        sorted[rank as usize]
+    }
+
+    /// Recent frame times, oldest first.
+    pub fn recent(&self) -> impl ExactSizeIterator<Item = Duration> + '_ {
+        self.recent.iter().copied()
+    }
+}
+
+/// Measures how long each frame took. [FrameClock::tick] should be called
+/// once per redraw, before anything is updated.
+#[derive(Debug, Clone)]
+pub struct FrameClock {
+    started: Instant,
+    last_tick: Option<Instant>,
+    dt: Duration,
+    elapsed: Duration,
+    frame: u64,
+    stats: FrameStats,
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameClock {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            last_tick: None,
+            dt: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            frame: 0,
+            stats: FrameStats::default(),
+        }
+    }
+
+    /// Starts a new frame. The first one has a `dt` of zero.
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        self.dt = self
+            .last_tick
+            .map_or(Duration::ZERO, |last| (now - last).min(MAX_FRAME_TIME));
+        if self.last_tick.is_some() {
+            self.stats.push(self.dt);
+        }
+        self.last_tick = Some(now);
+        self.elapsed += self.dt;
+        self.frame += 1;
+    }
+
+    /// Forgets when the last frame started, so time spent paused, like
+    /// while the app is suspended, doesn't count. The next frame has a `dt`
+    /// of zero.
+    pub fn pause(&mut self) {
+        self.last_tick = None;
+    }
+
+    /// How long the last frame took, capped at [MAX_FRAME_TIME].
+    pub fn dt(&self) -> Duration {
+        self.dt
+    }
+
+    /// [FrameClock::dt] in seconds.
+    pub fn dt_secs(&self) -> f32 {
+        self.dt.as_secs_f32()
+    }
+
+    /// The sum of every frame's `dt`. Unlike the wall clock time since the
+    /// clock was made, stalls only count up to [MAX_FRAME_TIME].
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Wall clock time since the clock was made.
+    pub fn since_start(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// How many frames have been ticked.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
+}
+
+/// How often [FixedTimestep] steps by default, 60 times a second.
+pub const FIXED_STEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+/// How many steps one frame can run before the rest of its time is dropped,
+/// so a slow frame can't snowball into ever slower ones.
+pub const MAX_FIXED_STEPS: u32 = 8;
+
+/// Splits frame times into steps of the same length, so simulations behave
+/// the same no matter how fast frames are drawn. Time that doesn't add up to
+/// a whole step carries over to the next frame.
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    step: Duration,
+    accumulator: Duration,
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::new(FIXED_STEP)
+    }
+}
+
+impl FixedTimestep {
+    pub fn new(step: Duration) -> Self {
+        Self {
+            step: step.max(Duration::from_micros(100)),
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    pub fn set_step(&mut self, step: Duration) {
+        *self = Self::new(step);
+    }
+
+    /// Adds a frame's `dt` and returns how many steps are due.
+    pub fn advance(&mut self, dt: Duration) -> u32 {
+        self.accumulator += dt;
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            steps += 1;
+            if steps == MAX_FIXED_STEPS {
+                self.accumulator = Duration::ZERO;
+                break;
+            }
+        }
+        steps
+    }
+
+    /// How far into the next step the time left over is, from 0 to 1. Drawing
+    /// things between their last two steps by this much keeps them smooth
+    /// when frames and steps don't line up.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_timestep_carries_over_partial_steps() {
+        let mut timestep = FixedTimestep::new(Duration::from_millis(10));
+        assert_eq!(timestep.advance(Duration::from_millis(25)), 2);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+        assert_eq!(timestep.advance(Duration::from_millis(3)), 0);
+        assert_eq!(timestep.advance(Duration::from_millis(2)), 1);
+        assert!(timestep.alpha().abs() < 1e-4);
+    }
+
+    #[test]
+    fn fixed_timestep_drops_time_past_max_steps() {
+        let step = Duration::from_millis(10);
+        let mut timestep = FixedTimestep::new(step);
+        assert_eq!(timestep.advance(step * 100), MAX_FIXED_STEPS);
+        assert_eq!(timestep.alpha(), 0.0);
+        assert_eq!(timestep.advance(step), 1);
+    }
+
+    #[test]
+    fn fixed_timestep_step_has_a_minimum() {
+        assert_eq!(
+            FixedTimestep::new(Duration::ZERO).step(),
+            Duration::from_micros(100)
+        );
+    }
+}
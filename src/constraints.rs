@@ -0,0 +1,131 @@
+use glam::{Affine2, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    scene::{NodeId, Scene},
+    shape::Geometry,
+};
+
+/// Passes over the constraints before giving up on ones that conflict.
+const MAX_ITERATIONS: usize = 8;
+/// How far an endpoint has to move, in world units, to count as a change.
+const TOLERANCE: f32 = 1e-3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ConstraintKind {
+    Parallel,
+    Perpendicular,
+    EqualLength,
+}
+
+/// Keeps line `b` in a relationship with line `a`. Lines are open polylines
+/// with exactly two points. Constraints on nodes that don't exist or aren't
+/// lines are ignored, so they come back to life if a removed node is
+/// restored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Constraint {
+    pub kind: ConstraintKind,
+    pub a: NodeId,
+    pub b: NodeId,
+}
+
+impl Constraint {
+    pub fn new(kind: ConstraintKind, a: NodeId, b: NodeId) -> Self {
+        Self { kind, a, b }
+    }
+
+    pub fn involves(&self, id: NodeId) -> bool {
+        self.a == id || self.b == id
+    }
+}
+
+impl ConstraintKind {
+    /// Moves the `target` endpoints so they satisfy the constraint against
+    /// `reference`, keeping the target's midpoint in place.
+    fn apply(&self, reference: [Vec2; 2], target: [Vec2; 2]) -> Option<[Vec2; 2]> {
+        let reference = reference[1] - reference[0];
+        let current = target[1] - target[0];
+        let center = (target[0] + target[1]) * 0.5;
+        let (direction, length) = match self {
+            Self::Parallel => (reference.try_normalize()?, current.length()),
+            Self::Perpendicular => (reference.perp().try_normalize()?, current.length()),
+            Self::EqualLength => (current.try_normalize()?, reference.length()),
+        };
+        // Keep the line pointing the way it was rather than flipping its
+        // endpoints around.
+        let direction = if direction.dot(current) < 0.0 {
+            -direction
+        } else {
+            direction
+        };
+        let half = direction * length * 0.5;
+        Some([center - half, center + half])
+    }
+}
+
+/// Returns the world transform and local endpoints of a line node.
+pub fn line(scene: &mut Scene, id: NodeId) -> Option<(Affine2, [Vec2; 2])> {
+    let world = scene.world_transform(id);
+    match &scene.get(id)?.shape()?.geometry {
+        Geometry::Polyline {
+            points,
+            closed: false,
+        } if points.len() == 2 => Some((world, [points[0], points[1]])),
+        _ => None,
+    }
+}
+
+/// Adjusts lines until the constraints hold. Lines in `pinned` are left
+/// alone and everything else bends around them. Returns the nodes whose
+/// points changed.
+pub fn solve(scene: &mut Scene, constraints: &[Constraint], pinned: &[NodeId]) -> Vec<NodeId> {
+    let mut changed = Vec::new();
+    for _ in 0..MAX_ITERATIONS {
+        let mut moved = false;
+        for constraint in constraints {
+            let (reference, target) = match (
+                pinned.contains(&constraint.a),
+                pinned.contains(&constraint.b),
+            ) {
+                (_, false) => (constraint.a, constraint.b),
+                (false, true) => (constraint.b, constraint.a),
+                (true, true) => continue,
+            };
+            let (Some((reference_world, reference)), Some((world, local))) =
+                (line(scene, reference), line(scene, target))
+            else {
+                continue;
+            };
+            let to_world = |p: [Vec2; 2], t: &Affine2| p.map(|p| t.transform_point2(p));
+            let current = to_world(local, &world);
+            let Some(solved) = constraint
+                .kind
+                .apply(to_world(reference, &reference_world), current)
+            else {
+                continue;
+            };
+            if current
+                .iter()
+                .zip(&solved)
+                .all(|(a, b)| a.distance(*b) < TOLERANCE)
+            {
+                continue;
+            }
+
+            let inverse = world.inverse();
+            let shape = scene.get_mut(target).and_then(|n| n.shape_mut());
+            if let Some(Geometry::Polyline { points, .. }) = shape.map(|s| &mut s.geometry) {
+                points[0] = inverse.transform_point2(solved[0]);
+                points[1] = inverse.transform_point2(solved[1]);
+            }
+            if !changed.contains(&target) {
+                changed.push(target);
+            }
+            moved = true;
+        }
+        if !moved {
+            break;
+        }
+    }
+    changed
+}
@@ -0,0 +1,191 @@
+use winit::{event::WindowEvent, window::Window};
+
+use crate::Canvas;
+
+/// An egui panel for tweaking settings and watching stats while
+/// developing. Drawn on top of everything, after post-processing.
+pub struct DebugUi {
+    context: egui::Context,
+    state: egui_winit::State,
+    renderer: egui_wgpu::Renderer,
+    visible: bool,
+}
+
+impl DebugUi {
+    /// `format` is the format of the view the panel is drawn into.
+    pub fn new(window: &Window, format: wgpu::TextureFormat, device: &wgpu::Device) -> Self {
+        let context = egui::Context::default();
+        let state = egui_winit::State::new(
+            context.clone(),
+            egui::ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            window.theme(),
+            Some(device.limits().max_texture_dimension_2d as usize),
+        );
+        Self {
+            context,
+            state,
+            renderer: egui_wgpu::Renderer::new(device, format, None, 1, false),
+            visible: false,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    /// Whether the panel used `event`, in which case nothing else should.
+    pub fn handle_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.visible && self.state.on_window_event(window, event).consumed
+    }
+
+    /// Lays out the panel, applies whatever was changed in it to `canvas`,
+    /// and draws it into `view`.
+    pub fn render(
+        &mut self,
+        canvas: &mut Canvas,
+        view: &wgpu::TextureView,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        if !self.visible {
+            return;
+        }
+        let input = self.state.take_egui_input(&canvas.window);
+        let output = self.context.run(input, |context| {
+            egui::Window::new("Debug")
+                .default_pos([10.0, 80.0])
+                .show(context, |ui| panel(ui, canvas));
+        });
+        self.state
+            .handle_platform_output(&canvas.window, output.platform_output);
+
+        let primitives = self
+            .context
+            .tessellate(output.shapes, output.pixels_per_point);
+        let screen = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [canvas.config.width, canvas.config.height],
+            pixels_per_point: output.pixels_per_point,
+        };
+        for (id, delta) in &output.textures_delta.set {
+            self.renderer
+                .update_texture(&canvas.device, &canvas.queue, *id, delta);
+        }
+        let commands = self.renderer.update_buffers(
+            &canvas.device,
+            &canvas.queue,
+            encoder,
+            &primitives,
+            &screen,
+        );
+        canvas.queue.submit(commands);
+        {
+            let mut pass = encoder
+                .begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("DebugUi::pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    ..Default::default()
+                })
+                .forget_lifetime();
+            self.renderer.render(&mut pass, &primitives, &screen);
+        }
+        for id in &output.textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+    }
+}
+
+fn panel(ui: &mut egui::Ui, canvas: &mut Canvas) {
+    egui::CollapsingHeader::new("Frame")
+        .default_open(true)
+        .show(ui, |ui| {
+            let stats = canvas.clock.stats();
+            let counts = canvas.render_stats();
+            ui.label(format!("FPS: {:.1}", stats.fps()));
+            ui.label(format!(
+                "Frame: {:.2} ms",
+                stats.average().as_secs_f64() * 1000.0
+            ));
+            ui.label(format!("Draw calls: {}", counts.draw_calls));
+            ui.label(format!("Vertices: {}", counts.vertices));
+            ui.label(format!("Instances: {}", counts.instances));
+            ui.label(format!("Texture binds: {}", counts.texture_binds));
+            ui.label(format!(
+                "Uploads: {} ({} bytes)",
+                counts.buffer_uploads, counts.upload_bytes
+            ));
+            ui.label(format!("Layers: {}", canvas.layers.iter().count()));
+            ui.label(format!("Present mode: {:?}", canvas.present_mode()));
+            ui.label(format!("Samples: {}", canvas.renderer.sample_count()));
+        });
+
+    egui::CollapsingHeader::new("Camera")
+        .default_open(true)
+        .show(ui, |ui| {
+            let mut center = canvas.camera.center();
+            let mut zoom = canvas.camera.zoom();
+            let mut rotation = canvas.camera.rotation().to_degrees();
+            let mut changed = false;
+            ui.horizontal(|ui| {
+                ui.label("Center");
+                changed |= ui.add(egui::DragValue::new(&mut center.x)).changed();
+                changed |= ui.add(egui::DragValue::new(&mut center.y)).changed();
+            });
+            changed |= ui
+                .add(
+                    egui::Slider::new(&mut zoom, 0.01..=100.0)
+                        .logarithmic(true)
+                        .text("Zoom"),
+                )
+                .changed();
+            changed |= ui
+                .add(egui::Slider::new(&mut rotation, -180.0..=180.0).text("Rotation"))
+                .changed();
+            if changed {
+                canvas.camera.set_center(center);
+                canvas.camera.set_zoom(zoom);
+                canvas.set_view_rotation(rotation.to_radians());
+            }
+        });
+
+    egui::CollapsingHeader::new("Font").show(ui, |ui| {
+        let mut settings = canvas.renderer.text_pipeline.font_settings();
+        let mut changed = false;
+        for (value, range, name) in [
+            (&mut settings.in_bias, -1.0..=1.0, "In bias"),
+            (&mut settings.out_bias, -1.0..=1.0, "Out bias"),
+            (&mut settings.smoothness, 0.0..=1.0, "Smoothness"),
+            (&mut settings.super_sample, 0.0..=1.0, "Super sample"),
+            (&mut settings.gamma, 0.1..=4.0, "Gamma"),
+        ] {
+            changed |= ui.add(egui::Slider::new(value, range).text(name)).changed();
+        }
+        if changed {
+            canvas
+                .renderer
+                .text_pipeline
+                .set_font_settings(settings, &canvas.queue);
+            canvas.request_redraw();
+        }
+    });
+
+    egui::CollapsingHeader::new("GPU").show(ui, |ui| {
+        let capabilities = canvas.capabilities();
+        ui.label(format!("Features: {:?}", capabilities.features()));
+        ui.label(format!(
+            "Max texture size: {}",
+            capabilities.max_texture_size()
+        ));
+    });
+}
@@ -0,0 +1,273 @@
+use std::{collections::HashMap, fmt::Debug, hash::Hash};
+
+use glam::{Vec2, Vec4};
+
+use crate::{
+    machine::StateMachine,
+    overlay::Overlay,
+    resources::{
+        buffer::IndexedBatch,
+        camera::CameraBinding,
+        font::{Font, TextBlock, TextPipeline},
+        geometry::{ColoredVertex, GeometryPipeline},
+    },
+    shape::{tessellate_polyline, Bounds},
+};
+
+pub const STATE_COLOR: Vec4 = Vec4::new(0.6, 0.6, 0.6, 1.0);
+pub const ACTIVE_STATE_COLOR: Vec4 = Vec4::new(1.0, 0.8, 0.2, 1.0);
+pub const TRANSITION_COLOR: Vec4 = Vec4::new(0.4, 0.7, 0.9, 1.0);
+/// Height of state names in pixels.
+const STATE_TEXT_SIZE: f32 = 14.0;
+/// Height of event names in pixels.
+const EVENT_TEXT_SIZE: f32 = 11.0;
+/// Space between a state's name and its box.
+const STATE_PADDING: Vec2 = Vec2::new(8.0, 4.0);
+const COLUMN_GAP: f32 = 40.0;
+const ROW_GAP: f32 = 40.0;
+/// How far each level of substates is indented.
+const SUBSTATE_INDENT: f32 = 24.0;
+/// How far apart transitions going both ways between two states are drawn.
+const TRANSITION_SEPARATION: f32 = 10.0;
+/// Length of the stub that transitions from any state start on.
+const ANY_STATE_STUB: f32 = 24.0;
+const SELF_LOOP_SIZE: f32 = 14.0;
+const ARROW_SIZE: f32 = 7.0;
+/// Gap between the outline of the current state and its second outline.
+const CURRENT_OUTLINE_GAP: f32 = 3.0;
+
+/// A drawing of a [StateMachine], with states as labeled boxes and
+/// transitions as arrows between them. Substates are stacked under their
+/// parent and the states the machine is in are highlighted.
+pub struct StateDiagram {
+    overlay: Overlay,
+    labels: TextBlock,
+    bounds: Bounds,
+}
+
+impl StateDiagram {
+    /// Lays out `machine` with its top left corner at `origin`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<S, E, C>(
+        machine: &StateMachine<S, E, C>,
+        origin: Vec2,
+        font: &Font,
+        text_pipeline: &TextPipeline,
+        geometry_pipeline: &GeometryPipeline,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Self>
+    where
+        S: Clone + Eq + Hash + Debug,
+        E: Clone + Eq + Hash + Debug,
+    {
+        let names: Vec<_> = machine.states().iter().map(|s| format!("{s:?}")).collect();
+        let transitions = machine.transitions();
+        // Transitions from any state come in from above.
+        let top = if transitions.iter().any(|(from, _, _)| from.is_none()) {
+            origin + Vec2::Y * ANY_STATE_STUB
+        } else {
+            origin
+        };
+        let boxes = layout(machine, &names, top, font);
+        let bounds = Bounds::from_points(
+            boxes
+                .values()
+                .flat_map(|b| [b.min, b.max])
+                .chain(std::iter::once(origin)),
+        )
+        .unwrap_or(Bounds::new(origin, origin));
+
+        let mut labels: Vec<(String, Vec2, f32)> = machine
+            .states()
+            .iter()
+            .zip(&names)
+            .map(|(state, name)| {
+                (
+                    name.clone(),
+                    boxes[state].min + STATE_PADDING,
+                    STATE_TEXT_SIZE,
+                )
+            })
+            .collect();
+
+        let mut overlay = Overlay::new(geometry_pipeline, device);
+        let mut batch = overlay.begin(device, queue);
+        for state in machine.states() {
+            let bounds = boxes[state];
+            if machine.is_in(state) {
+                tessellate_polyline(&bounds.corners(), true, ACTIVE_STATE_COLOR, &mut batch);
+                if machine.state() == state {
+                    let outer = bounds.expand(CURRENT_OUTLINE_GAP);
+                    tessellate_polyline(&outer.corners(), true, ACTIVE_STATE_COLOR, &mut batch);
+                }
+            } else {
+                tessellate_polyline(&bounds.corners(), true, STATE_COLOR, &mut batch);
+            }
+        }
+
+        let event_height = font.line_height(EVENT_TEXT_SIZE);
+        for (from, event, to) in transitions {
+            let target = boxes[to];
+            let (label_at, label_right) = match from {
+                None => {
+                    let end = Vec2::new(target.min.x + STATE_PADDING.x, target.min.y);
+                    let start = end - Vec2::Y * ANY_STATE_STUB;
+                    // A small tick marks where the transition starts.
+                    let tick = Vec2::X * ARROW_SIZE * 0.5;
+                    line(&mut batch, start - tick, start + tick);
+                    arrow(&mut batch, start, end);
+                    ((start + end) * 0.5, true)
+                }
+                Some(from) if from == to => {
+                    let right = target.max.x;
+                    let top = target.min.y + target.size().y * 0.25;
+                    let bottom = target.max.y - target.size().y * 0.25;
+                    let points = [
+                        Vec2::new(right, top),
+                        Vec2::new(right + SELF_LOOP_SIZE, top),
+                        Vec2::new(right + SELF_LOOP_SIZE, bottom),
+                    ];
+                    tessellate_polyline(&points, false, TRANSITION_COLOR, &mut batch);
+                    arrow(&mut batch, points[2], Vec2::new(right, bottom));
+                    (Vec2::new(right + SELF_LOOP_SIZE, target.center().y), true)
+                }
+                Some(from) => {
+                    let source = boxes[from];
+                    let direction = (target.center() - source.center()).normalize_or_zero();
+                    // Keeps transitions going both ways from overlapping.
+                    let side = direction.perp() * TRANSITION_SEPARATION * 0.5;
+                    let start = box_exit(&source, source.center() + side, direction);
+                    let end = box_exit(&target, target.center() + side, -direction);
+                    arrow(&mut batch, start, end);
+                    ((start + end) * 0.5 + side, side.x >= 0.0)
+                }
+            };
+            let text = format!("{event:?}");
+            let width = font.text_width(&text, EVENT_TEXT_SIZE);
+            let x = if label_right {
+                label_at.x + ARROW_SIZE * 0.5
+            } else {
+                label_at.x - width - ARROW_SIZE * 0.5
+            };
+            labels.push((
+                text,
+                Vec2::new(x, label_at.y - event_height * 0.5),
+                EVENT_TEXT_SIZE,
+            ));
+        }
+        drop(batch);
+
+        let labels: Vec<_> = labels
+            .iter()
+            .map(|(text, position, size)| (text.as_str(), *position, *size))
+            .collect();
+        Ok(Self {
+            overlay,
+            labels: text_pipeline.buffer_labels(font, device, &labels)?,
+            bounds,
+        })
+    }
+
+    /// The area the states and the transitions from any state take up.
+    /// Labels of other transitions can stick out of it.
+    pub fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    pub fn draw(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        geometry_pipeline: &GeometryPipeline,
+        text_pipeline: &TextPipeline,
+        camera_binding: &CameraBinding,
+    ) {
+        self.overlay.draw(pass, geometry_pipeline, camera_binding);
+        text_pipeline.draw_block(pass, &self.labels, camera_binding);
+    }
+}
+
+/// Gives each top level state a column, with its substates under it in
+/// depth first order, indented by how deep they are.
+fn layout<S, E, C>(
+    machine: &StateMachine<S, E, C>,
+    names: &[String],
+    origin: Vec2,
+    font: &Font,
+) -> HashMap<S, Bounds>
+where
+    S: Clone + Eq + Hash + Debug,
+    E: Clone + Eq + Hash + Debug,
+{
+    let states = machine.states();
+    let height = font.line_height(STATE_TEXT_SIZE) + 2.0 * STATE_PADDING.y;
+    let children = |parent: Option<&S>| -> Vec<usize> {
+        (0..states.len())
+            .filter(|&i| machine.parent(&states[i]) == parent)
+            .collect()
+    };
+
+    let mut boxes = HashMap::new();
+    let mut x = origin.x;
+    for root in children(None) {
+        let mut stack = vec![(root, 0)];
+        let mut row = 0;
+        let mut right = x;
+        while let Some((i, depth)) = stack.pop() {
+            let width = font.text_width(&names[i], STATE_TEXT_SIZE) + 2.0 * STATE_PADDING.x;
+            let min = Vec2::new(
+                x + depth as f32 * SUBSTATE_INDENT,
+                origin.y + row as f32 * (height + ROW_GAP),
+            );
+            let max = min + Vec2::new(width, height);
+            boxes.insert(states[i].clone(), Bounds::new(min, max));
+            right = right.max(max.x);
+            row += 1;
+            let substates = children(Some(&states[i]));
+            stack.extend(substates.into_iter().rev().map(|j| (j, depth + 1)));
+        }
+        x = right + COLUMN_GAP;
+    }
+    boxes
+}
+
+/// Where a line leaving `bounds` from `inside` in `direction` crosses its
+/// edge.
+fn box_exit(bounds: &Bounds, inside: Vec2, direction: Vec2) -> Vec2 {
+    let distance = |p: f32, d: f32, min: f32, max: f32| {
+        if d > 0.0 {
+            (max - p) / d
+        } else if d < 0.0 {
+            (min - p) / d
+        } else {
+            f32::INFINITY
+        }
+    };
+    let t = distance(inside.x, direction.x, bounds.min.x, bounds.max.x).min(distance(
+        inside.y,
+        direction.y,
+        bounds.min.y,
+        bounds.max.y,
+    ));
+    if t.is_finite() {
+        inside + direction * t.max(0.0)
+    } else {
+        inside
+    }
+}
+
+fn line(batch: &mut IndexedBatch<'_, ColoredVertex>, a: Vec2, b: Vec2) {
+    batch.line(
+        ColoredVertex::new(a, TRANSITION_COLOR),
+        ColoredVertex::new(b, TRANSITION_COLOR),
+    );
+}
+
+/// A line from `start` to `end` with an arrowhead at `end`.
+fn arrow(batch: &mut IndexedBatch<'_, ColoredVertex>, start: Vec2, end: Vec2) {
+    line(batch, start, end);
+    let back = (start - end).normalize_or_zero() * ARROW_SIZE;
+    let side = back.perp() * 0.5;
+    line(batch, end, end + back + side);
+    line(batch, end, end + back - side);
+}
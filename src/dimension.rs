@@ -0,0 +1,216 @@
+use glam::Vec2;
+#[cfg(feature = "text")]
+use glam::{Affine2, Vec4};
+
+use crate::scene::{NodeId, Scene};
+#[cfg(feature = "text")]
+use crate::{
+    resources::{
+        buffer::{BackedBuffer, IndexedBatch},
+        camera::CameraBinding,
+        font::{Font, TextBuffer, TextPipeline},
+        geometry::ColoredVertex,
+        instance::InstanceTransform,
+    },
+    shape::tessellate_polyline,
+};
+
+#[cfg(feature = "text")]
+pub const DIMENSION_COLOR: Vec4 = Vec4::new(0.9, 0.9, 0.3, 1.0);
+/// Height of the distance labels in pixels.
+#[cfg(feature = "text")]
+const LABEL_HEIGHT: f32 = 14.0;
+/// Gap between a dimension line and its label in pixels.
+#[cfg(feature = "text")]
+const LABEL_GAP: f32 = 4.0;
+/// Length of the ticks at either end of a dimension line in pixels.
+#[cfg(feature = "text")]
+const TICK_SIZE: f32 = 6.0;
+
+/// Where a dimension line ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anchor {
+    /// A fixed position in world space.
+    Point(Vec2),
+    /// A point of a shape's outline, by index. The dimension follows the
+    /// shape when it's moved or edited.
+    Vertex { node: NodeId, index: usize },
+}
+
+impl Anchor {
+    /// Attaches to the closest outline point in `scene` within `radius` of
+    /// `point`, or to `point` itself if there isn't one.
+    pub fn at(scene: &mut Scene, point: Vec2, radius: f32) -> Self {
+        let ids: Vec<NodeId> = scene.iter().map(|(id, _)| id).collect();
+        let mut best: Option<(f32, Anchor)> = None;
+        for id in ids {
+            let world = scene.world_transform(id);
+            let Some(shape) = scene.get(id).and_then(|n| n.shape()) else {
+                continue;
+            };
+            for (index, p) in shape.outline().into_iter().enumerate() {
+                let d = world.transform_point2(p).distance(point);
+                if d <= radius && best.is_none_or(|(bd, _)| d < bd) {
+                    best = Some((d, Anchor::Vertex { node: id, index }));
+                }
+            }
+        }
+        best.map_or(Anchor::Point(point), |(_, anchor)| anchor)
+    }
+
+    /// World position of the anchor. `None` if the shape it's attached to
+    /// is gone or no longer has that many points.
+    pub fn resolve(&self, scene: &mut Scene) -> Option<Vec2> {
+        match *self {
+            Anchor::Point(p) => Some(p),
+            Anchor::Vertex { node, index } => {
+                let world = scene.world_transform(node);
+                let p = *scene.get(node)?.shape()?.outline().get(index)?;
+                Some(world.transform_point2(p))
+            }
+        }
+    }
+}
+
+/// Measures the distance between two anchors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dimension {
+    pub start: Anchor,
+    pub end: Anchor,
+}
+
+impl Dimension {
+    pub fn new(start: Anchor, end: Anchor) -> Self {
+        Self { start, end }
+    }
+
+    pub fn endpoints(&self, scene: &mut Scene) -> Option<[Vec2; 2]> {
+        Some([self.start.resolve(scene)?, self.end.resolve(scene)?])
+    }
+}
+
+#[cfg(feature = "text")]
+struct Label {
+    text: String,
+    buffer: TextBuffer,
+    /// Size of the text in font units.
+    size: Vec2,
+}
+
+/// Draws dimension lines with their lengths written alongside. Labels are
+/// sized in pixels so they stay readable at any zoom.
+#[cfg(feature = "text")]
+pub struct DimensionRenderer {
+    labels: Vec<Label>,
+    /// Number of labels drawn this frame.
+    visible: usize,
+    instances: BackedBuffer<InstanceTransform>,
+}
+
+#[cfg(feature = "text")]
+impl DimensionRenderer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            labels: Vec::new(),
+            visible: 0,
+            instances: BackedBuffer::with_capacity(device, 16, wgpu::BufferUsages::VERTEX),
+        }
+    }
+
+    /// Writes the dimension lines into the overlay `batch` and lays out a
+    /// label for each one.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare(
+        &mut self,
+        lines: impl IntoIterator<Item = [Vec2; 2]>,
+        zoom: f32,
+        font: &Font,
+        text_pipeline: &TextPipeline,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        batch: &mut IndexedBatch<'_, ColoredVertex>,
+    ) -> anyhow::Result<()> {
+        let pixel = 1.0 / zoom;
+        let line_height = font.info.common.line_height as f32;
+        let mut transforms = Vec::new();
+        for [a, b] in lines {
+            let length = a.distance(b);
+            let Some(dir) = (b - a).try_normalize() else {
+                continue;
+            };
+            let normal = dir.perp() * TICK_SIZE * 0.5 * pixel;
+            tessellate_polyline(&[a, b], false, DIMENSION_COLOR, batch);
+            tessellate_polyline(&[a - normal, a + normal], false, DIMENSION_COLOR, batch);
+            tessellate_polyline(&[b - normal, b + normal], false, DIMENSION_COLOR, batch);
+
+            let text = format!("{length:.1}");
+            let size = Vec2::new(text_width(font, &text), line_height);
+            let i = transforms.len();
+            match self.labels.get_mut(i) {
+                Some(label) if label.text == text => {}
+                Some(label) => {
+                    text_pipeline.update_text(font, &text, &mut label.buffer, device, queue)?;
+                    label.text = text;
+                    label.size = size;
+                }
+                None => {
+                    let buffer = text_pipeline.buffer_text(font, device, &text)?;
+                    self.labels.push(Label { text, buffer, size });
+                }
+            }
+            let label = &self.labels[i];
+
+            // Keep the text upright whichever way the line points.
+            let mut angle = dir.to_angle();
+            if angle.abs() > std::f32::consts::FRAC_PI_2 {
+                angle -= std::f32::consts::PI.copysign(angle);
+            }
+            let up = Vec2::from_angle(angle).rotate(Vec2::NEG_Y);
+            let scale = LABEL_HEIGHT / line_height * pixel;
+            let center = (a + b) * 0.5 + up * (LABEL_GAP * pixel + LABEL_HEIGHT * pixel * 0.5);
+            let rotation =
+                Affine2::from_scale_angle_translation(Vec2::splat(scale), angle, Vec2::ZERO);
+            let offset = rotation.transform_vector2(label.size * 0.5);
+            transforms.push(InstanceTransform::from(
+                Affine2::from_translation(center - offset) * rotation,
+            ));
+        }
+
+        self.visible = transforms.len();
+        self.instances.clear();
+        let mut batch = self.instances.batch(device, queue);
+        for transform in transforms {
+            batch.push(transform);
+        }
+        Ok(())
+    }
+
+    pub fn draw(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        text_pipeline: &TextPipeline,
+        camera_binding: &CameraBinding,
+    ) {
+        for (i, label) in self.labels[..self.visible].iter().enumerate() {
+            text_pipeline.draw_text(
+                pass,
+                &label.buffer,
+                &self.instances,
+                i as u32,
+                camera_binding,
+            );
+        }
+    }
+}
+
+/// Width of `text` in font units.
+#[cfg(feature = "text")]
+fn text_width(font: &Font, text: &str) -> f32 {
+    text.chars()
+        .map(|c| {
+            font.glyph(c)
+                .unwrap_or_else(|| font.unknown_glyph())
+                .xadvance as f32
+        })
+        .sum()
+}
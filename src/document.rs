@@ -0,0 +1,512 @@
+use std::collections::HashMap;
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+pub use crate::format::Format;
+use crate::{
+    constraints::{Constraint, ConstraintKind},
+    dimension::{Anchor, Dimension},
+    layers::{Layer, LayerStack},
+    resources::{
+        camera::OrthoCamera,
+        font::{Font, TextPipeline},
+        geometry::GeometryPipeline,
+        loader::{LoadContext, Loader},
+        texture::{Texture, TextureId, TextureRegistry},
+    },
+    scene::{NodeId, NodeKind, Scene, Transform2D},
+    shape::Fill,
+    style::StyleSheet,
+};
+
+/// Version written to new files. Bump this whenever the layout of
+/// [Document] changes and teach [Document::parse] how to upgrade the old
+/// layout.
+///
+/// - 1: Initial format.
+/// - 2: Shapes have an optional `fill`. Version 1 files load without one.
+/// - 3: Fills can reference textures by id. The images themselves aren't
+///   saved, so they need to be loaded again in the same order.
+/// - 4: Shapes have an `sdf_op`, defaulting to none.
+/// - 5: Shared `styles`, which shapes can reference by `style`.
+/// - 6: Layers have `constraints` between lines.
+/// - 7: Layers have `dimensions`.
+/// - 8: Images used by texture fills are embedded as `textures`. Fills
+///   referencing textures that aren't embedded still need them loaded.
+/// - 9: Embedded textures' `data` is base64 rather than a list of bytes,
+///   which is still read.
+pub const FORMAT_VERSION: u32 = 9;
+
+/// A saved drawing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Document {
+    pub version: u32,
+    pub camera: CameraData,
+    pub active_layer: usize,
+    pub layers: Vec<LayerData>,
+    #[serde(default)]
+    pub styles: StyleSheet,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub textures: Vec<TextureData>,
+}
+
+/// An image embedded in a document, stored the way it was encoded when it
+/// was loaded. `id` is what fills in the document use to refer to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextureData {
+    pub id: TextureId,
+    pub name: String,
+    #[serde(with = "base64_bytes")]
+    pub data: Vec<u8>,
+}
+
+/// Bytes written as base64, as a list of numbers takes several times the
+/// space and, pretty printed, a line each.
+mod base64_bytes {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+
+    /// Also reads the list of numbers older versions wrote.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Encoded {
+            Base64(String),
+            Bytes(Vec<u8>),
+        }
+        match Encoded::deserialize(deserializer)? {
+            Encoded::Base64(text) => STANDARD.decode(text).map_err(serde::de::Error::custom),
+            Encoded::Bytes(data) => Ok(data),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CameraData {
+    pub position: Vec2,
+    pub zoom: f32,
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+impl Default for CameraData {
+    fn default() -> Self {
+        Self {
+            position: Vec2::ZERO,
+            zoom: 1.0,
+            rotation: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerData {
+    pub name: String,
+    pub visible: bool,
+    pub locked: bool,
+    pub opacity: f32,
+    pub nodes: Vec<NodeData>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub constraints: Vec<ConstraintData>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dimensions: Vec<DimensionData>,
+}
+
+/// A [Constraint] with its lines referenced by their position in the
+/// layer, counting depth first the way [Scene::iter] does.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ConstraintData {
+    pub kind: ConstraintKind,
+    pub a: usize,
+    pub b: usize,
+}
+
+/// A [Dimension] with nodes referenced the same way as in
+/// [ConstraintData].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DimensionData {
+    pub start: AnchorData,
+    pub end: AnchorData,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum AnchorData {
+    Point(Vec2),
+    Vertex { node: usize, index: usize },
+}
+
+impl AnchorData {
+    fn capture(anchor: &Anchor, order: &[NodeId]) -> Option<Self> {
+        Some(match *anchor {
+            Anchor::Point(p) => Self::Point(p),
+            Anchor::Vertex { node, index } => Self::Vertex {
+                node: order.iter().position(|&n| n == node)?,
+                index,
+            },
+        })
+    }
+
+    fn build(&self, order: &[NodeId]) -> anyhow::Result<Anchor> {
+        Ok(match *self {
+            Self::Point(p) => Anchor::Point(p),
+            Self::Vertex { node, index } => Anchor::Vertex {
+                node: *order.get(node).ok_or_else(|| {
+                    anyhow::anyhow!("Dimension refers to a node that doesn't exist")
+                })?,
+                index,
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeData {
+    pub name: String,
+    pub transform: Transform2D,
+    pub kind: NodeKind,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<NodeData>,
+}
+
+/// Loads saved drawings, in the format their extension says.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DocumentLoader;
+
+impl Loader for DocumentLoader {
+    type Asset = Document;
+
+    fn extensions(&self) -> &[&str] {
+        &["ron", "json"]
+    }
+
+    fn load(&self, data: Vec<u8>, context: &LoadContext) -> anyhow::Result<Document> {
+        Document::parse(&String::from_utf8(data)?, Format::from_path(context.path))
+    }
+}
+
+/// Just enough of a document to find out what version it is.
+#[derive(Deserialize)]
+#[serde(rename = "Document")]
+struct Header {
+    version: u32,
+}
+
+impl Document {
+    /// Snapshots the drawing, embedding the textures its fills use.
+    pub fn capture(layers: &LayerStack, camera: &OrthoCamera, textures: &TextureRegistry) -> Self {
+        let mut used: Vec<TextureId> = layers
+            .iter()
+            .flat_map(|layer| layer.scene().iter())
+            .filter_map(|(_, node)| match &node.shape()?.fill {
+                Some(Fill::Texture(fill)) => Some(fill.texture),
+                _ => None,
+            })
+            .collect();
+        used.sort();
+        used.dedup();
+        Self {
+            version: FORMAT_VERSION,
+            camera: CameraData {
+                position: camera.position(),
+                zoom: camera.zoom(),
+                rotation: camera.rotation(),
+            },
+            active_layer: layers.active_index(),
+            layers: layers
+                .iter()
+                .map(|layer| {
+                    let order: Vec<NodeId> = layer.scene().iter().map(|(id, _)| id).collect();
+                    let position = |id| order.iter().position(|&n| n == id);
+                    LayerData {
+                        name: layer.name().to_string(),
+                        visible: layer.is_visible(),
+                        locked: layer.is_locked(),
+                        opacity: layer.opacity(),
+                        nodes: layer
+                            .scene()
+                            .roots()
+                            .iter()
+                            .filter_map(|&id| NodeData::capture(layer.scene(), id))
+                            .collect(),
+                        // Constraints on removed nodes are dropped.
+                        constraints: layer
+                            .constraints()
+                            .iter()
+                            .filter_map(|c| {
+                                Some(ConstraintData {
+                                    kind: c.kind,
+                                    a: position(c.a)?,
+                                    b: position(c.b)?,
+                                })
+                            })
+                            .collect(),
+                        dimensions: layer
+                            .dimensions()
+                            .iter()
+                            .filter_map(|d| {
+                                Some(DimensionData {
+                                    start: AnchorData::capture(&d.start, &order)?,
+                                    end: AnchorData::capture(&d.end, &order)?,
+                                })
+                            })
+                            .collect(),
+                    }
+                })
+                .collect(),
+            styles: layers.styles().clone(),
+            textures: used
+                .into_iter()
+                .filter_map(|id| {
+                    let texture = textures.get(id)?;
+                    // Only the file name, so where it was opened from on
+                    // this machine isn't shared along with the drawing.
+                    let name = std::path::Path::new(texture.name())
+                        .file_name()
+                        .map_or_else(|| texture.name().into(), |n| n.to_string_lossy().into());
+                    Some(TextureData {
+                        id,
+                        name,
+                        data: texture.source()?.to_vec(),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    pub fn serialize(&self, format: Format) -> anyhow::Result<String> {
+        Ok(match format {
+            Format::Json => serde_json::to_string_pretty(self)?,
+            Format::Ron => ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?,
+        })
+    }
+
+    /// Parses a document, upgrading it if it was written by an older
+    /// version.
+    pub fn parse(data: &str, format: Format) -> anyhow::Result<Self> {
+        let header: Header = match format {
+            Format::Json => serde_json::from_str(data)?,
+            Format::Ron => ron::from_str(data)?,
+        };
+        match header.version {
+            0 => anyhow::bail!("Invalid document version 0"),
+            v if v > FORMAT_VERSION => anyhow::bail!(
+                "Document version {v} is newer than the supported version {FORMAT_VERSION}"
+            ),
+            // Every field added since version 1 is `#[serde(default)]`, so
+            // older versions parse as is.
+            _ => {}
+        }
+        Ok(match format {
+            Format::Json => serde_json::from_str(data)?,
+            Format::Ron => ron::from_str(data)?,
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.serialize(Format::from_path(path))?)?;
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read_to_string(path)?;
+        Self::parse(&data, Format::from_path(path))
+    }
+
+    /// Recreates the layers described by the document. Embedded textures
+    /// are added to `textures` unless one loaded from the same image is
+    /// already there.
+    #[allow(clippy::too_many_arguments)]
+    pub fn build_layers(
+        &self,
+        geometry_pipeline: &GeometryPipeline,
+        textures: &mut TextureRegistry,
+        font: &Font,
+        text_pipeline: &TextPipeline,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<LayerStack> {
+        // Checked before any are loaded, so a document that can't be opened
+        // leaves the registry as it was.
+        let max_size = device.limits().max_texture_dimension_2d;
+        for texture in &self.textures {
+            if textures.find_source(&texture.data).is_none() {
+                Texture::ensure_fits(&texture.data, &texture.name, max_size)?;
+            }
+        }
+
+        // Ids in the registry won't match the ones the document was saved
+        // with, so fills are pointed at wherever their texture ends up.
+        let mut remap = HashMap::new();
+        for texture in &self.textures {
+            let id = match textures.find_source(&texture.data) {
+                Some(id) => id,
+                None => textures.load(texture.name.clone(), &texture.data, device, queue)?,
+            };
+            remap.insert(texture.id, id);
+        }
+
+        let mut layers = Vec::with_capacity(self.layers.len());
+        for data in &self.layers {
+            let mut layer = Layer::new(data.name.clone(), geometry_pipeline, device);
+            layer.set_visible(data.visible);
+            layer.set_locked(data.locked);
+            layer.set_opacity(data.opacity, queue);
+
+            for node in &data.nodes {
+                let mut node = node.clone();
+                node.remap_textures(&remap);
+                node.build(&mut layer, None, font, text_pipeline, device, queue)?;
+            }
+            let order: Vec<NodeId> = layer.scene().iter().map(|(id, _)| id).collect();
+            for constraint in &data.constraints {
+                let (Some(&a), Some(&b)) = (order.get(constraint.a), order.get(constraint.b))
+                else {
+                    anyhow::bail!("Constraint refers to a node that doesn't exist");
+                };
+                layer.add_constraint(Constraint::new(constraint.kind, a, b));
+            }
+            for dimension in &data.dimensions {
+                layer.add_dimension(Dimension::new(
+                    dimension.start.build(&order)?,
+                    dimension.end.build(&order)?,
+                ));
+            }
+            layers.push(layer);
+        }
+
+        LayerStack::from_layers(layers, self.active_layer, self.styles.clone())
+            .ok_or_else(|| anyhow::anyhow!("Document has no layers"))
+    }
+
+    pub fn apply_camera(&self, camera: &mut OrthoCamera) {
+        camera.set_position(self.camera.position);
+        camera.set_zoom(self.camera.zoom);
+        camera.set_rotation(self.camera.rotation);
+    }
+}
+
+impl NodeData {
+    /// Snapshots a node and its descendants.
+    pub fn capture(scene: &Scene, id: NodeId) -> Option<Self> {
+        let node = scene.get(id)?;
+        Some(Self {
+            name: node.name.clone(),
+            transform: *node.transform(),
+            kind: node.kind.clone(),
+            children: node
+                .children()
+                .iter()
+                .filter_map(|&c| Self::capture(scene, c))
+                .collect(),
+        })
+    }
+
+    /// Points texture fills of the node and its descendants at new ids.
+    /// Textures missing from `remap` are left alone.
+    fn remap_textures(&mut self, remap: &HashMap<TextureId, TextureId>) {
+        if let NodeKind::Shape(shape) = &mut self.kind {
+            if let Some(Fill::Texture(fill)) = &mut shape.fill {
+                if let Some(&id) = remap.get(&fill.texture) {
+                    fill.texture = id;
+                }
+            }
+        }
+        for child in &mut self.children {
+            child.remap_textures(remap);
+        }
+    }
+
+    /// Adds the node and its descendants to `layer` and returns the id of
+    /// the new node.
+    pub fn build(
+        &self,
+        layer: &mut Layer,
+        parent: Option<NodeId>,
+        font: &Font,
+        text_pipeline: &TextPipeline,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<NodeId> {
+        let mut root = None;
+        let mut stack = vec![(self, parent)];
+        while let Some((node, parent)) = stack.pop() {
+            let id = match &node.kind {
+                NodeKind::Group => layer.add_group(node.name.clone(), node.transform, parent),
+                NodeKind::Shape(shape) => layer.add_shape(
+                    node.name.clone(),
+                    shape.clone(),
+                    node.transform,
+                    parent,
+                    device,
+                    queue,
+                ),
+                NodeKind::Text(text) => layer.add_text(
+                    text.clone(),
+                    node.transform,
+                    parent,
+                    font,
+                    text_pipeline,
+                    device,
+                )?,
+            };
+            root.get_or_insert(id);
+            stack.extend(node.children.iter().rev().map(|c| (c, Some(id))));
+        }
+        // The stack starts with `self`, so there's always a root.
+        Ok(root.unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_texture(data: &[u8]) -> Document {
+        Document {
+            version: FORMAT_VERSION,
+            camera: CameraData::default(),
+            active_layer: 0,
+            layers: Vec::new(),
+            styles: StyleSheet::default(),
+            textures: vec![TextureData {
+                id: serde_json::from_str("3").unwrap(),
+                name: "image.png".into(),
+                data: data.to_vec(),
+            }],
+        }
+    }
+
+    #[test]
+    fn embedded_textures_round_trip_as_base64() {
+        let data: Vec<u8> = (0..=255).collect();
+        for format in [Format::Json, Format::Ron] {
+            let text = with_texture(&data).serialize(format).unwrap();
+            assert!(text.contains("AAECAwQF"), "{format:?} isn't base64");
+            let document = Document::parse(&text, format).unwrap();
+            assert_eq!(document.textures[0].data, data);
+            assert_eq!(document.textures[0].name, "image.png");
+        }
+    }
+
+    #[test]
+    fn version_8_texture_bytes_still_parse() {
+        let text = r#"{
+            "version": 8,
+            "camera": { "position": [0.0, 0.0], "zoom": 1.0 },
+            "active_layer": 0,
+            "layers": [],
+            "textures": [{ "id": 0, "name": "image.png", "data": [1, 2, 3] }]
+        }"#;
+        let document = Document::parse(text, Format::Json).unwrap();
+        assert_eq!(document.textures[0].data, [1, 2, 3]);
+    }
+}
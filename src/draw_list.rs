@@ -0,0 +1,226 @@
+use std::ops::Range;
+
+use crate::{resources::camera::CameraBinding, stats};
+
+/// Bind groups a [Draw] can set. Matches wgpu's default limit.
+pub const MAX_BIND_GROUPS: usize = 4;
+/// Vertex buffers a [Draw] can set.
+pub const MAX_VERTEX_BUFFERS: usize = 2;
+
+#[derive(Debug, Clone, Copy)]
+struct Binding<'a> {
+    bind_group: &'a wgpu::BindGroup,
+    offset: Option<u32>,
+    /// Counted in [RenderStats::texture_binds](stats::RenderStats::texture_binds).
+    texture: bool,
+}
+
+impl Binding<'_> {
+    fn same(&self, other: &Self) -> bool {
+        std::ptr::eq(self.bind_group, other.bind_group) && self.offset == other.offset
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Elements {
+    Vertices(Range<u32>),
+    Indices(Range<u32>),
+}
+
+/// One draw call along with the state it needs. Made with [Draw::new] and
+/// the setters, then ended with [Draw::vertices] or [Draw::indexed].
+#[derive(Debug, Clone)]
+pub struct Draw<'a> {
+    layer: u32,
+    pipeline: &'a wgpu::RenderPipeline,
+    bind_groups: [Option<Binding<'a>>; MAX_BIND_GROUPS],
+    vertex_buffers: [Option<&'a wgpu::Buffer>; MAX_VERTEX_BUFFERS],
+    index_buffer: Option<&'a wgpu::Buffer>,
+    elements: Elements,
+    instances: Range<u32>,
+}
+
+impl<'a> Draw<'a> {
+    pub fn new(pipeline: &'a wgpu::RenderPipeline) -> Self {
+        Self {
+            layer: 0,
+            pipeline,
+            bind_groups: [None; MAX_BIND_GROUPS],
+            vertex_buffers: [None; MAX_VERTEX_BUFFERS],
+            index_buffer: None,
+            elements: Elements::Vertices(0..0),
+            instances: 0..0,
+        }
+    }
+
+    pub fn bind(mut self, index: u32, bind_group: &'a wgpu::BindGroup) -> Self {
+        self.bind_groups[index as usize] = Some(Binding {
+            bind_group,
+            offset: None,
+            texture: false,
+        });
+        self
+    }
+
+    /// Binds a group with one dynamic offset, like a camera's.
+    pub fn bind_at(mut self, index: u32, bind_group: &'a wgpu::BindGroup, offset: u32) -> Self {
+        self.bind_groups[index as usize] = Some(Binding {
+            bind_group,
+            offset: Some(offset),
+            texture: false,
+        });
+        self
+    }
+
+    /// Binds `camera_binding` to group `index`.
+    pub fn camera(self, index: u32, camera_binding: &'a CameraBinding) -> Self {
+        self.bind_at(index, camera_binding.bind_group(), camera_binding.offset())
+    }
+
+    /// Binds a group holding a texture.
+    pub fn texture(mut self, index: u32, bind_group: &'a wgpu::BindGroup) -> Self {
+        self.bind_groups[index as usize] = Some(Binding {
+            bind_group,
+            offset: None,
+            texture: true,
+        });
+        self
+    }
+
+    /// Sets the whole of `buffer` as vertex buffer `slot`.
+    pub fn vertex_buffer(mut self, slot: u32, buffer: &'a wgpu::Buffer) -> Self {
+        self.vertex_buffers[slot as usize] = Some(buffer);
+        self
+    }
+
+    /// Sets the whole of `buffer` as the `u32` index buffer.
+    pub fn index_buffer(mut self, buffer: &'a wgpu::Buffer) -> Self {
+        self.index_buffer = Some(buffer);
+        self
+    }
+
+    pub fn vertices(mut self, vertices: Range<u32>, instances: Range<u32>) -> Self {
+        self.elements = Elements::Vertices(vertices);
+        self.instances = instances;
+        self
+    }
+
+    pub fn indexed(mut self, indices: Range<u32>, instances: Range<u32>) -> Self {
+        self.elements = Elements::Indices(indices);
+        self.instances = instances;
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        let elements = match &self.elements {
+            Elements::Vertices(range) | Elements::Indices(range) => range,
+        };
+        elements.is_empty() || self.instances.is_empty()
+    }
+
+    /// Draws on the same layer with the same pipeline and bind groups end
+    /// up next to each other.
+    fn key(&self) -> (u32, usize, [usize; MAX_BIND_GROUPS]) {
+        (
+            self.layer,
+            self.pipeline as *const _ as usize,
+            self.bind_groups
+                .map(|binding| binding.map_or(0, |b| b.bind_group as *const _ as usize)),
+        )
+    }
+}
+
+/// Collects draws so they can be sorted by layer, pipeline and bind groups,
+/// then records them setting only the state that changes between them.
+/// Draws on one layer can be reordered, so ones that have to stay stacked
+/// in order go on successive layers with [DrawList::next_layer].
+#[derive(Debug, Default)]
+pub struct DrawList<'a> {
+    draws: Vec<Draw<'a>>,
+    layer: u32,
+}
+
+impl<'a> DrawList<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The layer draws are pushed onto.
+    pub fn layer(&self) -> u32 {
+        self.layer
+    }
+
+    /// Puts the draws pushed from now on above all the ones before.
+    pub fn next_layer(&mut self) {
+        self.layer += 1;
+    }
+
+    /// Adds `draw` on the current layer. Empty draws are dropped.
+    pub fn push(&mut self, mut draw: Draw<'a>) {
+        if !draw.is_empty() {
+            draw.layer = self.layer;
+            self.draws.push(draw);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    /// Sorts the draws and records them into `pass`, leaving the list
+    /// empty. State set in `pass` beforehand is assumed to be stale.
+    pub fn execute(&mut self, pass: &mut wgpu::RenderPass<'_>) {
+        // Stable, so draws with the same state keep their order.
+        self.draws.sort_by_key(Draw::key);
+
+        let mut pipeline = None;
+        let mut bind_groups: [Option<Binding>; MAX_BIND_GROUPS] = [None; MAX_BIND_GROUPS];
+        let mut vertex_buffers: [Option<&wgpu::Buffer>; MAX_VERTEX_BUFFERS] =
+            [None; MAX_VERTEX_BUFFERS];
+        let mut index_buffer = None;
+        for draw in self.draws.drain(..) {
+            if !pipeline.is_some_and(|current| std::ptr::eq(current, draw.pipeline)) {
+                pass.set_pipeline(draw.pipeline);
+                pipeline = Some(draw.pipeline);
+            }
+            for (i, binding) in draw.bind_groups.iter().enumerate() {
+                let Some(binding) = binding else {
+                    continue;
+                };
+                if bind_groups[i].is_some_and(|current| current.same(binding)) {
+                    continue;
+                }
+                match (binding.offset, binding.texture) {
+                    (None, true) => stats::bind_texture(pass, i as u32, binding.bind_group),
+                    (offset, _) => {
+                        pass.set_bind_group(i as u32, binding.bind_group, offset.as_slice())
+                    }
+                }
+                bind_groups[i] = Some(*binding);
+            }
+            for (slot, buffer) in draw.vertex_buffers.iter().enumerate() {
+                let Some(buffer) = *buffer else {
+                    continue;
+                };
+                if !vertex_buffers[slot].is_some_and(|current| std::ptr::eq(current, buffer)) {
+                    pass.set_vertex_buffer(slot as u32, buffer.slice(..));
+                    vertex_buffers[slot] = Some(buffer);
+                }
+            }
+            if let Some(buffer) = draw.index_buffer {
+                if !index_buffer.is_some_and(|current| std::ptr::eq(current, buffer)) {
+                    pass.set_index_buffer(buffer.slice(..), wgpu::IndexFormat::Uint32);
+                    index_buffer = Some(buffer);
+                }
+            }
+            match draw.elements {
+                Elements::Vertices(vertices) => stats::draw(pass, vertices, draw.instances),
+                Elements::Indices(indices) => stats::draw_indexed(pass, indices, 0, draw.instances),
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use futures_channel::oneshot;
+
+/// A rendered image on its way back from the GPU. The copy is queued when
+/// this is created, and [PendingImage::png] resolves once the buffer has
+/// been mapped. On native the device needs to be polled for that to happen.
+pub struct PendingImage {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    format: wgpu::TextureFormat,
+    mapped: oneshot::Receiver<Result<(), wgpu::BufferAsyncError>>,
+}
+
+impl PendingImage {
+    /// Copies `texture` into a mappable buffer and submits the copy along
+    /// with `encoder`, which should contain the commands that draw into it.
+    pub fn new(
+        texture: &wgpu::Texture,
+        mut encoder: wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Self> {
+        let format = texture.format();
+        anyhow::ensure!(
+            matches!(
+                format.remove_srgb_suffix(),
+                wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Bgra8Unorm
+            ),
+            "Unable to read back {format:?} textures"
+        );
+
+        let width = texture.width();
+        let height = texture.height();
+        let padded_bytes_per_row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("PendingImage::buffer"),
+            size: padded_bytes_per_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        queue.submit([encoder.finish()]);
+
+        let (sender, mapped) = oneshot::channel();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+
+        Ok(Self {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            format,
+            mapped,
+        })
+    }
+
+    /// Waits for the image.
+    pub async fn image(mut self) -> anyhow::Result<image::RgbaImage> {
+        (&mut self.mapped).await??;
+        self.read()
+    }
+
+    /// Waits for the image and encodes it as a PNG.
+    pub async fn png(self) -> anyhow::Result<Vec<u8>> {
+        let image = self.image().await?;
+        let mut png = Vec::new();
+        image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+        Ok(png)
+    }
+
+    /// Returns the image if the buffer has been mapped, without waiting.
+    pub fn try_image(&mut self) -> Option<anyhow::Result<image::RgbaImage>> {
+        match self.mapped.try_recv() {
+            Ok(None) => None,
+            Ok(Some(Ok(()))) => Some(self.read()),
+            Ok(Some(Err(e))) => Some(Err(e.into())),
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+
+    /// Copies the pixels out of the mapped buffer.
+    fn read(&self) -> anyhow::Result<image::RgbaImage> {
+        let row_bytes = self.width as usize * 4;
+        let mut pixels = Vec::with_capacity(row_bytes * self.height as usize);
+        {
+            let data = self.buffer.slice(..).get_mapped_range();
+            for row in data.chunks(self.padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..row_bytes]);
+            }
+        }
+        self.buffer.unmap();
+
+        if self.format.remove_srgb_suffix() == wgpu::TextureFormat::Bgra8Unorm {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        image::RgbaImage::from_raw(self.width, self.height, pixels)
+            .ok_or_else(|| anyhow::anyhow!("Image data doesn't match its size"))
+    }
+}
+
+/// A copy of a frame drawn to the window, asked for with
+/// `Canvas::capture_frame`. It's taken the next time the canvas is drawn.
+pub struct FrameCapture {
+    image: oneshot::Receiver<anyhow::Result<image::RgbaImage>>,
+}
+
+impl FrameCapture {
+    /// Waits for the image. The canvas has to keep being drawn for this to
+    /// resolve, so it can't be blocked on from the thread that draws it.
+    pub async fn image(self) -> anyhow::Result<image::RgbaImage> {
+        self.image.await?
+    }
+
+    /// Returns the image if it's ready, without waiting.
+    pub fn try_image(&mut self) -> Option<anyhow::Result<image::RgbaImage>> {
+        match self.image.try_recv() {
+            Ok(image) => image,
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
+type CaptureSender = oneshot::Sender<anyhow::Result<image::RgbaImage>>;
+
+/// Frame captures waiting for a frame, and ones waiting on the GPU.
+#[derive(Default)]
+pub struct FrameCaptures {
+    requested: Vec<CaptureSender>,
+    in_flight: Vec<(PendingImage, Vec<CaptureSender>)>,
+}
+
+impl FrameCaptures {
+    pub fn request(&mut self) -> FrameCapture {
+        let (sender, image) = oneshot::channel();
+        self.requested.push(sender);
+        FrameCapture { image }
+    }
+
+    /// Whether there's a capture that hasn't been handed over yet.
+    pub fn is_pending(&self) -> bool {
+        !self.requested.is_empty() || !self.in_flight.is_empty()
+    }
+
+    pub fn is_requested(&self) -> bool {
+        !self.requested.is_empty()
+    }
+
+    /// Copies `frame` for every capture asked for since the last frame.
+    /// `encoder` should contain the commands that draw into it, and is
+    /// submitted along with the copy.
+    pub fn capture(
+        &mut self,
+        frame: &wgpu::Texture,
+        encoder: wgpu::CommandEncoder,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        let senders = std::mem::take(&mut self.requested);
+        match PendingImage::new(frame, encoder, device, queue) {
+            Ok(image) => self.in_flight.push((image, senders)),
+            Err(e) => {
+                for sender in senders {
+                    let _ = sender.send(Err(anyhow::anyhow!("{e}")));
+                }
+            }
+        }
+    }
+
+    /// Hands over the images that have finished copying.
+    pub fn poll(&mut self) {
+        self.in_flight.retain_mut(|(image, senders)| {
+            let Some(result) = image.try_image() else {
+                return true;
+            };
+            for sender in senders.drain(..) {
+                let result = match &result {
+                    Ok(image) => Ok(image.clone()),
+                    Err(e) => Err(anyhow::anyhow!("{e}")),
+                };
+                let _ = sender.send(result);
+            }
+            false
+        });
+    }
+}
+
+/// Has the browser download `data` as a file.
+#[cfg(target_arch = "wasm32")]
+pub fn download(data: &[u8], file_name: &str, mime: &str) -> Result<(), wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsCast;
+
+    let array = js_sys::Uint8Array::from(data);
+    let parts = js_sys::Array::of1(&array);
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime);
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+
+    let window = web_sys::window().ok_or("No window")?;
+    let document = window.document().ok_or("No document")?;
+    let anchor: web_sys::HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    // The download starts after the click returns, so the URL has to stay
+    // valid until then.
+    let revoke = wasm_bindgen::closure::Closure::once_into_js(move || {
+        let _ = web_sys::Url::revoke_object_url(&url);
+    });
+    window.set_timeout_with_callback_and_timeout_and_arguments_0(revoke.unchecked_ref(), 0)?;
+    Ok(())
+}
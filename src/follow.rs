@@ -0,0 +1,77 @@
+use glam::Vec2;
+
+use crate::{
+    layers::LayerStack,
+    resources::camera::{Camera, OrthoCamera},
+    tools::select::Selection,
+};
+
+/// Part of the view, as a fraction of its size, that a followed object can
+/// move around in without the camera moving.
+pub const DEFAULT_DEAD_ZONE: Vec2 = Vec2::new(0.5, 0.5);
+
+/// What a [CameraFollow] keeps in view.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FollowTarget {
+    /// The primary selection, which is usually whatever was edited last.
+    Selection,
+    Node(Selection),
+    /// The first node with this name, looking through the layers in order.
+    Named(String),
+}
+
+/// Moves the camera to keep a target inside a dead zone around the middle
+/// of the view.
+#[derive(Debug, Clone)]
+pub struct CameraFollow {
+    pub target: FollowTarget,
+    /// Size of the dead zone as a fraction of the view.
+    pub dead_zone: Vec2,
+}
+
+impl CameraFollow {
+    pub fn new(target: FollowTarget) -> Self {
+        Self {
+            target,
+            dead_zone: DEFAULT_DEAD_ZONE,
+        }
+    }
+
+    /// World position of the middle of the target, or `None` if it's gone.
+    pub fn target_position(
+        &self,
+        layers: &mut LayerStack,
+        selection: Option<Selection>,
+    ) -> Option<Vec2> {
+        let (layer, node) = match &self.target {
+            FollowTarget::Selection => {
+                let selection = selection?;
+                (selection.layer, selection.node)
+            }
+            FollowTarget::Node(selection) => (selection.layer, selection.node),
+            FollowTarget::Named(name) => layers.iter().enumerate().find_map(|(index, layer)| {
+                let (id, _) = layer.scene().iter().find(|(_, node)| &node.name == name)?;
+                Some((index, id))
+            })?,
+        };
+        let scene = layers.get_mut(layer)?.scene_mut();
+        if !scene.contains(node) {
+            return None;
+        }
+        match scene.world_bounds(node) {
+            Some(bounds) => Some(bounds.center()),
+            None => Some(scene.world_transform(node).translation),
+        }
+    }
+
+    /// Where `camera` should be centered to bring `position` back into the
+    /// dead zone, or `None` if it's already in it.
+    pub fn recenter(&self, camera: &OrthoCamera, position: Vec2) -> Option<Vec2> {
+        let size = camera.viewport_size();
+        let middle = size * 0.5;
+        let half_zone = size * self.dead_zone.clamp(Vec2::ZERO, Vec2::ONE) * 0.5;
+        let offset = camera.world_to_screen(position) - middle;
+        let overshoot = offset - offset.clamp(-half_zone, half_zone);
+        (overshoot != Vec2::ZERO).then(|| camera.screen_to_world(middle + overshoot))
+    }
+}
@@ -0,0 +1,18 @@
+use std::path::Path;
+
+/// How a file's data is written, for files that can be either.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Ron,
+}
+
+impl Format {
+    /// Picks a format based on the file extension, defaulting to RON.
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Ron,
+        }
+    }
+}
@@ -0,0 +1,429 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+use winit::window::Window;
+
+use crate::{capabilities::GpuCapabilities, window::WindowConfig};
+
+/// Samples per pixel when the surface format supports multisampling.
+const MSAA_SAMPLE_COUNT: u32 = 4;
+
+/// The device and the window's surface, set up for drawing.
+pub struct Gpu {
+    /// Kept to recreate the surface when the app is resumed.
+    pub instance: wgpu::Instance,
+    pub adapter: wgpu::Adapter,
+    /// `None` while the app is suspended.
+    pub surface: Option<wgpu::Surface<'static>>,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub config: wgpu::SurfaceConfiguration,
+    /// What the surface is drawn in, and what every pipeline drawing into
+    /// it targets. See [select_format].
+    pub format: wgpu::TextureFormat,
+    pub capabilities: GpuCapabilities,
+    pub present_modes: Vec<wgpu::PresentMode>,
+    /// Samples per pixel for pipelines drawing into the surface.
+    pub sample_count: u32,
+    /// Transparent if the window is.
+    pub clear_color: wgpu::Color,
+}
+
+impl Gpu {
+    /// Sets up drawing into `window`, which was created with
+    /// `window_config`. The surface is configured once the window has a
+    /// size, which on the web may be after the first resize. WebGL2 is used
+    /// where WebGPU isn't supported.
+    pub async fn new(window: Arc<Window>, window_config: &WindowConfig) -> anyhow::Result<Self> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let backends = wgpu::Backends::all();
+        #[cfg(target_arch = "wasm32")]
+        let backends = if wgpu::util::is_browser_webgpu_supported().await {
+            wgpu::Backends::BROWSER_WEBGPU
+        } else {
+            log::warn!("WebGPU isn't supported, falling back to WebGL2");
+            wgpu::Backends::GL
+        };
+        log::info!("Backends: {backends:?}");
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends,
+            ..Default::default()
+        });
+        log::info!("Creating surface");
+        let surface = instance.create_surface(window.clone())?;
+        log::info!("Requesting adapter");
+        #[cfg(not(target_arch = "wasm32"))]
+        let named = window_config.adapter_name.as_deref().and_then(|name| {
+            let adapter = find_adapter(&instance, backends, &surface, name);
+            if adapter.is_none() {
+                log::warn!("No compatible adapter named {name:?}");
+            }
+            adapter
+        });
+        #[cfg(target_arch = "wasm32")]
+        let named = None;
+        let adapter = match named {
+            Some(adapter) => Some(adapter),
+            None => {
+                instance
+                    .request_adapter(&wgpu::RequestAdapterOptions {
+                        power_preference: window_config.power_preference,
+                        compatible_surface: Some(&surface),
+                        ..Default::default()
+                    })
+                    .await
+            }
+        };
+        #[cfg(target_arch = "wasm32")]
+        if adapter.is_none() {
+            show_error();
+        }
+        let adapter = adapter.with_context(|| "No compatible adapter")?;
+        let info = adapter.get_info();
+        log::info!("Adapter: {} ({:?})", info.name, info.backend);
+        let device_request = adapter
+            .request_device(
+                &GpuCapabilities::negotiate(&adapter).device_descriptor(),
+                None,
+            )
+            .await;
+        log::info!("Requesting device");
+        #[cfg(not(target_arch = "wasm32"))]
+        let (device, queue) = device_request?;
+        #[cfg(target_arch = "wasm32")]
+        let (device, queue) = device_request.unwrap_throw();
+        let capabilities = GpuCapabilities::granted(&device);
+        log::info!("Optional features: {:?}", capabilities.features());
+
+        let mut config = surface
+            .get_default_config(
+                &adapter,
+                window.inner_size().width,
+                window.inner_size().height,
+            )
+            .with_context(|| "Surface is invalid")?;
+        let surface_capabilities = surface.get_capabilities(&adapter);
+        let has_view_formats = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::SURFACE_VIEW_FORMATS);
+        let (surface_format, format) = select_format(
+            &surface_capabilities.formats,
+            has_view_formats,
+            window_config.hdr,
+        )
+        .with_context(|| "Surface has no formats")?;
+        if !format.is_srgb() && !crate::post::is_hdr(format) {
+            log::warn!("The surface can't be drawn in sRGB, colors will be off");
+        }
+        log::info!("Surface format: {surface_format:?}, drawn as {format:?}");
+        config.format = surface_format;
+        if format != surface_format {
+            config.view_formats.push(format);
+        }
+        let transparent_alpha = surface_capabilities
+            .alpha_modes
+            .iter()
+            .copied()
+            .find(|mode| {
+                matches!(
+                    mode,
+                    wgpu::CompositeAlphaMode::PreMultiplied
+                        | wgpu::CompositeAlphaMode::PostMultiplied
+                )
+            });
+        let clear_color = match transparent_alpha.filter(|_| window_config.transparent) {
+            Some(alpha_mode) => {
+                config.alpha_mode = alpha_mode;
+                wgpu::Color::TRANSPARENT
+            }
+            None => {
+                if window_config.transparent {
+                    log::warn!("The surface can't be transparent");
+                }
+                wgpu::Color::BLACK
+            }
+        };
+        // Lets frames be copied out for captures.
+        if surface_capabilities
+            .usages
+            .contains(wgpu::TextureUsages::COPY_SRC)
+        {
+            config.usage |= wgpu::TextureUsages::COPY_SRC;
+        }
+        let present_modes = surface_capabilities.present_modes;
+        let sample_count = if adapter
+            .get_texture_format_features(format)
+            .flags
+            .sample_count_supported(MSAA_SAMPLE_COUNT)
+        {
+            MSAA_SAMPLE_COUNT
+        } else {
+            1
+        };
+        log::info!("Samples per pixel: {sample_count}");
+
+        if config.width > 0 && config.height > 0 {
+            surface.configure(&device, &config);
+        }
+
+        Ok(Self {
+            instance,
+            adapter,
+            surface: Some(surface),
+            device,
+            queue,
+            config,
+            format,
+            capabilities,
+            present_modes,
+            sample_count,
+            clear_color,
+        })
+    }
+
+    /// Reconfigures the surface for the window's new size.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.config.width = width.max(1);
+        self.config.height = height.max(1);
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+    }
+
+    /// Drops the surface. Mobile platforms take the window's away while
+    /// the app is suspended.
+    pub fn suspend(&mut self) {
+        self.surface = None;
+    }
+
+    /// Recreates the surface for `window` after [Gpu::suspend]. See
+    /// [recreate_surface].
+    pub fn resume(&mut self, window: Arc<Window>) -> anyhow::Result<()> {
+        self.surface = None;
+        let size = window.inner_size();
+        let (surface, present_modes) =
+            recreate_surface(&self.instance, &self.adapter, window, &mut self.config)?;
+        self.surface = Some(surface);
+        self.present_modes = present_modes;
+        self.resize(size.width, size.height);
+        Ok(())
+    }
+
+    /// The view to draw the frame into.
+    pub fn view(&self, frame: &wgpu::SurfaceTexture) -> wgpu::TextureView {
+        frame_view(frame, self.format)
+    }
+}
+
+/// Picks the format a surface is created with and the one it's drawn in,
+/// from the ones it supports, most preferred first. Everything is blended
+/// in linear space and written out as sRGB, so in order:
+///
+/// 0. With `hdr`, an [HDR format](crate::post::is_hdr), drawn as is. SDR
+///    colors need a [Tonemap](crate::post::Tonemap) to show up right.
+/// 1. An 8-bit sRGB format, drawn as is.
+/// 2. An 8-bit linear format drawn through an sRGB view, where surfaces
+///    can be viewed in other formats. WebGL2 surfaces can't.
+/// 3. The most preferred format, drawn as is. Colors will be off.
+///
+/// Returns `None` if nothing is supported.
+pub fn select_format(
+    supported: &[wgpu::TextureFormat],
+    has_view_formats: bool,
+    hdr: bool,
+) -> Option<(wgpu::TextureFormat, wgpu::TextureFormat)> {
+    use wgpu::TextureFormat::*;
+    if let Some(&format) = supported
+        .iter()
+        .find(|&&format| hdr && crate::post::is_hdr(format))
+    {
+        return Some((format, format));
+    }
+    let srgb = supported
+        .iter()
+        .find(|format| matches!(format, Bgra8UnormSrgb | Rgba8UnormSrgb));
+    let linear = supported
+        .iter()
+        .find(|format| matches!(format, Bgra8Unorm | Rgba8Unorm))
+        .filter(|_| has_view_formats);
+    match (srgb, linear) {
+        (Some(&format), _) => Some((format, format)),
+        (None, Some(&format)) => Some((format, format.add_srgb_suffix())),
+        (None, None) => supported.first().map(|&format| (format, format)),
+    }
+}
+
+/// A view of `frame` in `format`, which is the surface's format or one of
+/// its view formats.
+pub fn frame_view(frame: &wgpu::SurfaceTexture, format: wgpu::TextureFormat) -> wgpu::TextureView {
+    frame.texture.create_view(&wgpu::TextureViewDescriptor {
+        format: Some(format),
+        ..Default::default()
+    })
+}
+
+/// The names of the adapters that could be picked with
+/// [WindowConfig::adapter_name].
+#[cfg(not(target_arch = "wasm32"))]
+pub fn adapter_names() -> Vec<String> {
+    wgpu::Instance::default()
+        .enumerate_adapters(wgpu::Backends::all())
+        .into_iter()
+        .map(|adapter| adapter.get_info().name)
+        .collect()
+}
+
+/// The first adapter that can draw to `surface` whose name contains `name`,
+/// ignoring case.
+#[cfg(not(target_arch = "wasm32"))]
+fn find_adapter(
+    instance: &wgpu::Instance,
+    backends: wgpu::Backends,
+    surface: &wgpu::Surface,
+    name: &str,
+) -> Option<wgpu::Adapter> {
+    let name = name.to_lowercase();
+    instance
+        .enumerate_adapters(backends)
+        .into_iter()
+        .find(|adapter| {
+            adapter.get_info().name.to_lowercase().contains(&name)
+                && adapter.is_surface_supported(surface)
+        })
+}
+
+/// `requested` if it's in `supported`, otherwise the closest mode that is.
+/// Low latency modes fall back on each other before VSync, which every
+/// surface supports.
+pub fn fallback_present_mode(
+    requested: wgpu::PresentMode,
+    supported: &[wgpu::PresentMode],
+) -> wgpu::PresentMode {
+    use wgpu::PresentMode::*;
+    let fallbacks: &[_] = match requested {
+        // wgpu picks from what's supported for these itself.
+        AutoVsync | AutoNoVsync => return requested,
+        Mailbox => &[Mailbox, Immediate, FifoRelaxed],
+        Immediate => &[Immediate, Mailbox, FifoRelaxed],
+        FifoRelaxed => &[FifoRelaxed],
+        Fifo => &[],
+    };
+    fallbacks
+        .iter()
+        .copied()
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(Fifo)
+}
+
+/// Creates a surface for `window`, which may be on another monitor or GPU
+/// than the one `config` was made for. The present mode, alpha mode and
+/// usages are changed to ones it supports, but it has to support the
+/// format, as pipelines were made for it. Returns the surface with the
+/// present modes it supports. The surface isn't configured.
+pub fn recreate_surface(
+    instance: &wgpu::Instance,
+    adapter: &wgpu::Adapter,
+    window: Arc<Window>,
+    config: &mut wgpu::SurfaceConfiguration,
+) -> anyhow::Result<(wgpu::Surface<'static>, Vec<wgpu::PresentMode>)> {
+    let surface = instance.create_surface(window)?;
+    let capabilities = surface.get_capabilities(adapter);
+    anyhow::ensure!(
+        capabilities.formats.contains(&config.format),
+        "The new surface can't be drawn in {:?}",
+        config.format
+    );
+    config.present_mode = fallback_present_mode(config.present_mode, &capabilities.present_modes);
+    if !capabilities.alpha_modes.contains(&config.alpha_mode) {
+        config.alpha_mode = wgpu::CompositeAlphaMode::Auto;
+    }
+    config.usage &= capabilities.usages;
+    Ok((surface, capabilities.present_modes))
+}
+
+/// The next frame to draw into `surface`, or nothing if this one should be
+/// skipped and drawn again. Fails if the frame can't be drawn at all.
+pub fn next_frame(
+    surface: &wgpu::Surface<'_>,
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+) -> anyhow::Result<Option<wgpu::SurfaceTexture>> {
+    match surface.get_current_texture() {
+        Ok(frame) => Ok(Some(frame)),
+        // The window was resized or moved to another display, or the
+        // driver reset. Drawing again once it's reconfigured is enough.
+        Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+            surface.configure(device, config);
+            Ok(None)
+        }
+        Err(wgpu::SurfaceError::Timeout) => {
+            log::warn!("Timed out waiting for a frame");
+            Ok(None)
+        }
+        Err(e @ wgpu::SurfaceError::OutOfMemory) => Err(e.into()),
+    }
+}
+
+/// Reveals the page's error message, for when there's nothing to draw with.
+#[cfg(target_arch = "wasm32")]
+fn show_error() {
+    let document = wgpu::web_sys::window()
+        .and_then(|window| window.document())
+        .unwrap_throw();
+    if let Some(error) = document.get_element_by_id("error") {
+        error.set_class_name("revealed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::{PresentMode::*, TextureFormat::*};
+
+    use super::*;
+
+    #[test]
+    fn present_modes_fall_back_to_vsync() {
+        assert_eq!(fallback_present_mode(Mailbox, &[Fifo, Mailbox]), Mailbox);
+        assert_eq!(
+            fallback_present_mode(Mailbox, &[Fifo, Immediate]),
+            Immediate
+        );
+        assert_eq!(fallback_present_mode(Immediate, &[Fifo]), Fifo);
+        assert_eq!(fallback_present_mode(FifoRelaxed, &[Fifo, Mailbox]), Fifo);
+        assert_eq!(fallback_present_mode(AutoNoVsync, &[]), AutoNoVsync);
+    }
+
+    #[test]
+    fn select_format_prefers_srgb() {
+        assert_eq!(
+            select_format(&[Bgra8Unorm, Bgra8UnormSrgb], true, false),
+            Some((Bgra8UnormSrgb, Bgra8UnormSrgb))
+        );
+        // Drawn through an sRGB view when there's one.
+        assert_eq!(
+            select_format(&[Rgba8Unorm], true, false),
+            Some((Rgba8Unorm, Rgba8UnormSrgb))
+        );
+        assert_eq!(
+            select_format(&[Rgba8Unorm], false, false),
+            Some((Rgba8Unorm, Rgba8Unorm))
+        );
+        assert_eq!(select_format(&[], true, false), None);
+    }
+
+    #[test]
+    fn select_format_only_picks_hdr_when_asked() {
+        let supported = [Bgra8UnormSrgb, Rgba16Float];
+        assert_eq!(
+            select_format(&supported, true, true),
+            Some((Rgba16Float, Rgba16Float))
+        );
+        assert_eq!(
+            select_format(&supported, true, false),
+            Some((Bgra8UnormSrgb, Bgra8UnormSrgb))
+        );
+    }
+}
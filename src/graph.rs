@@ -0,0 +1,212 @@
+use crate::resources::{depth::DepthBuffer, target::RenderTarget};
+
+/// Handle to a target added with [RenderGraph::add_target].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TargetId(usize);
+
+/// What a pass draws into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PassOutput {
+    /// The frame that ends up in the window.
+    Surface,
+    Target(TargetId),
+}
+
+/// The frame the graph draws into, and the depth buffer that goes with it.
+pub struct GraphSurface<'a> {
+    /// Builds the color attachment, which may draw through a multisampled
+    /// texture or a post-processing target instead of the surface itself.
+    pub color: Box<dyn Fn(wgpu::LoadOp<wgpu::Color>) -> wgpu::RenderPassColorAttachment<'a> + 'a>,
+    pub depth: Option<&'a DepthBuffer>,
+}
+
+type Encode<'a> = Box<dyn FnMut(&mut wgpu::CommandEncoder, &GraphSurface<'a>) + 'a>;
+
+enum PassWork<'a> {
+    Draw(Box<dyn Fn(&mut wgpu::RenderPass<'_>) + 'a>),
+    Encode(Encode<'a>),
+}
+
+/// One render pass and what it needs.
+pub struct GraphPass<'a> {
+    name: &'static str,
+    output: PassOutput,
+    depth: bool,
+    clear: Option<wgpu::Color>,
+    reads: Vec<TargetId>,
+    work: PassWork<'a>,
+}
+
+impl<'a> GraphPass<'a> {
+    pub fn new(
+        name: &'static str,
+        output: PassOutput,
+        draw: impl Fn(&mut wgpu::RenderPass<'_>) + 'a,
+    ) -> Self {
+        Self {
+            name,
+            output,
+            depth: false,
+            clear: None,
+            reads: Vec::new(),
+            work: PassWork::Draw(Box::new(draw)),
+        }
+    }
+
+    /// A pass that records its own commands, like any number of render
+    /// passes of its own. It's still scheduled as drawing into `output`,
+    /// but clearing and depth are up to it.
+    pub fn encode(
+        name: &'static str,
+        output: PassOutput,
+        encode: impl FnMut(&mut wgpu::CommandEncoder, &GraphSurface<'a>) + 'a,
+    ) -> Self {
+        Self {
+            name,
+            output,
+            depth: false,
+            clear: None,
+            reads: Vec::new(),
+            work: PassWork::Encode(Box::new(encode)),
+        }
+    }
+
+    /// Tests against the output's depth buffer, which is cleared first.
+    pub fn with_depth(mut self) -> Self {
+        self.depth = true;
+        self
+    }
+
+    /// Clears the output to `color` instead of drawing over it.
+    pub fn clear(mut self, color: wgpu::Color) -> Self {
+        self.clear = Some(color);
+        self
+    }
+
+    /// Samples `target`, so the passes drawing into it have to run first.
+    pub fn reads(mut self, target: TargetId) -> Self {
+        self.reads.push(target);
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// Passes that say what they draw into and what they read, run in an order
+/// that has every target drawn before it's read. Passes drawing into the
+/// same output run in the order they were added.
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    targets: Vec<&'a RenderTarget>,
+    passes: Vec<GraphPass<'a>>,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self {
+            targets: Vec::new(),
+            passes: Vec::new(),
+        }
+    }
+
+    pub fn add_target(&mut self, target: &'a RenderTarget) -> TargetId {
+        self.targets.push(target);
+        TargetId(self.targets.len() - 1)
+    }
+
+    pub fn add_pass(&mut self, pass: GraphPass<'a>) {
+        self.passes.push(pass);
+    }
+
+    /// Adds the passes from `other`. Its targets aren't carried over, so
+    /// its passes should only draw into the surface.
+    pub fn extend(&mut self, other: RenderGraph<'a>) {
+        self.passes.extend(other.passes);
+    }
+
+    pub fn passes(&self) -> &[GraphPass<'a>] {
+        &self.passes
+    }
+
+    /// The order the passes run in, as indices into
+    /// [RenderGraph::passes]. Fails if passes read each other's output in a
+    /// cycle.
+    pub fn schedule(&self) -> anyhow::Result<Vec<usize>> {
+        let count = self.passes.len();
+        let mut after = vec![Vec::new(); count];
+        for (i, pass) in self.passes.iter().enumerate() {
+            for (j, other) in self.passes.iter().enumerate() {
+                let draws_first = i < j && pass.output == other.output;
+                let feeds =
+                    matches!(pass.output, PassOutput::Target(t) if other.reads.contains(&t));
+                if i != j && (draws_first || feeds) {
+                    after[i].push(j);
+                }
+            }
+        }
+        let mut waiting_on = vec![0; count];
+        for &j in after.iter().flatten() {
+            waiting_on[j] += 1;
+        }
+        let mut order = Vec::with_capacity(count);
+        let mut done = vec![false; count];
+        while order.len() < count {
+            // The earliest added pass that's ready, to keep the order
+            // predictable.
+            let Some(next) = (0..count).find(|&i| !done[i] && waiting_on[i] == 0) else {
+                let stuck: Vec<_> = (0..count)
+                    .filter(|&i| !done[i])
+                    .map(|i| self.passes[i].name)
+                    .collect();
+                anyhow::bail!("Render passes depend on each other: {}", stuck.join(", "));
+            };
+            done[next] = true;
+            order.push(next);
+            for &j in &after[next] {
+                waiting_on[j] -= 1;
+            }
+        }
+        Ok(order)
+    }
+
+    /// Records every pass into `encoder`.
+    pub fn execute(
+        mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        surface: &GraphSurface<'a>,
+    ) -> anyhow::Result<()> {
+        for i in self.schedule()? {
+            let pass = &mut self.passes[i];
+            let draw = match &mut pass.work {
+                PassWork::Draw(draw) => draw,
+                PassWork::Encode(encode) => {
+                    encode(encoder, surface);
+                    continue;
+                }
+            };
+            let load = pass.clear.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear);
+            let (color, depth) = match pass.output {
+                PassOutput::Surface => ((surface.color)(load), surface.depth),
+                PassOutput::Target(TargetId(t)) => {
+                    let target = self.targets[t];
+                    (target.color_attachment(load), target.depth())
+                }
+            };
+            let depth = match (pass.depth, depth) {
+                (true, Some(depth)) => Some(depth.attachment()),
+                (true, None) => anyhow::bail!("{} needs a depth buffer", pass.name),
+                (false, _) => None,
+            };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(pass.name),
+                color_attachments: &[Some(color)],
+                depth_stencil_attachment: depth,
+                ..Default::default()
+            });
+            draw(&mut render_pass);
+        }
+        Ok(())
+    }
+}
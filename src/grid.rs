@@ -0,0 +1,222 @@
+use glam::Vec4;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::{
+    resources::camera::{CameraBinder, CameraBinding},
+    stats,
+    utils::RenderPipelineBuilder,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridSettings {
+    /// Distance between minor lines in world units.
+    pub spacing: f32,
+    /// Every nth line is a major line.
+    pub major_every: u32,
+    pub minor_color: Vec4,
+    pub major_color: Vec4,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            // Matches the default snapping grid.
+            spacing: 20.0,
+            major_every: 5,
+            minor_color: Vec4::new(0.02, 0.02, 0.02, 1.0),
+            major_color: Vec4::new(0.06, 0.06, 0.06, 1.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct GridUniform {
+    minor_color: [f32; 4],
+    major_color: [f32; 4],
+    spacing: f32,
+    major_spacing: f32,
+    _padding: [f32; 2],
+}
+
+impl From<&GridSettings> for GridUniform {
+    fn from(settings: &GridSettings) -> Self {
+        let spacing = settings.spacing.max(f32::EPSILON);
+        Self {
+            minor_color: settings.minor_color.to_array(),
+            major_color: settings.major_color.to_array(),
+            spacing,
+            major_spacing: spacing * settings.major_every.max(1) as f32,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+/// Background grid that covers the whole view. Lines are computed per pixel
+/// from the camera, so the grid never runs out, and fade away as they get
+/// too close together on screen.
+pub struct Grid {
+    settings: GridSettings,
+    visible: bool,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    #[cfg(not(target_arch = "wasm32"))]
+    pipeline_layout: wgpu::PipelineLayout,
+    #[cfg(not(target_arch = "wasm32"))]
+    surface_format: wgpu::TextureFormat,
+    #[cfg(not(target_arch = "wasm32"))]
+    sample_count: u32,
+    fullscreen_quad: wgpu::RenderPipeline,
+}
+
+impl Grid {
+    pub fn new(
+        camera_binder: &CameraBinder,
+        shader: &wgpu::ShaderModule,
+        surface_format: wgpu::TextureFormat,
+        sample_count: u32,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<Self> {
+        let settings = GridSettings::default();
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Grid::buffer"),
+            contents: bytemuck::bytes_of(&GridUniform::from(&settings)),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+        });
+
+        // Binding 2 keeps clear of the font texture, which shares the group
+        // in shader.wgsl.
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Grid::layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Grid::bind_group"),
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 2,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Grid::pipeline_layout"),
+            bind_group_layouts: &[&layout, camera_binder.layout()],
+            push_constant_ranges: &[],
+        });
+
+        let fullscreen_quad = Self::build(
+            &pipeline_layout,
+            shader,
+            surface_format,
+            sample_count,
+            device,
+        )?;
+
+        Ok(Self {
+            settings,
+            visible: true,
+            buffer,
+            bind_group,
+            #[cfg(not(target_arch = "wasm32"))]
+            pipeline_layout,
+            #[cfg(not(target_arch = "wasm32"))]
+            surface_format,
+            #[cfg(not(target_arch = "wasm32"))]
+            sample_count,
+            fullscreen_quad,
+        })
+    }
+
+    fn build(
+        pipeline_layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        surface_format: wgpu::TextureFormat,
+        sample_count: u32,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        RenderPipelineBuilder::new()
+            .label("Grid::fullscreen_quad")
+            .layout(pipeline_layout)
+            .vertex(wgpu::VertexState {
+                module: shader,
+                entry_point: Some("fullscreen_quad"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            })
+            .fragment(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("grid"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            })
+            .sample_count(sample_count)
+            .build(device)
+    }
+
+    /// Rebuilds the pipeline from an edited `shader`. Keeps the old one if
+    /// that fails.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload(
+        &mut self,
+        shader: &wgpu::ShaderModule,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<()> {
+        self.fullscreen_quad = crate::utils::validated(device, || {
+            Self::build(
+                &self.pipeline_layout,
+                shader,
+                self.surface_format,
+                self.sample_count,
+                device,
+            )
+        })??;
+        Ok(())
+    }
+
+    pub fn settings(&self) -> &GridSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: GridSettings, queue: &wgpu::Queue) {
+        self.settings = settings;
+        stats::write_buffer(
+            queue,
+            &self.buffer,
+            0,
+            bytemuck::bytes_of(&GridUniform::from(&self.settings)),
+        );
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn draw(&self, pass: &mut wgpu::RenderPass<'_>, camera_binding: &CameraBinding) {
+        if !self.visible {
+            return;
+        }
+        pass.set_pipeline(&self.fullscreen_quad);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        camera_binding.set(pass, 1);
+        stats::draw(pass, 0..3, 0..1);
+    }
+}
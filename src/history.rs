@@ -0,0 +1,1131 @@
+use glam::{Affine2, Vec4};
+
+use crate::{
+    constraints::Constraint,
+    dimension::Dimension,
+    layers::{DetachedNodes, Layer, LayerStack},
+    resources::font::TextBuffer,
+    scene::{NodeId, NodeKind, Transform2D},
+    shape::{Fill, Geometry, SdfOp},
+    style::{Style, StyleId},
+};
+
+/// Everything a [Command] is allowed to touch.
+pub struct CommandContext<'a> {
+    pub layers: &'a mut LayerStack,
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+}
+
+/// A reversible change to the drawing.
+pub trait Command {
+    fn name(&self) -> &str;
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()>;
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()>;
+}
+
+/// Undo/redo stacks.
+pub struct History {
+    undo: Vec<Box<dyn Command>>,
+    redo: Vec<Box<dyn Command>>,
+    limit: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl History {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            limit: limit.max(1),
+        }
+    }
+
+    /// Applies `command` and records it.
+    pub fn execute(
+        &mut self,
+        mut command: Box<dyn Command>,
+        ctx: &mut CommandContext<'_>,
+    ) -> anyhow::Result<()> {
+        command.apply(ctx)?;
+        self.push(command);
+        Ok(())
+    }
+
+    /// Records a command whose effect has already been applied, such as a
+    /// stroke that was drawn interactively.
+    pub fn push(&mut self, command: Box<dyn Command>) {
+        self.redo.clear();
+        self.undo.push(command);
+        if self.undo.len() > self.limit {
+            self.undo.remove(0);
+        }
+    }
+
+    pub fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<bool> {
+        let Some(mut command) = self.undo.pop() else {
+            return Ok(false);
+        };
+        log::debug!("Undo {}", command.name());
+        if let Err(e) = command.undo(ctx) {
+            // The command couldn't be reversed so the stacks are no longer
+            // trustworthy.
+            self.clear();
+            return Err(e);
+        }
+        self.redo.push(command);
+        Ok(true)
+    }
+
+    pub fn redo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<bool> {
+        let Some(mut command) = self.redo.pop() else {
+            return Ok(false);
+        };
+        log::debug!("Redo {}", command.name());
+        if let Err(e) = command.apply(ctx) {
+            self.clear();
+            return Err(e);
+        }
+        self.undo.push(command);
+        Ok(true)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+/// Several commands that are applied and undone as one.
+pub struct Compound {
+    name: String,
+    commands: Vec<Box<dyn Command>>,
+}
+
+impl Compound {
+    pub fn new(name: impl Into<String>, commands: Vec<Box<dyn Command>>) -> Self {
+        Self {
+            name: name.into(),
+            commands,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+impl Command for Compound {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        for i in 0..self.commands.len() {
+            if let Err(e) = self.commands[i].apply(ctx) {
+                // Roll back so the command is all or nothing.
+                for command in self.commands[..i].iter_mut().rev() {
+                    command.undo(ctx)?;
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        for command in self.commands.iter_mut().rev() {
+            command.undo(ctx)?;
+        }
+        Ok(())
+    }
+}
+
+/// Adding a node is the same as removing it in reverse.
+pub struct AddNode(RemoveNode);
+
+impl AddNode {
+    /// Records a node that has already been added to `layer`.
+    pub fn added(layer: usize, node: NodeId) -> Self {
+        Self(RemoveNode::new(layer, node))
+    }
+}
+
+impl Command for AddNode {
+    fn name(&self) -> &str {
+        "Add shape"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        // The node is already in the scene the first time around.
+        if self.0.detached.is_none() {
+            return Ok(());
+        }
+        self.0.undo(ctx)
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.0.apply(ctx)
+    }
+}
+
+pub struct RemoveNode {
+    layer: usize,
+    node: NodeId,
+    detached: Option<DetachedNodes>,
+}
+
+impl RemoveNode {
+    pub fn new(layer: usize, node: NodeId) -> Self {
+        Self {
+            layer,
+            node,
+            detached: None,
+        }
+    }
+}
+
+impl Command for RemoveNode {
+    fn name(&self) -> &str {
+        "Delete"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        let layer = ctx
+            .layers
+            .get_mut(self.layer)
+            .ok_or_else(|| anyhow::anyhow!("Layer {} doesn't exist", self.layer))?;
+        self.detached = Some(
+            layer
+                .remove(self.node, ctx.device, ctx.queue)
+                .ok_or_else(|| anyhow::anyhow!("Node {:?} doesn't exist", self.node))?,
+        );
+        Ok(())
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        let layer = ctx
+            .layers
+            .get_mut(self.layer)
+            .ok_or_else(|| anyhow::anyhow!("Layer {} doesn't exist", self.layer))?;
+        let detached = self
+            .detached
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Nothing to restore"))?;
+        if let Err(detached) = layer.restore(detached, ctx.device, ctx.queue) {
+            self.detached = Some(detached);
+            anyhow::bail!("Unable to restore {:?}", self.node);
+        }
+        Ok(())
+    }
+}
+
+/// Adding a layer is the same as removing it in reverse.
+pub struct AddLayer(RemoveLayer);
+
+impl AddLayer {
+    /// Records a layer that has already been added at `index`.
+    pub fn added(index: usize) -> Self {
+        Self(RemoveLayer::new(index))
+    }
+}
+
+impl Command for AddLayer {
+    fn name(&self) -> &str {
+        "Add layer"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        // The layer is already in the stack the first time around.
+        if self.0.layer.is_none() {
+            return Ok(());
+        }
+        self.0.undo(ctx)
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.0.apply(ctx)
+    }
+}
+
+/// Removes a layer along with everything on it.
+pub struct RemoveLayer {
+    index: usize,
+    /// Active before the layer was removed, to go back to on undo.
+    active: usize,
+    layer: Option<Layer>,
+}
+
+impl RemoveLayer {
+    pub fn new(index: usize) -> Self {
+        Self {
+            index,
+            active: 0,
+            layer: None,
+        }
+    }
+}
+
+impl Command for RemoveLayer {
+    fn name(&self) -> &str {
+        "Delete layer"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        let active = ctx.layers.active_index();
+        self.layer = Some(
+            ctx.layers
+                .remove(self.index)
+                .ok_or_else(|| anyhow::anyhow!("Layer {} can't be removed", self.index))?,
+        );
+        self.active = active;
+        Ok(())
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        let layer = self
+            .layer
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Nothing to restore"))?;
+        ctx.layers.insert(self.index, layer);
+        ctx.layers.set_active(self.active);
+        Ok(())
+    }
+}
+
+/// Moves a layer up or down the stack.
+pub struct MoveLayer {
+    from: usize,
+    to: usize,
+}
+
+impl MoveLayer {
+    pub fn new(from: usize, to: usize) -> Self {
+        Self { from, to }
+    }
+}
+
+impl Command for MoveLayer {
+    fn name(&self) -> &str {
+        "Move layer"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        let len = ctx.layers.len();
+        anyhow::ensure!(
+            self.from < len && self.to < len,
+            "Layer {} can't be moved to {}",
+            self.from,
+            self.to
+        );
+        ctx.layers.reorder(self.from, self.to);
+        Ok(())
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        ctx.layers.reorder(self.to, self.from);
+        Ok(())
+    }
+}
+
+/// Move, scale or rotate a node.
+pub struct SetTransform {
+    pub layer: usize,
+    pub node: NodeId,
+    pub before: Transform2D,
+    pub after: Transform2D,
+}
+
+impl SetTransform {
+    fn set(&self, ctx: &mut CommandContext<'_>, transform: Transform2D) -> anyhow::Result<()> {
+        let scene = ctx
+            .layers
+            .get_mut(self.layer)
+            .ok_or_else(|| anyhow::anyhow!("Layer {} doesn't exist", self.layer))?
+            .scene_mut();
+        anyhow::ensure!(
+            scene.contains(self.node),
+            "Node {:?} doesn't exist",
+            self.node
+        );
+        scene.set_transform(self.node, transform);
+        Ok(())
+    }
+}
+
+impl Command for SetTransform {
+    fn name(&self) -> &str {
+        "Transform"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.set(ctx, self.after)
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.set(ctx, self.before)
+    }
+}
+
+/// Change what a text node says. The text is laid out up front, since
+/// commands don't have the font.
+pub struct SetText {
+    layer: usize,
+    node: NodeId,
+    /// What the node will say after the next apply or undo.
+    swapped: Option<(String, TextBuffer)>,
+}
+
+impl SetText {
+    pub fn new(layer: usize, node: NodeId, text: String, buffer: TextBuffer) -> Self {
+        Self {
+            layer,
+            node,
+            swapped: Some((text, buffer)),
+        }
+    }
+
+    fn swap(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        let layer = ctx
+            .layers
+            .get_mut(self.layer)
+            .ok_or_else(|| anyhow::anyhow!("Layer {} doesn't exist", self.layer))?;
+        let (text, buffer) = self
+            .swapped
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("Nothing to swap in"))?;
+        self.swapped = layer.replace_text(self.node, text, buffer);
+        anyhow::ensure!(
+            self.swapped.is_some(),
+            "Node {:?} isn't a text node",
+            self.node
+        );
+        Ok(())
+    }
+}
+
+impl Command for SetText {
+    fn name(&self) -> &str {
+        "Edit text"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.swap(ctx)
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.swap(ctx)
+    }
+}
+
+/// Replace the geometry of a shape, such as after dragging one of its
+/// points.
+pub struct SetGeometry {
+    pub layer: usize,
+    pub node: NodeId,
+    pub before: Geometry,
+    pub after: Geometry,
+}
+
+impl SetGeometry {
+    fn set(&self, ctx: &mut CommandContext<'_>, geometry: &Geometry) -> anyhow::Result<()> {
+        let layer = ctx
+            .layers
+            .get_mut(self.layer)
+            .ok_or_else(|| anyhow::anyhow!("Layer {} doesn't exist", self.layer))?;
+        let updated = layer.update_shape(
+            self.node,
+            |shape| shape.geometry = geometry.clone(),
+            ctx.device,
+            ctx.queue,
+        );
+        anyhow::ensure!(updated, "Node {:?} isn't a shape", self.node);
+        Ok(())
+    }
+}
+
+impl Command for SetGeometry {
+    fn name(&self) -> &str {
+        "Edit shape"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.set(ctx, &self.after)
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.set(ctx, &self.before)
+    }
+}
+
+/// Constrain one line against another. Line `a` stays put and `b` is
+/// adjusted, along with anything constrained to it.
+pub struct AddConstraint {
+    layer: usize,
+    constraint: Constraint,
+    /// Geometry of the constrained lines from before the constraint was
+    /// solved.
+    before: Vec<(NodeId, Geometry)>,
+}
+
+impl AddConstraint {
+    pub fn new(layer: usize, constraint: Constraint) -> Self {
+        Self {
+            layer,
+            constraint,
+            before: Vec::new(),
+        }
+    }
+}
+
+impl Command for AddConstraint {
+    fn name(&self) -> &str {
+        "Add constraint"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        let layer = ctx
+            .layers
+            .get_mut(self.layer)
+            .ok_or_else(|| anyhow::anyhow!("Layer {} doesn't exist", self.layer))?;
+        layer.add_constraint(self.constraint);
+        self.before = constrained_geometry(layer);
+        layer.solve_constraints(&[self.constraint.a], ctx.device, ctx.queue);
+        Ok(())
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        let layer = ctx
+            .layers
+            .get_mut(self.layer)
+            .ok_or_else(|| anyhow::anyhow!("Layer {} doesn't exist", self.layer))?;
+        layer.pop_constraint();
+        for (node, geometry) in self.before.drain(..) {
+            layer.update_shape(
+                node,
+                |shape| shape.geometry = geometry,
+                ctx.device,
+                ctx.queue,
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Add a dimension line to a layer.
+pub struct AddDimension {
+    layer: usize,
+    dimension: Dimension,
+}
+
+impl AddDimension {
+    pub fn new(layer: usize, dimension: Dimension) -> Self {
+        Self { layer, dimension }
+    }
+}
+
+impl Command for AddDimension {
+    fn name(&self) -> &str {
+        "Add dimension"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        ctx.layers
+            .get_mut(self.layer)
+            .ok_or_else(|| anyhow::anyhow!("Layer {} doesn't exist", self.layer))?
+            .add_dimension(self.dimension);
+        Ok(())
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        ctx.layers
+            .get_mut(self.layer)
+            .ok_or_else(|| anyhow::anyhow!("Layer {} doesn't exist", self.layer))?
+            .pop_dimension();
+        Ok(())
+    }
+}
+
+/// Snapshots the geometry of every shape with a constraint on it.
+pub fn constrained_geometry(layer: &Layer) -> Vec<(NodeId, Geometry)> {
+    let mut nodes: Vec<NodeId> = layer
+        .constraints()
+        .iter()
+        .flat_map(|c| [c.a, c.b])
+        .collect();
+    nodes.sort();
+    nodes.dedup();
+    nodes
+        .into_iter()
+        .filter_map(|id| {
+            let shape = layer.scene().get(id)?.shape()?;
+            Some((id, shape.geometry.clone()))
+        })
+        .collect()
+}
+
+/// Change the color of a shape.
+pub struct SetColor {
+    layer: usize,
+    node: NodeId,
+    color: Vec4,
+}
+
+impl SetColor {
+    pub fn new(layer: usize, node: NodeId, color: Vec4) -> Self {
+        Self { layer, node, color }
+    }
+
+    /// Swaps the stored color with the shape's, so applying and undoing are
+    /// the same operation.
+    fn swap(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        let layer = ctx
+            .layers
+            .get_mut(self.layer)
+            .ok_or_else(|| anyhow::anyhow!("Layer {} doesn't exist", self.layer))?;
+        let color = &mut self.color;
+        let updated = layer.update_shape(
+            self.node,
+            |shape| std::mem::swap(&mut shape.color, color),
+            ctx.device,
+            ctx.queue,
+        );
+        anyhow::ensure!(updated, "Node {:?} isn't a shape", self.node);
+        Ok(())
+    }
+}
+
+impl Command for SetColor {
+    fn name(&self) -> &str {
+        "Change color"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.swap(ctx)
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.swap(ctx)
+    }
+}
+
+/// Change or remove the fill of a shape.
+pub struct SetFill {
+    layer: usize,
+    node: NodeId,
+    fill: Option<Fill>,
+}
+
+impl SetFill {
+    pub fn new(layer: usize, node: NodeId, fill: Option<Fill>) -> Self {
+        Self { layer, node, fill }
+    }
+
+    fn swap(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        let layer = ctx
+            .layers
+            .get_mut(self.layer)
+            .ok_or_else(|| anyhow::anyhow!("Layer {} doesn't exist", self.layer))?;
+        let fill = &mut self.fill;
+        let updated = layer.update_shape(
+            self.node,
+            |shape| std::mem::swap(&mut shape.fill, fill),
+            ctx.device,
+            ctx.queue,
+        );
+        anyhow::ensure!(updated, "Node {:?} isn't a shape", self.node);
+        Ok(())
+    }
+}
+
+impl Command for SetFill {
+    fn name(&self) -> &str {
+        "Change fill"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.swap(ctx)
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.swap(ctx)
+    }
+}
+
+/// Change how a shape combines with the one before it in SDF mode.
+pub struct SetSdfOp {
+    layer: usize,
+    node: NodeId,
+    op: SdfOp,
+}
+
+impl SetSdfOp {
+    pub fn new(layer: usize, node: NodeId, op: SdfOp) -> Self {
+        Self { layer, node, op }
+    }
+
+    fn swap(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        let layer = ctx
+            .layers
+            .get_mut(self.layer)
+            .ok_or_else(|| anyhow::anyhow!("Layer {} doesn't exist", self.layer))?;
+        let op = &mut self.op;
+        let updated = layer.update_shape(
+            self.node,
+            |shape| std::mem::swap(&mut shape.sdf_op, op),
+            ctx.device,
+            ctx.queue,
+        );
+        anyhow::ensure!(updated, "Node {:?} isn't a shape", self.node);
+        Ok(())
+    }
+}
+
+impl Command for SetSdfOp {
+    fn name(&self) -> &str {
+        "Change SDF operation"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.swap(ctx)
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.swap(ctx)
+    }
+}
+
+/// Point a shape at a shared style, or back at its own color and fill.
+pub struct SetShapeStyle {
+    layer: usize,
+    node: NodeId,
+    style: Option<StyleId>,
+}
+
+impl SetShapeStyle {
+    pub fn new(layer: usize, node: NodeId, style: Option<StyleId>) -> Self {
+        Self { layer, node, style }
+    }
+
+    fn swap(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        if let Some(id) = self.style {
+            anyhow::ensure!(
+                ctx.layers.styles().get(id).is_some(),
+                "Style {id:?} doesn't exist"
+            );
+        }
+        let layer = ctx
+            .layers
+            .get_mut(self.layer)
+            .ok_or_else(|| anyhow::anyhow!("Layer {} doesn't exist", self.layer))?;
+        let style = &mut self.style;
+        let updated = layer.update_shape(
+            self.node,
+            |shape| std::mem::swap(&mut shape.style, style),
+            ctx.device,
+            ctx.queue,
+        );
+        anyhow::ensure!(updated, "Node {:?} isn't a shape", self.node);
+        Ok(())
+    }
+}
+
+impl Command for SetShapeStyle {
+    fn name(&self) -> &str {
+        "Change shape style"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.swap(ctx)
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.swap(ctx)
+    }
+}
+
+/// Edit a shared style, which changes every shape using it.
+pub struct SetStyle {
+    id: StyleId,
+    style: Style,
+}
+
+impl SetStyle {
+    pub fn new(id: StyleId, style: Style) -> Self {
+        Self { id, style }
+    }
+
+    fn swap(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        let old = ctx
+            .layers
+            .styles_mut()
+            .set(self.id, self.style.clone())
+            .ok_or_else(|| anyhow::anyhow!("Style {:?} doesn't exist", self.id))?;
+        self.style = old;
+        Ok(())
+    }
+}
+
+impl Command for SetStyle {
+    fn name(&self) -> &str {
+        "Edit style"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.swap(ctx)
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.swap(ctx)
+    }
+}
+
+/// Where a node sits in the scene.
+#[derive(Debug, Clone, Copy)]
+struct Placement {
+    parent: Option<NodeId>,
+    position: usize,
+    transform: Transform2D,
+}
+
+/// A node in a [Group] along with its transform inside the group and where
+/// it goes when ungrouped.
+#[derive(Debug, Clone, Copy)]
+struct Member {
+    node: NodeId,
+    inside: Transform2D,
+    outside: Placement,
+}
+
+/// Move nodes into a new group without changing where they are in the
+/// world.
+pub struct Group {
+    layer: usize,
+    group: NodeId,
+    parent: Option<NodeId>,
+    position: usize,
+    members: Vec<Member>,
+    /// The group node while it isn't in the scene.
+    detached: Option<DetachedNodes>,
+}
+
+impl Group {
+    /// Prepares a group for `nodes`. Nodes whose ancestors are also being
+    /// grouped come along with them. The group goes where the first node
+    /// in drawing order was, under the nodes' parent if they all share
+    /// one.
+    pub fn new(
+        layer_index: usize,
+        layer: &mut Layer,
+        nodes: &[NodeId],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Self> {
+        let scene = layer.scene_mut();
+        let ordered: Vec<NodeId> = scene
+            .iter()
+            .map(|(id, _)| id)
+            .filter(|id| nodes.contains(id))
+            .collect();
+        let nodes: Vec<NodeId> = ordered
+            .iter()
+            .copied()
+            .filter(|&id| {
+                !ordered
+                    .iter()
+                    .any(|&other| other != id && scene.is_ancestor_of(other, id))
+            })
+            .collect();
+        let Some(&first) = nodes.first() else {
+            anyhow::bail!("Nothing to group");
+        };
+
+        let parent_of = |id| scene.get(id).and_then(|n| n.parent());
+        let first_parent = parent_of(first);
+        let (parent, position) = if nodes.iter().all(|&id| parent_of(id) == first_parent) {
+            (first_parent, scene.position(first).unwrap_or(0))
+        } else {
+            (None, scene.position(scene.root_of(first)).unwrap_or(0))
+        };
+        let inverse = match parent {
+            Some(p) => scene.world_transform(p).inverse(),
+            None => Affine2::IDENTITY,
+        };
+
+        let mut members = Vec::with_capacity(nodes.len());
+        for id in nodes {
+            let world = scene.world_transform(id);
+            let Some(node) = scene.get(id) else {
+                continue;
+            };
+            let inside = if node.parent() == parent {
+                *node.transform()
+            } else {
+                Transform2D::from_affine(inverse * world)
+            };
+            members.push(Member {
+                node: id,
+                inside,
+                outside: Placement {
+                    parent: node.parent(),
+                    position: scene.position(id).unwrap_or(0),
+                    transform: *node.transform(),
+                },
+            });
+        }
+
+        // The group isn't part of the scene until the command is applied.
+        let group = layer.add_group("Group", Transform2D::IDENTITY, parent);
+        let detached = layer
+            .remove(group, device, queue)
+            .ok_or_else(|| anyhow::anyhow!("Unable to create group"))?;
+        Ok(Self {
+            layer: layer_index,
+            group,
+            parent,
+            position,
+            members,
+            detached: Some(detached),
+        })
+    }
+
+    pub fn group(&self) -> NodeId {
+        self.group
+    }
+
+    pub fn members(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.members.iter().map(|m| m.node)
+    }
+
+    fn layer<'a>(&self, ctx: &'a mut CommandContext<'_>) -> anyhow::Result<&'a mut Layer> {
+        ctx.layers
+            .get_mut(self.layer)
+            .ok_or_else(|| anyhow::anyhow!("Layer {} doesn't exist", self.layer))
+    }
+
+    fn group_members(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        let (device, queue) = (ctx.device, ctx.queue);
+        let layer = self.layer(ctx)?;
+        if let Some(detached) = self.detached.take() {
+            if let Err(detached) = layer.restore(detached, device, queue) {
+                self.detached = Some(detached);
+                anyhow::bail!("Unable to restore {:?}", self.group);
+            }
+        }
+        let scene = layer.scene_mut();
+        anyhow::ensure!(
+            scene.move_to(self.group, self.parent, self.position),
+            "Unable to place {:?}",
+            self.group
+        );
+        for member in &self.members {
+            anyhow::ensure!(
+                scene.move_to(member.node, Some(self.group), usize::MAX),
+                "Unable to group {:?}",
+                member.node
+            );
+            scene.set_transform(member.node, member.inside);
+        }
+        Ok(())
+    }
+
+    fn ungroup_members(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        let (device, queue) = (ctx.device, ctx.queue);
+        let layer = self.layer(ctx)?;
+        let scene = layer.scene_mut();
+        // Get the group out of the way so the members go back to the exact
+        // positions they had among their siblings.
+        anyhow::ensure!(
+            scene.set_parent(self.group, None),
+            "Node {:?} doesn't exist",
+            self.group
+        );
+        let mut members = self.members.clone();
+        members.sort_by_key(|m| m.outside.position);
+        for member in members {
+            let outside = member.outside;
+            anyhow::ensure!(
+                scene.move_to(member.node, outside.parent, outside.position),
+                "Unable to ungroup {:?}",
+                member.node
+            );
+            scene.set_transform(member.node, outside.transform);
+        }
+        self.detached = Some(
+            layer
+                .remove(self.group, device, queue)
+                .ok_or_else(|| anyhow::anyhow!("Node {:?} doesn't exist", self.group))?,
+        );
+        Ok(())
+    }
+}
+
+impl Command for Group {
+    fn name(&self) -> &str {
+        "Group"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.group_members(ctx)
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.ungroup_members(ctx)
+    }
+}
+
+/// Ungrouping is the same as grouping in reverse. The group's children move
+/// to where the group was without changing where they are in the world.
+pub struct Ungroup(Group);
+
+impl Ungroup {
+    pub fn new(layer_index: usize, layer: &Layer, group: NodeId) -> anyhow::Result<Self> {
+        let scene = layer.scene();
+        let node = scene
+            .get(group)
+            .ok_or_else(|| anyhow::anyhow!("Node {group:?} doesn't exist"))?;
+        anyhow::ensure!(
+            matches!(node.kind, NodeKind::Group),
+            "Node {group:?} isn't a group"
+        );
+        let parent = node.parent();
+        let position = scene.position(group).unwrap_or(0);
+        let group_local = node.transform().to_affine();
+        let members = node
+            .children()
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &id)| {
+                let child = scene.get(id)?;
+                Some(Member {
+                    node: id,
+                    inside: *child.transform(),
+                    outside: Placement {
+                        parent,
+                        position: position + i,
+                        transform: Transform2D::from_affine(
+                            group_local * child.transform().to_affine(),
+                        ),
+                    },
+                })
+            })
+            .collect();
+        Ok(Self(Group {
+            layer: layer_index,
+            group,
+            parent,
+            position,
+            members,
+            detached: None,
+        }))
+    }
+
+    pub fn members(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.0.members()
+    }
+}
+
+impl Command for Ungroup {
+    fn name(&self) -> &str {
+        "Ungroup"
+    }
+
+    fn apply(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.0.ungroup_members(ctx)
+    }
+
+    fn undo(&mut self, ctx: &mut CommandContext<'_>) -> anyhow::Result<()> {
+        self.0.group_members(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+    use crate::{resources::geometry::test_pipeline, shape::Shape, utils::test_device};
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn undo_finds_nodes_after_layers_move() {
+        let (device, queue) = test_device();
+        let pipeline = test_pipeline(&device);
+        let mut layers = LayerStack::new(&pipeline, &device);
+        layers.add("Top", &pipeline, &device);
+        let node = layers.get_mut(1).unwrap().add_shape(
+            "Dot",
+            Shape::circle(Vec2::ZERO, 1.0, Vec4::ONE),
+            Transform2D::IDENTITY,
+            None,
+            &device,
+            &queue,
+        );
+        let mut history = History::default();
+        history.push(Box::new(AddNode::added(1, node)));
+        let mut ctx = CommandContext {
+            layers: &mut layers,
+            device: &device,
+            queue: &queue,
+        };
+        history
+            .execute(Box::new(MoveLayer::new(1, 0)), &mut ctx)
+            .unwrap();
+        assert_eq!(ctx.layers.get(0).unwrap().name(), "Top");
+        assert!(ctx.layers.get(0).unwrap().scene().contains(node));
+
+        // The move is undone before the node is taken off its layer.
+        assert!(history.undo(&mut ctx).unwrap());
+        assert!(history.undo(&mut ctx).unwrap());
+        assert_eq!(ctx.layers.get(1).unwrap().name(), "Top");
+        assert!(!ctx.layers.get(1).unwrap().scene().contains(node));
+
+        assert!(history.redo(&mut ctx).unwrap());
+        assert!(history.redo(&mut ctx).unwrap());
+        assert_eq!(ctx.layers.get(0).unwrap().name(), "Top");
+        assert!(ctx.layers.get(0).unwrap().scene().contains(node));
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn removed_layers_come_back_where_they_were() {
+        let (device, queue) = test_device();
+        let pipeline = test_pipeline(&device);
+        let mut layers = LayerStack::new(&pipeline, &device);
+        layers.add("Top", &pipeline, &device);
+        layers.set_active(0);
+        let mut history = History::default();
+        let mut ctx = CommandContext {
+            layers: &mut layers,
+            device: &device,
+            queue: &queue,
+        };
+
+        history
+            .execute(Box::new(RemoveLayer::new(0)), &mut ctx)
+            .unwrap();
+        assert_eq!(ctx.layers.len(), 1);
+        // There always has to be a layer to draw on.
+        assert!(history
+            .execute(Box::new(RemoveLayer::new(0)), &mut ctx)
+            .is_err());
+
+        assert!(history.undo(&mut ctx).unwrap());
+        assert_eq!(ctx.layers.get(0).unwrap().name(), "Layer 1");
+        assert_eq!(ctx.layers.active_index(), 0);
+        assert!(!history.undo(&mut ctx).unwrap());
+    }
+}
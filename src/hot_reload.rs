@@ -0,0 +1,171 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+
+/// Where the built in shaders are compiled in from.
+pub const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src");
+/// Where the [snippets](crate::wgsl::SNIPPETS) they include are.
+pub const SNIPPET_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders");
+
+/// The snippet called `name` as it is on disk, for composing edited
+/// built in shaders with.
+pub fn snippet(name: &str) -> Option<String> {
+    std::fs::read_to_string(Path::new(SNIPPET_DIR).join(name)).ok()
+}
+
+/// Rebuilds what uses a user shader from its edited source.
+pub type ShaderCallback = Box<dyn FnMut(&str, &wgpu::Device) -> anyhow::Result<()>>;
+
+/// Watches the built in shaders and any user shaders for edits.
+pub struct ShaderWatcher {
+    watcher: notify::RecommendedWatcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+    /// Directories watched for user shaders.
+    dirs: Vec<PathBuf>,
+    user_shaders: Vec<(PathBuf, ShaderCallback)>,
+}
+
+impl ShaderWatcher {
+    /// Watches the `.wgsl` files in [SHADER_DIR] and [SNIPPET_DIR], calling `wake` from
+    /// another thread when one changes so the app can redraw.
+    pub fn new(wake: impl Fn() + Send + 'static) -> anyhow::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            if sender.send(event).is_ok() {
+                wake();
+            }
+        })?;
+        watcher.watch(Path::new(SHADER_DIR), RecursiveMode::NonRecursive)?;
+        watcher.watch(Path::new(SNIPPET_DIR), RecursiveMode::NonRecursive)?;
+        Ok(Self {
+            watcher,
+            events,
+            dirs: Vec::new(),
+            user_shaders: Vec::new(),
+        })
+    }
+
+    /// Calls `callback` with the shader at `path` whenever it's saved. The
+    /// directory it's in is watched rather than the file, as editors often
+    /// save by replacing it.
+    pub fn watch(&mut self, path: &Path, callback: ShaderCallback) -> anyhow::Result<()> {
+        let path = path.canonicalize()?;
+        let dir = path
+            .parent()
+            .ok_or_else(|| anyhow::anyhow!("{} isn't in a directory", path.display()))?;
+        if !self.dirs.iter().any(|watched| watched == dir) {
+            self.watcher.watch(dir, RecursiveMode::NonRecursive)?;
+            self.dirs.push(dir.to_owned());
+        }
+        self.user_shaders.push((path, callback));
+        Ok(())
+    }
+
+    /// The callback for the user shader at `path`, if it's one.
+    pub fn user_shader(&mut self, path: &Path) -> Option<&mut ShaderCallback> {
+        self.user_shaders
+            .iter_mut()
+            .find(|(shader, _)| shader == path)
+            .map(|(_, callback)| callback)
+    }
+
+    /// The shaders saved since the last call, each once. Saving a snippet
+    /// counts as saving every built in shader.
+    pub fn changed(&self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        let mut snippet_changed = false;
+        for path in written(&self.events) {
+            if is_wgsl(&path, SNIPPET_DIR) {
+                snippet_changed = true;
+            } else if self.is_shader(&path) && !changed.contains(&path) {
+                changed.push(path);
+            }
+        }
+        if snippet_changed {
+            let built_in = std::fs::read_dir(SHADER_DIR)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| is_wgsl(path, SHADER_DIR));
+            for path in built_in {
+                if !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+        }
+        changed
+    }
+
+    fn is_shader(&self, path: &Path) -> bool {
+        is_wgsl(path, SHADER_DIR) || self.user_shaders.iter().any(|(shader, _)| shader == path)
+    }
+}
+
+/// Watches a directory of resources, like [RES_DIR](crate::resources::RES_DIR),
+/// and everything under it for changes.
+pub struct AssetWatcher {
+    /// Only held to keep watching.
+    _watcher: notify::RecommendedWatcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+    dir: PathBuf,
+}
+
+impl AssetWatcher {
+    /// Calls `wake` from another thread when a file under `dir` changes.
+    pub fn new(dir: impl AsRef<Path>, wake: impl Fn() + Send + 'static) -> anyhow::Result<Self> {
+        let dir = dir.as_ref().canonicalize()?;
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            if sender.send(event).is_ok() {
+                wake();
+            }
+        })?;
+        watcher.watch(&dir, RecursiveMode::Recursive)?;
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            dir,
+        })
+    }
+
+    /// The files written since the last call, each once, relative to the
+    /// watched directory.
+    pub fn changed(&self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        for path in written(&self.events) {
+            let Ok(path) = path.strip_prefix(&self.dir) else {
+                continue;
+            };
+            if self.dir.join(path).is_file() {
+                let path = path.to_owned();
+                if !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+        }
+        changed
+    }
+}
+
+/// The paths of the files created or modified in `events` so far.
+fn written(
+    events: &mpsc::Receiver<notify::Result<notify::Event>>,
+) -> impl Iterator<Item = PathBuf> + '_ {
+    events
+        .try_iter()
+        .filter_map(|event| {
+            event
+                .inspect_err(|e| log::warn!("Unable to watch for changes: {e}"))
+                .ok()
+        })
+        .filter(|event| matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)))
+        .flat_map(|event| event.paths)
+}
+
+fn is_wgsl(path: &Path, dir: &str) -> bool {
+    path.parent() == Some(Path::new(dir)) && path.extension().is_some_and(|ext| ext == "wgsl")
+}
@@ -0,0 +1,415 @@
+use std::{collections::HashSet, time::Duration};
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use web_time::Instant;
+use winit::{
+    event::{DeviceEvent, ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{Key, KeyCode, ModifiersState, PhysicalKey},
+};
+
+/// Pixels scrolled per line for mice that scroll in lines.
+pub const WHEEL_LINE_PIXELS: f32 = 20.0;
+/// How long smoothed scrolling takes to cover most of the way, roughly.
+pub const SCROLL_SMOOTHING: Duration = Duration::from_millis(80);
+/// Smoothed scrolling left over that's less than this many logical pixels is
+/// delivered all at once.
+const SCROLL_SETTLE: f32 = 0.05;
+
+/// Something pointing at the window. Each one can drive its own stroke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PointerId {
+    Mouse,
+    Touch(u64),
+}
+
+/// A key going up or down. Unlike [KeyEvent] it can be made up, so input
+/// can be recorded and replayed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyInput {
+    pub physical_key: PhysicalKey,
+    pub logical_key: Key,
+    /// What the key types, if anything.
+    pub text: Option<String>,
+    pub state: ElementState,
+    pub repeat: bool,
+}
+
+impl KeyInput {
+    pub fn from_event(event: &KeyEvent) -> Self {
+        Self {
+            physical_key: event.physical_key,
+            logical_key: event.logical_key.clone(),
+            text: event.text.as_ref().map(|text| text.to_string()),
+            state: event.state,
+            repeat: event.repeat,
+        }
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.state == ElementState::Pressed
+    }
+
+    /// The key's code if it went down.
+    pub fn pressed_code(&self) -> Option<KeyCode> {
+        match self.physical_key {
+            PhysicalKey::Code(code) if self.is_pressed() => Some(code),
+            _ => None,
+        }
+    }
+}
+
+/// How the mouse wheel turns into scrolling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WheelSettings {
+    /// Pixels scrolled per line for mice that scroll in lines.
+    pub line_pixels: f32,
+    /// Scales every scroll. Negative values flip the direction.
+    pub sensitivity: f32,
+    /// Spreads scrolls by the line over a few frames so they don't jump.
+    /// Scrolls by the pixel come from trackpads and the like, which already
+    /// smooth them, so they're left alone.
+    pub smoothing: Option<Duration>,
+}
+
+impl Default for WheelSettings {
+    fn default() -> Self {
+        Self {
+            line_pixels: WHEEL_LINE_PIXELS,
+            sensitivity: 1.0,
+            smoothing: Some(SCROLL_SMOOTHING),
+        }
+    }
+}
+/// How soon after the last press a press has to come to add to its clicks.
+pub const MULTI_CLICK_TIME: Duration = Duration::from_millis(500);
+/// How close in logical pixels to the last press a press has to be to add
+/// to its clicks.
+pub const MULTI_CLICK_DISTANCE: f32 = 4.0;
+
+/// Counts presses of a button that come quickly one after another in about
+/// the same place, so a press can be told apart as a single, double or
+/// triple click.
+#[derive(Debug, Clone)]
+pub struct ClickCounter {
+    time: Duration,
+    distance: f32,
+    last: Option<(MouseButton, Vec2, Instant)>,
+    count: u32,
+}
+
+impl Default for ClickCounter {
+    fn default() -> Self {
+        Self::new(MULTI_CLICK_TIME, MULTI_CLICK_DISTANCE)
+    }
+}
+
+impl ClickCounter {
+    pub fn new(time: Duration, distance: f32) -> Self {
+        Self {
+            time,
+            distance,
+            last: None,
+            count: 0,
+        }
+    }
+
+    /// Records a press of `button` at `position` in logical pixels and
+    /// returns how many clicks it makes, starting at 1.
+    pub fn press(&mut self, button: MouseButton, position: Vec2) -> u32 {
+        self.press_at(button, position, Instant::now())
+    }
+
+    pub fn press_at(&mut self, button: MouseButton, position: Vec2, now: Instant) -> u32 {
+        let follows = self.last.is_some_and(|(last, at, time)| {
+            last == button
+                && at.distance(position) <= self.distance
+                && now.saturating_duration_since(time) <= self.time
+        });
+        self.count = if follows { self.count + 1 } else { 1 };
+        self.last = Some((button, position, now));
+        self.count
+    }
+
+    /// Clicks made by the last press, or 0 if there hasn't been one.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Makes the next press a single click.
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.count = 0;
+    }
+}
+
+/// Keys, mouse buttons, cursor and wheel collected from window events, so
+/// update code can ask what's held down or what changed this frame instead
+/// of matching on events itself.
+///
+/// The "just" sets and the deltas cover the events since the last
+/// [InputState::end_frame].
+#[derive(Debug, Default)]
+pub struct InputState {
+    keys_down: HashSet<KeyCode>,
+    keys_pressed: HashSet<KeyCode>,
+    keys_released: HashSet<KeyCode>,
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+    modifiers: ModifiersState,
+    /// In logical window pixels. `None` while the cursor is outside the
+    /// window.
+    cursor: Option<Vec2>,
+    cursor_delta: Vec2,
+    /// In logical pixels, positive when scrolling right or up.
+    wheel_delta: Vec2,
+    wheel: WheelSettings,
+    /// Smoothed scrolling still to come.
+    scroll_pending: Vec2,
+    /// Scrolling this frame with the wheel settings applied.
+    scroll: Vec2,
+    last_frame: Option<Instant>,
+    /// Unaccelerated mouse movement, which keeps coming when the cursor is
+    /// stuck at the edge of the screen or locked in place.
+    raw_motion: Vec2,
+    clicks: ClickCounter,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `event`. `scale_factor` converts physical pixels to logical
+    /// ones.
+    pub fn handle_event(&mut self, event: &WindowEvent, scale_factor: f32) {
+        match event {
+            WindowEvent::KeyboardInput { event, .. } => {
+                self.handle_key(&KeyInput::from_event(event))
+            }
+            WindowEvent::MouseInput { state, button, .. } => match state {
+                ElementState::Pressed => {
+                    if self.buttons_down.insert(*button) {
+                        self.buttons_pressed.insert(*button);
+                        // Presses outside the window can't be placed.
+                        match self.cursor {
+                            Some(cursor) => {
+                                self.clicks.press(*button, cursor);
+                            }
+                            None => self.clicks.reset(),
+                        }
+                    }
+                }
+                ElementState::Released => {
+                    if self.buttons_down.remove(button) {
+                        self.buttons_released.insert(*button);
+                    }
+                }
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = position.to_logical::<f32>(scale_factor as f64);
+                let position = Vec2::new(position.x, position.y);
+                if let Some(last) = self.cursor {
+                    self.cursor_delta += position - last;
+                }
+                self.cursor = Some(position);
+            }
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::CursorLeft { .. } => self.cursor = None,
+            WindowEvent::MouseWheel { delta, .. } => match delta {
+                MouseScrollDelta::LineDelta(x, y) => {
+                    let delta = Vec2::new(*x, *y) * self.wheel.line_pixels;
+                    self.wheel_delta += delta;
+                    let delta = delta * self.wheel.sensitivity;
+                    match self.wheel.smoothing {
+                        Some(_) => self.scroll_pending += delta,
+                        None => self.scroll += delta,
+                    }
+                }
+                MouseScrollDelta::PixelDelta(delta) => {
+                    let delta = delta.to_logical::<f32>(scale_factor as f64);
+                    let delta = Vec2::new(delta.x, delta.y);
+                    self.wheel_delta += delta;
+                    self.scroll += delta * self.wheel.sensitivity;
+                }
+            },
+            // Releases that happen while another window has focus never
+            // arrive.
+            WindowEvent::Focused(false) => {
+                self.keys_released.extend(self.keys_down.drain());
+                self.buttons_released.extend(self.buttons_down.drain());
+                self.modifiers = ModifiersState::empty();
+            }
+            _ => {}
+        }
+    }
+
+    pub fn handle_key(&mut self, key: &KeyInput) {
+        let PhysicalKey::Code(code) = key.physical_key else {
+            return;
+        };
+        if key.repeat {
+            return;
+        }
+        match key.state {
+            ElementState::Pressed => {
+                if self.keys_down.insert(code) {
+                    self.keys_pressed.insert(code);
+                }
+            }
+            ElementState::Released => {
+                if self.keys_down.remove(&code) {
+                    self.keys_released.insert(code);
+                }
+            }
+        }
+    }
+
+    /// Records `event`, which comes from the mouse itself rather than the
+    /// window.
+    pub fn handle_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta: (x, y) } = event {
+            self.raw_motion += Vec2::new(*x as f32, *y as f32);
+        }
+    }
+
+    /// Lets smoothed scrolling catch up with the time since the last
+    /// frame. Call it before anything looks at [InputState::scroll].
+    pub fn begin_frame(&mut self) {
+        let now = Instant::now();
+        let elapsed = self
+            .last_frame
+            .replace(now)
+            .map_or(Duration::ZERO, |last| now.saturating_duration_since(last));
+        let step = match self.wheel.smoothing {
+            Some(smoothing) if !smoothing.is_zero() => {
+                1.0 - (-elapsed.as_secs_f32() / smoothing.as_secs_f32()).exp()
+            }
+            _ => 1.0,
+        };
+        let mut delta = self.scroll_pending * step;
+        if (self.scroll_pending - delta).length() < SCROLL_SETTLE {
+            delta = self.scroll_pending;
+        }
+        self.scroll_pending -= delta;
+        self.scroll += delta;
+    }
+
+    /// Clears what happened this frame. Call it once everything has had a
+    /// chance to look.
+    pub fn end_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.cursor_delta = Vec2::ZERO;
+        self.wheel_delta = Vec2::ZERO;
+        self.scroll = Vec2::ZERO;
+        self.raw_motion = Vec2::ZERO;
+    }
+
+    pub fn is_key_down(&self, key: KeyCode) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    /// Whether `key` went down this frame. Key repeats don't count.
+    pub fn key_just_pressed(&self, key: KeyCode) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    pub fn key_just_released(&self, key: KeyCode) -> bool {
+        self.keys_released.contains(&key)
+    }
+
+    pub fn keys_down(&self) -> impl Iterator<Item = KeyCode> + '_ {
+        self.keys_down.iter().copied()
+    }
+
+    pub fn modifiers(&self) -> ModifiersState {
+        self.modifiers
+    }
+
+    /// Whether Ctrl, or Cmd on macOS, is held.
+    pub fn command_held(&self) -> bool {
+        self.modifiers.control_key() || self.modifiers.super_key()
+    }
+
+    pub fn is_button_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    pub fn button_just_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    pub fn button_just_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    /// How many clicks the last press of `button` made, if it was pressed
+    /// this frame. 2 is a double click and 3 a triple click.
+    pub fn clicks(&self, button: MouseButton) -> Option<u32> {
+        self.button_just_pressed(button)
+            .then(|| self.clicks.count())
+            .filter(|&count| count > 0)
+    }
+
+    /// Where the cursor is in logical window pixels, if it's over the
+    /// window.
+    pub fn cursor(&self) -> Option<Vec2> {
+        self.cursor
+    }
+
+    /// How far the cursor moved this frame in logical pixels.
+    pub fn cursor_delta(&self) -> Vec2 {
+        self.cursor_delta
+    }
+
+    /// How far the mouse moved this frame in its own units, which are about
+    /// a pixel each without acceleration. Unlike
+    /// [InputState::cursor_delta] it doesn't stop at the edge of the screen.
+    pub fn raw_motion(&self) -> Vec2 {
+        self.raw_motion
+    }
+
+    /// How far the wheel scrolled this frame in logical pixels, without
+    /// the wheel settings other than the line size applied.
+    pub fn wheel_delta(&self) -> Vec2 {
+        self.wheel_delta
+    }
+
+    /// How far to scroll this frame in logical pixels, with sensitivity and
+    /// smoothing applied. Positive when scrolling right or up.
+    pub fn scroll(&self) -> Vec2 {
+        self.scroll
+    }
+
+    /// [InputState::scroll] as a single axis for things that only scroll
+    /// one way, such as zooming. Mice with only a vertical wheel often
+    /// scroll sideways with Shift held, so whichever way scrolled further
+    /// wins.
+    pub fn scroll_axis(&self) -> f32 {
+        if self.scroll.x.abs() > self.scroll.y.abs() {
+            self.scroll.x
+        } else {
+            self.scroll.y
+        }
+    }
+
+    /// Whether smoothed scrolling has more to deliver.
+    pub fn is_scrolling(&self) -> bool {
+        self.scroll_pending != Vec2::ZERO
+    }
+
+    pub fn wheel_settings(&self) -> &WheelSettings {
+        &self.wheel
+    }
+
+    pub fn set_wheel_settings(&mut self, settings: WheelSettings) {
+        if settings.smoothing.is_none() {
+            self.scroll += std::mem::take(&mut self.scroll_pending);
+        }
+        self.wheel = settings;
+    }
+}
@@ -0,0 +1,966 @@
+use std::{collections::HashMap, ops::Range};
+
+use glam::{Vec2, Vec4};
+
+use crate::{
+    constraints::{self, Constraint},
+    dimension::Dimension,
+    draw_list::DrawList,
+    resources::{
+        buffer::{BackedBuffer, IndexedBatch},
+        camera::CameraBinding,
+        geometry::{
+            ColoredVertex, FillVertex, GeometryBuffer, GeometryPipeline, GradientUniform,
+            LayerBinding, PatternVertex, ShapePipelines, StrokeVertex, MAX_GRADIENTS,
+        },
+        instance::InstanceTransform,
+        texture::{TextureId, TextureRegistry},
+    },
+    scene::{NodeId, NodeKind, Scene, Subtree, Transform2D},
+    shape::{tessellate_polyline, triangulate, Fill, Geometry, Shape},
+    style::StyleSheet,
+};
+
+#[cfg(feature = "text")]
+use crate::resources::font::{Font, TextBuffer, TextPipeline};
+
+/// Nodes that were removed from a layer along with their GPU resources.
+pub struct DetachedNodes {
+    subtree: Subtree,
+    #[cfg(feature = "text")]
+    texts: HashMap<NodeId, TextBuffer>,
+}
+
+impl DetachedNodes {
+    pub fn subtree(&self) -> &Subtree {
+        &self.subtree
+    }
+}
+
+/// Index ranges of a tessellated shape.
+struct ShapeRanges {
+    lines: Range<u32>,
+    fill: Option<Range<u32>>,
+    pattern: Option<(Range<u32>, TextureId)>,
+    stroke: Option<Range<u32>>,
+}
+
+/// Pipeline a range of a layer is drawn with.
+enum DrawKind<'a> {
+    Fill,
+    Pattern(&'a wgpu::BindGroup),
+    Stroke,
+    Line,
+}
+
+pub struct Layer {
+    name: String,
+    visible: bool,
+    locked: bool,
+    opacity: f32,
+    /// Distance from the camera when layers are ordered with a depth
+    /// buffer.
+    depth: f32,
+    scene: Scene,
+    geometry: GeometryBuffer,
+    /// Index ranges of each shape node in `geometry`.
+    ranges: HashMap<NodeId, Range<u32>>,
+    fills: GeometryBuffer<FillVertex>,
+    /// Index ranges of each filled shape node in `fills`.
+    fill_ranges: HashMap<NodeId, Range<u32>>,
+    /// Gradients in the order of their slots in the layer binding.
+    gradients: Vec<GradientUniform>,
+    patterns: GeometryBuffer<PatternVertex>,
+    /// Index ranges of each texture filled shape node in `patterns`.
+    pattern_ranges: HashMap<NodeId, (Range<u32>, TextureId)>,
+    strokes: GeometryBuffer<StrokeVertex>,
+    /// Index ranges of each styled shape node in `strokes`.
+    stroke_ranges: HashMap<NodeId, Range<u32>>,
+    #[cfg(feature = "text")]
+    texts: HashMap<NodeId, TextBuffer>,
+    constraints: Vec<Constraint>,
+    dimensions: Vec<Dimension>,
+    /// World transforms indexed by [NodeId::index].
+    instances: BackedBuffer<InstanceTransform>,
+    binding: LayerBinding,
+}
+
+impl Layer {
+    pub fn new(
+        name: impl Into<String>,
+        pipeline: &GeometryPipeline,
+        device: &wgpu::Device,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            visible: true,
+            locked: false,
+            opacity: 1.0,
+            depth: 0.0,
+            scene: Scene::new(),
+            geometry: GeometryBuffer::new(device),
+            ranges: HashMap::new(),
+            fills: GeometryBuffer::new(device),
+            fill_ranges: HashMap::new(),
+            gradients: Vec::new(),
+            patterns: GeometryBuffer::new(device),
+            pattern_ranges: HashMap::new(),
+            strokes: GeometryBuffer::new(device),
+            stroke_ranges: HashMap::new(),
+            #[cfg(feature = "text")]
+            texts: HashMap::new(),
+            constraints: Vec::new(),
+            dimensions: Vec::new(),
+            instances: BackedBuffer::with_capacity(device, 64, wgpu::BufferUsages::VERTEX),
+            binding: pipeline.bind_layer(device, 1.0),
+        }
+    }
+
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    /// Gives mutable access to the scene graph. Transform changes are picked
+    /// up by [Layer::prepare], but changes to shape geometry need a call to
+    /// [Layer::rebuild].
+    pub fn scene_mut(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+
+    pub fn geometry(&self) -> &GeometryBuffer {
+        &self.geometry
+    }
+
+    pub fn instances(&self) -> &BackedBuffer<InstanceTransform> {
+        &self.instances
+    }
+
+    pub fn add_shape(
+        &mut self,
+        name: impl Into<String>,
+        shape: Shape,
+        transform: Transform2D,
+        parent: Option<NodeId>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> NodeId {
+        let ranges = self.tessellate(&shape, device, queue);
+        if matches!(shape.fill, Some(Fill::Gradient(_))) {
+            self.binding.update_gradients(&self.gradients, queue);
+        }
+        let id = self
+            .scene
+            .insert(name, NodeKind::Shape(shape), transform, parent);
+        self.insert_ranges(id, ranges);
+        id
+    }
+
+    /// Appends a shape's outline and fill to the layer's buffers, returning
+    /// their index ranges.
+    fn tessellate(
+        &mut self,
+        shape: &Shape,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> ShapeRanges {
+        let start = self.geometry.num_indices();
+        shape.tessellate(&mut self.geometry.batch(device, queue));
+        let lines = start..self.geometry.num_indices();
+
+        let stroke = shape.style.map(|_| {
+            let start = self.strokes.num_indices();
+            shape.tessellate_stroke(&mut self.strokes.batch(device, queue));
+            start..self.strokes.num_indices()
+        });
+
+        if let (Some(Fill::Texture(fill)), None) = (&shape.fill, shape.style) {
+            if shape.is_closed() {
+                let start = self.patterns.num_indices();
+                shape.tessellate_pattern(&mut self.patterns.batch(device, queue));
+                let pattern = start..self.patterns.num_indices();
+                return ShapeRanges {
+                    lines,
+                    fill: None,
+                    pattern: Some((pattern, fill.texture)),
+                    stroke,
+                };
+            }
+        }
+
+        let fill = shape.has_fill().then(|| {
+            let slot = match shape.fill.as_ref().filter(|_| shape.style.is_none()) {
+                Some(Fill::Gradient(gradient)) if self.gradients.len() < MAX_GRADIENTS => {
+                    self.gradients.push(gradient.into());
+                    Some(self.gradients.len() as u32 - 1)
+                }
+                Some(Fill::Gradient(_)) => {
+                    log::warn!(
+                        "Layer \"{}\" has more than {MAX_GRADIENTS} gradients",
+                        self.name
+                    );
+                    None
+                }
+                _ => None,
+            };
+            let start = self.fills.num_indices();
+            shape.tessellate_fill(slot, &mut self.fills.batch(device, queue));
+            start..self.fills.num_indices()
+        });
+        ShapeRanges {
+            lines,
+            fill,
+            pattern: None,
+            stroke,
+        }
+    }
+
+    fn insert_ranges(&mut self, id: NodeId, ranges: ShapeRanges) {
+        self.ranges.insert(id, ranges.lines);
+        if let Some(fill) = ranges.fill {
+            self.fill_ranges.insert(id, fill);
+        }
+        if let Some(pattern) = ranges.pattern {
+            self.pattern_ranges.insert(id, pattern);
+        }
+        if let Some(stroke) = ranges.stroke {
+            self.stroke_ranges.insert(id, stroke);
+        }
+    }
+
+    /// Appends points to a polyline shape. If the shape is the last thing in
+    /// the geometry buffer the new segments are batched onto the end,
+    /// otherwise the whole layer gets rebuilt.
+    pub fn extend_polyline(
+        &mut self,
+        id: NodeId,
+        new_points: &[Vec2],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        let Some(shape) = self.scene.get_mut(id).and_then(|n| n.shape_mut()) else {
+            return;
+        };
+        let color = shape.color;
+        let Geometry::Polyline { points, .. } = &mut shape.geometry else {
+            return;
+        };
+        let connect = points.last().copied();
+        points.extend_from_slice(new_points);
+        let filled = shape.fill.is_some() && shape.is_closed();
+
+        let is_last = !filled
+            && shape.style.is_none()
+            && self
+                .ranges
+                .get(&id)
+                .map(|r| r.end == self.geometry.num_indices())
+                .unwrap_or(false);
+        if !is_last {
+            self.rebuild(device, queue);
+            return;
+        }
+
+        let segment: Vec<Vec2> = connect
+            .into_iter()
+            .chain(new_points.iter().copied())
+            .collect();
+        tessellate_polyline(
+            &segment,
+            false,
+            color,
+            &mut self.geometry.batch(device, queue),
+        );
+        let end = self.geometry.num_indices();
+        if let Some(range) = self.ranges.get_mut(&id) {
+            range.end = end;
+        }
+    }
+
+    /// Adds a text node. The node is named after its contents.
+    #[cfg(feature = "text")]
+    pub fn add_text(
+        &mut self,
+        text: impl Into<String>,
+        transform: Transform2D,
+        parent: Option<NodeId>,
+        font: &Font,
+        text_pipeline: &TextPipeline,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<NodeId> {
+        let text = text.into();
+        let buffer = text_pipeline.buffer_text(font, device, &text)?;
+        let id = self
+            .scene
+            .insert(text.clone(), NodeKind::Text(text), transform, parent);
+        self.texts.insert(id, buffer);
+        Ok(id)
+    }
+
+    /// Buffers the text nodes again with `font`, after it's replaced.
+    #[cfg(feature = "text")]
+    pub fn rebuild_text(
+        &mut self,
+        font: &Font,
+        text_pipeline: &TextPipeline,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<()> {
+        for (id, node) in self.scene.iter() {
+            if let NodeKind::Text(text) = &node.kind {
+                let buffer = text_pipeline.buffer_text(font, device, text)?;
+                self.texts.insert(id, buffer);
+            }
+        }
+        Ok(())
+    }
+
+    /// Swaps the text of a text node and the buffer drawing it, and renames
+    /// the node after it. Returns the old text and buffer, or `None` if
+    /// `id` isn't a text node.
+    #[cfg(feature = "text")]
+    pub fn replace_text(
+        &mut self,
+        id: NodeId,
+        text: String,
+        buffer: TextBuffer,
+    ) -> Option<(String, TextBuffer)> {
+        let node = self.scene.get_mut(id)?;
+        let NodeKind::Text(old) = &mut node.kind else {
+            return None;
+        };
+        let old = std::mem::replace(old, text.clone());
+        node.name = text;
+        let old_buffer = self.texts.insert(id, buffer)?;
+        Some((old, old_buffer))
+    }
+
+    pub fn add_group(
+        &mut self,
+        name: impl Into<String>,
+        transform: Transform2D,
+        parent: Option<NodeId>,
+    ) -> NodeId {
+        self.scene.insert(name, NodeKind::Group, transform, parent)
+    }
+
+    /// Removes a node along with its children. The returned value can be
+    /// handed to [Layer::restore] to undo the removal.
+    pub fn remove(
+        &mut self,
+        id: NodeId,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Option<DetachedNodes> {
+        let subtree = self.scene.remove(id)?;
+        let mut had_shapes = false;
+        #[cfg(feature = "text")]
+        let mut texts = HashMap::new();
+        for (id, _) in subtree.iter() {
+            had_shapes |= self.ranges.remove(&id).is_some();
+            self.fill_ranges.remove(&id);
+            self.pattern_ranges.remove(&id);
+            self.stroke_ranges.remove(&id);
+            #[cfg(feature = "text")]
+            if let Some(text) = self.texts.remove(&id) {
+                texts.insert(id, text);
+            }
+        }
+        if had_shapes {
+            self.rebuild(device, queue);
+        }
+        Some(DetachedNodes {
+            subtree,
+            #[cfg(feature = "text")]
+            texts,
+        })
+    }
+
+    pub fn restore(
+        &mut self,
+        mut detached: DetachedNodes,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<(), DetachedNodes> {
+        if let Err(subtree) = self.scene.restore(detached.subtree) {
+            detached.subtree = subtree;
+            return Err(detached);
+        }
+        #[cfg(feature = "text")]
+        self.texts.extend(detached.texts);
+        self.rebuild(device, queue);
+        Ok(())
+    }
+
+    pub fn constraints(&self) -> &[Constraint] {
+        &self.constraints
+    }
+
+    /// Adds a constraint between two lines. The constraint isn't solved
+    /// until [Layer::solve_constraints] is called.
+    pub fn add_constraint(&mut self, constraint: Constraint) {
+        self.constraints.push(constraint);
+    }
+
+    /// Removes the most recently added constraint.
+    pub fn pop_constraint(&mut self) -> Option<Constraint> {
+        self.constraints.pop()
+    }
+
+    pub fn dimensions(&self) -> &[Dimension] {
+        &self.dimensions
+    }
+
+    pub fn add_dimension(&mut self, dimension: Dimension) {
+        self.dimensions.push(dimension);
+    }
+
+    /// Removes the most recently added dimension.
+    pub fn pop_dimension(&mut self) -> Option<Dimension> {
+        self.dimensions.pop()
+    }
+
+    /// World space endpoints of every dimension whose anchors still exist.
+    pub fn dimension_lines(&mut self) -> Vec<[Vec2; 2]> {
+        let scene = &mut self.scene;
+        self.dimensions
+            .iter()
+            .filter_map(|d| d.endpoints(scene))
+            .collect()
+    }
+
+    /// Bends constrained lines around the `pinned` ones until the
+    /// constraints hold again, regenerating the geometry if anything moved.
+    /// Returns the nodes that changed.
+    pub fn solve_constraints(
+        &mut self,
+        pinned: &[NodeId],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Vec<NodeId> {
+        if self.constraints.is_empty() {
+            return Vec::new();
+        }
+        let changed = constraints::solve(&mut self.scene, &self.constraints, pinned);
+        if !changed.is_empty() {
+            self.rebuild(device, queue);
+        }
+        changed
+    }
+
+    /// Modifies a shape node and regenerates the layer's geometry.
+    pub fn update_shape(
+        &mut self,
+        id: NodeId,
+        f: impl FnOnce(&mut Shape),
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> bool {
+        let Some(shape) = self.scene.get_mut(id).and_then(|n| n.shape_mut()) else {
+            return false;
+        };
+        f(shape);
+        self.rebuild(device, queue);
+        true
+    }
+
+    /// Regenerates the geometry buffers from the shapes in the scene.
+    pub fn rebuild(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.geometry.clear();
+        self.ranges.clear();
+        self.fills.clear();
+        self.fill_ranges.clear();
+        self.gradients.clear();
+        self.patterns.clear();
+        self.pattern_ranges.clear();
+        self.strokes.clear();
+        self.stroke_ranges.clear();
+
+        let shapes: Vec<(NodeId, Shape)> = self
+            .scene
+            .iter()
+            .filter_map(|(id, node)| Some((id, node.shape()?.clone())))
+            .collect();
+        for (id, shape) in shapes {
+            let ranges = self.tessellate(&shape, device, queue);
+            self.insert_ranges(id, ranges);
+        }
+        self.binding.update_gradients(&self.gradients, queue);
+    }
+
+    /// Uploads any world transforms that changed since the last frame.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let missing = self
+            .scene
+            .capacity()
+            .saturating_sub(self.instances.len() as usize);
+        if missing > 0 {
+            let mut batch = self.instances.batch(device, queue);
+            for _ in 0..missing {
+                batch.push(InstanceTransform::IDENTITY);
+            }
+        }
+
+        let mut changed = Vec::new();
+        self.scene
+            .update_transforms(|id, world| changed.push((id.index() as usize, world)));
+        if !changed.is_empty() {
+            self.instances.update(queue, |data| {
+                for (i, world) in changed.drain(..) {
+                    data[i] = world.into();
+                }
+            });
+        }
+    }
+
+    pub fn draw(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        geometry_pipeline: &GeometryPipeline,
+        #[cfg(feature = "text")] text_pipeline: &TextPipeline,
+        textures: &TextureRegistry,
+        camera_binding: &CameraBinding,
+    ) {
+        let mut list = DrawList::new();
+        self.submit(
+            &mut list,
+            geometry_pipeline.shapes(),
+            #[cfg(feature = "text")]
+            text_pipeline,
+            textures,
+            camera_binding,
+        );
+        list.execute(pass);
+    }
+
+    /// Adds the layer's shapes and then its text to `list`, each on layers
+    /// above what's already there.
+    pub fn submit<'a>(
+        &'a self,
+        list: &mut DrawList<'a>,
+        pipelines: &'a ShapePipelines,
+        #[cfg(feature = "text")] text_pipeline: &'a TextPipeline,
+        textures: &'a TextureRegistry,
+        camera_binding: &'a CameraBinding,
+    ) {
+        self.submit_shapes(list, pipelines, textures, camera_binding);
+        #[cfg(feature = "text")]
+        self.submit_text(list, text_pipeline, camera_binding);
+    }
+
+    /// Draws the layer's shapes, but not its text, with `pipelines`.
+    pub fn draw_shapes(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        pipelines: &ShapePipelines,
+        textures: &TextureRegistry,
+        camera_binding: &CameraBinding,
+    ) {
+        let mut list = DrawList::new();
+        self.submit_shapes(&mut list, pipelines, textures, camera_binding);
+        list.execute(pass);
+    }
+
+    /// Adds the layer's shapes to `list`. Every run of shapes drawn the
+    /// same way goes on its own layer of the list, so shapes stay stacked
+    /// in scene order.
+    pub fn submit_shapes<'a>(
+        &'a self,
+        list: &mut DrawList<'a>,
+        pipelines: &'a ShapePipelines,
+        textures: &'a TextureRegistry,
+        camera_binding: &'a CameraBinding,
+    ) {
+        let mut draws = Vec::new();
+        for (id, _) in self.scene.iter() {
+            if let Some(range) = self.fill_ranges.get(&id) {
+                draws.push((DrawKind::Fill, range.clone(), id.index()));
+            }
+            if let Some((range, texture)) = self.pattern_ranges.get(&id) {
+                // Shapes using a texture that isn't loaded aren't filled.
+                if let Some(texture) = textures.get(*texture) {
+                    let kind = DrawKind::Pattern(texture.bind_group());
+                    draws.push((kind, range.clone(), id.index()));
+                }
+            }
+            if let Some(range) = self.stroke_ranges.get(&id) {
+                draws.push((DrawKind::Stroke, range.clone(), id.index()));
+            }
+            if let Some(range) = self.ranges.get(&id) {
+                draws.push((DrawKind::Line, range.clone(), id.index()));
+            }
+        }
+        let same_state = |a: &DrawKind, b: &DrawKind| match (a, b) {
+            (DrawKind::Pattern(a), DrawKind::Pattern(b)) => std::ptr::eq(*a, *b),
+            _ => std::mem::discriminant(a) == std::mem::discriminant(b),
+        };
+        for run in draws.chunk_by(|a, b| same_state(&a.0, &b.0)) {
+            list.next_layer();
+            let ranges = run.iter().map(|(_, range, i)| (range.clone(), *i));
+            match run[0].0 {
+                DrawKind::Fill => pipelines.submit_fills(
+                    list,
+                    &self.fills,
+                    &self.instances,
+                    &self.binding,
+                    camera_binding,
+                    ranges,
+                ),
+                DrawKind::Pattern(texture) => pipelines.submit_patterns(
+                    list,
+                    &self.patterns,
+                    &self.instances,
+                    &self.binding,
+                    camera_binding,
+                    ranges.map(|(range, i)| (range, i, texture)),
+                ),
+                DrawKind::Stroke => pipelines.submit_strokes(
+                    list,
+                    &self.strokes,
+                    &self.instances,
+                    &self.binding,
+                    camera_binding,
+                    ranges,
+                ),
+                DrawKind::Line => pipelines.submit_lines(
+                    list,
+                    &self.geometry,
+                    &self.instances,
+                    &self.binding,
+                    camera_binding,
+                    ranges,
+                ),
+            }
+        }
+    }
+
+    /// Draws only the layer's text nodes.
+    #[cfg(feature = "text")]
+    pub fn draw_text(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        text_pipeline: &TextPipeline,
+        camera_binding: &CameraBinding,
+    ) {
+        let mut list = DrawList::new();
+        self.submit_text(&mut list, text_pipeline, camera_binding);
+        list.execute(pass);
+    }
+
+    /// Adds the layer's text nodes to `list`, all on one layer.
+    #[cfg(feature = "text")]
+    pub fn submit_text<'a>(
+        &'a self,
+        list: &mut DrawList<'a>,
+        text_pipeline: &'a TextPipeline,
+        camera_binding: &'a CameraBinding,
+    ) {
+        list.next_layer();
+        for (id, _) in self.scene.iter() {
+            if let Some(text) = self.texts.get(&id) {
+                text_pipeline.submit_text(list, text, &self.instances, id.index(), camera_binding);
+            }
+        }
+    }
+
+    /// Writes the triangles of every fill and a marker for every outline
+    /// point to `batch` in world space. `marker_size` is the size of the
+    /// point markers in world units.
+    pub fn draw_wireframe(&self, batch: &mut IndexedBatch<'_, ColoredVertex>, marker_size: f32) {
+        const TRIANGLE_COLOR: Vec4 = Vec4::new(1.0, 0.0, 1.0, 1.0);
+        const POINT_COLOR: Vec4 = Vec4::new(0.0, 1.0, 1.0, 1.0);
+        let half = marker_size * 0.5;
+        for (_, node) in self.scene.iter() {
+            let Some(shape) = node.shape() else {
+                continue;
+            };
+            let world = node.world();
+            let outline: Vec<Vec2> = shape
+                .outline()
+                .iter()
+                .map(|&p| world.transform_point2(p))
+                .collect();
+            if shape.fill.is_some() && shape.is_closed() {
+                for triangle in triangulate(&outline).chunks_exact(3) {
+                    for i in 0..3 {
+                        let a = outline[triangle[i] as usize];
+                        let b = outline[triangle[(i + 1) % 3] as usize];
+                        batch.line(
+                            ColoredVertex::new(a, TRIANGLE_COLOR),
+                            ColoredVertex::new(b, TRIANGLE_COLOR),
+                        );
+                    }
+                }
+            }
+            for p in outline {
+                batch.line(
+                    ColoredVertex::new(p - Vec2::X * half, POINT_COLOR),
+                    ColoredVertex::new(p + Vec2::X * half, POINT_COLOR),
+                );
+                batch.line(
+                    ColoredVertex::new(p - Vec2::Y * half, POINT_COLOR),
+                    ColoredVertex::new(p + Vec2::Y * half, POINT_COLOR),
+                );
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn rename(&mut self, name: impl Into<String>) {
+        self.name = name.into();
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    pub fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+    }
+
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    pub fn set_opacity(&mut self, opacity: f32, queue: &wgpu::Queue) {
+        self.opacity = opacity.clamp(0.0, 1.0);
+        self.binding.update(self.opacity, self.depth, queue);
+    }
+
+    pub fn depth(&self) -> f32 {
+        self.depth
+    }
+
+    /// Sets how far the layer is from the camera. Only matters when drawing
+    /// with [GeometryPipeline::depth_tested], where closer layers cover
+    /// further ones whatever order they're drawn in.
+    pub fn set_depth(&mut self, depth: f32, queue: &wgpu::Queue) {
+        self.depth = depth;
+        self.binding.update(self.opacity, self.depth, queue);
+    }
+
+    pub fn binding(&self) -> &LayerBinding {
+        &self.binding
+    }
+
+    /// Whether tools are allowed to modify this layer.
+    pub fn is_editable(&self) -> bool {
+        self.visible && !self.locked
+    }
+}
+
+/// Ordered list of layers. Index 0 is the bottom most layer and is drawn
+/// first.
+pub struct LayerStack {
+    layers: Vec<Layer>,
+    active: usize,
+    /// Bumped whenever layers are added, removed or moved.
+    revision: u64,
+    /// Styles shared by shapes on every layer.
+    styles: StyleSheet,
+}
+
+impl LayerStack {
+    pub fn new(pipeline: &GeometryPipeline, device: &wgpu::Device) -> Self {
+        Self {
+            layers: vec![Layer::new("Layer 1", pipeline, device)],
+            active: 0,
+            revision: 0,
+            styles: StyleSheet::default(),
+        }
+    }
+
+    /// Builds a stack from existing layers. Returns `None` if `layers` is
+    /// empty since there always needs to be a layer to draw on.
+    pub fn from_layers(layers: Vec<Layer>, active: usize, styles: StyleSheet) -> Option<Self> {
+        if layers.is_empty() {
+            return None;
+        }
+        let active = active.min(layers.len() - 1);
+        Some(Self {
+            layers,
+            active,
+            revision: 0,
+            styles,
+        })
+    }
+
+    pub fn styles(&self) -> &StyleSheet {
+        &self.styles
+    }
+
+    pub fn styles_mut(&mut self) -> &mut StyleSheet {
+        &mut self.styles
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Adds a layer above the active one and makes it active.
+    pub fn add(
+        &mut self,
+        name: impl Into<String>,
+        pipeline: &GeometryPipeline,
+        device: &wgpu::Device,
+    ) -> usize {
+        let index = (self.active + 1).min(self.layers.len());
+        self.layers
+            .insert(index, Layer::new(name, pipeline, device));
+        self.active = index;
+        self.revision += 1;
+        index
+    }
+
+    /// Puts `layer` back at `index`, keeping the active layer the same.
+    pub fn insert(&mut self, index: usize, layer: Layer) {
+        let index = index.min(self.layers.len());
+        self.layers.insert(index, layer);
+        if self.active >= index && self.layers.len() > 1 {
+            self.active += 1;
+        }
+        self.revision += 1;
+    }
+
+    /// Removes a layer. The last remaining layer can't be removed.
+    pub fn remove(&mut self, index: usize) -> Option<Layer> {
+        if self.layers.len() <= 1 || index >= self.layers.len() {
+            return None;
+        }
+        let layer = self.layers.remove(index);
+        if self.active >= index && self.active > 0 {
+            self.active -= 1;
+        }
+        self.revision += 1;
+        Some(layer)
+    }
+
+    /// Moves a layer to a new position in the stack, keeping track of the
+    /// active layer.
+    pub fn reorder(&mut self, from: usize, to: usize) {
+        if from >= self.layers.len() || to >= self.layers.len() || from == to {
+            return;
+        }
+        let layer = self.layers.remove(from);
+        self.layers.insert(to, layer);
+        self.revision += 1;
+
+        if self.active == from {
+            self.active = to;
+        } else if from < self.active && to >= self.active {
+            self.active -= 1;
+        } else if from > self.active && to <= self.active {
+            self.active += 1;
+        }
+    }
+
+    pub fn raise(&mut self, index: usize) {
+        self.reorder(index, index + 1);
+    }
+
+    pub fn lower(&mut self, index: usize) {
+        if index > 0 {
+            self.reorder(index, index - 1);
+        }
+    }
+
+    /// Changes whenever layers are added, removed or moved, which is when
+    /// indices into the stack held onto elsewhere go stale.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    pub fn set_active(&mut self, index: usize) {
+        if index < self.layers.len() {
+            self.active = index;
+        }
+    }
+
+    pub fn active(&self) -> &Layer {
+        &self.layers[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut Layer {
+        &mut self.layers[self.active]
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Layer> {
+        self.layers.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Layer> {
+        self.layers.get_mut(index)
+    }
+
+    /// Iterates the layers bottom to top, which is the order they should be
+    /// drawn in.
+    pub fn iter(&self) -> impl Iterator<Item = &Layer> {
+        self.layers.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Layer> {
+        self.layers.iter_mut()
+    }
+
+    /// Spreads the layers between `near` and `far` so later layers are
+    /// closer to the camera, matching the order they're drawn in.
+    pub fn update_depths(&mut self, near: f32, far: f32, queue: &wgpu::Queue) {
+        let n = self.layers.len() as f32;
+        for (i, layer) in self.layers.iter_mut().enumerate() {
+            let depth = near + (far - near) * (n - i as f32) / (n + 1.0);
+            if layer.depth() != depth {
+                layer.set_depth(depth, queue);
+            }
+        }
+    }
+
+    pub fn visible(&self) -> impl Iterator<Item = &Layer> {
+        self.layers.iter().filter(|l| l.visible)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{resources::geometry::test_pipeline, utils::test_device};
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn moving_layers_keeps_the_active_one() {
+        let (device, _) = test_device();
+        let pipeline = test_pipeline(&device);
+        let mut layers = LayerStack::new(&pipeline, &device);
+        layers.add("Middle", &pipeline, &device);
+        layers.add("Top", &pipeline, &device);
+        layers.set_active(1);
+        let revision = layers.revision();
+
+        layers.reorder(2, 0);
+        assert_eq!(layers.get(0).unwrap().name(), "Top");
+        assert_eq!(layers.active().name(), "Middle");
+        assert_ne!(layers.revision(), revision);
+
+        let top = layers.remove(0).unwrap();
+        assert_eq!(layers.active().name(), "Middle");
+        layers.insert(2, top);
+        assert_eq!(layers.get(2).unwrap().name(), "Top");
+        assert_eq!(layers.active().name(), "Middle");
+    }
+}
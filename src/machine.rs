@@ -0,0 +1,828 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Debug,
+    future::Future,
+    hash::Hash,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+use futures_channel::mpsc;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    format::Format,
+    resources::Resources,
+    utils::{spawn, MaybeSend},
+};
+
+type Guard<C> = Box<dyn Fn(&C) -> bool>;
+type Action<E, C> = Box<dyn FnMut(&mut C, &E) -> anyhow::Result<()>>;
+type StateHook<C> = Box<dyn FnMut(&mut C)>;
+type TransitionHook<S, E, C> = Box<dyn FnMut(&mut C, &S, &E, &S)>;
+type AsyncHook<S, E, C> = Box<dyn FnMut(&mut C, Completion<S, E>)>;
+type TransitionTable<S, E, C> = HashMap<(S, E), Vec<Transition<S, E, C>>>;
+
+struct Transition<S, E, C> {
+    to: S,
+    guard: Option<Guard<C>>,
+    action: Option<Action<E, C>>,
+}
+
+impl<S, E, C> Transition<S, E, C> {
+    fn new(to: S) -> Self {
+        Self {
+            to,
+            guard: None,
+            action: None,
+        }
+    }
+}
+
+/// An event sent back by the work an async enter hook started, along with
+/// the visit to the state it was started in.
+struct Completed<S, E> {
+    state: S,
+    visit: u64,
+    event: E,
+}
+
+/// Sends the event an async enter hook finishes with back to the machine.
+struct Completion<S, E> {
+    sender: mpsc::UnboundedSender<Completed<S, E>>,
+    state: S,
+    visit: u64,
+}
+
+impl<S, E> Completion<S, E> {
+    fn send(self, event: E) {
+        // The machine is gone, so nothing is waiting for this.
+        let _ = self.sender.unbounded_send(Completed {
+            state: self.state,
+            visit: self.visit,
+            event,
+        });
+    }
+}
+
+/// Posts events to a [StateMachine] to be handled on its next
+/// [StateMachine::update]. It's cheap to clone, so hooks, async work and
+/// input handlers can each keep one.
+pub struct EventSender<E> {
+    queue: Arc<Mutex<VecDeque<E>>>,
+}
+
+impl<E> Clone for EventSender<E> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<E> EventSender<E> {
+    pub fn post(&self, event: E) {
+        self.queue.lock().unwrap().push_back(event);
+    }
+}
+
+/// How many transitions a [StateMachine] remembers unless told otherwise.
+pub const DEFAULT_HISTORY_LEN: usize = 16;
+
+/// A transition a [StateMachine] took, for debugging.
+#[derive(Debug, Clone)]
+pub struct TransitionRecord<S, E> {
+    pub from: S,
+    pub event: E,
+    pub to: S,
+    /// When the transition happened, counted from when the machine was
+    /// built.
+    pub time: Duration,
+}
+
+/// Where a transition is kept.
+enum Slot<S, E> {
+    Table(S, E, usize),
+    Any(E, usize),
+}
+
+/// A finite state machine over states `S` and events `E`. Hooks get a
+/// context `C`, such as the tool or app the machine drives.
+///
+/// States can be nested with [StateMachineBuilder::substate]. The machine
+/// is always in a leaf state, and is also in all of that state's parents.
+/// Transitions are checked in the order they were added, first the ones for
+/// the current state, then the ones for its parents going up, and then the
+/// ones added with [StateMachineBuilder::from_any].
+pub struct StateMachine<S, E, C = ()> {
+    state: S,
+    /// Every state the builder was told about, in the order it first came
+    /// up.
+    states: Vec<S>,
+    parents: HashMap<S, S>,
+    /// The child a composite state starts in when it's entered.
+    initial: HashMap<S, S>,
+    transitions: TransitionTable<S, E, C>,
+    any_state: HashMap<E, Vec<Transition<S, E, C>>>,
+    on_enter: HashMap<S, Vec<StateHook<C>>>,
+    on_exit: HashMap<S, Vec<StateHook<C>>>,
+    on_transition: Vec<TransitionHook<S, E, C>>,
+    on_enter_async: HashMap<S, Vec<AsyncHook<S, E, C>>>,
+    /// How many times each state has been entered, to tell whether async
+    /// work finished during the visit that started it.
+    visits: HashMap<S, u64>,
+    completed_sender: mpsc::UnboundedSender<Completed<S, E>>,
+    completed: mpsc::UnboundedReceiver<Completed<S, E>>,
+    /// Events posted to be handled on the next update, in order.
+    queued: Arc<Mutex<VecDeque<E>>>,
+    /// The last transitions taken, oldest first.
+    history: VecDeque<TransitionRecord<S, E>>,
+    history_len: usize,
+    started: web_time::Instant,
+}
+
+impl<S, E, C> StateMachine<S, E, C>
+where
+    S: Clone + Eq + Hash + Debug,
+    E: Clone + Eq + Hash + Debug,
+{
+    pub fn builder(initial: S) -> StateMachineBuilder<S, E, C> {
+        let (completed_sender, completed) = mpsc::unbounded();
+        StateMachineBuilder {
+            last: None,
+            machine: Self {
+                states: vec![initial.clone()],
+                state: initial,
+                parents: HashMap::new(),
+                initial: HashMap::new(),
+                transitions: HashMap::new(),
+                any_state: HashMap::new(),
+                on_enter: HashMap::new(),
+                on_exit: HashMap::new(),
+                on_transition: Vec::new(),
+                on_enter_async: HashMap::new(),
+                visits: HashMap::new(),
+                completed_sender,
+                completed,
+                queued: Arc::default(),
+                history: VecDeque::new(),
+                history_len: DEFAULT_HISTORY_LEN,
+                started: web_time::Instant::now(),
+            },
+        }
+    }
+
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Whether the machine is in `state` or one of its substates.
+    pub fn is_in(&self, state: &S) -> bool {
+        self.path(&self.state).contains(state)
+    }
+
+    pub fn parent(&self, state: &S) -> Option<&S> {
+        self.parents.get(state)
+    }
+
+    /// Every state, in the order they were first used when building.
+    pub fn states(&self) -> &[S] {
+        &self.states
+    }
+
+    /// Every transition as `(from, event, to)`, where `from` is `None` for
+    /// ones that apply in any state. They're ordered by the states they
+    /// connect.
+    pub fn transitions(&self) -> Vec<(Option<&S>, &E, &S)> {
+        let mut transitions: Vec<_> = self
+            .transitions
+            .iter()
+            .flat_map(|((from, event), ts)| ts.iter().map(move |t| (Some(from), event, &t.to)))
+            .chain(
+                self.any_state
+                    .iter()
+                    .flat_map(|(event, ts)| ts.iter().map(move |t| (None, event, &t.to))),
+            )
+            .collect();
+        let index = |state: Option<&S>| {
+            state.map_or(usize::MAX, |state| {
+                self.states
+                    .iter()
+                    .position(|s| s == state)
+                    .unwrap_or(usize::MAX)
+            })
+        };
+        transitions.sort_by_cached_key(|&(from, event, to)| {
+            (index(from), index(Some(to)), format!("{event:?}"))
+        });
+        transitions
+    }
+
+    /// The state `event` would lead to, if any. If that's a composite
+    /// state, the machine ends up in its initial substate.
+    pub fn next_state(&self, event: &E, ctx: &C) -> Option<&S> {
+        let slot = self.find(event, ctx).ok()?;
+        Some(&self.get(&slot)?.to)
+    }
+
+    /// Moves to the state `event` leads to. The states that are left run
+    /// their exit hooks innermost first, then the transition hooks run, and
+    /// then the states that are entered run their enter hooks outermost
+    /// first. Parents shared by both states aren't left, unless the target
+    /// is one of them, in which case it's left and entered again.
+    ///
+    /// The transition's action runs before any of that, so if it fails, or
+    /// there's no transition whose guard passes, the machine stays where it
+    /// is and the error is returned.
+    pub fn handle(&mut self, event: &E, ctx: &mut C) -> anyhow::Result<()> {
+        let slot = self.find(event, ctx)?;
+        let state = self.state.clone();
+        let transition = self.get_mut(&slot).context("Transition went missing")?;
+        if let Some(action) = &mut transition.action {
+            action(ctx, event)
+                .with_context(|| format!("Transition from {state:?} on {event:?} failed"))?;
+        }
+        let target = transition.to.clone();
+
+        let up = self.path(&self.state);
+        let down = self.path(&target);
+        let common = up.iter().find(|state| down.contains(state));
+        let mut exits: Vec<S> = up
+            .iter()
+            .take_while(|&state| Some(state) != common)
+            .cloned()
+            .collect();
+        let mut enters: Vec<S> = down
+            .iter()
+            .take_while(|&state| Some(state) != common)
+            .cloned()
+            .collect();
+        enters.reverse();
+        if common == Some(&target) {
+            exits.push(target.clone());
+            enters.insert(0, target.clone());
+        }
+        let to = self.descend(target, &mut enters);
+
+        let from = std::mem::replace(&mut self.state, to.clone());
+        for state in &exits {
+            for hook in self.on_exit.get_mut(state).into_iter().flatten() {
+                hook(ctx);
+            }
+        }
+        for hook in &mut self.on_transition {
+            hook(ctx, &from, event, &to);
+        }
+        if self.history_len > 0 {
+            if self.history.len() == self.history_len {
+                self.history.pop_front();
+            }
+            self.history.push_back(TransitionRecord {
+                from: from.clone(),
+                event: event.clone(),
+                to: to.clone(),
+                time: self.started.elapsed(),
+            });
+        }
+        for state in &enters {
+            let visit = self.visits.entry(state.clone()).or_default();
+            *visit += 1;
+            let visit = *visit;
+            for hook in self.on_enter.get_mut(state).into_iter().flatten() {
+                hook(ctx);
+            }
+            for hook in self.on_enter_async.get_mut(state).into_iter().flatten() {
+                hook(
+                    ctx,
+                    Completion {
+                        sender: self.completed_sender.clone(),
+                        state: state.clone(),
+                        visit,
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles the events sent back by async enter hooks that have finished
+    /// since the last call. Call it once a frame. Events from states the
+    /// machine has left since the work started are dropped. Returns how
+    /// many events were handled, or the first error.
+    pub fn poll(&mut self, ctx: &mut C) -> anyhow::Result<usize> {
+        let mut handled = 0;
+        while let Ok(completed) = self.completed.try_recv() {
+            let current = self.is_in(&completed.state)
+                && self.visits.get(&completed.state) == Some(&completed.visit);
+            if current {
+                self.handle(&completed.event, ctx)?;
+                handled += 1;
+            }
+        }
+        Ok(handled)
+    }
+
+    /// Queues `event` to be handled on the next [Self::update] rather than
+    /// right away.
+    pub fn post(&self, event: E) {
+        self.queued.lock().unwrap().push_back(event);
+    }
+
+    /// A handle for posting events from places that can't borrow the
+    /// machine, like its own hooks.
+    pub fn sender(&self) -> EventSender<E> {
+        EventSender {
+            queue: self.queued.clone(),
+        }
+    }
+
+    /// Events posted that the next [Self::update] will handle, in order.
+    pub fn pending(&self) -> Vec<E> {
+        self.queued.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// The last transitions taken, oldest first.
+    pub fn history(&self) -> &VecDeque<TransitionRecord<S, E>> {
+        &self.history
+    }
+
+    /// Changes how many transitions [Self::history] keeps. Zero turns it
+    /// off.
+    pub fn set_history_len(&mut self, len: usize) {
+        self.history_len = len;
+        while self.history.len() > len {
+            self.history.pop_front();
+        }
+    }
+
+    /// How long it's been since the machine was built, on the same clock as
+    /// [TransitionRecord::time].
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Handles finished async work like [Self::poll], then the events
+    /// posted since the last update in the order they were posted. Events
+    /// posted while these are handled wait for the next update, so hooks
+    /// that post events never run inside another transition. Call it once a
+    /// frame.
+    ///
+    /// An event that fails doesn't stop the ones after it. Returns how many
+    /// events were handled, or the first error.
+    pub fn update(&mut self, ctx: &mut C) -> anyhow::Result<usize> {
+        let mut handled = self.poll(ctx)?;
+        let events = std::mem::take(&mut *self.queued.lock().unwrap());
+        let mut result = Ok(());
+        for event in events {
+            match self.handle(&event, ctx) {
+                Ok(()) => handled += 1,
+                Err(e) => {
+                    if result.is_ok() {
+                        result = Err(e);
+                    }
+                }
+            }
+        }
+        result.map(|()| handled)
+    }
+
+    /// Jumps to `state`, or its initial substate, without running any
+    /// hooks, e.g. when a tool is reset.
+    pub fn reset(&mut self, state: S) {
+        self.state = self.descend(state, &mut Vec::new());
+    }
+
+    /// The first transition for `event` whose guard passes.
+    fn find(&self, event: &E, ctx: &C) -> anyhow::Result<Slot<S, E>> {
+        let passes = |t: &Transition<S, E, C>| t.guard.as_ref().is_none_or(|guard| guard(ctx));
+        let mut rejected = false;
+        for state in self.path(&self.state) {
+            let key = (state, event.clone());
+            if let Some(transitions) = self.transitions.get(&key) {
+                if let Some(i) = transitions.iter().position(passes) {
+                    return Ok(Slot::Table(key.0, key.1, i));
+                }
+                rejected = true;
+            }
+        }
+        if let Some(transitions) = self.any_state.get(event) {
+            if let Some(i) = transitions.iter().position(passes) {
+                return Ok(Slot::Any(event.clone(), i));
+            }
+            rejected = true;
+        }
+        if rejected {
+            anyhow::bail!(
+                "Guards blocked every transition from {:?} on {event:?}",
+                self.state
+            );
+        }
+        anyhow::bail!("No transition from {:?} on {event:?}", self.state)
+    }
+
+    fn get(&self, slot: &Slot<S, E>) -> Option<&Transition<S, E, C>> {
+        match slot {
+            Slot::Table(state, event, i) => self
+                .transitions
+                .get(&(state.clone(), event.clone()))?
+                .get(*i),
+            Slot::Any(event, i) => self.any_state.get(event)?.get(*i),
+        }
+    }
+
+    fn get_mut(&mut self, slot: &Slot<S, E>) -> Option<&mut Transition<S, E, C>> {
+        match slot {
+            Slot::Table(state, event, i) => self
+                .transitions
+                .get_mut(&(state.clone(), event.clone()))?
+                .get_mut(*i),
+            Slot::Any(event, i) => self.any_state.get_mut(event)?.get_mut(*i),
+        }
+    }
+
+    /// `state` followed by its parents, outermost last.
+    fn path(&self, state: &S) -> Vec<S> {
+        let mut path = vec![state.clone()];
+        while let Some(parent) = self.parents.get(path.last().unwrap()) {
+            path.push(parent.clone());
+        }
+        path
+    }
+
+    fn register(&mut self, state: &S) {
+        if !self.states.contains(state) {
+            self.states.push(state.clone());
+        }
+    }
+
+    /// Follows initial substates down from `state` to a leaf, adding the
+    /// ones it passes through to `entered`.
+    fn descend(&self, mut state: S, entered: &mut Vec<S>) -> S {
+        while let Some(child) = self.initial.get(&state) {
+            entered.push(child.clone());
+            state = child.clone();
+        }
+        state
+    }
+}
+
+/// Builds the transition table and hooks of a [StateMachine].
+pub struct StateMachineBuilder<S, E, C = ()> {
+    machine: StateMachine<S, E, C>,
+    /// The transition [Self::guard] and [Self::action] change.
+    last: Option<Slot<S, E>>,
+}
+
+impl<S, E, C> StateMachineBuilder<S, E, C>
+where
+    S: Clone + Eq + Hash + Debug,
+    E: Clone + Eq + Hash + Debug,
+{
+    /// Nests `child` in `parent`. Transitions and hooks of `parent` apply
+    /// while the machine is in `child`. A state can only have one parent,
+    /// and a state can't be nested in itself, so those calls are ignored.
+    pub fn substate(mut self, parent: S, child: S) -> Self {
+        if self.machine.path(&parent).contains(&child) {
+            log::warn!("Ignoring a substate that would nest a state in itself");
+            return self;
+        }
+        if let Some(current) = self.machine.parents.get(&child) {
+            if current != &parent {
+                log::warn!(
+                    "Ignoring a substate of {parent:?}, as {child:?} is already in {current:?}"
+                );
+            }
+            return self;
+        }
+        self.machine.register(&parent);
+        self.machine.register(&child);
+        self.machine.parents.insert(child, parent);
+        self
+    }
+
+    /// Like [Self::substate], and also makes `child` the state the machine
+    /// goes to when it moves to `parent`.
+    pub fn initial_substate(mut self, parent: S, child: S) -> Self {
+        self = self.substate(parent.clone(), child.clone());
+        if self.machine.parents.get(&child) == Some(&parent) {
+            self.machine.initial.insert(parent, child);
+        }
+        self
+    }
+
+    /// Moves from `from` to `to` on `event`.
+    pub fn transition(self, from: S, event: E, to: S) -> Self {
+        self.add(from, event, to, None)
+    }
+
+    /// Like [Self::transition], but only when `guard` passes.
+    pub fn guarded(self, from: S, event: E, to: S, guard: impl Fn(&C) -> bool + 'static) -> Self {
+        self.add(from, event, to, Some(Box::new(guard)))
+    }
+
+    /// Moves to `to` on `event` whatever the current state is.
+    pub fn from_any(mut self, event: E, to: S) -> Self {
+        self.machine.register(&to);
+        let transitions = self.machine.any_state.entry(event.clone()).or_default();
+        transitions.push(Transition::new(to));
+        self.last = Some(Slot::Any(event, transitions.len() - 1));
+        self
+    }
+
+    /// Only takes the last added transition when `guard` passes, e.g. only
+    /// saving when there are changes. Replaces any guard it already has.
+    pub fn guard(mut self, guard: impl Fn(&C) -> bool + 'static) -> Self {
+        if let Some(transition) = self.last_mut() {
+            transition.guard = Some(Box::new(guard));
+        }
+        self
+    }
+
+    /// Runs `action` when the last added transition is taken, before any
+    /// states are left. If it fails the transition doesn't happen.
+    pub fn action(
+        mut self,
+        action: impl FnMut(&mut C, &E) -> anyhow::Result<()> + 'static,
+    ) -> Self {
+        if let Some(transition) = self.last_mut() {
+            transition.action = Some(Box::new(action));
+        }
+        self
+    }
+
+    pub fn on_enter(mut self, state: S, hook: impl FnMut(&mut C) + 'static) -> Self {
+        self.machine
+            .on_enter
+            .entry(state)
+            .or_default()
+            .push(Box::new(hook));
+        self
+    }
+
+    pub fn on_exit(mut self, state: S, hook: impl FnMut(&mut C) + 'static) -> Self {
+        self.machine
+            .on_exit
+            .entry(state)
+            .or_default()
+            .push(Box::new(hook));
+        self
+    }
+
+    /// Starts the future `start` returns whenever `state` is entered, after
+    /// the other enter hooks. The event it finishes with is handled by the
+    /// next [StateMachine::poll], as long as the machine is still in the
+    /// same visit to `state`. Useful for loading states that move on once
+    /// the loading is done.
+    pub fn on_enter_async<F, Fut>(mut self, state: S, mut start: F) -> Self
+    where
+        F: FnMut(&mut C) -> Fut + 'static,
+        Fut: Future<Output = E> + MaybeSend + 'static,
+        S: MaybeSend + 'static,
+        E: MaybeSend + 'static,
+    {
+        self.machine
+            .on_enter_async
+            .entry(state)
+            .or_default()
+            .push(Box::new(move |ctx, completion| {
+                let future = start(ctx);
+                spawn(async move { completion.send(future.await) });
+            }));
+        self
+    }
+
+    /// Runs `hook` with the old state, the event and the new state on every
+    /// transition.
+    pub fn on_transition(mut self, hook: impl FnMut(&mut C, &S, &E, &S) + 'static) -> Self {
+        self.machine.on_transition.push(Box::new(hook));
+        self
+    }
+
+    /// A handle for posting events to the machine being built, for hooks
+    /// to hold on to.
+    pub fn sender(&self) -> EventSender<E> {
+        self.machine.sender()
+    }
+
+    pub fn build(mut self) -> StateMachine<S, E, C> {
+        let initial = self.machine.state.clone();
+        self.machine.reset(initial);
+        self.machine
+    }
+
+    fn add(mut self, from: S, event: E, to: S, guard: Option<Guard<C>>) -> Self {
+        self.machine.register(&from);
+        self.machine.register(&to);
+        let transitions = self
+            .machine
+            .transitions
+            .entry((from.clone(), event.clone()))
+            .or_default();
+        transitions.push(Transition {
+            guard,
+            ..Transition::new(to)
+        });
+        self.last = Some(Slot::Table(from, event, transitions.len() - 1));
+        self
+    }
+
+    fn last_mut(&mut self) -> Option<&mut Transition<S, E, C>> {
+        let slot = self.last.as_ref()?;
+        self.machine.get_mut(slot)
+    }
+}
+
+/// States and transitions of a [StateMachine] as data, so they can be
+/// loaded from a RON or JSON file. Guards, actions and hooks are code, so
+/// they're added to the [StateMachineBuilder] this makes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(deserialize = "S: Deserialize<'de>, E: Deserialize<'de>"))]
+pub struct MachineDefinition<S, E> {
+    pub initial: S,
+    #[serde(default)]
+    pub substates: Vec<SubstateDefinition<S>>,
+    #[serde(default)]
+    pub transitions: Vec<TransitionDefinition<S, E>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubstateDefinition<S> {
+    pub parent: S,
+    pub child: S,
+    /// Whether the parent starts in this child when it's entered.
+    #[serde(default)]
+    pub initial: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(deserialize = "S: Deserialize<'de>, E: Deserialize<'de>"))]
+pub struct TransitionDefinition<S, E> {
+    /// Leaving this out makes the transition apply in any state.
+    #[serde(default)]
+    pub from: Option<S>,
+    pub event: E,
+    pub to: S,
+}
+
+impl<S, E> MachineDefinition<S, E>
+where
+    S: Clone + Eq + Hash + Debug + DeserializeOwned,
+    E: Clone + Eq + Hash + Debug + DeserializeOwned,
+{
+    pub fn parse(data: &str, format: Format) -> anyhow::Result<Self> {
+        Ok(match format {
+            Format::Json => serde_json::from_str(data)?,
+            #[cfg(feature = "ron")]
+            Format::Ron => ron::from_str(data)?,
+            #[cfg(not(feature = "ron"))]
+            Format::Ron => anyhow::bail!("Reading RON needs the ron feature"),
+        })
+    }
+
+    /// Loads a definition from `path`, picking the format by its extension.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(resources: &Resources, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        pollster::block_on(Self::load_async(resources, path))
+    }
+
+    pub async fn load_async(resources: &Resources, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let data = resources.load_string_async(path).await?;
+        Self::parse(&data, Format::from_path(path))
+            .with_context(|| format!("Invalid state machine in {}", path.display()))
+    }
+
+    /// Starts building the machine, with the states and transitions added
+    /// in the order they're listed.
+    pub fn builder<C>(&self) -> StateMachineBuilder<S, E, C> {
+        let mut builder = StateMachine::builder(self.initial.clone());
+        for substate in &self.substates {
+            let (parent, child) = (substate.parent.clone(), substate.child.clone());
+            builder = if substate.initial {
+                builder.initial_substate(parent, child)
+            } else {
+                builder.substate(parent, child)
+            };
+        }
+        for transition in &self.transitions {
+            let (event, to) = (transition.event.clone(), transition.to.clone());
+            builder = match &transition.from {
+                Some(from) => builder.transition(from.clone(), event, to),
+                None => builder.from_any(event, to),
+            };
+        }
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Log = Vec<String>;
+
+    fn log(entry: &'static str) -> impl FnMut(&mut Log) {
+        move |log| log.push(entry.to_string())
+    }
+
+    #[test]
+    fn transitions_run_hooks_in_order() {
+        let mut machine = StateMachine::<_, _, Log>::builder("idle")
+            .initial_substate("active", "drawing")
+            .substate("active", "panning")
+            .transition("idle", "press", "active")
+            .transition("drawing", "pan", "panning")
+            .transition("active", "release", "idle")
+            .on_exit("idle", log("exit idle"))
+            .on_enter("active", log("enter active"))
+            .on_exit("active", log("exit active"))
+            .on_enter("drawing", log("enter drawing"))
+            .on_exit("drawing", log("exit drawing"))
+            .on_transition(|log, from, event, to| log.push(format!("{from} {event} {to}")))
+            .build();
+        let mut log = Log::new();
+
+        machine.handle(&"press", &mut log).unwrap();
+        assert_eq!(machine.state(), &"drawing");
+        assert!(machine.is_in(&"active"));
+        assert_eq!(
+            log,
+            [
+                "exit idle",
+                "idle press drawing",
+                "enter active",
+                "enter drawing"
+            ]
+        );
+
+        log.clear();
+        machine.handle(&"pan", &mut log).unwrap();
+        assert_eq!(log, ["exit drawing", "drawing pan panning"]);
+
+        // Taken from the parent, leaving both states.
+        log.clear();
+        machine.handle(&"release", &mut log).unwrap();
+        assert_eq!(machine.state(), &"idle");
+        assert_eq!(log, ["exit active", "panning release idle"]);
+    }
+
+    #[test]
+    fn guards_pick_the_first_transition_that_passes() {
+        let machine = StateMachine::<_, _, u32>::builder("idle")
+            .guarded("idle", "go", "fast", |&speed| speed > 1)
+            .transition("idle", "go", "slow")
+            .from_any("go", "never")
+            .build();
+        assert_eq!(machine.next_state(&"go", &2), Some(&"fast"));
+        assert_eq!(machine.next_state(&"go", &0), Some(&"slow"));
+    }
+
+    #[test]
+    fn blocked_transitions_stay_put() {
+        let mut machine = StateMachine::<_, _, bool>::builder("idle")
+            .guarded("idle", "save", "saving", |&dirty| dirty)
+            .transition("idle", "fail", "failed")
+            .action(|_, _| anyhow::bail!("Unable to fail"))
+            .build();
+        let mut dirty = false;
+        assert!(machine.handle(&"save", &mut dirty).is_err());
+        assert!(machine.handle(&"fail", &mut dirty).is_err());
+        assert!(machine.handle(&"unknown", &mut dirty).is_err());
+        assert_eq!(machine.state(), &"idle");
+        assert!(machine.history().is_empty());
+
+        dirty = true;
+        machine.handle(&"save", &mut dirty).unwrap();
+        assert_eq!(machine.state(), &"saving");
+    }
+
+    #[test]
+    fn substates_keep_their_first_parent() {
+        let mut machine = StateMachine::<_, _, ()>::builder("idle")
+            .initial_substate("first", "child")
+            .substate("second", "child")
+            .transition("idle", "go", "first")
+            .build();
+        machine.handle(&"go", &mut ()).unwrap();
+        assert_eq!(machine.state(), &"child");
+        assert!(machine.is_in(&"first"));
+        assert!(!machine.is_in(&"second"));
+    }
+
+    #[test]
+    fn posted_events_are_handled_in_order_on_update() {
+        let mut machine = StateMachine::<_, _, ()>::builder("a")
+            .transition("a", "next", "b")
+            .transition("b", "next", "c")
+            .build();
+        machine.post("next");
+        machine.sender().post("next");
+        assert_eq!(machine.state(), &"a");
+        assert_eq!(machine.update(&mut ()).unwrap(), 2);
+        assert_eq!(machine.state(), &"c");
+        let path: Vec<_> = machine.history().iter().map(|t| t.to).collect();
+        assert_eq!(path, ["b", "c"]);
+    }
+}
@@ -0,0 +1,116 @@
+use glam::{Vec2, Vec4};
+
+use crate::{
+    overlay::Overlay,
+    resources::{
+        camera::{CameraBinding, OrthoCamera},
+        geometry::GeometryPipeline,
+    },
+    shape::{tessellate_polyline, Bounds},
+    viewport::{ViewRect, Viewport},
+};
+
+/// Size of the minimap in logical pixels.
+pub const MINIMAP_SIZE: Vec2 = Vec2::new(200.0, 150.0);
+/// Distance in pixels from the minimap to the edges of the window.
+const MINIMAP_MARGIN: f32 = 10.0;
+/// Space in pixels left around the drawing inside the minimap.
+const MINIMAP_PADDING: f32 = 8.0;
+pub const VIEW_INDICATOR_COLOR: Vec4 = Vec4::new(0.9, 0.9, 0.9, 1.0);
+
+/// A small view of the whole drawing in the bottom left corner of the
+/// window, with a rectangle showing what the main camera can see.
+pub struct Minimap {
+    viewport: Viewport,
+    overlay: Overlay,
+    visible: bool,
+}
+
+impl Minimap {
+    pub fn new(
+        camera_binding: CameraBinding,
+        geometry_pipeline: &GeometryPipeline,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Self {
+        Self {
+            viewport: Viewport::new(
+                ViewRect::new(Vec2::ZERO, MINIMAP_SIZE),
+                camera_binding,
+                queue,
+            ),
+            overlay: Overlay::new(geometry_pipeline, device),
+            visible: false,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn viewport(&self) -> &Viewport {
+        &self.viewport
+    }
+
+    /// Whether a position in logical window pixels is over the minimap.
+    pub fn contains(&self, screen: Vec2) -> bool {
+        self.visible && self.viewport.rect().contains(screen)
+    }
+
+    /// Places the minimap in a window of `window_size` logical pixels and
+    /// fits `bounds`, the extent of the drawing, along with what `camera`
+    /// can see.
+    pub fn prepare(
+        &mut self,
+        window_size: Vec2,
+        bounds: Option<Bounds>,
+        camera: &OrthoCamera,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        if !self.visible {
+            return;
+        }
+        let rect = ViewRect::new(
+            Vec2::new(
+                MINIMAP_MARGIN,
+                window_size.y - MINIMAP_SIZE.y - MINIMAP_MARGIN,
+            ),
+            MINIMAP_SIZE,
+        );
+        if rect != self.viewport.rect() {
+            self.viewport.set_rect(rect, queue);
+        }
+
+        let size = camera.size();
+        let corners = [
+            Vec2::ZERO,
+            Vec2::new(size.x, 0.0),
+            size,
+            Vec2::new(0.0, size.y),
+        ]
+        .map(|corner| camera.screen_to_world(corner));
+        let view = Bounds::from_points(corners).unwrap_or(Bounds::new(Vec2::ZERO, Vec2::ZERO));
+        let all = bounds.map_or(view, |b| b.union(&view));
+        let room = (MINIMAP_SIZE - 2.0 * MINIMAP_PADDING).max(Vec2::ONE);
+        let size = all.size().max(Vec2::splat(f32::EPSILON));
+        let zoom = (room / size).min_element();
+        self.viewport.update_camera(queue, |camera| {
+            camera.set_zoom(zoom);
+            camera.set_center(all.center());
+        });
+
+        let mut batch = self.overlay.begin(device, queue);
+        tessellate_polyline(&corners, true, VIEW_INDICATOR_COLOR, &mut batch);
+    }
+
+    /// Draws the view indicator. The drawing itself is drawn by the caller
+    /// with the minimap's [Viewport].
+    pub fn draw_indicator(&self, pass: &mut wgpu::RenderPass<'_>, pipeline: &GeometryPipeline) {
+        self.overlay.draw(pass, pipeline, self.viewport.binding());
+    }
+}
@@ -0,0 +1,56 @@
+use crate::resources::{
+    buffer::{BackedBuffer, IndexedBatch},
+    camera::CameraBinding,
+    geometry::{ColoredVertex, GeometryBuffer, GeometryPipeline, LayerBinding},
+    instance::InstanceTransform,
+};
+
+/// Immediate mode geometry drawn on top of the scene. Tools use this for
+/// things like selection handles and previews. The contents are rebuilt
+/// every frame.
+pub struct Overlay {
+    geometry: GeometryBuffer,
+    binding: LayerBinding,
+    instances: BackedBuffer<InstanceTransform>,
+}
+
+impl Overlay {
+    pub fn new(pipeline: &GeometryPipeline, device: &wgpu::Device) -> Self {
+        Self {
+            geometry: GeometryBuffer::new(device),
+            binding: pipeline.bind_layer(device, 1.0),
+            instances: BackedBuffer::with_data(
+                device,
+                vec![InstanceTransform::IDENTITY],
+                wgpu::BufferUsages::VERTEX,
+            ),
+        }
+    }
+
+    /// Clears the overlay and returns a batch to write this frame's
+    /// geometry into. Coordinates are in world space.
+    pub fn begin<'a>(
+        &'a mut self,
+        device: &'a wgpu::Device,
+        queue: &'a wgpu::Queue,
+    ) -> IndexedBatch<'a, ColoredVertex> {
+        self.geometry.clear();
+        self.geometry.batch(device, queue)
+    }
+
+    pub fn draw(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        pipeline: &GeometryPipeline,
+        camera_binding: &CameraBinding,
+    ) {
+        pipeline.draw_lines(
+            pass,
+            &self.geometry,
+            &self.instances,
+            &self.binding,
+            camera_binding,
+            [(0..self.geometry.num_indices(), 0)],
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use glam::{vec2, Vec2};
+
+use crate::{
+    clock::FrameClock,
+    renderer::Renderer,
+    resources::{
+        buffer::BackedBuffer, camera::CameraBinding, font::TextBuffer, instance::InstanceTransform,
+    },
+    stats::RenderStats,
+};
+
+/// Height of the performance overlay's text in logical pixels.
+pub const PERF_TEXT_SIZE: f32 = 14.0;
+/// Where the performance overlay's first line starts in logical pixels.
+const PERF_POSITION: Vec2 = vec2(10.0, 10.0);
+const PERF_LINES: usize = 5;
+
+/// Frame rate, frame times and what the last frame asked of the GPU, in the
+/// top left corner of the window. Only updated while it's visible.
+pub struct PerfOverlay {
+    visible: bool,
+    lines: Vec<TextBuffer>,
+    instances: BackedBuffer<InstanceTransform>,
+}
+
+impl PerfOverlay {
+    pub fn new(renderer: &Renderer, device: &wgpu::Device) -> anyhow::Result<Self> {
+        let font = renderer.font();
+        let lines = (0..PERF_LINES)
+            .map(|_| renderer.text_pipeline().buffer_text(font, device, "----"))
+            .collect::<anyhow::Result<_>>()?;
+        let line_height = font.line_height(PERF_TEXT_SIZE);
+        let transforms = (0..PERF_LINES)
+            .map(|i| {
+                glam::Affine2::from_scale_angle_translation(
+                    Vec2::splat(font.scale(PERF_TEXT_SIZE)),
+                    0.0,
+                    PERF_POSITION + vec2(0.0, i as f32 * line_height),
+                )
+                .into()
+            })
+            .collect();
+        Ok(Self {
+            visible: false,
+            lines,
+            instances: BackedBuffer::with_data(device, transforms, wgpu::BufferUsages::VERTEX),
+        })
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    /// Rewrites the text with the latest numbers.
+    pub fn update(
+        &mut self,
+        clock: &FrameClock,
+        stats: RenderStats,
+        renderer: &Renderer,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<()> {
+        if !self.visible {
+            return Ok(());
+        }
+        let frames = clock.stats();
+        let ms = |p: f32| frames.percentile(p).as_secs_f64() * 1000.0;
+        let text = [
+            format!("FPS: {:.1}", frames.fps()),
+            format!(
+                "Frame: {:.2} ms (p50 {:.2}, p95 {:.2}, p99 {:.2})",
+                frames.average().as_secs_f64() * 1000.0,
+                ms(50.0),
+                ms(95.0),
+                ms(99.0),
+            ),
+            format!(
+                "Draw calls: {}, texture binds: {}",
+                stats.draw_calls, stats.texture_binds
+            ),
+            format!(
+                "Vertices: {}, instances: {}",
+                stats.vertices, stats.instances
+            ),
+            format!(
+                "Uploads: {} ({:.1} KiB)",
+                stats.buffer_uploads,
+                stats.upload_bytes as f64 / 1024.0
+            ),
+        ];
+        for (line, text) in self.lines.iter_mut().zip(&text) {
+            renderer
+                .text_pipeline()
+                .update_text(renderer.font(), text, line, device, queue)?;
+        }
+        Ok(())
+    }
+
+    /// Draws the overlay with a camera that's in logical window pixels.
+    pub fn draw(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        renderer: &Renderer,
+        camera_binding: &CameraBinding,
+    ) {
+        if !self.visible {
+            return;
+        }
+        for (i, line) in self.lines.iter().enumerate() {
+            renderer.text_pipeline().draw_text(
+                pass,
+                line,
+                &self.instances,
+                i as u32,
+                camera_binding,
+            );
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use glam::{Affine2, Vec2};
+
+use crate::{
+    layers::LayerStack,
+    scene::{NodeId, Scene},
+    shape::{distance_to_segment, point_in_polygon, Bounds, Shape},
+};
+
+/// Result of a successful pick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    pub layer: usize,
+    pub node: NodeId,
+    /// Distance from the pick point to the shape in world units. Zero when
+    /// the point is inside a filled shape.
+    pub distance: f32,
+}
+
+/// Finds the topmost shape under `point` (in world space). Hidden layers are
+/// skipped. `tolerance` is how far away from a stroke a point may be and
+/// still count as a hit.
+pub fn pick(layers: &mut LayerStack, point: Vec2, tolerance: f32) -> Option<Hit> {
+    let num_layers = layers.len();
+    for layer_index in (0..num_layers).rev() {
+        let layer = layers.get_mut(layer_index)?;
+        if !layer.is_visible() {
+            continue;
+        }
+
+        let scene = layer.scene_mut();
+        let ids: Vec<NodeId> = scene.iter().map(|(id, _)| id).collect();
+        for id in ids.into_iter().rev() {
+            let world = scene.world_transform(id);
+            let Some(shape) = scene.get(id).and_then(|n| n.shape()) else {
+                continue;
+            };
+            if let Some(distance) = hit_test(shape, &world, point, tolerance) {
+                return Some(Hit {
+                    layer: layer_index,
+                    node: id,
+                    distance,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Tests a single shape with the given world transform, returning the
+/// distance to it if `point` is within `tolerance`.
+pub fn hit_test(shape: &Shape, world: &Affine2, point: Vec2, tolerance: f32) -> Option<f32> {
+    let inverse = world.inverse();
+    let local = inverse.transform_point2(point);
+
+    if let Some(d) = shape.sdf(local) {
+        // Distances in local space get stretched by the transform, so use the
+        // smaller scale axis to stay conservative.
+        let scale = world
+            .matrix2
+            .x_axis
+            .length()
+            .min(world.matrix2.y_axis.length());
+        let d = d * scale;
+        return (d <= tolerance).then_some(d.max(0.0));
+    }
+
+    let outline = shape.outline();
+    if shape.is_closed() && outline.len() > 2 && point_in_polygon(local, &outline) {
+        return Some(0.0);
+    }
+
+    let world_points: Vec<Vec2> = outline.iter().map(|&p| world.transform_point2(p)).collect();
+    let mut best = f32::INFINITY;
+    for segment in world_points.windows(2) {
+        best = best.min(distance_to_segment(point, segment[0], segment[1]));
+    }
+    if shape.is_closed() && world_points.len() > 2 {
+        best = best.min(distance_to_segment(
+            point,
+            world_points[world_points.len() - 1],
+            world_points[0],
+        ));
+    }
+    if world_points.len() == 1 {
+        best = world_points[0].distance(point);
+    }
+
+    (best <= tolerance).then_some(best)
+}
+
+/// An area in world space dragged out to select everything inside it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Region {
+    Rect(Bounds),
+    /// A freeform outline, closed back to its first point.
+    Lasso(Vec<Vec2>),
+}
+
+impl Region {
+    pub fn contains(&self, point: Vec2) -> bool {
+        match self {
+            Self::Rect(bounds) => bounds.contains(point),
+            Self::Lasso(points) => points.len() > 2 && point_in_polygon(point, points),
+        }
+    }
+}
+
+/// Finds the root nodes lying entirely inside `region`, bottom layer first.
+/// Rectangles test the bounds of each node, lassos test every vertex.
+/// Hidden layers and nodes without shapes are skipped.
+pub fn pick_region(layers: &mut LayerStack, region: &Region) -> Vec<(usize, NodeId)> {
+    let mut picked = Vec::new();
+    for layer_index in 0..layers.len() {
+        let Some(layer) = layers.get_mut(layer_index) else {
+            continue;
+        };
+        if !layer.is_visible() {
+            continue;
+        }
+
+        let scene = layer.scene_mut();
+        for root in scene.roots().to_vec() {
+            let inside = match region {
+                Region::Rect(rect) => scene
+                    .world_bounds(root)
+                    .is_some_and(|b| rect.contains(b.min) && rect.contains(b.max)),
+                Region::Lasso(_) => {
+                    let points = world_points(scene, root);
+                    !points.is_empty() && points.iter().all(|&p| region.contains(p))
+                }
+            };
+            if inside {
+                picked.push((layer_index, root));
+            }
+        }
+    }
+    picked
+}
+
+/// World space outline points of every shape in the subtree at `id`.
+fn world_points(scene: &mut Scene, id: NodeId) -> Vec<Vec2> {
+    let mut points = Vec::new();
+    let mut stack = vec![id];
+    while let Some(id) = stack.pop() {
+        let world = scene.world_transform(id);
+        let Some(node) = scene.get(id) else {
+            continue;
+        };
+        stack.extend(node.children().iter().copied());
+        if let Some(shape) = node.shape() {
+            points.extend(shape.outline().iter().map(|&p| world.transform_point2(p)));
+        }
+    }
+    points
+}
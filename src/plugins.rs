@@ -0,0 +1,155 @@
+use crate::{
+    graph::GraphSurface,
+    renderer::Renderer,
+    resources::{camera::CameraBinding, depth::DepthBuffer},
+};
+
+/// When a [RenderPlugin] runs, right after the canvas's own pass of the
+/// same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PassStage {
+    /// After the grid, under the drawing.
+    Background,
+    /// After the drawing.
+    Scene,
+    /// After tool overlays, dimensions and viewports.
+    Overlay,
+    /// After the performance overlay and app states. The last stage that's
+    /// post-processed.
+    Hud,
+    /// After post-processing, drawing straight into the window.
+    Post,
+}
+
+/// Custom drawing that runs every frame as part of `Canvas::render`.
+pub trait RenderPlugin {
+    fn render(&mut self, context: &mut PluginContext<'_, '_>);
+}
+
+/// What a [RenderPlugin] gets to draw with.
+pub struct PluginContext<'a, 'b> {
+    pub encoder: &'b mut wgpu::CommandEncoder,
+    surface: &'b GraphSurface<'a>,
+    pub device: &'b wgpu::Device,
+    pub queue: &'b wgpu::Queue,
+    /// Pipelines that draw into the frame need its format and sample count.
+    pub renderer: &'b Renderer,
+    /// The canvas's camera.
+    pub camera: &'b CameraBinding,
+    /// A camera in logical window pixels, with the origin in the top left.
+    pub screen: &'b CameraBinding,
+}
+
+impl<'a, 'b> PluginContext<'a, 'b> {
+    pub fn new(
+        encoder: &'b mut wgpu::CommandEncoder,
+        surface: &'b GraphSurface<'a>,
+        device: &'b wgpu::Device,
+        queue: &'b wgpu::Queue,
+        renderer: &'b Renderer,
+        camera: &'b CameraBinding,
+        screen: &'b CameraBinding,
+    ) -> Self {
+        Self {
+            encoder,
+            surface,
+            device,
+            queue,
+            renderer,
+            camera,
+            screen,
+        }
+    }
+
+    /// Where this stage draws to. Passes should use this instead of the
+    /// surface, which may be behind a multisampled texture or the
+    /// post-processing chain.
+    pub fn color_attachment(
+        &self,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'a> {
+        (self.surface.color)(load)
+    }
+
+    /// The frame's depth buffer, if depth ordering is on. Not available in
+    /// [PassStage::Post].
+    pub fn depth(&self) -> Option<&'a DepthBuffer> {
+        self.surface.depth
+    }
+
+    /// Starts a pass that draws over what's there so far.
+    pub fn begin_pass(&mut self, label: &str) -> wgpu::RenderPass<'_> {
+        let color = self.color_attachment(wgpu::LoadOp::Load);
+        self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(color)],
+            ..Default::default()
+        })
+    }
+}
+
+/// Handle to a plugin added with [PluginRegistry::register].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PluginId(u64);
+
+struct Registered {
+    id: PluginId,
+    stage: PassStage,
+    order: i32,
+    plugin: Box<dyn RenderPlugin>,
+}
+
+/// The [RenderPlugin]s the canvas runs, by stage and then by order.
+#[derive(Default)]
+pub struct PluginRegistry {
+    plugins: Vec<Registered>,
+    next_id: u64,
+}
+
+impl PluginRegistry {
+    /// Runs `plugin` every frame in `stage`. Plugins in the same stage run
+    /// from the lowest `order` up, and in the order they were registered
+    /// when that's the same.
+    pub fn register(
+        &mut self,
+        stage: PassStage,
+        order: i32,
+        plugin: impl RenderPlugin + 'static,
+    ) -> PluginId {
+        let id = PluginId(self.next_id);
+        self.next_id += 1;
+        let index = self
+            .plugins
+            .partition_point(|p| (p.stage, p.order) <= (stage, order));
+        self.plugins.insert(
+            index,
+            Registered {
+                id,
+                stage,
+                order,
+                plugin: Box::new(plugin),
+            },
+        );
+        id
+    }
+
+    /// Returns false if there's no plugin with `id`.
+    pub fn remove(&mut self, id: PluginId) -> bool {
+        let count = self.plugins.len();
+        self.plugins.retain(|p| p.id != id);
+        self.plugins.len() != count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    /// The plugins for each stage, in [PassStage] order.
+    pub fn stages_mut(&mut self) -> [Vec<&mut dyn RenderPlugin>; 5] {
+        let mut stages: [Vec<&mut dyn RenderPlugin>; 5] = Default::default();
+        for registered in &mut self.plugins {
+            stages[registered.stage as usize].push(registered.plugin.as_mut());
+        }
+        stages
+    }
+}
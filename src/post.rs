@@ -0,0 +1,428 @@
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::{resources::target::RenderTarget, stats, utils::RenderPipelineBuilder};
+
+/// How many effects can be chained. The rest are skipped.
+pub const MAX_POST_EFFECTS: usize = 8;
+
+/// Whether `format` is one of the HDR surface formats: extended linear
+/// sRGB in half floats, or 10 bit HDR10.
+pub fn is_hdr(format: wgpu::TextureFormat) -> bool {
+    matches!(
+        format,
+        wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::Rgb10a2Unorm
+    )
+}
+
+/// How SDR colors are shown on an HDR surface. Brightnesses are in nits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tonemap {
+    /// How bright white is.
+    pub paper_white: f32,
+    /// How bright the display gets. Anything brighter than white, like
+    /// bloom, rolls off towards this.
+    pub max_luminance: f32,
+}
+
+impl Default for Tonemap {
+    fn default() -> Self {
+        // The reference white for mixing SDR into HDR from ITU-R BT.2408.
+        Self {
+            paper_white: 203.0,
+            max_luminance: 1000.0,
+        }
+    }
+}
+
+impl Tonemap {
+    fn uniform(&self, hdr10: bool) -> PostUniform {
+        PostUniform {
+            texel_size: glam::Vec2::ZERO,
+            strength: self.paper_white.max(1.0),
+            radius: self.max_luminance.max(self.paper_white),
+            threshold: if hdr10 { 1.0 } else { 0.0 },
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+/// A fullscreen pass that reads what's been drawn so far and redraws it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostEffect {
+    /// Smooths jagged edges.
+    Fxaa,
+    /// Darkens the corners by up to `strength`, from 0 to 1. The darkening
+    /// starts `radius` of the way from the center to the corners.
+    Vignette { strength: f32, radius: f32 },
+    /// Makes colors brighter than `threshold` glow about `radius` pixels
+    /// out, `intensity` times as bright.
+    Bloom {
+        threshold: f32,
+        intensity: f32,
+        radius: f32,
+    },
+}
+
+impl PostEffect {
+    fn uniform(&self, texel_size: glam::Vec2) -> PostUniform {
+        let (strength, radius, threshold) = match *self {
+            Self::Fxaa => (0.0, 0.0, 0.0),
+            // Past 1 the smoothstep in the shader would be flipped.
+            Self::Vignette { strength, radius } => (strength, radius.clamp(0.0, 0.99), 0.0),
+            Self::Bloom {
+                threshold,
+                intensity,
+                radius,
+            } => (intensity, radius, threshold),
+        };
+        PostUniform {
+            texel_size,
+            strength,
+            radius,
+            threshold,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostUniform {
+    texel_size: glam::Vec2,
+    strength: f32,
+    radius: f32,
+    threshold: f32,
+    _padding: [f32; 3],
+}
+
+/// A pipeline per kind of pass.
+struct PostPipelines {
+    fxaa: wgpu::RenderPipeline,
+    vignette: wgpu::RenderPipeline,
+    bloom: wgpu::RenderPipeline,
+    tonemap: wgpu::RenderPipeline,
+}
+
+impl PostPipelines {
+    fn new(
+        pipeline_layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<Self> {
+        let pipeline = |label, entry_point| {
+            RenderPipelineBuilder::new()
+                .label(label)
+                .layout(pipeline_layout)
+                .vertex(wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some("fullscreen_quad"),
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                })
+                .fragment(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some(entry_point),
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                })
+                .build(device)
+        };
+        Ok(Self {
+            fxaa: pipeline("PostProcessor::fxaa", "post_fxaa")?,
+            vignette: pipeline("PostProcessor::vignette", "post_vignette")?,
+            bloom: pipeline("PostProcessor::bloom", "post_bloom")?,
+            tonemap: pipeline("PostProcessor::tonemap", "post_tonemap")?,
+        })
+    }
+}
+
+/// Draws the frame into a [RenderTarget] and then runs it through a chain
+/// of [PostEffect]s on its way to the surface. Effects ping-pong between two
+/// more targets, and the last one draws straight into the surface. On HDR
+/// surfaces a [Tonemap] runs after them.
+// The layout and format are only kept for reloading the effects, which
+// wasm can't do.
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+pub struct PostProcessor {
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    /// One [PostUniform] per effect and one for the tonemap, read with
+    /// dynamic offsets.
+    uniforms: wgpu::Buffer,
+    uniform_stride: u32,
+    pipeline_layout: wgpu::PipelineLayout,
+    format: wgpu::TextureFormat,
+    pipelines: PostPipelines,
+    /// Whether the output is HDR10 rather than scRGB.
+    hdr10: bool,
+    scene: RenderTarget,
+    ping: RenderTarget,
+    pong: RenderTarget,
+    /// Reading from `scene`, `ping` and `pong`.
+    bind_groups: [wgpu::BindGroup; 3],
+    effects: Vec<PostEffect>,
+    tonemap: Option<Tonemap>,
+}
+
+impl PostProcessor {
+    /// `shader` is the one with the `fullscreen_quad` entry point. The
+    /// frame is drawn with `sample_count` samples per pixel and processed
+    /// with one.
+    pub fn new(
+        shader: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        width: u32,
+        height: u32,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<Self> {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("PostProcessor::layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(size_of::<PostUniform>() as _),
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("PostProcessor::sampler"),
+            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let uniform_stride = (size_of::<PostUniform>() as u32)
+            .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment);
+        let uniforms = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("PostProcessor::uniforms"),
+            contents: &vec![0; uniform_stride as usize * (MAX_POST_EFFECTS + 1)],
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("PostProcessor::pipeline_layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipelines = PostPipelines::new(&pipeline_layout, shader, format, device)?;
+
+        let scene = RenderTarget::new(width, height, format, sample_count, device);
+        let ping = RenderTarget::new(width, height, format, 1, device);
+        let pong = RenderTarget::new(width, height, format, 1, device);
+        let bind_groups = Self::bind(&layout, &sampler, &uniforms, [&scene, &ping, &pong], device);
+
+        Ok(Self {
+            layout,
+            sampler,
+            uniforms,
+            uniform_stride,
+            pipeline_layout,
+            format,
+            pipelines,
+            hdr10: format == wgpu::TextureFormat::Rgb10a2Unorm,
+            scene,
+            ping,
+            pong,
+            bind_groups,
+            effects: Vec::new(),
+            // The scene is drawn as SDR either way, so HDR surfaces always
+            // need one.
+            tonemap: is_hdr(format).then(Tonemap::default),
+        })
+    }
+
+    /// Rebuilds the pipelines from an edited `shader`. Keeps the old ones
+    /// if that fails.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload(
+        &mut self,
+        shader: &wgpu::ShaderModule,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<()> {
+        self.pipelines = crate::utils::validated(device, || {
+            PostPipelines::new(&self.pipeline_layout, shader, self.format, device)
+        })??;
+        Ok(())
+    }
+
+    fn bind(
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        uniforms: &wgpu::Buffer,
+        targets: [&RenderTarget; 3],
+        device: &wgpu::Device,
+    ) -> [wgpu::BindGroup; 3] {
+        targets.map(|target| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("PostProcessor::bind_group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::TextureView(target.view()),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 5,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: uniforms,
+                            offset: 0,
+                            size: wgpu::BufferSize::new(size_of::<PostUniform>() as _),
+                        }),
+                    },
+                ],
+            })
+        })
+    }
+
+    /// Recreates the targets if they don't match the new size.
+    pub fn resize(&mut self, width: u32, height: u32, device: &wgpu::Device) {
+        if self.scene.size() == glam::uvec2(width.max(1), height.max(1)) {
+            return;
+        }
+        self.scene.resize(width, height, device);
+        self.ping.resize(width, height, device);
+        self.pong.resize(width, height, device);
+        self.bind_groups = Self::bind(
+            &self.layout,
+            &self.sampler,
+            &self.uniforms,
+            [&self.scene, &self.ping, &self.pong],
+            device,
+        );
+    }
+
+    pub fn effects(&self) -> &[PostEffect] {
+        &self.effects
+    }
+
+    /// Replaces the chain. Effects run in order, and can be changed every
+    /// frame.
+    pub fn set_effects(&mut self, effects: Vec<PostEffect>) {
+        if effects.len() > MAX_POST_EFFECTS {
+            log::warn!(
+                "Only the first {MAX_POST_EFFECTS} of {} post effects are used",
+                effects.len()
+            );
+        }
+        self.effects = effects;
+    }
+
+    pub fn tonemap(&self) -> Option<&Tonemap> {
+        self.tonemap.as_ref()
+    }
+
+    /// Changes how the frame is tonemapped. Ignored unless drawing to an
+    /// HDR surface.
+    pub fn set_tonemap(&mut self, tonemap: Tonemap) {
+        if let Some(current) = &mut self.tonemap {
+            *current = tonemap;
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.effects.is_empty() || self.tonemap.is_some()
+    }
+
+    /// Where the frame should be drawn for the effects to read it.
+    pub fn scene(&self) -> &RenderTarget {
+        &self.scene
+    }
+
+    /// Runs the effects on what's been drawn into [PostProcessor::scene],
+    /// and the tonemap if there is one, drawing the result into `output`.
+    /// Does nothing without either.
+    pub fn apply(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        queue: &wgpu::Queue,
+    ) {
+        let texel_size = 1.0 / self.scene.size().as_vec2();
+        let passes: Vec<_> = self.effects[..self.effects.len().min(MAX_POST_EFFECTS)]
+            .iter()
+            .map(|effect| {
+                let pipeline = match effect {
+                    PostEffect::Fxaa => &self.pipelines.fxaa,
+                    PostEffect::Vignette { .. } => &self.pipelines.vignette,
+                    PostEffect::Bloom { .. } => &self.pipelines.bloom,
+                };
+                (pipeline, effect.uniform(texel_size))
+            })
+            .chain(
+                self.tonemap
+                    .map(|tonemap| (&self.pipelines.tonemap, tonemap.uniform(self.hdr10))),
+            )
+            .collect();
+        for (i, (_, uniform)) in passes.iter().enumerate() {
+            stats::write_buffer(
+                queue,
+                &self.uniforms,
+                (i as u32 * self.uniform_stride) as _,
+                bytemuck::bytes_of(uniform),
+            );
+        }
+
+        for (i, (pipeline, _)) in passes.iter().enumerate() {
+            // Reads from the scene first, then whichever of ping and pong
+            // was written last.
+            let input = if i == 0 { 0 } else { 1 + (i - 1) % 2 };
+            let view = if i + 1 == passes.len() {
+                output
+            } else if i % 2 == 0 {
+                self.ping.view()
+            } else {
+                self.pong.view()
+            };
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("PostProcessor::pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(
+                0,
+                &self.bind_groups[input],
+                &[i as u32 * self.uniform_stride],
+            );
+            stats::draw(&mut pass, 0..3, 0..1);
+        }
+    }
+}
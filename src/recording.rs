@@ -0,0 +1,178 @@
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::mpsc::{self, SyncSender, TrySendError},
+    thread::{self, JoinHandle},
+};
+
+use image::{codecs::gif::GifEncoder, Delay, Frame, RgbaImage};
+
+use crate::export::{FrameCapture, FrameCaptures};
+
+/// How many frames can be copying back from the GPU at once.
+const MAX_FRAMES_IN_FLIGHT: usize = 3;
+/// How many copied frames can wait to be written before frames are skipped.
+const MAX_FRAMES_QUEUED: usize = 8;
+
+/// What a [FrameRecorder] writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingFormat {
+    /// Numbered PNGs in a directory.
+    PngSequence,
+    /// One animated GIF. Slow to encode for large windows.
+    Gif,
+}
+
+/// Captures consecutive frames drawn to the window and writes them on a
+/// thread of its own. When the GPU or the writer falls behind, frames are
+/// skipped instead of holding up the window. Dropping the recorder waits
+/// for the frames it captured to be written, so files aren't left cut
+/// short.
+pub struct FrameRecorder {
+    remaining: u32,
+    captures: VecDeque<FrameCapture>,
+    /// `None` once the writer has been told there's nothing more coming.
+    frames: Option<SyncSender<RgbaImage>>,
+    writer: Option<JoinHandle<()>>,
+    skipped: u32,
+}
+
+impl FrameRecorder {
+    /// Records `count` frames to `path`, which is a directory for PNGs and a
+    /// file for GIFs. GIFs play back at `fps` frames a second.
+    pub fn new(
+        path: impl Into<PathBuf>,
+        format: RecordingFormat,
+        count: u32,
+        fps: u32,
+    ) -> anyhow::Result<Self> {
+        let path = path.into();
+        anyhow::ensure!(fps > 0, "Recordings need at least one frame a second");
+        let (frames, received) = mpsc::sync_channel(MAX_FRAMES_QUEUED);
+        let write = match format {
+            RecordingFormat::PngSequence => {
+                std::fs::create_dir_all(&path)?;
+                let path = path.clone();
+                Box::new(move || write_png_sequence(&path, received))
+                    as Box<dyn FnOnce() -> anyhow::Result<u32> + Send>
+            }
+            RecordingFormat::Gif => {
+                let file = std::fs::File::create(&path)?;
+                Box::new(move || write_gif(file, fps, received))
+            }
+        };
+        let writer =
+            thread::Builder::new()
+                .name("FrameRecorder".into())
+                .spawn(move || match write() {
+                    Ok(written) => log::info!("Recorded {written} frames to {}", path.display()),
+                    Err(e) => log::error!("Unable to record to {}: {e}", path.display()),
+                })?;
+        Ok(Self {
+            remaining: count,
+            captures: VecDeque::new(),
+            frames: Some(frames),
+            writer: Some(writer),
+            skipped: 0,
+        })
+    }
+
+    /// Hands finished captures to the writer and asks for the next frame.
+    /// Called once a frame, before it's drawn.
+    pub fn update(&mut self, captures: &mut FrameCaptures) {
+        while let Some(result) = self.captures.front_mut().and_then(FrameCapture::try_image) {
+            self.captures.pop_front();
+            let image = match result {
+                Ok(image) => image,
+                Err(e) => {
+                    log::error!("Unable to capture a frame: {e}");
+                    self.stop();
+                    return;
+                }
+            };
+            let Some(frames) = &self.frames else {
+                continue;
+            };
+            match frames.try_send(image) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => self.skipped += 1,
+                Err(TrySendError::Disconnected(_)) => {
+                    log::error!("Frame recording stopped early");
+                    self.stop();
+                }
+            }
+        }
+
+        if self.remaining == 0 {
+            if self.captures.is_empty() {
+                self.stop();
+            }
+        } else if self.captures.len() < MAX_FRAMES_IN_FLIGHT {
+            self.captures.push_back(captures.request());
+            self.remaining -= 1;
+        } else {
+            self.skipped += 1;
+        }
+    }
+
+    /// Stops capturing. Frames already captured are still written.
+    pub fn stop(&mut self) {
+        self.remaining = 0;
+        self.captures.clear();
+        if self.frames.take().is_some() && self.skipped > 0 {
+            log::warn!("Skipped {} frames to keep up", self.skipped);
+        }
+    }
+
+    /// Whether frames are still being captured. Writing them can take a
+    /// while longer.
+    pub fn is_recording(&self) -> bool {
+        self.frames.is_some()
+    }
+
+    /// Whether captured frames are still being written.
+    pub fn is_writing(&self) -> bool {
+        self.writer
+            .as_ref()
+            .is_some_and(|writer| !writer.is_finished())
+    }
+}
+
+impl Drop for FrameRecorder {
+    fn drop(&mut self) {
+        self.stop();
+        if let Some(writer) = self.writer.take() {
+            if writer.join().is_err() {
+                log::error!("The frame writer panicked");
+            }
+        }
+    }
+}
+
+fn write_png_sequence(
+    directory: &std::path::Path,
+    frames: mpsc::Receiver<RgbaImage>,
+) -> anyhow::Result<u32> {
+    let mut written = 0;
+    for frame in frames {
+        frame.save(directory.join(format!("frame_{written:05}.png")))?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+fn write_gif(
+    file: std::fs::File,
+    fps: u32,
+    frames: mpsc::Receiver<RgbaImage>,
+) -> anyhow::Result<u32> {
+    let mut encoder = GifEncoder::new(std::io::BufWriter::new(file));
+    encoder.set_repeat(image::codecs::gif::Repeat::Infinite)?;
+    let delay = Delay::from_numer_denom_ms(1000, fps);
+    let mut written = 0;
+    for frame in frames {
+        encoder.encode_frame(Frame::from_parts(frame, 0, 0, delay))?;
+        written += 1;
+    }
+    Ok(written)
+}
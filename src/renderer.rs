@@ -0,0 +1,380 @@
+use glam::{Vec2, Vec4};
+
+use crate::{
+    draw_list::DrawList,
+    layers::LayerStack,
+    overlay::Overlay,
+    resources::{
+        buffer::BackedBuffer,
+        camera::{CameraBinder, CameraBinding, OrthoCamera},
+        geometry::GeometryPipeline,
+        texture::{TextureId, TextureRegistry},
+    },
+    shape::tessellate_polyline,
+    sprite::{Sprite, SpriteRenderer},
+    wgsl,
+};
+
+#[cfg(feature = "text")]
+use crate::resources::{
+    cache::Handle,
+    font::{Font, TextBlock, TextPipeline},
+    Resources,
+};
+#[cfg(feature = "shapes")]
+use crate::sdf::SdfPipeline;
+
+/// The font [Renderer::load] uses, relative to the resource directory.
+#[cfg(feature = "text")]
+pub const DEFAULT_FONT_PATH: &str = "OpenSans MSDF.zip";
+/// Drawn in place of characters the font doesn't have.
+#[cfg(feature = "text")]
+pub const FALLBACK_CHAR: char = '�';
+
+/// The pipelines and resources for drawing shapes and text with wgpu. It
+/// doesn't own a window, surface, device or queue, so it can draw into any
+/// wgpu app's render passes as long as their color target has the format
+/// it was made for.
+pub struct Renderer {
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    pub(crate) shader: wgpu::ShaderModule,
+    pub(crate) camera_binder: CameraBinder,
+    #[cfg(feature = "text")]
+    pub(crate) font: Handle<Font>,
+    #[cfg(feature = "text")]
+    pub(crate) text_pipeline: TextPipeline,
+    pub(crate) geometry_pipeline: GeometryPipeline,
+    #[cfg(feature = "shapes")]
+    pub(crate) sdf_pipeline: SdfPipeline,
+    pub(crate) sprite_renderer: SpriteRenderer,
+    pub(crate) textures: TextureRegistry,
+}
+
+impl Renderer {
+    /// Draws text with `font` into targets of `format` that have
+    /// `sample_count` samples per pixel. The font can be shared with other
+    /// renderers through a [Handle].
+    pub fn new(
+        #[cfg(feature = "text")] font: impl Into<Handle<Font>>,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<Self> {
+        #[cfg(feature = "text")]
+        let font = font.into();
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(
+                wgsl::compose_built_in(include_str!("shader.wgsl"))?.into(),
+            ),
+        });
+        let camera_binder = CameraBinder::new(device);
+        let textures = TextureRegistry::new(device);
+        #[cfg(feature = "text")]
+        let text_pipeline = TextPipeline::new(
+            &font,
+            &camera_binder,
+            format,
+            sample_count,
+            textures.layout(),
+            &shader,
+            device,
+        )?;
+        let geometry_pipeline = GeometryPipeline::new(
+            &camera_binder,
+            textures.layout(),
+            format,
+            sample_count,
+            device,
+        )?;
+        #[cfg(feature = "shapes")]
+        let sdf_pipeline = SdfPipeline::new(&camera_binder, format, sample_count, device)?;
+        let sprite_renderer = SpriteRenderer::new(
+            &camera_binder,
+            textures.layout(),
+            format,
+            sample_count,
+            device,
+        )?;
+        Ok(Self {
+            format,
+            sample_count,
+            shader,
+            camera_binder,
+            #[cfg(feature = "text")]
+            font,
+            #[cfg(feature = "text")]
+            text_pipeline,
+            geometry_pipeline,
+            #[cfg(feature = "shapes")]
+            sdf_pipeline,
+            sprite_renderer,
+            textures,
+        })
+    }
+
+    /// Uses the font at [DEFAULT_FONT_PATH] in `res`.
+    #[cfg(all(feature = "text", not(target_arch = "wasm32")))]
+    pub fn load(
+        res: &Resources,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Self> {
+        let font = Font::load(res, DEFAULT_FONT_PATH, FALLBACK_CHAR, device, queue)?;
+        Self::new(font, format, sample_count, device)
+    }
+
+    #[cfg(feature = "text")]
+    pub async fn load_async(
+        res: &Resources,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Self> {
+        let font = Font::load_async(res, DEFAULT_FONT_PATH, FALLBACK_CHAR, device, queue).await?;
+        Self::new(font, format, sample_count, device)
+    }
+
+    /// The color target format everything is drawn into, picked by
+    /// `gpu::select_format` when drawing to a window.
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    /// Samples per pixel of the targets everything is drawn into. Passes
+    /// need a color target, and a depth buffer if they use one, with this
+    /// many samples.
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// The module with the vertex and fragment entry points the built in
+    /// pipelines share.
+    pub fn shader(&self) -> &wgpu::ShaderModule {
+        &self.shader
+    }
+
+    /// Recompiles the built in shader named `file_name`, like
+    /// `"shapes.wgsl"`, from `source` and rebuilds the pipelines using it.
+    /// `source` has to have been through [wgsl::compose] already.
+    /// Returns `false` if no pipeline here uses a shader by that name. The
+    /// old pipelines are kept if the new shader doesn't compile.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload_shader(
+        &mut self,
+        file_name: &str,
+        source: &str,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<bool> {
+        match file_name {
+            "shader.wgsl" => {
+                let shader = crate::utils::validated(device, || {
+                    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                        label: Some("shader.wgsl"),
+                        source: wgpu::ShaderSource::Wgsl(source.into()),
+                    })
+                })?;
+                #[cfg(feature = "text")]
+                self.text_pipeline.reload(&shader, device)?;
+                self.shader = shader;
+            }
+            "shapes.wgsl" => self.geometry_pipeline.reload(source, device)?,
+            #[cfg(feature = "shapes")]
+            "sdf.wgsl" => self.sdf_pipeline.reload(source, device)?,
+            "sprite.wgsl" => self.sprite_renderer.reload(source, device)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    #[cfg(feature = "text")]
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    #[cfg(feature = "text")]
+    pub fn font_handle(&self) -> &Handle<Font> {
+        &self.font
+    }
+
+    /// Draws text with `font` from now on. Text buffered with the old font
+    /// has to be buffered again, with [Layer::rebuild_text](crate::layers::Layer::rebuild_text)
+    /// for layers.
+    #[cfg(feature = "text")]
+    pub fn set_font(
+        &mut self,
+        font: impl Into<Handle<Font>>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        let font = font.into();
+        self.text_pipeline.set_font(&font, device, queue);
+        self.font = font;
+    }
+
+    #[cfg(feature = "text")]
+    pub fn text_pipeline(&self) -> &TextPipeline {
+        &self.text_pipeline
+    }
+
+    pub fn geometry_pipeline(&self) -> &GeometryPipeline {
+        &self.geometry_pipeline
+    }
+
+    #[cfg(feature = "shapes")]
+    pub fn sdf_pipeline(&self) -> &SdfPipeline {
+        &self.sdf_pipeline
+    }
+
+    pub fn sprite_renderer(&self) -> &SpriteRenderer {
+        &self.sprite_renderer
+    }
+
+    pub fn camera_binder(&self) -> &CameraBinder {
+        &self.camera_binder
+    }
+
+    /// Textures that shapes can be filled with.
+    pub fn textures(&self) -> &TextureRegistry {
+        &self.textures
+    }
+
+    pub fn textures_mut(&mut self) -> &mut TextureRegistry {
+        &mut self.textures
+    }
+
+    /// Makes a binding for drawing as seen by `camera`. Update it when the
+    /// camera moves.
+    pub fn bind_camera(&self, camera: &OrthoCamera, device: &wgpu::Device) -> CameraBinding {
+        self.camera_binder.bind(device, camera)
+    }
+
+    /// An empty stack of layers to add shapes and text to.
+    pub fn layers(&self, device: &wgpu::Device) -> LayerStack {
+        LayerStack::new(&self.geometry_pipeline, device)
+    }
+
+    pub fn draw_layers(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        layers: &LayerStack,
+        camera_binding: &CameraBinding,
+    ) {
+        let mut list = DrawList::new();
+        for layer in layers.visible() {
+            layer.submit(
+                &mut list,
+                self.geometry_pipeline.shapes(),
+                #[cfg(feature = "text")]
+                &self.text_pipeline,
+                &self.textures,
+                camera_binding,
+            );
+        }
+        list.execute(pass);
+    }
+
+    /// Buffers `text` to be drawn with its top left corner at `position`,
+    /// one line under the other, `size` units high.
+    #[cfg(feature = "text")]
+    pub fn text(
+        &self,
+        text: &str,
+        position: Vec2,
+        size: f32,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<TextBlock> {
+        self.text_pipeline
+            .buffer_block(&self.font, device, text, position, size)
+    }
+
+    /// Buffers separate pieces of text, each with its own position and
+    /// size.
+    #[cfg(feature = "text")]
+    pub fn labels(
+        &self,
+        labels: &[(&str, Vec2, f32)],
+        device: &wgpu::Device,
+    ) -> anyhow::Result<TextBlock> {
+        self.text_pipeline.buffer_labels(&self.font, device, labels)
+    }
+
+    /// How wide one line of `text` is when drawn `size` units high, and how
+    /// far down from its top the baseline is.
+    #[cfg(feature = "text")]
+    pub fn text_metrics(&self, text: &str, size: f32) -> (f32, f32) {
+        (self.font.text_width(text, size), self.font.baseline(size))
+    }
+
+    #[cfg(feature = "text")]
+    pub fn draw_text(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        text: &TextBlock,
+        camera_binding: &CameraBinding,
+    ) {
+        self.text_pipeline.draw_block(pass, text, camera_binding);
+    }
+
+    /// Draws `sprites` with a texture from [Renderer::textures]. Nothing is
+    /// drawn if it's been removed.
+    pub fn draw_sprites(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        sprites: &BackedBuffer<Sprite>,
+        texture: TextureId,
+        camera_binding: &CameraBinding,
+    ) {
+        if let Some(texture) = self.textures.get(texture) {
+            self.sprite_renderer
+                .draw(pass, sprites, texture.bind_group(), camera_binding);
+        }
+    }
+
+    /// An empty overlay to tessellate lines and shapes into.
+    pub fn overlay(&self, device: &wgpu::Device) -> Overlay {
+        Overlay::new(&self.geometry_pipeline, device)
+    }
+
+    /// Buffers `polylines`, closing the ones marked closed.
+    pub fn polylines(
+        &self,
+        polylines: &[(&[Vec2], bool)],
+        color: Vec4,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Overlay {
+        let mut overlay = self.overlay(device);
+        let mut batch = overlay.begin(device, queue);
+        for (points, closed) in polylines {
+            tessellate_polyline(points, *closed, color, &mut batch);
+        }
+        drop(batch);
+        overlay
+    }
+
+    /// Buffers separate line segments.
+    pub fn lines(
+        &self,
+        lines: &[[Vec2; 2]],
+        color: Vec4,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Overlay {
+        let polylines: Vec<_> = lines.iter().map(|line| (&line[..], false)).collect();
+        self.polylines(&polylines, color, device, queue)
+    }
+
+    pub fn draw_overlay(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        overlay: &Overlay,
+        camera_binding: &CameraBinding,
+    ) {
+        overlay.draw(pass, &self.geometry_pipeline, camera_binding);
+    }
+}
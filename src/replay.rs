@@ -0,0 +1,255 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use web_time::Instant;
+use winit::{
+    dpi::PhysicalPosition,
+    event::{
+        DeviceId, ElementState, Ime, Modifiers, MouseButton, MouseScrollDelta, Touch, TouchPhase,
+        WindowEvent,
+    },
+    keyboard::ModifiersState,
+};
+
+use crate::{document::Format, input::KeyInput};
+
+/// Bumped whenever recordings change in a way older builds can't read.
+pub const RECORDING_VERSION: u32 = 1;
+
+/// A window event that can be written to a file and played back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RecordedEvent {
+    Key(KeyInput),
+    MouseInput {
+        button: MouseButton,
+        state: ElementState,
+    },
+    /// In physical pixels.
+    CursorMoved(PhysicalPosition<f64>),
+    CursorLeft,
+    MouseWheel(MouseScrollDelta),
+    Modifiers(ModifiersState),
+    Focused(bool),
+    Ime(Ime),
+    Touch {
+        id: u64,
+        phase: TouchPhase,
+        location: PhysicalPosition<f64>,
+    },
+}
+
+impl RecordedEvent {
+    /// The parts of `event` worth replaying, if it's input.
+    pub fn from_event(event: &WindowEvent) -> Option<Self> {
+        Some(match event {
+            WindowEvent::KeyboardInput { event, .. } => Self::Key(KeyInput::from_event(event)),
+            WindowEvent::MouseInput { button, state, .. } => Self::MouseInput {
+                button: *button,
+                state: *state,
+            },
+            WindowEvent::CursorMoved { position, .. } => Self::CursorMoved(*position),
+            WindowEvent::CursorLeft { .. } => Self::CursorLeft,
+            WindowEvent::MouseWheel { delta, .. } => Self::MouseWheel(*delta),
+            WindowEvent::ModifiersChanged(modifiers) => Self::Modifiers(modifiers.state()),
+            WindowEvent::Focused(focused) => Self::Focused(*focused),
+            WindowEvent::Ime(ime) => Self::Ime(ime.clone()),
+            WindowEvent::Touch(touch) => Self::Touch {
+                id: touch.id,
+                phase: touch.phase,
+                location: touch.location,
+            },
+            _ => return None,
+        })
+    }
+
+    /// The window event to play back. Keys can't be turned back into
+    /// window events, so they go through [crate::states::AppState::handle_key]
+    /// instead.
+    pub fn to_event(&self) -> Option<WindowEvent> {
+        let device_id = DeviceId::dummy();
+        Some(match self {
+            Self::Key(_) => return None,
+            Self::MouseInput { button, state } => WindowEvent::MouseInput {
+                device_id,
+                state: *state,
+                button: *button,
+            },
+            Self::CursorMoved(position) => WindowEvent::CursorMoved {
+                device_id,
+                position: *position,
+            },
+            Self::CursorLeft => WindowEvent::CursorLeft { device_id },
+            Self::MouseWheel(delta) => WindowEvent::MouseWheel {
+                device_id,
+                delta: *delta,
+                phase: TouchPhase::Moved,
+            },
+            Self::Modifiers(state) => WindowEvent::ModifiersChanged(Modifiers::from(*state)),
+            Self::Focused(focused) => WindowEvent::Focused(*focused),
+            Self::Ime(ime) => WindowEvent::Ime(ime.clone()),
+            Self::Touch {
+                id,
+                phase,
+                location,
+            } => WindowEvent::Touch(Touch {
+                device_id,
+                phase: *phase,
+                location: *location,
+                force: None,
+                id: *id,
+            }),
+        })
+    }
+}
+
+/// The input that arrived before one frame was drawn.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// Since the recording started.
+    pub time: Duration,
+    pub events: Vec<RecordedEvent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputRecording {
+    pub version: u32,
+    /// Cursor positions are in physical pixels, so replays are only exact
+    /// at the same scale factor.
+    pub scale_factor: f64,
+    /// Frames without any input are left out.
+    pub frames: Vec<RecordedFrame>,
+}
+
+impl InputRecording {
+    /// How long the recording runs until its last input.
+    pub fn duration(&self) -> Duration {
+        self.frames
+            .last()
+            .map_or(Duration::ZERO, |frame| frame.time)
+    }
+
+    pub fn serialize(&self, format: Format) -> anyhow::Result<String> {
+        Ok(match format {
+            Format::Json => serde_json::to_string_pretty(self)?,
+            Format::Ron => ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?,
+        })
+    }
+
+    pub fn parse(data: &str, format: Format) -> anyhow::Result<Self> {
+        let recording: Self = match format {
+            Format::Json => serde_json::from_str(data)?,
+            Format::Ron => ron::from_str(data)?,
+        };
+        anyhow::ensure!(
+            recording.version <= RECORDING_VERSION,
+            "Recording version {} is newer than {RECORDING_VERSION}",
+            recording.version
+        );
+        Ok(recording)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.serialize(Format::from_path(path))?)?;
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read_to_string(path)?;
+        Self::parse(&data, Format::from_path(path))
+    }
+}
+
+/// Collects input a frame at a time into an [InputRecording].
+#[derive(Debug)]
+pub struct InputRecorder {
+    started: Instant,
+    scale_factor: f64,
+    events: Vec<RecordedEvent>,
+    frames: Vec<RecordedFrame>,
+}
+
+impl InputRecorder {
+    pub fn new(scale_factor: f64) -> Self {
+        Self {
+            started: Instant::now(),
+            scale_factor,
+            events: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, event: &WindowEvent) {
+        if let Some(event) = RecordedEvent::from_event(event) {
+            self.events.push(event);
+        }
+    }
+
+    /// Closes the frame the input so far arrived in.
+    pub fn end_frame(&mut self) {
+        if self.events.is_empty() {
+            return;
+        }
+        self.frames.push(RecordedFrame {
+            time: self.started.elapsed(),
+            events: std::mem::take(&mut self.events),
+        });
+    }
+
+    /// Input since the last [InputRecorder::end_frame] is left out.
+    pub fn finish(self) -> InputRecording {
+        InputRecording {
+            version: RECORDING_VERSION,
+            scale_factor: self.scale_factor,
+            frames: self.frames,
+        }
+    }
+}
+
+/// Hands back the frames of an [InputRecording] as their time comes.
+#[derive(Debug)]
+pub struct InputReplayer {
+    recording: InputRecording,
+    started: Instant,
+    next: usize,
+}
+
+impl InputReplayer {
+    pub fn new(recording: InputRecording) -> Self {
+        Self {
+            recording,
+            started: Instant::now(),
+            next: 0,
+        }
+    }
+
+    /// Input from every frame that's due. With `step`, only the next frame
+    /// is due and the timestamps are ignored, which plays the recording
+    /// back the same way every time no matter how fast frames are drawn.
+    pub fn due(&mut self, step: bool) -> Vec<RecordedEvent> {
+        let elapsed = self.started.elapsed();
+        let mut events = Vec::new();
+        while let Some(frame) = self.recording.frames.get(self.next) {
+            if !step && frame.time > elapsed {
+                break;
+            }
+            events.extend(frame.events.iter().cloned());
+            self.next += 1;
+            if step {
+                break;
+            }
+        }
+        events
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.recording.frames.len()
+    }
+
+    pub fn recording(&self) -> &InputRecording {
+        &self.recording
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    io::{Cursor, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+
+/// A zip archive whose files can be read by name, as if they were in the
+/// directory it's mounted over.
+#[derive(Clone)]
+pub(crate) struct ZipMount {
+    /// Where the archive came from, for errors.
+    name: PathBuf,
+    archive: zip::ZipArchive<Cursor<Arc<[u8]>>>,
+    /// The files' names without a leading `./` or `/`, which is how
+    /// they're looked up, with their indices in archive order.
+    files: Arc<[(String, usize)]>,
+}
+
+impl ZipMount {
+    pub fn new(data: Vec<u8>, name: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let name = name.as_ref().to_owned();
+        let archive = zip::ZipArchive::new(Cursor::new(Arc::from(data)))
+            .with_context(|| format!("{} isn't a zip archive", name.display()))?;
+        let files = archive
+            .file_names()
+            .filter(|entry| !entry.ends_with('/'))
+            .filter_map(|entry| {
+                let index = archive.index_for_name(entry)?;
+                Some((entry_name(Path::new(entry)), index))
+            })
+            .collect::<Vec<_>>()
+            .into();
+        Ok(Self {
+            name,
+            archive,
+            files,
+        })
+    }
+
+    pub fn name(&self) -> &Path {
+        &self.name
+    }
+
+    /// The file at `path` in the archive, or `None` if it isn't in it.
+    pub fn read(&self, path: &Path) -> Option<anyhow::Result<Vec<u8>>> {
+        let entry = entry_name(path);
+        let &(_, index) = self.files.iter().find(|(name, _)| *name == entry)?;
+        // Reading needs the archive mutably, and clones share everything
+        // but where they're reading from.
+        let mut archive = self.archive.clone();
+        Some(
+            read_entry(&mut archive, index)
+                .with_context(|| format!("Unable to read {entry} in {}", self.name.display())),
+        )
+    }
+
+    /// The names of the files in the archive, as [ZipMount::read] takes
+    /// them.
+    pub fn file_names(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// The first file whose name ends with `suffix`.
+    #[cfg(feature = "text")]
+    pub fn find(&self, suffix: &str) -> Option<&str> {
+        self.file_names().find(|name| name.ends_with(suffix))
+    }
+}
+
+impl std::fmt::Debug for ZipMount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ZipMount").field(&self.name).finish()
+    }
+}
+
+fn read_entry(
+    archive: &mut zip::ZipArchive<Cursor<Arc<[u8]>>>,
+    index: usize,
+) -> anyhow::Result<Vec<u8>> {
+    let mut file = archive.by_index(index)?;
+    // The size in the header isn't trusted, as it could be anything.
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// How `path` is named in an archive, which always uses forward slashes.
+fn entry_name(path: &Path) -> String {
+    let name = path.to_string_lossy().replace('\\', "/");
+    name.trim_start_matches("./")
+        .trim_start_matches('/')
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn entries_are_found_without_their_leading_dot_slash() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        writer.start_file("./fonts/OpenSans.json", options).unwrap();
+        writer.write_all(b"{}").unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        let mount = ZipMount::new(data, "test.zip").unwrap();
+        let name = mount.file_names().next().unwrap();
+        assert_eq!(name, "fonts/OpenSans.json");
+        assert_eq!(mount.read(Path::new(name)).unwrap().unwrap(), b"{}");
+        assert!(mount.read(Path::new("./fonts/OpenSans.json")).is_some());
+    }
+}
@@ -1,5 +1,7 @@
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 
+use crate::stats;
+
 pub struct BackedBuffer<T> {
     data: Vec<T>,
     buffer: wgpu::Buffer,
@@ -45,6 +47,20 @@ impl<T: bytemuck::Pod + bytemuck::Zeroable> BackedBuffer<T> {
         self.data.len() as _
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Drops all CPU-side data. The GPU buffer is kept around so it can be
+    /// reused by the next batch.
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
     pub fn version(&self) -> u32 {
         self.version
     }
@@ -67,14 +83,13 @@ impl<T: bytemuck::Pod + bytemuck::Zeroable> BackedBuffer<T> {
         IndexedBatch::new(device, queue, self, indices)
     }
 
-    #[allow(unused)]
     pub fn slice(&self) -> wgpu::BufferSlice<'_> {
         self.buffer.slice(..)
     }
 
     pub fn update(&mut self, queue: &wgpu::Queue, mut f: impl FnMut(&mut [T])) {
         f(&mut self.data);
-        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&self.data));
+        stats::write_buffer(queue, &self.buffer, 0, bytemuck::cast_slice(&self.data));
     }
 
     pub fn buffer(&self) -> &wgpu::Buffer {
@@ -120,7 +135,8 @@ impl<'a, T: bytemuck::Pod + bytemuck::Zeroable> Drop for Batch<'a, T> {
                     usage: self.vertices.usage,
                     mapped_at_creation: false,
                 });
-                self.queue.write_buffer(
+                stats::write_buffer(
+                    self.queue,
                     &self.vertices.buffer,
                     0,
                     bytemuck::cast_slice(&self.vertices.data),
@@ -128,7 +144,8 @@ impl<'a, T: bytemuck::Pod + bytemuck::Zeroable> Drop for Batch<'a, T> {
                 self.vertices.version += 1;
             } else {
                 let offset = (self.start_vertex * size_of::<T>()) as wgpu::BufferAddress;
-                self.queue.write_buffer(
+                stats::write_buffer(
+                    self.queue,
                     &self.vertices.buffer,
                     offset,
                     bytemuck::cast_slice(&self.vertices.data[self.start_vertex..]),
@@ -171,12 +188,20 @@ impl<'a, T: bytemuck::Pod + bytemuck::Zeroable> IndexedBatch<'a, T> {
         self.vertex(b);
         self
     }
+
+    /// Appends vertices along with indices relative to the first of them.
+    pub fn mesh(&mut self, vertices: &[T], indices: &[u32]) -> &mut Self {
+        let base = self.batch.vertices.len();
+        self.indices.data.extend(indices.iter().map(|i| base + i));
+        self.batch.vertices.data.extend_from_slice(vertices);
+        self
+    }
 }
 
 impl<'a, T: bytemuck::Pod + bytemuck::Zeroable> Drop for IndexedBatch<'a, T> {
     fn drop(&mut self) {
         if self.start_index < self.indices.data.len() {
-            let size = (self.indices.data.capacity() * size_of::<T>()) as wgpu::BufferAddress;
+            let size = (self.indices.data.capacity() * size_of::<u32>()) as wgpu::BufferAddress;
             if size > self.indices.buffer.size() {
                 self.indices.buffer = self.batch.device.create_buffer(&wgpu::BufferDescriptor {
                     label: None,
@@ -184,15 +209,17 @@ impl<'a, T: bytemuck::Pod + bytemuck::Zeroable> Drop for IndexedBatch<'a, T> {
                     usage: self.indices.usage,
                     mapped_at_creation: false,
                 });
-                self.batch.queue.write_buffer(
+                stats::write_buffer(
+                    self.batch.queue,
                     &self.indices.buffer,
                     0,
                     bytemuck::cast_slice(&self.indices.data),
                 );
                 self.indices.version += 1;
             } else {
-                let offset = (self.start_index * size_of::<T>()) as wgpu::BufferAddress;
-                self.batch.queue.write_buffer(
+                let offset = (self.start_index * size_of::<u32>()) as wgpu::BufferAddress;
+                stats::write_buffer(
+                    self.batch.queue,
                     &self.indices.buffer,
                     offset,
                     bytemuck::cast_slice(&self.indices.data[self.start_index..]),
@@ -201,3 +228,117 @@ impl<'a, T: bytemuck::Pod + bytemuck::Zeroable> Drop for IndexedBatch<'a, T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{test_device, RenderPipelineBuilder};
+
+    const SHADER: &str = "
+@vertex
+fn vs_main(@location(0) position: vec2<f32>) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(position, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}
+";
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn batches_count_uploads_and_draws() {
+        let (device, queue) = test_device();
+        let mut vertices =
+            BackedBuffer::<[f32; 2]>::with_capacity(&device, 16, wgpu::BufferUsages::VERTEX);
+        let mut indices =
+            BackedBuffer::<u32>::with_capacity(&device, 16, wgpu::BufferUsages::INDEX);
+        stats::take();
+
+        vertices
+            .batch(&device, &queue)
+            .push([0.0, 0.0])
+            .push([1.0, 0.0])
+            .push([0.0, 1.0]);
+        let uploads = stats::take();
+        assert_eq!(uploads.buffer_uploads, 1);
+        assert_eq!(uploads.upload_bytes, 3 * 8);
+
+        vertices
+            .batch_indexed(&device, &queue, &mut indices)
+            .mesh(&[[1.0, 1.0], [2.0, 1.0], [2.0, 2.0]], &[0, 1, 2]);
+        assert_eq!(indices.data(), &[3, 4, 5]);
+        let uploads = stats::take();
+        assert_eq!(uploads.buffer_uploads, 2);
+        assert_eq!(uploads.upload_bytes, 3 * 8 + 3 * 4);
+
+        // Nothing new, nothing uploaded.
+        vertices.batch(&device, &queue);
+        assert_eq!(stats::take(), stats::RenderStats::default());
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = RenderPipelineBuilder::new()
+            .vertex(wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: 8,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2],
+                }],
+            })
+            .fragment(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(format.into())],
+            })
+            .build(&device)
+            .unwrap();
+        let target = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: 4,
+                    height: 4,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&Default::default());
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target,
+                    resolve_target: None,
+                    ops: Default::default(),
+                })],
+                ..Default::default()
+            });
+            pass.set_pipeline(&pipeline);
+            pass.set_vertex_buffer(0, vertices.slice());
+            pass.set_index_buffer(indices.slice(), wgpu::IndexFormat::Uint32);
+            stats::draw(&mut pass, 0..3, 0..2);
+            stats::draw_indexed(&mut pass, 0..indices.len(), 0, 0..1);
+        }
+        queue.submit([encoder.finish()]);
+
+        let draws = stats::take();
+        assert_eq!(draws.draw_calls, 2);
+        assert_eq!(draws.instances, 3);
+        assert_eq!(draws.vertices, 3 * 2 + 3);
+        assert_eq!(draws.buffer_uploads, 0);
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    ops::Deref,
+    path::{Path, PathBuf},
+    rc::{Rc, Weak},
+};
+
+#[cfg(feature = "text")]
+use super::font::Font;
+use super::{texture::Texture, Resources};
+
+/// A shared asset. Cloning it is cheap, and the asset, along with any GPU
+/// resources it owns, is freed when the last handle to it is dropped.
+pub struct Handle<T>(Rc<T>);
+
+impl<T> Handle<T> {
+    pub fn new(asset: T) -> Self {
+        Self(Rc::new(asset))
+    }
+
+    /// How many handles to the asset there are, this one included.
+    pub fn ref_count(&self) -> usize {
+        Rc::strong_count(&self.0)
+    }
+
+    /// Whether both handles are to the same asset.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Deref for Handle<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> From<T> for Handle<T> {
+    fn from(asset: T) -> Self {
+        Self::new(asset)
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Handle").field(&self.0).finish()
+    }
+}
+
+/// Loads each asset once, however many places ask for it. The cache only
+/// keeps weak references, so assets are still freed once nothing else
+/// holds a [Handle] to them, and loaded again the next time they're asked
+/// for.
+#[derive(Default)]
+pub struct AssetCache {
+    assets: HashMap<(PathBuf, TypeId), Weak<dyn Any>>,
+}
+
+impl AssetCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The asset of type `T` loaded from `path`, if there's still a handle
+    /// to it.
+    pub fn get<T: Any>(&self, path: impl AsRef<Path>) -> Option<Handle<T>> {
+        let asset = self
+            .assets
+            .get(&(path.as_ref().to_owned(), TypeId::of::<T>()))?
+            .upgrade()?;
+        asset.downcast().ok().map(Handle)
+    }
+
+    /// The asset of type `T` loaded from `path`, loading it with `load` if
+    /// there's no handle to it left.
+    pub fn get_or_load<T: Any>(
+        &mut self,
+        path: impl AsRef<Path>,
+        load: impl FnOnce(&Path) -> anyhow::Result<T>,
+    ) -> anyhow::Result<Handle<T>> {
+        let path = path.as_ref();
+        if let Some(handle) = self.get(path) {
+            return Ok(handle);
+        }
+        Ok(self.insert(path, load(path)?))
+    }
+
+    /// Caches `asset` as the one loaded from `path`, replacing whatever was
+    /// there. Handles to the old asset keep it alive.
+    pub fn insert<T: Any>(&mut self, path: impl AsRef<Path>, asset: T) -> Handle<T> {
+        self.purge();
+        let asset = Rc::new(asset);
+        let weak = Rc::downgrade(&asset) as Weak<dyn Any>;
+        self.assets
+            .insert((path.as_ref().to_owned(), TypeId::of::<T>()), weak);
+        Handle(asset)
+    }
+
+    /// The font at `path` in `resources`.
+    #[cfg(all(feature = "text", not(target_arch = "wasm32")))]
+    pub fn font(
+        &mut self,
+        resources: &Resources,
+        path: impl AsRef<Path>,
+        unknown_char: char,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Handle<Font>> {
+        self.get_or_load(path, |path| {
+            Font::load(resources, path, unknown_char, device, queue)
+        })
+    }
+
+    #[cfg(feature = "text")]
+    pub async fn font_async(
+        &mut self,
+        resources: &Resources,
+        path: impl AsRef<Path>,
+        unknown_char: char,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Handle<Font>> {
+        let path = path.as_ref();
+        if let Some(font) = self.get(path) {
+            return Ok(font);
+        }
+        let font = Font::load_async(resources, path, unknown_char, device, queue).await?;
+        Ok(self.insert(path, font))
+    }
+
+    /// The image at `path` in `resources`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn texture(
+        &mut self,
+        resources: &Resources,
+        path: impl AsRef<Path>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Handle<Texture>> {
+        self.load(resources, path, device, queue)
+    }
+
+    pub async fn texture_async(
+        &mut self,
+        resources: &Resources,
+        path: impl AsRef<Path>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Handle<Texture>> {
+        self.load_async(resources, path, device, queue).await
+    }
+
+    /// The `T` at `path` in `resources`, loaded with the
+    /// [Loader](super::loader::Loader) added for it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load<T: Any>(
+        &mut self,
+        resources: &Resources,
+        path: impl AsRef<Path>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Handle<T>> {
+        self.get_or_load(path, |path| resources.load_asset(path, device, queue))
+    }
+
+    pub async fn load_async<T: Any>(
+        &mut self,
+        resources: &Resources,
+        path: impl AsRef<Path>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Handle<T>> {
+        let path = path.as_ref();
+        if let Some(asset) = self.get(path) {
+            return Ok(asset);
+        }
+        let asset = resources.load_asset_async(path, device, queue).await?;
+        Ok(self.insert(path, asset))
+    }
+
+    /// Forgets the assets that have been freed.
+    pub fn purge(&mut self) {
+        self.assets.retain(|_, asset| asset.strong_count() > 0);
+    }
+
+    /// How many assets are still loaded.
+    pub fn len(&self) -> usize {
+        self.assets
+            .values()
+            .filter(|asset| asset.strong_count() > 0)
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
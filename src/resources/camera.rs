@@ -1,13 +1,35 @@
+use std::sync::{Arc, Mutex};
+
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 
+use crate::stats;
+
 pub trait Camera {
     fn view_proj(&self) -> glam::Mat4;
+    /// Size of the area the camera draws to in logical pixels.
+    fn viewport_size(&self) -> glam::Vec2;
 }
 
 #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C)]
 pub struct CameraUniform {
     pub view_proj: glam::Mat4,
+    /// Takes clip space back to the world, for fullscreen shaders.
+    pub inv_view_proj: glam::Mat4,
+    pub viewport_size: glam::Vec2,
+    pub _padding: [f32; 2],
+}
+
+impl CameraUniform {
+    pub fn new(camera: &impl Camera) -> Self {
+        let view_proj = camera.view_proj();
+        Self {
+            view_proj,
+            inv_view_proj: view_proj.inverse(),
+            viewport_size: camera.viewport_size(),
+            _padding: [0.0; 2],
+        }
+    }
 }
 
 pub struct CameraBinder {
@@ -20,11 +42,16 @@ impl CameraBinder {
             label: Some("CameraBinder"),
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
+                // The grid reads the camera per pixel.
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
                 ty: wgpu::BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+                    // Every binding is a slot in a buffer, even if it's the
+                    // only one. See [CameraArray].
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(
+                        std::mem::size_of::<CameraUniform>() as u64
+                    ),
                 },
                 count: None,
             }],
@@ -35,22 +62,53 @@ impl CameraBinder {
     pub fn bind(&self, device: &wgpu::Device, camera: &impl Camera) -> CameraBinding {
         let buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("CameraBinding::buffer"),
-            contents: bytemuck::bytes_of(&CameraUniform {
-                view_proj: camera.view_proj(),
-            }),
+            contents: bytemuck::bytes_of(&CameraUniform::new(camera)),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bind_group = self.bind_group(device, &buffer);
+
+        CameraBinding {
+            buffer: Arc::new(buffer),
+            bind_group: Arc::new(bind_group),
+            offset: 0,
+            free_slots: None,
+        }
+    }
+
+    /// Creates a buffer with room for `capacity` cameras that all share one
+    /// bind group.
+    pub fn bind_array(&self, device: &wgpu::Device, capacity: u32) -> CameraArray {
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let stride = (std::mem::size_of::<CameraUniform>() as u64).next_multiple_of(alignment);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("CameraArray::buffer"),
+            size: stride * capacity.max(1) as u64,
             usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+            mapped_at_creation: false,
         });
+        let bind_group = self.bind_group(device, &buffer);
+        let free_slots = (0..capacity).rev().map(|i| i * stride as u32).collect();
+        CameraArray {
+            buffer: Arc::new(buffer),
+            bind_group: Arc::new(bind_group),
+            free_slots: Arc::new(Mutex::new(free_slots)),
+        }
+    }
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+    fn bind_group(&self, device: &wgpu::Device, buffer: &wgpu::Buffer) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("CameraBinding::bind_group"),
             layout: &self.layout,
             entries: &[wgpu::BindGroupEntry {
                 binding: 0,
-                resource: buffer.as_entire_binding(),
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<CameraUniform>() as u64),
+                }),
             }],
-        });
-
-        CameraBinding { bind_group, buffer }
+        })
     }
 
     pub(crate) fn layout(&self) -> &wgpu::BindGroupLayout {
@@ -59,32 +117,89 @@ impl CameraBinder {
 }
 
 pub struct CameraBinding {
-    buffer: wgpu::Buffer,
-    bind_group: wgpu::BindGroup,
+    buffer: Arc<wgpu::Buffer>,
+    bind_group: Arc<wgpu::BindGroup>,
+    /// Where the camera is in `buffer`.
+    offset: u32,
+    /// Set when the binding came from a [CameraArray]. The slot is given
+    /// back when the binding is dropped.
+    free_slots: Option<Arc<Mutex<Vec<u32>>>>,
 }
 
 impl CameraBinding {
     pub fn update(&mut self, camera: &impl Camera, queue: &wgpu::Queue) {
-        queue.write_buffer(
+        stats::write_buffer(
+            queue,
             &self.buffer,
-            0,
-            bytemuck::bytes_of(&CameraUniform {
-                view_proj: camera.view_proj(),
-            }),
+            self.offset as u64,
+            bytemuck::bytes_of(&CameraUniform::new(camera)),
         );
     }
 
-    pub fn bind_group(&self) -> &wgpu::BindGroup {
+    /// Binds the camera to bind group `index`.
+    pub fn set(&self, pass: &mut wgpu::RenderPass<'_>, index: u32) {
+        pass.set_bind_group(index, self.bind_group.as_ref(), &[self.offset]);
+    }
+
+    pub(crate) fn bind_group(&self) -> &wgpu::BindGroup {
         &self.bind_group
     }
+
+    pub(crate) fn offset(&self) -> u32 {
+        self.offset
+    }
 }
 
-#[derive(Debug)]
+impl Drop for CameraBinding {
+    fn drop(&mut self) {
+        if let Some(free_slots) = &self.free_slots {
+            if let Ok(mut free_slots) = free_slots.lock() {
+                free_slots.push(self.offset);
+            }
+        }
+    }
+}
+
+/// Many cameras packed into one buffer and bind group, told apart with
+/// dynamic offsets. Saves making a buffer and bind group per viewport.
+pub struct CameraArray {
+    buffer: Arc<wgpu::Buffer>,
+    bind_group: Arc<wgpu::BindGroup>,
+    /// Offsets of the slots no binding is using.
+    free_slots: Arc<Mutex<Vec<u32>>>,
+}
+
+impl CameraArray {
+    /// Puts `camera` in a free slot. Returns `None` if the array is full.
+    pub fn bind(&self, camera: &impl Camera, queue: &wgpu::Queue) -> Option<CameraBinding> {
+        let offset = self.free_slots.lock().ok()?.pop()?;
+        let mut binding = CameraBinding {
+            buffer: self.buffer.clone(),
+            bind_group: self.bind_group.clone(),
+            offset,
+            free_slots: Some(self.free_slots.clone()),
+        };
+        binding.update(camera, queue);
+        Some(binding)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct OrthoCamera {
     left: f32,
     right: f32,
     bottom: f32,
     top: f32,
+    /// Distances to the closest and furthest things the camera can see.
+    /// Geometry sits at `z = -depth`.
+    near: f32,
+    far: f32,
+    /// World position of the top left corner of the view, before rotating.
+    position: glam::Vec2,
+    zoom: f32,
+    /// How far the canvas is turned clockwise around the middle of the
+    /// view, in radians.
+    rotation: f32,
 }
 
 impl OrthoCamera {
@@ -94,17 +209,209 @@ impl OrthoCamera {
             right,
             bottom,
             top,
+            near: 0.0,
+            far: 1.0,
+            position: glam::Vec2::ZERO,
+            zoom: 1.0,
+            rotation: 0.0,
+        }
+    }
+
+    pub fn position(&self) -> glam::Vec2 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: glam::Vec2) {
+        self.position = position;
+    }
+
+    pub fn depth_range(&self) -> (f32, f32) {
+        (self.near, self.far)
+    }
+
+    /// Sets how far from the camera layers can be and still be drawn.
+    pub fn set_depth_range(&mut self, near: f32, far: f32) {
+        self.near = near;
+        self.far = far;
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.max(f32::EPSILON);
+    }
+
+    pub fn rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    /// Turns the canvas around the middle of the view. The angle is kept
+    /// within a full turn.
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation.rem_euclid(std::f32::consts::TAU);
+    }
+
+    /// World position at the middle of the view.
+    pub fn center(&self) -> glam::Vec2 {
+        self.position
+            + (glam::vec2(self.left, self.top) + glam::vec2(self.right, self.bottom)) * 0.5
+                / self.zoom
+    }
+
+    /// Moves the camera so `center` is in the middle of the view.
+    pub fn set_center(&mut self, center: glam::Vec2) {
+        self.position = center
+            - (glam::vec2(self.left, self.top) + glam::vec2(self.right, self.bottom)) * 0.5
+                / self.zoom;
+    }
+
+    /// Size of the view in logical pixels.
+    pub fn size(&self) -> glam::Vec2 {
+        glam::vec2(self.right - self.left, self.bottom - self.top)
+    }
+
+    /// Converts a position in logical window pixels to world space.
+    pub fn screen_to_world(&self, screen: glam::Vec2) -> glam::Vec2 {
+        let ndc = screen / self.size() * glam::vec2(2.0, -2.0) + glam::vec2(-1.0, 1.0);
+        self.view_proj()
+            .inverse()
+            .project_point3(ndc.extend(0.0))
+            .truncate()
+    }
+
+    /// Converts a world position to logical window pixels.
+    pub fn world_to_screen(&self, world: glam::Vec2) -> glam::Vec2 {
+        let ndc = self
+            .view_proj()
+            .project_point3(world.extend(0.0))
+            .truncate();
+        (ndc - glam::vec2(-1.0, 1.0)) / glam::vec2(2.0, -2.0) * self.size()
+    }
+
+    /// Sets the size of the view in logical pixels.
+    pub fn resize(&mut self, width: f32, height: f32) {
+        self.right = width;
+        self.bottom = height;
+    }
+}
+
+/// Where a [CameraAnimator] is taking the camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraTarget {
+    pub center: glam::Vec2,
+    pub zoom: f32,
+}
+
+/// Eases an [OrthoCamera] towards a target over several steps. Each
+/// update closes a fixed fraction of the remaining distance, so moves slow
+/// down as they arrive, and zoom changes by the same factor every step.
+/// Meant to be updated in fixed steps and drawn with
+/// [CameraAnimator::interpolate] between them.
+#[derive(Debug)]
+pub struct CameraAnimator {
+    target: Option<CameraTarget>,
+    /// Where the camera was before the last update that moved it.
+    previous: Option<CameraTarget>,
+    /// Seconds it takes to cover half of the remaining distance.
+    half_life: f32,
+}
+
+impl Default for CameraAnimator {
+    fn default() -> Self {
+        Self::new(0.08)
+    }
+}
+
+impl CameraAnimator {
+    pub fn new(half_life: f32) -> Self {
+        Self {
+            target: None,
+            previous: None,
+            half_life,
         }
     }
 
-    pub(crate) fn resize(&mut self, width: u32, height: u32) {
-        self.right = width as f32;
-        self.bottom = height as f32;
+    pub fn target(&self) -> Option<CameraTarget> {
+        self.target
+    }
+
+    /// Starts moving towards `target`, replacing any move in progress.
+    pub fn animate_to(&mut self, target: CameraTarget) {
+        self.target = Some(target);
+    }
+
+    /// Stops where the camera is, e.g. when the user takes over.
+    pub fn stop(&mut self) {
+        self.target = None;
+        self.previous = None;
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.target.is_some()
+    }
+
+    /// Advances the camera by `dt` seconds. Returns whether it moved.
+    pub fn update(&mut self, camera: &mut OrthoCamera, dt: f32) -> bool {
+        let Some(target) = self.target else {
+            self.previous = None;
+            return false;
+        };
+        self.previous = Some(CameraTarget {
+            center: camera.center(),
+            zoom: camera.zoom(),
+        });
+        let t = if self.half_life > 0.0 {
+            1.0 - 0.5f32.powf(dt / self.half_life)
+        } else {
+            1.0
+        };
+        let center = camera.center().lerp(target.center, t);
+        let zoom = (camera.zoom().ln() + (target.zoom.ln() - camera.zoom().ln()) * t).exp();
+
+        // Finish once the rest of the move would be under a pixel.
+        let done =
+            (zoom / target.zoom - 1.0).abs() < 1e-3 && center.distance(target.center) * zoom < 0.1;
+        let (center, zoom) = if done {
+            self.target = None;
+            (target.center, target.zoom)
+        } else {
+            (center, zoom)
+        };
+        camera.set_zoom(zoom);
+        camera.set_center(center);
+        true
+    }
+
+    /// `camera` as it should be drawn `alpha` of the way from its last
+    /// update to its next one, or `None` if it hasn't moved since the update
+    /// before last. Once the move is done it's drawn where it ended.
+    pub fn interpolate(&self, camera: &OrthoCamera, alpha: f32) -> Option<OrthoCamera> {
+        let previous = self.previous?;
+        let mut drawn = camera.clone();
+        if self.target.is_some() {
+            let t = alpha.clamp(0.0, 1.0);
+            let zoom = (previous.zoom.ln() + (camera.zoom().ln() - previous.zoom.ln()) * t).exp();
+            drawn.set_zoom(zoom);
+            drawn.set_center(previous.center.lerp(camera.center(), t));
+        }
+        Some(drawn)
     }
 }
 
 impl Camera for OrthoCamera {
     fn view_proj(&self) -> glam::Mat4 {
-        glam::Mat4::orthographic_rh(self.left, self.right, self.bottom, self.top, 0.0, 1.0)
+        let min = self.position + glam::vec2(self.left, self.top) / self.zoom;
+        let max = self.position + glam::vec2(self.right, self.bottom) / self.zoom;
+        let proj = glam::Mat4::orthographic_rh(min.x, max.x, max.y, min.y, self.near, self.far);
+        let center = self.center().extend(0.0);
+        proj * glam::Mat4::from_translation(center)
+            * glam::Mat4::from_rotation_z(self.rotation)
+            * glam::Mat4::from_translation(-center)
+    }
+
+    fn viewport_size(&self) -> glam::Vec2 {
+        self.size()
     }
 }
@@ -0,0 +1,562 @@
+/// The RGBA8 texels of a 4×4 block, by row.
+type Texels = [[u8; 4]; 16];
+
+/// Decodes `data`, the blocks of a `width`×`height` image in `format`, to
+/// tightly packed RGBA8 rows, for when the adapter can't sample it. `None`
+/// if there's no decoder for `format`.
+pub(crate) fn decompress(
+    format: wgpu::TextureFormat,
+    data: &[u8],
+    width: u32,
+    height: u32,
+) -> Option<Vec<u8>> {
+    use wgpu::TextureFormat as F;
+    let (block_size, decode): (usize, fn(&[u8]) -> Texels) = match format {
+        F::Bc1RgbaUnorm | F::Bc1RgbaUnormSrgb => (8, |b| bc1(b, true)),
+        F::Bc2RgbaUnorm | F::Bc2RgbaUnormSrgb => (16, bc2),
+        F::Bc3RgbaUnorm | F::Bc3RgbaUnormSrgb => (16, bc3),
+        F::Bc4RUnorm => (8, bc4),
+        F::Bc5RgUnorm => (16, bc5),
+        F::Etc2Rgb8Unorm | F::Etc2Rgb8UnormSrgb => (8, |b| etc2(b, false)),
+        F::Etc2Rgb8A1Unorm | F::Etc2Rgb8A1UnormSrgb => (8, |b| etc2(b, true)),
+        F::Etc2Rgba8Unorm | F::Etc2Rgba8UnormSrgb => (16, etc2_eac),
+        F::EacR11Unorm => (8, eac_r11),
+        F::EacRg11Unorm => (16, eac_rg11),
+        _ => return None,
+    };
+
+    let (width, height) = (width as usize, height as usize);
+    let blocks_x = width.div_ceil(4);
+    let blocks_y = height.div_ceil(4);
+    if data.len() < blocks_x * blocks_y * block_size {
+        return None;
+    }
+    let mut rgba = vec![0; width * height * 4];
+    for (i, block) in data
+        .chunks_exact(block_size)
+        .take(blocks_x * blocks_y)
+        .enumerate()
+    {
+        let (bx, by) = (i % blocks_x * 4, i / blocks_x * 4);
+        let texels = decode(block);
+        for y in 0..4.min(height - by) {
+            for x in 0..4.min(width - bx) {
+                let offset = ((by + y) * width + bx + x) * 4;
+                rgba[offset..offset + 4].copy_from_slice(&texels[y * 4 + x]);
+            }
+        }
+    }
+    Some(rgba)
+}
+
+fn rgb565(c: u16) -> [u8; 3] {
+    let r = (c >> 11) & 0x1f;
+    let g = (c >> 5) & 0x3f;
+    let b = c & 0x1f;
+    [
+        ((r << 3) | (r >> 2)) as u8,
+        ((g << 2) | (g >> 4)) as u8,
+        ((b << 3) | (b >> 2)) as u8,
+    ]
+}
+
+/// A BC1 color block. BC2 and BC3 blocks always use four colors, so
+/// `punch_through` is only set for BC1 itself.
+fn bc1(block: &[u8], punch_through: bool) -> Texels {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let (a, b) = (rgb565(c0), rgb565(c1));
+    let mix = |wa: u16, wb: u16| {
+        let mut c = [0, 0, 0, 255];
+        for i in 0..3 {
+            c[i] = ((a[i] as u16 * wa + b[i] as u16 * wb) / (wa + wb)) as u8;
+        }
+        c
+    };
+    let palette = if c0 > c1 || !punch_through {
+        [mix(1, 0), mix(0, 1), mix(2, 1), mix(1, 2)]
+    } else {
+        [mix(1, 0), mix(0, 1), mix(1, 1), [0; 4]]
+    };
+    std::array::from_fn(|i| palette[(indices >> (i * 2)) as usize & 3])
+}
+
+fn bc2(block: &[u8]) -> Texels {
+    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+    let mut texels = bc1(&block[8..], false);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[3] = ((alpha >> (i * 4)) & 0xf) as u8 * 17;
+    }
+    texels
+}
+
+fn bc3(block: &[u8]) -> Texels {
+    let alpha = bc4_channel(&block[..8]);
+    let mut texels = bc1(&block[8..], false);
+    for (texel, alpha) in texels.iter_mut().zip(alpha) {
+        texel[3] = alpha;
+    }
+    texels
+}
+
+fn bc4(block: &[u8]) -> Texels {
+    bc4_channel(block).map(|r| [r, 0, 0, 255])
+}
+
+fn bc5(block: &[u8]) -> Texels {
+    let r = bc4_channel(&block[..8]);
+    let g = bc4_channel(&block[8..]);
+    std::array::from_fn(|i| [r[i], g[i], 0, 255])
+}
+
+/// One channel interpolated between two endpoints, as in BC3's alpha.
+fn bc4_channel(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut bits = [0; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    let palette: [u8; 8] = std::array::from_fn(|i| match i as u32 {
+        0 => a0 as u8,
+        1 => a1 as u8,
+        i if a0 > a1 => ((a0 * (8 - i) + a1 * (i - 1)) / 7) as u8,
+        i @ 2..=5 => ((a0 * (6 - i) + a1 * (i - 1)) / 5) as u8,
+        6 => 0,
+        _ => 255,
+    });
+    std::array::from_fn(|i| palette[(indices >> (i * 3)) as usize & 7])
+}
+
+const ETC_MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+const ETC_DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+fn bits(block: u64, high: u32, count: u32) -> i32 {
+    ((block >> (high + 1 - count)) & ((1 << count) - 1)) as i32
+}
+
+fn extend(value: i32, from: u32) -> i32 {
+    (value << (8 - from)) | (value >> (2 * from - 8))
+}
+
+fn offset(color: [i32; 3], by: i32) -> [u8; 4] {
+    let [r, g, b] = color.map(|c| (c + by).clamp(0, 255) as u8);
+    [r, g, b, 255]
+}
+
+/// An ETC2 RGB block, or an RGB8A1 one with its punch-through alpha.
+fn etc2(block: &[u8], punch_through: bool) -> Texels {
+    let block = u64::from_be_bytes(block[..8].try_into().unwrap());
+    // Texels are stored by column, with their index split over two halves.
+    let index = |i: usize| {
+        let (x, y) = (i % 4, i / 4);
+        let bit = x * 4 + y;
+        (((block >> (bit + 16)) & 1) << 1 | (block >> bit) & 1) as usize
+    };
+    // Punch-through blocks use the differential bit to say they're opaque.
+    let differential = bits(block, 33, 1) == 1;
+    let transparent = punch_through && !differential;
+    let punch = |i: usize, color: [u8; 4]| {
+        if transparent && index(i) == 2 {
+            [0; 4]
+        } else {
+            color
+        }
+    };
+
+    if !differential && !punch_through {
+        let base = |high| [high, high - 8, high - 16].map(|high| extend(bits(block, high, 4), 4));
+        return etc_subblocks(block, [base(63), base(59)], false, index);
+    }
+
+    let base = [63, 55, 47].map(|high| bits(block, high, 5));
+    let delta = [58, 50, 42].map(|high| (bits(block, high, 3) << 29) >> 29);
+    let second: [i32; 3] = std::array::from_fn(|i| base[i] + delta[i]);
+    if !(0..32).contains(&second[0]) {
+        // T mode.
+        let c0 = [
+            bits(block, 60, 2) << 2 | bits(block, 57, 2),
+            bits(block, 55, 4),
+            bits(block, 51, 4),
+        ]
+        .map(|c| extend(c, 4));
+        let c1 = [47, 43, 39].map(|high| extend(bits(block, high, 4), 4));
+        let d = ETC_DISTANCES[(bits(block, 35, 2) << 1 | bits(block, 32, 1)) as usize];
+        let palette = [offset(c0, 0), offset(c1, d), offset(c1, 0), offset(c1, -d)];
+        std::array::from_fn(|i| punch(i, palette[index(i)]))
+    } else if !(0..32).contains(&second[1]) {
+        // H mode.
+        let c0 = [
+            bits(block, 62, 4),
+            bits(block, 58, 3) << 1 | bits(block, 52, 1),
+            bits(block, 51, 1) << 3 | bits(block, 49, 3),
+        ];
+        let c1 = [bits(block, 46, 4), bits(block, 42, 4), bits(block, 38, 4)];
+        let value = |c: [i32; 3]| c[0] << 8 | c[1] << 4 | c[2];
+        let d = ETC_DISTANCES[(bits(block, 34, 1) << 2
+            | bits(block, 32, 1) << 1
+            | (value(c0) >= value(c1)) as i32) as usize];
+        let (c0, c1) = (c0.map(|c| extend(c, 4)), c1.map(|c| extend(c, 4)));
+        let palette = [offset(c0, d), offset(c0, -d), offset(c1, d), offset(c1, -d)];
+        std::array::from_fn(|i| punch(i, palette[index(i)]))
+    } else if !(0..32).contains(&second[2]) {
+        // Planar mode, which has no alpha.
+        let o = [
+            extend(bits(block, 62, 6), 6),
+            extend(bits(block, 56, 1) << 6 | bits(block, 54, 6), 7),
+            extend(
+                bits(block, 48, 1) << 5 | bits(block, 44, 2) << 3 | bits(block, 41, 3),
+                6,
+            ),
+        ];
+        let h = [
+            extend(bits(block, 38, 5) << 1 | bits(block, 32, 1), 6),
+            extend(bits(block, 31, 7), 7),
+            extend(bits(block, 24, 6), 6),
+        ];
+        let v = [
+            extend(bits(block, 18, 6), 6),
+            extend(bits(block, 12, 7), 7),
+            extend(bits(block, 5, 6), 6),
+        ];
+        std::array::from_fn(|i| {
+            let (x, y) = ((i % 4) as i32, (i / 4) as i32);
+            let [r, g, b] = std::array::from_fn(|c| {
+                ((x * (h[c] - o[c]) + y * (v[c] - o[c]) + 4 * o[c] + 2) >> 2).clamp(0, 255) as u8
+            });
+            [r, g, b, 255]
+        })
+    } else {
+        let texels = etc_subblocks(
+            block,
+            [base.map(|c| extend(c, 5)), second.map(|c| extend(c, 5))],
+            transparent,
+            index,
+        );
+        std::array::from_fn(|i| punch(i, texels[i]))
+    }
+}
+
+/// The individual and differential modes, where each half of the block has
+/// a base color and a table of modifiers.
+fn etc_subblocks(
+    block: u64,
+    bases: [[i32; 3]; 2],
+    transparent: bool,
+    index: impl Fn(usize) -> usize,
+) -> Texels {
+    let flipped = bits(block, 32, 1) == 1;
+    let tables = [bits(block, 39, 3), bits(block, 36, 3)].map(|t| ETC_MODIFIERS[t as usize]);
+    std::array::from_fn(|i| {
+        let (x, y) = (i % 4, i / 4);
+        let half = if flipped { y / 2 } else { x / 2 };
+        let [a, b] = tables[half];
+        let modifier = match index(i) {
+            // Transparent blocks give up the small positive modifier.
+            0 if transparent => 0,
+            0 => a,
+            1 => b,
+            2 => -a,
+            _ => -b,
+        };
+        offset(bases[half], modifier)
+    })
+}
+
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+/// One channel of an EAC block. The alpha of ETC2 RGBA blocks is 8 bits,
+/// while R11 and RG11 blocks are decoded to 11 bits then cut down to 8.
+fn eac_channel(block: &[u8], eleven_bits: bool) -> [u8; 16] {
+    let block = u64::from_be_bytes(block[..8].try_into().unwrap());
+    let base = bits(block, 63, 8);
+    let multiplier = bits(block, 55, 4);
+    let table = EAC_MODIFIERS[bits(block, 51, 4) as usize];
+    std::array::from_fn(|i| {
+        let (x, y) = (i % 4, i / 4);
+        let modifier = table[bits(block, 47 - (x * 4 + y) as u32 * 3, 3) as usize];
+        if !eleven_bits {
+            return (base + modifier * multiplier).clamp(0, 255) as u8;
+        }
+        let scaled = match multiplier {
+            0 => modifier,
+            m => modifier * m * 8,
+        };
+        ((base * 8 + 4 + scaled).clamp(0, 2047) >> 3) as u8
+    })
+}
+
+fn etc2_eac(block: &[u8]) -> Texels {
+    let alpha = eac_channel(&block[..8], false);
+    let mut texels = etc2(&block[8..], false);
+    for (texel, alpha) in texels.iter_mut().zip(alpha) {
+        texel[3] = alpha;
+    }
+    texels
+}
+
+fn eac_r11(block: &[u8]) -> Texels {
+    eac_channel(block, true).map(|r| [r, 0, 0, 255])
+}
+
+fn eac_rg11(block: &[u8]) -> Texels {
+    let r = eac_channel(&block[..8], true);
+    let g = eac_channel(&block[8..], true);
+    std::array::from_fn(|i| [r[i], g[i], 0, 255])
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::TextureFormat as F;
+
+    use super::*;
+
+    /// Decodes a single 4×4 block through [decompress].
+    fn block(format: wgpu::TextureFormat, data: &[u8]) -> Texels {
+        let rgba = decompress(format, data, 4, 4).unwrap();
+        std::array::from_fn(|i| rgba[i * 4..i * 4 + 4].try_into().unwrap())
+    }
+
+    /// Red and blue endpoints with indices 0 to 3 along each row.
+    const BC1_RED_BLUE: [u8; 8] = [0x00, 0xf8, 0x1f, 0x00, 0xe4, 0xe4, 0xe4, 0xe4];
+    /// Endpoints 255 and 0, so eight values, with indices 0, 1, 2 and 7 in
+    /// the first row.
+    const BC4_EIGHT_VALUES: [u8; 8] = [255, 0, 0x88, 0x0e, 0, 0, 0, 0];
+    /// Individual mode with the first texel at index 3 and the last in the
+    /// first row at index 2.
+    const ETC2_INDIVIDUAL: [u8; 8] = [0xf0, 0x80, 0x0f, 0x1c, 0x10, 0x01, 0x00, 0x01];
+    /// Base 128 and multiplier 2, with the first texel at index 7 and the
+    /// one to its right at index 3.
+    const EAC_ALPHA: [u8; 8] = [0x80, 0x2d, 0xe0, 0x06, 0, 0, 0, 0];
+
+    #[test]
+    fn bc1_interpolates_four_colors() {
+        let texels = block(F::Bc1RgbaUnorm, &BC1_RED_BLUE);
+        assert_eq!(
+            texels[..4],
+            [
+                [255, 0, 0, 255],
+                [0, 0, 255, 255],
+                [170, 0, 85, 255],
+                [85, 0, 170, 255]
+            ]
+        );
+    }
+
+    #[test]
+    fn bc1_punches_through_when_the_endpoints_are_in_order() {
+        let texels = block(
+            F::Bc1RgbaUnorm,
+            &[0x1f, 0x00, 0x00, 0xf8, 0xe4, 0xe4, 0xe4, 0xe4],
+        );
+        assert_eq!(
+            texels[..4],
+            [
+                [0, 0, 255, 255],
+                [255, 0, 0, 255],
+                [127, 0, 127, 255],
+                [0, 0, 0, 0]
+            ]
+        );
+    }
+
+    #[test]
+    fn bc2_has_four_bit_alpha_and_opaque_colors() {
+        let mut data = vec![0x10, 0xf2, 0, 0, 0, 0, 0, 0];
+        // Would punch through in BC1.
+        data.extend([0x1f, 0x00, 0x00, 0xf8, 0xff, 0xff, 0xff, 0xff]);
+        let texels = block(F::Bc2RgbaUnorm, &data);
+        let alpha: Vec<_> = texels[..4].iter().map(|t| t[3]).collect();
+        assert_eq!(alpha, [0, 17, 34, 255]);
+        assert_eq!(texels[3][..3], [170, 0, 85]);
+    }
+
+    #[test]
+    fn bc3_interpolates_alpha() {
+        let mut data = BC4_EIGHT_VALUES.to_vec();
+        data.extend(BC1_RED_BLUE);
+        let texels = block(F::Bc3RgbaUnorm, &data);
+        assert_eq!(
+            texels[..4],
+            [
+                [255, 0, 0, 255],
+                [0, 0, 255, 0],
+                [170, 0, 85, 218],
+                [85, 0, 170, 36]
+            ]
+        );
+        assert_eq!(texels[15][3], 255);
+    }
+
+    #[test]
+    fn bc4_and_bc5_use_six_values_when_the_endpoints_are_in_order() {
+        let six_values = [0, 255, 0x88, 0x0e, 0, 0, 0, 0];
+        let red: Vec<_> = block(F::Bc4RUnorm, &six_values)[..4].to_vec();
+        assert_eq!(
+            red,
+            [
+                [0, 0, 0, 255],
+                [255, 0, 0, 255],
+                [51, 0, 0, 255],
+                [255, 0, 0, 255]
+            ]
+        );
+
+        let mut data = BC4_EIGHT_VALUES.to_vec();
+        data.extend(six_values);
+        let texels = block(F::Bc5RgUnorm, &data);
+        assert_eq!(
+            texels[..4],
+            [
+                [255, 0, 0, 255],
+                [0, 255, 0, 255],
+                [218, 51, 0, 255],
+                [36, 255, 0, 255]
+            ]
+        );
+    }
+
+    #[test]
+    fn etc2_individual_mode() {
+        let texels = block(F::Etc2Rgb8Unorm, &ETC2_INDIVIDUAL);
+        assert_eq!(
+            texels[..4],
+            [
+                [247, 128, 0, 255],
+                [255, 138, 2, 255],
+                [47, 47, 255, 255],
+                [0, 0, 208, 255]
+            ]
+        );
+    }
+
+    #[test]
+    fn etc2_differential_mode_flipped() {
+        let texels = block(F::Etc2Rgb8Unorm, &[0x83, 0x00, 0xfc, 0x03, 0, 0, 0, 0]);
+        assert_eq!(texels[0], [134, 2, 255, 255]);
+        assert_eq!(texels[7], [134, 2, 255, 255]);
+        assert_eq!(texels[8], [158, 2, 224, 255]);
+    }
+
+    #[test]
+    fn etc2_t_mode() {
+        let texels = block(
+            F::Etc2Rgb8Unorm,
+            &[0xf9, 0x0f, 0x88, 0x87, 0x11, 0x00, 0x10, 0x10],
+        );
+        assert_eq!(
+            texels[..4],
+            [
+                [221, 0, 255, 255],
+                [152, 152, 152, 255],
+                [136, 136, 136, 255],
+                [120, 120, 120, 255]
+            ]
+        );
+    }
+
+    #[test]
+    fn etc2_h_mode() {
+        let texels = block(
+            F::Etc2Rgb8Unorm,
+            &[0x41, 0xf9, 0x92, 0x36, 0x11, 0x00, 0x10, 0x10],
+        );
+        assert_eq!(
+            texels[..4],
+            [
+                [168, 83, 219, 255],
+                [104, 19, 155, 255],
+                [66, 100, 134, 255],
+                [2, 36, 70, 255]
+            ]
+        );
+    }
+
+    #[test]
+    fn etc2_planar_mode() {
+        // Red grows to the right and green downwards.
+        let texels = block(
+            F::Etc2Rgb8Unorm,
+            &[0x00, 0x00, 0x04, 0x7f, 0x00, 0x00, 0x1f, 0xc0],
+        );
+        assert_eq!(texels[0], [0, 0, 0, 255]);
+        assert_eq!(texels[3], [191, 0, 0, 255]);
+        assert_eq!(texels[9], [64, 128, 0, 255]);
+        assert_eq!(texels[12], [0, 191, 0, 255]);
+        assert_eq!(texels[15], [191, 191, 0, 255]);
+    }
+
+    #[test]
+    fn etc2_punch_through_without_the_opaque_bit() {
+        let data = [0x83, 0x00, 0xfc, 0x01, 0x00, 0x01, 0x00, 0x02];
+        let texels = block(F::Etc2Rgb8A1Unorm, &data);
+        assert_eq!(texels[0], [0, 0, 0, 0]);
+        // Index 0 has no modifier in transparent blocks.
+        assert_eq!(texels[1], [132, 0, 255, 255]);
+        assert_eq!(texels[4], [140, 8, 255, 255]);
+        assert_eq!(texels[8], [156, 0, 222, 255]);
+    }
+
+    #[test]
+    fn etc2_eac_alpha() {
+        let mut data = EAC_ALPHA.to_vec();
+        data.extend(ETC2_INDIVIDUAL);
+        let texels = block(F::Etc2Rgba8Unorm, &data);
+        assert_eq!(texels[0], [247, 128, 0, 146]);
+        assert_eq!(texels[1], [255, 138, 2, 108]);
+        assert_eq!(texels[4][3], 126);
+    }
+
+    #[test]
+    fn eac_eleven_bit_channels() {
+        let mut data = EAC_ALPHA.to_vec();
+        // Without a multiplier the modifiers aren't scaled up.
+        data.extend([0x80, 0x0d, 0xe0, 0x06, 0, 0, 0, 0]);
+        let texels = block(F::EacRg11Unorm, &data);
+        assert_eq!(texels[0], [146, 129, 0, 255]);
+        assert_eq!(texels[1], [108, 127, 0, 255]);
+        assert_eq!(texels[4], [126, 128, 0, 255]);
+        assert_eq!(block(F::EacR11Unorm, &EAC_ALPHA)[1], [108, 0, 0, 255]);
+    }
+
+    #[test]
+    fn partial_blocks_are_cropped() {
+        let mut data = BC1_RED_BLUE.to_vec();
+        // Solid green.
+        data.extend([0xe0, 0x07, 0xe0, 0x07, 0, 0, 0, 0]);
+        let rgba = decompress(F::Bc1RgbaUnorm, &data, 5, 2).unwrap();
+        let texels: Vec<_> = rgba.chunks(4).collect();
+        assert_eq!(texels.len(), 10);
+        for row in texels.chunks(5) {
+            assert_eq!(row[0], [255, 0, 0, 255]);
+            assert_eq!(row[3], [85, 0, 170, 255]);
+            assert_eq!(row[4], [0, 255, 0, 255]);
+        }
+        assert_eq!(decompress(F::Bc1RgbaUnorm, &data[..8], 5, 2), None);
+    }
+}
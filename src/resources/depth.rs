@@ -0,0 +1,49 @@
+/// Depth attachment for drawing layers ordered by their depth rather than
+/// the order they're drawn in.
+pub struct DepthBuffer {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+impl DepthBuffer {
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+    /// `sample_count` has to match the color target's.
+    pub fn new(width: u32, height: u32, sample_count: u32, device: &wgpu::Device) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("DepthBuffer::texture"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+        Self { texture, view }
+    }
+
+    /// Recreates the texture if it doesn't match the new size.
+    pub fn resize(&mut self, width: u32, height: u32, device: &wgpu::Device) {
+        if self.texture.width() != width.max(1) || self.texture.height() != height.max(1) {
+            *self = Self::new(width, height, self.texture.sample_count(), device);
+        }
+    }
+
+    /// Attachment that clears to the far plane.
+    pub fn attachment(&self) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        wgpu::RenderPassDepthStencilAttachment {
+            view: &self.view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: wgpu::StoreOp::Discard,
+            }),
+            stencil_ops: None,
+        }
+    }
+}
@@ -1,17 +1,25 @@
 use std::{
     collections::HashMap,
-    io::{Cursor, Read},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use anyhow::Context;
+
 use glam::{vec2, Vec2};
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 
-use crate::utils::RenderPipelineBuilder;
+use crate::{
+    draw_list::{Draw, DrawList},
+    stats,
+    utils::RenderPipelineBuilder,
+};
 
 use super::{
+    archive::ZipMount,
+    buffer::BackedBuffer,
     camera::{CameraBinder, CameraBinding},
+    instance::InstanceTransform,
+    texture::Texture,
     Resources,
 };
 
@@ -45,9 +53,29 @@ struct FontUniforms {
     _padding: u32,
 }
 
+/// How glyph edges are drawn from the font's distance field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FontSettings {
+    /// Grows the inside of glyphs, in units of the distance range.
+    pub in_bias: f32,
+    /// Grows the outside of glyphs, in units of the distance range.
+    pub out_bias: f32,
+    /// Softens the edges.
+    pub smoothness: f32,
+    /// Samples around each pixel, which helps small text.
+    pub super_sample: f32,
+    pub gamma: f32,
+}
+
 pub struct TextPipeline {
     font_uniforms: FontUniforms,
     font_uniform_buffer: wgpu::Buffer,
+    #[cfg(not(target_arch = "wasm32"))]
+    pipeline_layout: wgpu::PipelineLayout,
+    #[cfg(not(target_arch = "wasm32"))]
+    surface_format: wgpu::TextureFormat,
+    #[cfg(not(target_arch = "wasm32"))]
+    sample_count: u32,
     text_pipeline: wgpu::RenderPipeline,
     font_uniform_bg: wgpu::BindGroup,
     font_atlas: wgpu::BindGroup,
@@ -58,20 +86,18 @@ impl TextPipeline {
         font: &Font,
         camera_binder: &CameraBinder,
         surface_format: wgpu::TextureFormat,
+        sample_count: u32,
         texture_bindgroup_layout: &wgpu::BindGroupLayout,
         shader: &wgpu::ShaderModule,
         device: &wgpu::Device,
     ) -> anyhow::Result<Self> {
         let font_uniforms = FontUniforms {
-            unit_range: vec2(
-                font.info.distance_field.distance_range as f32 / font.info.common.scale_w as f32,
-                font.info.distance_field.distance_range as f32 / font.info.common.scale_h as f32,
-            ),
+            unit_range: unit_range(font),
             in_bias: 0.0,
             out_bias: 0.0,
             smoothness: 0.0,
             super_sample: 0.0,
-            inv_gamma: 1.0 / 1.0,
+            inv_gamma: 1.0,
             _padding: 0,
         };
 
@@ -115,16 +141,81 @@ impl TextPipeline {
             push_constant_ranges: &[],
         });
 
-        let text_pipeline = RenderPipelineBuilder::new()
-            .layout(&pipeline_layout)
+        let text_pipeline = Self::build(
+            &pipeline_layout,
+            shader,
+            surface_format,
+            sample_count,
+            device,
+        )?;
+
+        let font_atlas = Self::atlas(font, &text_pipeline, device);
+
+        Ok(Self {
+            font_uniforms,
+            font_uniform_buffer,
+            font_uniform_bg,
+            #[cfg(not(target_arch = "wasm32"))]
+            pipeline_layout,
+            #[cfg(not(target_arch = "wasm32"))]
+            surface_format,
+            #[cfg(not(target_arch = "wasm32"))]
+            sample_count,
+            text_pipeline,
+            font_atlas,
+        })
+    }
+
+    fn atlas(
+        font: &Font,
+        text_pipeline: &wgpu::RenderPipeline,
+        device: &wgpu::Device,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("font_atlas"),
+            layout: &text_pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&font.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&font.texture.sampler),
+                },
+            ],
+        })
+    }
+
+    /// Draws with `font`'s atlas from now on. Text buffered with the old
+    /// font has to be buffered again.
+    pub fn set_font(&mut self, font: &Font, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.font_atlas = Self::atlas(font, &self.text_pipeline, device);
+        self.font_uniforms.unit_range = unit_range(font);
+        queue.write_buffer(
+            &self.font_uniform_buffer,
+            0,
+            bytemuck::bytes_of(&self.font_uniforms),
+        );
+    }
+
+    fn build(
+        pipeline_layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        surface_format: wgpu::TextureFormat,
+        sample_count: u32,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        RenderPipelineBuilder::new()
+            .layout(pipeline_layout)
             .vertex(wgpu::VertexState {
-                module: &shader,
+                module: shader,
                 entry_point: Some("textured"),
                 compilation_options: Default::default(),
-                buffers: &[TexturedVertex::VB_DESC],
+                buffers: &[TexturedVertex::VB_DESC, InstanceTransform::VB_DESC],
             })
             .fragment(wgpu::FragmentState {
-                module: &shader,
+                module: shader,
                 entry_point: Some("msdf_text"),
                 compilation_options: Default::default(),
                 targets: &[Some(wgpu::ColorTargetState {
@@ -133,38 +224,56 @@ impl TextPipeline {
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             })
-            .build(&device)?;
+            .sample_count(sample_count)
+            .build(device)
+    }
 
-        let font_atlas = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("font_atlas"),
-            layout: &text_pipeline.get_bind_group_layout(0),
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(
-                        &font.texture.create_view(&Default::default()),
-                    ),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&device.create_sampler(
-                        &wgpu::SamplerDescriptor {
-                            min_filter: wgpu::FilterMode::Linear,
-                            mag_filter: wgpu::FilterMode::Linear,
-                            ..Default::default()
-                        },
-                    )),
-                },
-            ],
-        });
+    /// Rebuilds the pipeline from an edited `shader`. Keeps the old one if
+    /// that fails.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload(
+        &mut self,
+        shader: &wgpu::ShaderModule,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<()> {
+        self.text_pipeline = crate::utils::validated(device, || {
+            Self::build(
+                &self.pipeline_layout,
+                shader,
+                self.surface_format,
+                self.sample_count,
+                device,
+            )
+        })??;
+        Ok(())
+    }
 
-        Ok(Self {
-            font_uniforms,
-            font_uniform_buffer,
-            font_uniform_bg,
-            text_pipeline,
-            font_atlas,
-        })
+    pub fn font_settings(&self) -> FontSettings {
+        let uniforms = &self.font_uniforms;
+        FontSettings {
+            in_bias: uniforms.in_bias,
+            out_bias: uniforms.out_bias,
+            smoothness: uniforms.smoothness,
+            super_sample: uniforms.super_sample,
+            gamma: uniforms.inv_gamma.recip(),
+        }
+    }
+
+    pub fn set_font_settings(&mut self, settings: FontSettings, queue: &wgpu::Queue) {
+        self.font_uniforms = FontUniforms {
+            in_bias: settings.in_bias,
+            out_bias: settings.out_bias,
+            smoothness: settings.smoothness,
+            super_sample: settings.super_sample,
+            inv_gamma: settings.gamma.max(f32::EPSILON).recip(),
+            ..self.font_uniforms
+        };
+        stats::write_buffer(
+            queue,
+            &self.font_uniform_buffer,
+            0,
+            bytemuck::bytes_of(&self.font_uniforms),
+        );
     }
 
     pub fn buffer_text(
@@ -173,7 +282,7 @@ impl TextPipeline {
         device: &wgpu::Device,
         text: &str,
     ) -> anyhow::Result<TextBuffer> {
-        let (verts, indices) = generate_text_data(font, text, font.unknown_char);
+        let (verts, indices) = generate_text_data(font, text);
 
         let vb = device.create_buffer_init(&BufferInitDescriptor {
             label: Some(text),
@@ -201,7 +310,7 @@ impl TextPipeline {
         device: &wgpu::Device,
         queue: &wgpu::Queue,
     ) -> anyhow::Result<()> {
-        let (verts, indices) = generate_text_data(font, text, font.unknown_char);
+        let (verts, indices) = generate_text_data(font, text);
 
         if verts.len() * size_of::<TexturedVertex>() > buffer.vertices.size() as usize {
             buffer.vertices = device.create_buffer_init(&BufferInitDescriptor {
@@ -210,7 +319,7 @@ impl TextPipeline {
                 usage: buffer.vertices.usage(),
             });
         } else {
-            queue.write_buffer(&buffer.vertices, 0, bytemuck::cast_slice(&verts));
+            stats::write_buffer(queue, &buffer.vertices, 0, bytemuck::cast_slice(&verts));
         }
 
         if indices.len() * size_of::<TexturedVertex>() > buffer.indices.size() as usize {
@@ -220,7 +329,7 @@ impl TextPipeline {
                 usage: buffer.indices.usage(),
             });
         } else {
-            queue.write_buffer(&buffer.indices, 0, bytemuck::cast_slice(&indices));
+            stats::write_buffer(queue, &buffer.indices, 0, bytemuck::cast_slice(&indices));
         }
 
         buffer.num_indices = indices.len() as _;
@@ -228,25 +337,115 @@ impl TextPipeline {
         Ok(())
     }
 
+    /// Buffers each line of `text` with the first one starting at
+    /// `position`. `size` is the height of the font in pixels.
+    pub fn buffer_block(
+        &self,
+        font: &Font,
+        device: &wgpu::Device,
+        text: &str,
+        position: Vec2,
+        size: f32,
+    ) -> anyhow::Result<TextBlock> {
+        let line_height = font.line_height(size);
+        let labels: Vec<_> = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (line, position + vec2(0.0, i as f32 * line_height), size))
+            .collect();
+        self.buffer_labels(font, device, &labels)
+    }
+
+    /// Buffers single lines of text, each as `(text, position, size)`, so
+    /// they can be drawn together as a [TextBlock].
+    pub fn buffer_labels(
+        &self,
+        font: &Font,
+        device: &wgpu::Device,
+        labels: &[(&str, Vec2, f32)],
+    ) -> anyhow::Result<TextBlock> {
+        let lines = labels
+            .iter()
+            .map(|(text, _, _)| self.buffer_text(font, device, text))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let transforms = labels
+            .iter()
+            .map(|&(_, position, size)| {
+                glam::Affine2::from_scale_angle_translation(
+                    Vec2::splat(font.scale(size)),
+                    0.0,
+                    position,
+                )
+                .into()
+            })
+            .collect();
+        Ok(TextBlock {
+            lines,
+            instances: BackedBuffer::with_data(device, transforms, wgpu::BufferUsages::VERTEX),
+        })
+    }
+
+    pub fn draw_block(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        block: &TextBlock,
+        camera_binding: &CameraBinding,
+    ) {
+        let mut list = DrawList::new();
+        for (i, line) in block.lines.iter().enumerate() {
+            self.submit_text(&mut list, line, &block.instances, i as u32, camera_binding);
+        }
+        list.execute(pass);
+    }
+
+    /// Draws `text` using the transform at index `instance` of `instances`.
     pub fn draw_text(
         &self,
         pass: &mut wgpu::RenderPass<'_>,
         text: &TextBuffer,
+        instances: &BackedBuffer<InstanceTransform>,
+        instance: u32,
         camera_binding: &CameraBinding,
     ) {
-        pass.set_bind_group(0, &self.font_atlas, &[]);
-        pass.set_bind_group(1, camera_binding.bind_group(), &[]);
-        pass.set_bind_group(2, &self.font_uniform_bg, &[]);
-        pass.set_vertex_buffer(0, text.vertices.slice(..));
-        pass.set_index_buffer(text.indices.slice(..), wgpu::IndexFormat::Uint32);
-        pass.set_pipeline(&self.text_pipeline);
-        pass.draw_indexed(0..text.num_indices as u32, 0, 0..1);
+        let mut list = DrawList::new();
+        self.submit_text(&mut list, text, instances, instance, camera_binding);
+        list.execute(pass);
     }
+
+    /// Adds a draw of `text` to `list`. See [TextPipeline::draw_text].
+    pub fn submit_text<'a>(
+        &'a self,
+        list: &mut DrawList<'a>,
+        text: &'a TextBuffer,
+        instances: &'a BackedBuffer<InstanceTransform>,
+        instance: u32,
+        camera_binding: &'a CameraBinding,
+    ) {
+        list.push(
+            Draw::new(&self.text_pipeline)
+                .texture(0, &self.font_atlas)
+                .camera(1, camera_binding)
+                .bind(2, &self.font_uniform_bg)
+                .vertex_buffer(0, &text.vertices)
+                .vertex_buffer(1, instances.buffer())
+                .index_buffer(&text.indices)
+                .indexed(0..text.num_indices, instance..instance + 1),
+        );
+    }
+}
+
+/// How far the distance field reaches, in atlas uvs.
+fn unit_range(font: &Font) -> Vec2 {
+    let range = font.info.distance_field.distance_range as f32;
+    vec2(
+        range / font.info.common.scale_w as f32,
+        range / font.info.common.scale_h as f32,
+    )
 }
 
-fn generate_text_data(font: &Font, text: &str, unknown_char: char) -> (Vec<TexturedVertex>, Vec<u32>) {
-    let tex_width = font.texture.width() as f32;
-    let tex_height = font.texture.height() as f32;
+fn generate_text_data(font: &Font, text: &str) -> (Vec<TexturedVertex>, Vec<u32>) {
+    let tex_width = font.texture.size.x as f32;
+    let tex_height = font.texture.size.y as f32;
 
     let mut cursor = 0.0;
     let mut i = 0u32;
@@ -254,9 +453,7 @@ fn generate_text_data(font: &Font, text: &str, unknown_char: char) -> (Vec<Textu
     let mut verts = Vec::new();
     let mut indices = Vec::new();
     for c in text.chars() {
-        let glyph = font
-            .glyph(c)
-            .unwrap_or_else(|| font.unknown_glyph());
+        let glyph = font.glyph(c).unwrap_or_else(|| font.unknown_glyph());
 
         if glyph.width == 0 || glyph.height == 0 {
             cursor += glyph.xadvance as f32;
@@ -270,10 +467,7 @@ fn generate_text_data(font: &Font, text: &str, unknown_char: char) -> (Vec<Textu
                 glyph.height as f32 / tex_height,
             );
 
-        let p1 = glam::vec2(
-            cursor + glyph.xoffset as f32 + 20.0,
-            glyph.yoffset as f32 + 20.0,
-        );
+        let p1 = glam::vec2(cursor + glyph.xoffset as f32, glyph.yoffset as f32);
         let p2 = p1 + glam::vec2(glyph.width as f32, glyph.height as f32);
 
         verts.extend_from_slice(&[
@@ -310,14 +504,21 @@ pub struct TextBuffer {
     vertices: wgpu::Buffer,
 }
 
+/// Lines of text drawn one under the other.
+pub struct TextBlock {
+    lines: Vec<TextBuffer>,
+    instances: BackedBuffer<InstanceTransform>,
+}
+
 pub struct Font {
     unknown_char: char,
     pub info: FontData,
-    pub texture: wgpu::Texture,
+    pub texture: Texture,
     pub glyph_map: HashMap<char, usize>,
 }
 
 impl Font {
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn load(
         resources: &Resources,
         path: impl AsRef<Path>,
@@ -325,60 +526,89 @@ impl Font {
         device: &wgpu::Device,
         queue: &wgpu::Queue,
     ) -> anyhow::Result<Self> {
-        let bin = resources.load_binary(path)?;
-
-        let mut zip = zip::ZipArchive::new(Cursor::new(bin))?;
-
-        let mut buffer = Vec::new();
-
-        let texture = {
-            let mut zipped_img = zip.by_index(1)?;
-            let name = zipped_img.mangled_name();
-            zipped_img.read_to_end(&mut buffer)?;
-            let img = image::load_from_memory(&buffer)?.to_rgba8();
-
-            let dimensions = img.dimensions();
-            let texture_size = wgpu::Extent3d {
-                width: dimensions.0,
-                height: dimensions.1,
-                depth_or_array_layers: 1,
-            };
-            let texture = device.create_texture(&wgpu::TextureDescriptor {
-                size: texture_size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                label: Some(&format!("{}", name.display())),
-                view_formats: &[],
-            });
+        Self::from_zip(resources.load_binary(path)?, unknown_char, device, queue)
+    }
 
-            queue.write_texture(
-                wgpu::ImageCopyTexture {
-                    texture: &texture,
-                    mip_level: 0,
-                    origin: wgpu::Origin3d::ZERO,
-                    aspect: wgpu::TextureAspect::All,
-                },
-                &img,
-                wgpu::ImageDataLayout {
-                    offset: 0,
-                    bytes_per_row: Some(4 * dimensions.0),
-                    rows_per_image: Some(dimensions.1),
-                },
-                texture_size,
-            );
+    pub async fn load_async(
+        resources: &Resources,
+        path: impl AsRef<Path>,
+        unknown_char: char,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Self> {
+        let bin = resources.load_binary_async(path).await?;
+        Self::from_zip(bin, unknown_char, device, queue)
+    }
 
-            texture
-        };
+    /// Loads a font from its MSDF JSON at `path` and the atlas it names,
+    /// which is looked for next to it. Both can be in a
+    /// [mounted](Resources::mount_zip_async) archive.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_msdf(
+        resources: &Resources,
+        path: impl AsRef<Path>,
+        unknown_char: char,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Self> {
+        pollster::block_on(Self::load_msdf_async(
+            resources,
+            path,
+            unknown_char,
+            device,
+            queue,
+        ))
+    }
 
-        buffer.clear();
+    pub async fn load_msdf_async(
+        resources: &Resources,
+        path: impl AsRef<Path>,
+        unknown_char: char,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let json = resources.load_binary_async(path).await?;
+        let info: FontData = serde_json::from_slice(&json)
+            .with_context(|| format!("Invalid font in {}", path.display()))?;
+        let atlas_path = info.atlas_path(path)?;
+        let atlas = resources.load_binary_async(&atlas_path).await?;
+        Self::from_parts(info, &atlas, &atlas_path, unknown_char, device, queue)
+    }
 
-        zip.by_index(0)?.read_to_end(&mut buffer)?;
+    /// Reads a zip holding the font's MSDF JSON and the atlas it names.
+    pub fn from_zip(
+        bin: Vec<u8>,
+        unknown_char: char,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Self> {
+        let zip = ZipMount::new(bin, "font archive")?;
+        let json_path = PathBuf::from(
+            zip.find(".json")
+                .ok_or_else(|| anyhow::anyhow!("No font JSON in the font archive"))?,
+        );
+        let json = zip
+            .read(&json_path)
+            .ok_or_else(|| anyhow::anyhow!("No {} in the font archive", json_path.display()))??;
+        let info: FontData = serde_json::from_slice(&json)
+            .with_context(|| format!("Invalid font in {}", json_path.display()))?;
+        let atlas_path = info.atlas_path(&json_path)?;
+        let atlas = zip
+            .read(&atlas_path)
+            .ok_or_else(|| anyhow::anyhow!("No {} in the font archive", atlas_path.display()))??;
+        Self::from_parts(info, &atlas, &atlas_path, unknown_char, device, queue)
+    }
 
-        let json = String::from_utf8(buffer)?;
-        let info: FontData = serde_json::from_str(&json)?;
+    fn from_parts(
+        info: FontData,
+        atlas: &[u8],
+        atlas_path: &Path,
+        unknown_char: char,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Self> {
+        let texture = Texture::from_bytes(atlas, &atlas_path.to_string_lossy(), device, queue)?;
 
         let mut glyph_map = HashMap::new();
         for (i, glyph) in info.glyphs.iter().enumerate() {
@@ -400,10 +630,40 @@ impl Font {
     pub fn glyph(&self, c: char) -> Option<&Glyph> {
         self.glyph_map.get(&c).map(|&i| &self.info.glyphs[i])
     }
-    
+
     pub fn unknown_glyph(&self) -> &Glyph {
         self.glyph(self.unknown_char).unwrap()
     }
+
+    /// How much to scale the font's glyphs by to draw text `size` pixels
+    /// high.
+    pub fn scale(&self, size: f32) -> f32 {
+        size / self.info.info.size.max(1) as f32
+    }
+
+    /// Distance between lines of text `size` pixels high.
+    pub fn line_height(&self, size: f32) -> f32 {
+        self.info.common.line_height as f32 * self.scale(size)
+    }
+
+    /// Distance from the top of a line of text `size` pixels high to its
+    /// baseline.
+    pub fn baseline(&self, size: f32) -> f32 {
+        self.info.common.base as f32 * self.scale(size)
+    }
+
+    /// How wide one line of `text` is when drawn `size` pixels high.
+    pub fn text_width(&self, text: &str, size: f32) -> f32 {
+        let advance: u32 = text
+            .chars()
+            .map(|c| {
+                self.glyph(c)
+                    .unwrap_or_else(|| self.unknown_glyph())
+                    .xadvance
+            })
+            .sum();
+        advance as f32 * self.scale(size)
+    }
 }
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -417,6 +677,18 @@ pub struct FontData {
     pub distance_field: DistanceFieldInfo,
 }
 
+impl FontData {
+    /// Where the atlas is, given the JSON was at `json_path`. Only fonts
+    /// with one page are supported.
+    fn atlas_path(&self, json_path: &Path) -> anyhow::Result<PathBuf> {
+        let page = self
+            .pages
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("{} has no atlas", json_path.display()))?;
+        Ok(json_path.parent().unwrap_or(Path::new("")).join(page))
+    }
+}
+
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct Glyph {
     pub id: u32,
@@ -0,0 +1,911 @@
+use std::ops::Range;
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::{
+    draw_list::{Draw, DrawList},
+    shape::{Gradient, GradientKind, MAX_GRADIENT_STOPS},
+    stats,
+    style::{Style, StyleFill, StyleSheet, MAX_STYLES},
+    utils::{self, RenderPipelineBuilder},
+    wgsl,
+};
+
+use super::{
+    buffer::{BackedBuffer, IndexedBatch},
+    camera::{CameraBinder, CameraBinding},
+    instance::InstanceTransform,
+};
+
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct ColoredVertex {
+    pub position: glam::Vec2,
+    // Stored as an array as glam::Vec4 is 16 byte aligned, which would
+    // introduce padding.
+    pub color: [f32; 4],
+}
+
+impl ColoredVertex {
+    pub const VB_DESC: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<ColoredVertex>() as _,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![
+            0 => Float32x2,
+            1 => Float32x4,
+        ],
+    };
+
+    pub fn new(position: glam::Vec2, color: glam::Vec4) -> Self {
+        Self {
+            position,
+            color: color.to_array(),
+        }
+    }
+}
+
+/// Vertex of a filled shape. Positions are in the shape's local space so
+/// gradients can be evaluated per fragment.
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct FillVertex {
+    pub position: glam::Vec2,
+    pub color: [f32; 4],
+    /// Slot of the gradient in the layer's gradient buffer, or
+    /// [FillVertex::NO_GRADIENT] to use `color`.
+    pub gradient: u32,
+    /// [StyleId::index](crate::style::StyleId::index) of the style whose
+    /// fill is used instead of `color`, or [FillVertex::NO_STYLE].
+    pub style: u32,
+}
+
+impl FillVertex {
+    pub const NO_GRADIENT: u32 = u32::MAX;
+    pub const NO_STYLE: u32 = u32::MAX;
+
+    pub const VB_DESC: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<FillVertex>() as _,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![
+            0 => Float32x2,
+            1 => Float32x4,
+            2 => Uint32,
+            3 => Uint32,
+        ],
+    };
+
+    pub fn new(position: glam::Vec2, color: glam::Vec4, gradient: u32) -> Self {
+        Self {
+            position,
+            color: color.to_array(),
+            gradient,
+            style: Self::NO_STYLE,
+        }
+    }
+
+    /// A vertex that takes its color from a style.
+    pub fn styled(position: glam::Vec2, style: u32) -> Self {
+        Self {
+            position,
+            color: [1.0; 4],
+            gradient: Self::NO_GRADIENT,
+            style,
+        }
+    }
+}
+
+/// Vertex of a stroke with a width. Strokes are extruded from their center
+/// line in the vertex shader, so changing a style's width doesn't need new
+/// geometry.
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct StrokeVertex {
+    /// Point on the center line.
+    pub position: glam::Vec2,
+    /// Offset to the edge of a stroke one unit wide.
+    pub normal: glam::Vec2,
+    /// Distance along the line, used for dashes.
+    pub distance: f32,
+    /// [StyleId::index](crate::style::StyleId::index) of the stroke's style.
+    pub style: u32,
+}
+
+impl StrokeVertex {
+    pub const VB_DESC: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<StrokeVertex>() as _,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![
+            0 => Float32x2,
+            1 => Float32x2,
+            2 => Float32,
+            3 => Uint32,
+        ],
+    };
+
+    pub fn new(position: glam::Vec2, normal: glam::Vec2, distance: f32, style: u32) -> Self {
+        Self {
+            position,
+            normal,
+            distance,
+            style,
+        }
+    }
+}
+
+/// Vertex of a shape filled with a texture.
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct PatternVertex {
+    pub position: glam::Vec2,
+    pub uv: glam::Vec2,
+    pub tint: [f32; 4],
+    /// [Tiling::index](crate::shape::Tiling::index) of the fill.
+    pub tiling: u32,
+}
+
+impl PatternVertex {
+    pub const VB_DESC: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<PatternVertex>() as _,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![
+            0 => Float32x2,
+            1 => Float32x2,
+            2 => Float32x4,
+            3 => Uint32,
+        ],
+    };
+
+    pub fn new(position: glam::Vec2, uv: glam::Vec2, tint: glam::Vec4, tiling: u32) -> Self {
+        Self {
+            position,
+            uv,
+            tint: tint.to_array(),
+            tiling,
+        }
+    }
+}
+
+/// Number of gradients each layer can hold. This matches the shader and
+/// keeps the uniform buffer under WebGL's 16KB limit.
+pub const MAX_GRADIENTS: usize = 64;
+/// How many styles shapes can use on devices without storage buffers, like
+/// WebGL2, where they have to fit in a uniform buffer.
+pub const MAX_UNIFORM_STYLES: usize = 64;
+
+/// GPU layout of a [Gradient].
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct GradientUniform {
+    /// Kind (0 linear, 1 radial) and number of stops.
+    info: [f32; 4],
+    /// Start and end for linear gradients, center and radius for radial
+    /// ones.
+    geometry: [f32; 4],
+    offsets: [[f32; 4]; MAX_GRADIENT_STOPS / 4],
+    colors: [[f32; 4]; MAX_GRADIENT_STOPS],
+}
+
+impl From<&Gradient> for GradientUniform {
+    fn from(gradient: &Gradient) -> Self {
+        let (kind, geometry) = match gradient.kind() {
+            GradientKind::Linear { start, end } => (0.0, [start.x, start.y, end.x, end.y]),
+            GradientKind::Radial { center, radius } => (1.0, [center.x, center.y, radius, 0.0]),
+        };
+        let stops = &gradient.stops()[..gradient.stops().len().min(MAX_GRADIENT_STOPS)];
+        let mut uniform = Self {
+            info: [kind, stops.len() as f32, 0.0, 0.0],
+            geometry,
+            offsets: [[0.0; 4]; MAX_GRADIENT_STOPS / 4],
+            colors: [[0.0; 4]; MAX_GRADIENT_STOPS],
+        };
+        for (i, stop) in stops.iter().enumerate() {
+            uniform.offsets[i / 4][i % 4] = stop.offset;
+            uniform.colors[i] = stop.color.to_array();
+        }
+        uniform
+    }
+}
+
+/// GPU layout of a [Style].
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct StyleUniform {
+    stroke: [f32; 4],
+    fill: [f32; 4],
+    /// Stroke width, dash length, dash gap and fill kind (0 none, 1 solid,
+    /// 2 gradient).
+    params: [f32; 4],
+    gradient: GradientUniform,
+}
+
+impl From<&Style> for StyleUniform {
+    fn from(style: &Style) -> Self {
+        let (kind, fill, gradient) = match &style.fill {
+            None => (0.0, [0.0; 4], None),
+            Some(StyleFill::Solid(color)) => (1.0, color.to_array(), None),
+            Some(StyleFill::Gradient(gradient)) => (2.0, [0.0; 4], Some(gradient.into())),
+        };
+        let (dash, gap) = style.dash.map_or((0.0, 0.0), |d| (d.length, d.gap));
+        Self {
+            stroke: style.stroke.to_array(),
+            fill,
+            params: [style.stroke_width, dash, gap, kind],
+            gradient: gradient.unwrap_or(bytemuck::Zeroable::zeroed()),
+        }
+    }
+}
+
+/// Indexed vertex data for a set of primitives that are drawn together.
+pub struct GeometryBuffer<V = ColoredVertex> {
+    vertices: BackedBuffer<V>,
+    indices: BackedBuffer<u32>,
+}
+
+impl<V: bytemuck::Pod> GeometryBuffer<V> {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            vertices: BackedBuffer::with_capacity(device, 1024, wgpu::BufferUsages::VERTEX),
+            indices: BackedBuffer::with_capacity(device, 1024, wgpu::BufferUsages::INDEX),
+        }
+    }
+
+    pub fn batch<'a>(
+        &'a mut self,
+        device: &'a wgpu::Device,
+        queue: &'a wgpu::Queue,
+    ) -> IndexedBatch<'a, V> {
+        self.vertices
+            .batch_indexed(device, queue, &mut self.indices)
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    pub fn num_indices(&self) -> u32 {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+}
+
+/// How the canvas draws shapes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DrawMode {
+    #[default]
+    Color,
+    /// Shapes are drawn from their distance functions in a single
+    /// fullscreen pass.
+    Sdf,
+    /// Fills are faded out and the triangles they're made of are drawn on
+    /// top.
+    Wireframe,
+}
+
+impl DrawMode {
+    /// Moves on to the next mode, wrapping around at the end.
+    pub fn increment(&mut self) {
+        *self = match self {
+            Self::Color => Self::Sdf,
+            Self::Sdf => Self::Wireframe,
+            Self::Wireframe => Self::Color,
+        };
+    }
+
+    /// Value the shaders use to tell modes apart.
+    pub fn index(&self) -> u32 {
+        match self {
+            Self::Color => 0,
+            Self::Sdf => 1,
+            Self::Wireframe => 2,
+        }
+    }
+}
+
+/// Values shared by every layer.
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct GlobalsUniform {
+    draw_mode: u32,
+    _padding: [u32; 3],
+}
+
+impl GlobalsUniform {
+    fn new(draw_mode: DrawMode) -> Self {
+        Self {
+            draw_mode: draw_mode.index(),
+            _padding: [0; 3],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct LayerUniform {
+    opacity: f32,
+    depth: f32,
+    _padding: [f32; 2],
+}
+
+impl LayerUniform {
+    fn new(opacity: f32, depth: f32) -> Self {
+        Self {
+            opacity,
+            depth,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+/// Per layer uniforms that get applied to everything drawn for that layer.
+pub struct LayerBinding {
+    buffer: wgpu::Buffer,
+    gradients: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl LayerBinding {
+    /// `depth` only matters when drawing with
+    /// [GeometryPipeline::depth_tested].
+    pub fn update(&self, opacity: f32, depth: f32, queue: &wgpu::Queue) {
+        stats::write_buffer(
+            queue,
+            &self.buffer,
+            0,
+            bytemuck::bytes_of(&LayerUniform::new(opacity, depth)),
+        );
+    }
+
+    /// Uploads gradients to the first slots of the layer's gradient buffer.
+    /// Anything past [MAX_GRADIENTS] is ignored.
+    pub fn update_gradients(&self, gradients: &[GradientUniform], queue: &wgpu::Queue) {
+        let gradients = &gradients[..gradients.len().min(MAX_GRADIENTS)];
+        if !gradients.is_empty() {
+            stats::write_buffer(queue, &self.gradients, 0, bytemuck::cast_slice(gradients));
+        }
+    }
+}
+
+pub struct GeometryPipeline {
+    layer_layout: wgpu::BindGroupLayout,
+    globals: wgpu::Buffer,
+    /// Every [Style] of the drawing, indexed by
+    /// [StyleId::index](crate::style::StyleId::index).
+    styles: wgpu::Buffer,
+    /// How many of them fit in `styles`.
+    max_styles: usize,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    pattern_layout: wgpu::PipelineLayout,
+    surface_format: wgpu::TextureFormat,
+    sample_count: u32,
+    shapes: ShapePipelines,
+    depth_format: Option<wgpu::TextureFormat>,
+    depth_tested: Option<ShapePipelines>,
+}
+
+/// The pipelines shapes are drawn with. Layer bindings from the
+/// [GeometryPipeline] work with any set.
+pub struct ShapePipelines {
+    lines: wgpu::RenderPipeline,
+    fills: wgpu::RenderPipeline,
+    patterns: wgpu::RenderPipeline,
+    strokes: wgpu::RenderPipeline,
+}
+
+impl GeometryPipeline {
+    pub fn new(
+        camera_binder: &CameraBinder,
+        texture_layout: &wgpu::BindGroupLayout,
+        surface_format: wgpu::TextureFormat,
+        sample_count: u32,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<Self> {
+        let max_styles = if utils::has_storage_buffers(device) {
+            MAX_STYLES
+        } else {
+            MAX_UNIFORM_STYLES
+        };
+        let shader = utils::array_shader(
+            device,
+            "shapes.wgsl",
+            &wgsl::compose_built_in(include_str!("../shapes.wgsl"))?,
+            &[("styles", max_styles)],
+        );
+
+        let layer_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("GeometryPipeline::layer_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: utils::array_binding_type(device),
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let globals = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("GeometryPipeline::globals"),
+            contents: bytemuck::bytes_of(&GlobalsUniform::new(DrawMode::default())),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+        });
+
+        let styles = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GeometryPipeline::styles"),
+            size: (max_styles * size_of::<StyleUniform>()) as _,
+            usage: wgpu::BufferUsages::COPY_DST | utils::array_buffer_usage(device),
+            mapped_at_creation: false,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("GeometryPipeline::pipeline_layout"),
+            bind_group_layouts: &[camera_binder.layout(), &layer_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pattern_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("GeometryPipeline::pattern_layout"),
+            bind_group_layouts: &[camera_binder.layout(), &layer_layout, texture_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shapes = ShapePipelines::new(
+            &shader,
+            &pipeline_layout,
+            &pattern_layout,
+            surface_format,
+            sample_count,
+            None,
+            device,
+        )?;
+
+        Ok(Self {
+            layer_layout,
+            globals,
+            styles,
+            max_styles,
+            shader,
+            pipeline_layout,
+            pattern_layout,
+            surface_format,
+            sample_count,
+            shapes,
+            depth_format: None,
+            depth_tested: None,
+        })
+    }
+
+    /// Recompiles the shapes shader from `source` and rebuilds the
+    /// pipelines with it. Keeps the old ones if that fails.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload(&mut self, source: &str, device: &wgpu::Device) -> anyhow::Result<()> {
+        let (shader, shapes, depth_tested) = utils::validated(device, || {
+            let shader = utils::array_shader(
+                device,
+                "shapes.wgsl",
+                source,
+                &[("styles", self.max_styles)],
+            );
+            let pipelines = |depth_format| {
+                ShapePipelines::new(
+                    &shader,
+                    &self.pipeline_layout,
+                    &self.pattern_layout,
+                    self.surface_format,
+                    self.sample_count,
+                    depth_format,
+                    device,
+                )
+            };
+            let shapes = pipelines(None)?;
+            let depth_tested = self
+                .depth_format
+                .map(|format| pipelines(Some(format)))
+                .transpose()?;
+            anyhow::Ok((shader, shapes, depth_tested))
+        })??;
+        self.shader = shader;
+        self.shapes = shapes;
+        self.depth_tested = depth_tested;
+        Ok(())
+    }
+
+    /// Builds a second set of pipelines that test against and write to a
+    /// depth buffer of `format`, so layers can be ordered by their depth
+    /// instead of the order they're drawn in.
+    pub fn enable_depth(
+        &mut self,
+        format: wgpu::TextureFormat,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<()> {
+        self.depth_tested = Some(ShapePipelines::new(
+            &self.shader,
+            &self.pipeline_layout,
+            &self.pattern_layout,
+            self.surface_format,
+            self.sample_count,
+            Some(format),
+            device,
+        )?);
+        self.depth_format = Some(format);
+        Ok(())
+    }
+
+    /// Pipelines for passes without a depth buffer.
+    pub fn shapes(&self) -> &ShapePipelines {
+        &self.shapes
+    }
+
+    /// Pipelines for passes with a depth buffer, if
+    /// [GeometryPipeline::enable_depth] has been called.
+    pub fn depth_tested(&self) -> Option<&ShapePipelines> {
+        self.depth_tested.as_ref()
+    }
+
+    pub fn bind_layer(&self, device: &wgpu::Device, opacity: f32) -> LayerBinding {
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("LayerBinding::buffer"),
+            contents: bytemuck::bytes_of(&LayerUniform::new(opacity, 0.0)),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+        });
+
+        let gradients = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("LayerBinding::gradients"),
+            size: (MAX_GRADIENTS * size_of::<GradientUniform>()) as _,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("LayerBinding::bind_group"),
+            layout: &self.layer_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: gradients.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.globals.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.styles.as_entire_binding(),
+                },
+            ],
+        });
+
+        LayerBinding {
+            buffer,
+            gradients,
+            bind_group,
+        }
+    }
+
+    /// Switches the draw mode of every layer.
+    pub fn set_draw_mode(&self, mode: DrawMode, queue: &wgpu::Queue) {
+        stats::write_buffer(
+            queue,
+            &self.globals,
+            0,
+            bytemuck::bytes_of(&GlobalsUniform::new(mode)),
+        );
+    }
+
+    /// Uploads the styles shapes are drawn with. Anything past [MAX_STYLES],
+    /// or [MAX_UNIFORM_STYLES] without storage buffers, is ignored.
+    pub fn set_styles(&self, styles: &StyleSheet, queue: &wgpu::Queue) {
+        let styles: Vec<StyleUniform> = styles
+            .iter()
+            .take(self.max_styles)
+            .map(|(_, style)| style.into())
+            .collect();
+        if !styles.is_empty() {
+            stats::write_buffer(queue, &self.styles, 0, bytemuck::cast_slice(&styles));
+        }
+    }
+
+    /// Draws lines with [GeometryPipeline::shapes]. See
+    /// [ShapePipelines::draw_lines].
+    pub fn draw_lines(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        geometry: &GeometryBuffer,
+        instances: &BackedBuffer<InstanceTransform>,
+        layer: &LayerBinding,
+        camera_binding: &CameraBinding,
+        draws: impl IntoIterator<Item = (Range<u32>, u32)>,
+    ) {
+        self.shapes
+            .draw_lines(pass, geometry, instances, layer, camera_binding, draws);
+    }
+}
+
+impl ShapePipelines {
+    fn new(
+        shader: &wgpu::ShaderModule,
+        pipeline_layout: &wgpu::PipelineLayout,
+        pattern_layout: &wgpu::PipelineLayout,
+        surface_format: wgpu::TextureFormat,
+        sample_count: u32,
+        depth_format: Option<wgpu::TextureFormat>,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<Self> {
+        // Later layers are closer, and shapes within a layer share a depth,
+        // so ties go to whatever is drawn last.
+        let builder = || {
+            let builder = RenderPipelineBuilder::new().sample_count(sample_count);
+            match depth_format {
+                Some(format) => builder.depth(format, wgpu::CompareFunction::LessEqual),
+                None => builder,
+            }
+        };
+
+        let lines = builder()
+            .label("GeometryPipeline::lines")
+            .layout(pipeline_layout)
+            .topology(wgpu::PrimitiveTopology::LineList)
+            .vertex(wgpu::VertexState {
+                module: shader,
+                entry_point: Some("colored"),
+                compilation_options: Default::default(),
+                buffers: &[ColoredVertex::VB_DESC, InstanceTransform::VB_DESC],
+            })
+            .fragment(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("flat_color"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            })
+            .build(device)?;
+
+        let fills = builder()
+            .label("GeometryPipeline::fills")
+            .layout(pipeline_layout)
+            .topology(wgpu::PrimitiveTopology::TriangleList)
+            .vertex(wgpu::VertexState {
+                module: shader,
+                entry_point: Some("filled"),
+                compilation_options: Default::default(),
+                buffers: &[FillVertex::VB_DESC, InstanceTransform::VB_DESC],
+            })
+            .fragment(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fill_color"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            })
+            .build(device)?;
+
+        let patterns = builder()
+            .label("GeometryPipeline::patterns")
+            .layout(pattern_layout)
+            .topology(wgpu::PrimitiveTopology::TriangleList)
+            .vertex(wgpu::VertexState {
+                module: shader,
+                entry_point: Some("patterned"),
+                compilation_options: Default::default(),
+                buffers: &[PatternVertex::VB_DESC, InstanceTransform::VB_DESC],
+            })
+            .fragment(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("pattern_color"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            })
+            .build(device)?;
+
+        let strokes = builder()
+            .label("GeometryPipeline::strokes")
+            .layout(pipeline_layout)
+            .topology(wgpu::PrimitiveTopology::TriangleList)
+            .vertex(wgpu::VertexState {
+                module: shader,
+                entry_point: Some("stroked"),
+                compilation_options: Default::default(),
+                buffers: &[StrokeVertex::VB_DESC, InstanceTransform::VB_DESC],
+            })
+            .fragment(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("stroke_color"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            })
+            .build(device)?;
+
+        Ok(Self {
+            lines,
+            fills,
+            patterns,
+            strokes,
+        })
+    }
+
+    /// The state every draw from `geometry` shares.
+    fn draw<'a, V: bytemuck::Pod>(
+        pipeline: &'a wgpu::RenderPipeline,
+        geometry: &'a GeometryBuffer<V>,
+        instances: &'a BackedBuffer<InstanceTransform>,
+        layer: &'a LayerBinding,
+        camera_binding: &'a CameraBinding,
+    ) -> Draw<'a> {
+        Draw::new(pipeline)
+            .camera(0, camera_binding)
+            .bind(1, &layer.bind_group)
+            .vertex_buffer(0, geometry.vertices.buffer())
+            .vertex_buffer(1, instances.buffer())
+            .index_buffer(geometry.indices.buffer())
+    }
+
+    /// Adds draws of index ranges of `geometry` to `list`, each with the
+    /// transform at the given index in `instances`.
+    pub fn submit_lines<'a>(
+        &'a self,
+        list: &mut DrawList<'a>,
+        geometry: &'a GeometryBuffer,
+        instances: &'a BackedBuffer<InstanceTransform>,
+        layer: &'a LayerBinding,
+        camera_binding: &'a CameraBinding,
+        draws: impl IntoIterator<Item = (Range<u32>, u32)>,
+    ) {
+        if geometry.is_empty() || instances.is_empty() {
+            return;
+        }
+        let draw = Self::draw(&self.lines, geometry, instances, layer, camera_binding);
+        for (range, instance) in draws {
+            list.push(draw.clone().indexed(range, instance..instance + 1));
+        }
+    }
+
+    /// Adds filled triangles from `geometry` to `list`. Works like
+    /// [ShapePipelines::submit_lines].
+    pub fn submit_fills<'a>(
+        &'a self,
+        list: &mut DrawList<'a>,
+        geometry: &'a GeometryBuffer<FillVertex>,
+        instances: &'a BackedBuffer<InstanceTransform>,
+        layer: &'a LayerBinding,
+        camera_binding: &'a CameraBinding,
+        draws: impl IntoIterator<Item = (Range<u32>, u32)>,
+    ) {
+        if geometry.is_empty() || instances.is_empty() {
+            return;
+        }
+        let draw = Self::draw(&self.fills, geometry, instances, layer, camera_binding);
+        for (range, instance) in draws {
+            list.push(draw.clone().indexed(range, instance..instance + 1));
+        }
+    }
+
+    /// Adds textured triangles from `geometry` to `list`, each range with
+    /// the bind group of its texture.
+    pub fn submit_patterns<'a>(
+        &'a self,
+        list: &mut DrawList<'a>,
+        geometry: &'a GeometryBuffer<PatternVertex>,
+        instances: &'a BackedBuffer<InstanceTransform>,
+        layer: &'a LayerBinding,
+        camera_binding: &'a CameraBinding,
+        draws: impl IntoIterator<Item = (Range<u32>, u32, &'a wgpu::BindGroup)>,
+    ) {
+        if geometry.is_empty() || instances.is_empty() {
+            return;
+        }
+        let draw = Self::draw(&self.patterns, geometry, instances, layer, camera_binding);
+        for (range, instance, texture) in draws {
+            list.push(
+                draw.clone()
+                    .texture(2, texture)
+                    .indexed(range, instance..instance + 1),
+            );
+        }
+    }
+
+    /// Adds styled strokes from `geometry` to `list`. Works like
+    /// [ShapePipelines::submit_lines].
+    pub fn submit_strokes<'a>(
+        &'a self,
+        list: &mut DrawList<'a>,
+        geometry: &'a GeometryBuffer<StrokeVertex>,
+        instances: &'a BackedBuffer<InstanceTransform>,
+        layer: &'a LayerBinding,
+        camera_binding: &'a CameraBinding,
+        draws: impl IntoIterator<Item = (Range<u32>, u32)>,
+    ) {
+        if geometry.is_empty() || instances.is_empty() {
+            return;
+        }
+        let draw = Self::draw(&self.strokes, geometry, instances, layer, camera_binding);
+        for (range, instance) in draws {
+            list.push(draw.clone().indexed(range, instance..instance + 1));
+        }
+    }
+
+    /// Draws index ranges of `geometry` straight away. See
+    /// [ShapePipelines::submit_lines].
+    pub fn draw_lines(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        geometry: &GeometryBuffer,
+        instances: &BackedBuffer<InstanceTransform>,
+        layer: &LayerBinding,
+        camera_binding: &CameraBinding,
+        draws: impl IntoIterator<Item = (Range<u32>, u32)>,
+    ) {
+        let mut list = DrawList::new();
+        self.submit_lines(&mut list, geometry, instances, layer, camera_binding, draws);
+        list.execute(pass);
+    }
+}
+
+/// A pipeline drawing into 8-bit sRGB, for tests that need layers.
+#[cfg(test)]
+pub(crate) fn test_pipeline(device: &wgpu::Device) -> GeometryPipeline {
+    let textures = super::texture::TextureRegistry::new(device);
+    GeometryPipeline::new(
+        &CameraBinder::new(device),
+        textures.layout(),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        1,
+        device,
+    )
+    .unwrap()
+}
@@ -0,0 +1,39 @@
+/// Per instance 2D affine transform. The matrix is stored column major.
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct InstanceTransform {
+    pub matrix: [f32; 4],
+    pub translation: [f32; 2],
+}
+
+impl InstanceTransform {
+    pub const IDENTITY: Self = Self {
+        matrix: [1.0, 0.0, 0.0, 1.0],
+        translation: [0.0, 0.0],
+    };
+
+    pub const VB_DESC: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<InstanceTransform>() as _,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &wgpu::vertex_attr_array![
+            5 => Float32x4,
+            6 => Float32x2,
+        ],
+    };
+
+    pub fn from_translation(translation: glam::Vec2) -> Self {
+        Self {
+            translation: translation.to_array(),
+            ..Self::IDENTITY
+        }
+    }
+}
+
+impl From<glam::Affine2> for InstanceTransform {
+    fn from(value: glam::Affine2) -> Self {
+        Self {
+            matrix: value.matrix2.to_cols_array(),
+            translation: value.translation.to_array(),
+        }
+    }
+}
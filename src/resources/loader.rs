@@ -0,0 +1,174 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    path::Path,
+    sync::Arc,
+};
+
+#[cfg(feature = "text")]
+use super::font::Font;
+use super::texture::Texture;
+
+/// What a [Loader] is given to turn a file into an asset.
+pub struct LoadContext<'a> {
+    pub path: &'a Path,
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+}
+
+/// Parses files with some extensions into assets of one type. Registered
+/// with [Resources::add_loader](super::Resources::add_loader), after which
+/// [Resources::load_asset_async](super::Resources::load_asset_async) can
+/// load them.
+pub trait Loader: Send + Sync + 'static {
+    type Asset: Any;
+
+    /// The extensions of the files it loads, without the dot, like `"png"`.
+    /// Matched regardless of case.
+    fn extensions(&self) -> &[&str];
+
+    fn load(&self, data: Vec<u8>, context: &LoadContext) -> anyhow::Result<Self::Asset>;
+}
+
+/// A [Loader] with its asset type erased, so loaders of different types can
+/// be kept together.
+trait AnyLoader: Send + Sync {
+    fn asset_type(&self) -> TypeId;
+
+    fn load_any(&self, data: Vec<u8>, context: &LoadContext) -> anyhow::Result<Box<dyn Any>>;
+}
+
+impl<L: Loader> AnyLoader for L {
+    fn asset_type(&self) -> TypeId {
+        TypeId::of::<L::Asset>()
+    }
+
+    fn load_any(&self, data: Vec<u8>, context: &LoadContext) -> anyhow::Result<Box<dyn Any>> {
+        Ok(Box::new(self.load(data, context)?))
+    }
+}
+
+/// Loaders by the extensions they handle. An extension can have loaders
+/// for several types, like JSON files that hold different things.
+#[derive(Clone)]
+pub(crate) struct Loaders {
+    loaders: HashMap<String, Vec<Arc<dyn AnyLoader>>>,
+}
+
+impl Loaders {
+    /// Holds the built in [TextureLoader], [FontLoader] and
+    /// [DocumentLoader](crate::document::DocumentLoader).
+    pub fn new() -> Self {
+        let mut loaders = Self {
+            loaders: HashMap::new(),
+        };
+        loaders.add(TextureLoader);
+        #[cfg(feature = "text")]
+        loaders.add(FontLoader::default());
+        #[cfg(feature = "winit-app")]
+        loaders.add(crate::document::DocumentLoader);
+        loaders
+    }
+
+    /// Adds `loader`, replacing the ones for the same asset type and
+    /// extensions.
+    pub fn add<L: Loader>(&mut self, loader: L) {
+        let extensions: Vec<_> = loader
+            .extensions()
+            .iter()
+            .map(|extension| extension.to_ascii_lowercase())
+            .collect();
+        let loader: Arc<dyn AnyLoader> = Arc::new(loader);
+        for extension in extensions {
+            let loaders = self.loaders.entry(extension).or_default();
+            loaders.retain(|other| other.asset_type() != TypeId::of::<L::Asset>());
+            loaders.push(loader.clone());
+        }
+    }
+
+    /// Loads `data` from `context.path` as a `T`, with the loader for its
+    /// extension.
+    pub fn load<T: Any>(&self, data: Vec<u8>, context: &LoadContext) -> anyhow::Result<T> {
+        let path = context.path;
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        let loader = self
+            .loaders
+            .get(&extension)
+            .and_then(|loaders| {
+                loaders
+                    .iter()
+                    .find(|loader| loader.asset_type() == TypeId::of::<T>())
+            })
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No loader for {} as {}",
+                    path.display(),
+                    std::any::type_name::<T>()
+                )
+            })?;
+        let asset = loader.load_any(data, context)?;
+        Ok(*asset
+            .downcast()
+            .expect("loaders are found by their asset type"))
+    }
+}
+
+impl std::fmt::Debug for Loaders {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.loaders.keys()).finish()
+    }
+}
+
+/// Loads images as [Texture]s.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextureLoader;
+
+impl Loader for TextureLoader {
+    type Asset = Texture;
+
+    fn extensions(&self) -> &[&str] {
+        &["png", "jpg", "jpeg", "webp", "ktx2"]
+    }
+
+    fn load(&self, data: Vec<u8>, context: &LoadContext) -> anyhow::Result<Texture> {
+        Texture::from_bytes(
+            &data,
+            &context.path.to_string_lossy(),
+            context.device,
+            context.queue,
+        )
+    }
+}
+
+/// Loads zipped MSDF fonts as [Font]s.
+#[cfg(feature = "text")]
+#[derive(Debug, Clone, Copy)]
+pub struct FontLoader {
+    /// Drawn in place of characters the fonts don't have.
+    pub unknown_char: char,
+}
+
+#[cfg(feature = "text")]
+impl Default for FontLoader {
+    fn default() -> Self {
+        Self {
+            unknown_char: crate::renderer::FALLBACK_CHAR,
+        }
+    }
+}
+
+#[cfg(feature = "text")]
+impl Loader for FontLoader {
+    type Asset = Font;
+
+    fn extensions(&self) -> &[&str] {
+        &["zip"]
+    }
+
+    fn load(&self, data: Vec<u8>, context: &LoadContext) -> anyhow::Result<Font> {
+        Font::from_zip(data, self.unknown_char, context.device, context.queue)
+    }
+}
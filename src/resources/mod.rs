@@ -1,30 +1,525 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
 use std::{
-    fs,
+    any::Any,
+    collections::HashMap,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
 
+use anyhow::Context;
+
+use crate::wgsl;
+use archive::ZipMount;
+use loader::{LoadContext, Loader, Loaders};
+use progress::{Progress, ProgressTracker};
+
+mod archive;
 pub mod buffer;
+pub mod cache;
 pub mod camera;
+mod decompress;
+pub mod depth;
+#[cfg(feature = "text")]
 pub mod font;
+pub mod geometry;
+pub mod instance;
+pub mod loader;
+pub mod msaa;
+pub mod progress;
+pub mod target;
+pub mod texture;
+
+/// Where the app's resources are, relative to the working directory or
+/// the page.
+pub const RES_DIR: &str = "res";
+/// Lists the files of resources that are fetched, which can't be listed
+/// otherwise, as a JSON array of their paths relative to it.
+pub const MANIFEST: &str = "index.json";
+
+#[cfg(feature = "embedded-assets")]
+static EMBEDDED: include_dir::Dir<'static> = include_dir::include_dir!("$CARGO_MANIFEST_DIR/res");
 
+/// Where files are loaded from. Clones share their [Progress], so loads
+/// made on other threads or tasks count towards it.
+#[derive(Debug, Clone)]
 pub struct Resources {
     base_dir: PathBuf,
+    /// Fetched from instead of `base_dir` when set.
+    #[cfg(any(feature = "http", target_arch = "wasm32"))]
+    base_url: Option<String>,
+    /// Served instead of `base_dir` when set.
+    #[cfg(feature = "embedded-assets")]
+    embedded: Option<&'static include_dir::Dir<'static>>,
+    /// Looked in first, latest first.
+    mounts: Vec<ZipMount>,
+    progress: Arc<Mutex<ProgressTracker>>,
+    loaders: Loaders,
 }
 
 impl Resources {
     pub fn new(base_dir: impl AsRef<Path>) -> Self {
         Self {
             base_dir: base_dir.as_ref().to_owned(),
+            #[cfg(any(feature = "http", target_arch = "wasm32"))]
+            base_url: None,
+            #[cfg(feature = "embedded-assets")]
+            embedded: None,
+            mounts: Vec::new(),
+            progress: Default::default(),
+            loaders: Loaders::new(),
+        }
+    }
+
+    /// Fetches files from under `base_url`, like
+    /// `"https://cdn.example.com/res"`. Native builds need the `http`
+    /// feature for it.
+    #[cfg(any(feature = "http", target_arch = "wasm32"))]
+    pub fn url(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: Some(base_url.into()),
+            ..Self::new("")
+        }
+    }
+
+    /// The files in [RES_DIR], baked into the binary with the
+    /// `embedded-assets` feature and loaded from it otherwise.
+    pub fn app() -> Self {
+        #[cfg(feature = "embedded-assets")]
+        {
+            Self::embedded()
+        }
+        #[cfg(not(feature = "embedded-assets"))]
+        {
+            Self::new(RES_DIR)
+        }
+    }
+
+    /// The directory files are loaded from, or `None` if they're embedded
+    /// or fetched from a URL.
+    pub fn base_dir(&self) -> Option<&Path> {
+        #[cfg(any(feature = "http", target_arch = "wasm32"))]
+        if self.base_url.is_some() {
+            return None;
+        }
+        #[cfg(feature = "embedded-assets")]
+        if self.embedded.is_some() {
+            return None;
+        }
+        Some(&self.base_dir)
+    }
+
+    /// Serves the files in [RES_DIR] as they were when the crate was built.
+    #[cfg(feature = "embedded-assets")]
+    pub fn embedded() -> Self {
+        Self {
+            embedded: Some(&EMBEDDED),
+            ..Self::new("")
+        }
+    }
+
+    /// Mounts the zip archive at `path` over the other files, so the ones
+    /// in it can be loaded by their names in it, as if they were next to
+    /// it. Archives mounted later are looked in first.
+    pub async fn mount_zip_async(&mut self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let data = self.load_binary_async(path).await?;
+        self.mount_zip_data(data, path)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn mount_zip(&mut self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        pollster::block_on(self.mount_zip_async(path))
+    }
+
+    /// Mounts a zip archive that's already been loaded, like one that was
+    /// downloaded. `name` is what it's called in errors and unmounted by.
+    pub fn mount_zip_data(&mut self, data: Vec<u8>, name: impl AsRef<Path>) -> anyhow::Result<()> {
+        self.mounts.push(ZipMount::new(data, name)?);
+        Ok(())
+    }
+
+    /// Unmounts the archives mounted from `path`. Returns whether there
+    /// were any.
+    pub fn unmount_zip(&mut self, path: impl AsRef<Path>) -> bool {
+        let count = self.mounts.len();
+        self.mounts.retain(|mount| mount.name() != path.as_ref());
+        self.mounts.len() != count
+    }
+
+    /// The files directly in `dir`, sorted, with paths relative to the
+    /// base like the ones files are loaded with. Fetched resources are
+    /// listed by their [MANIFEST].
+    pub async fn list_async(&self, dir: impl AsRef<Path>) -> anyhow::Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        let dir = dir.strip_prefix(".").unwrap_or(dir);
+        let mut files = self.files().await?;
+        files.retain(|file| file.parent() == Some(dir));
+        Ok(files)
+    }
+
+    /// The files anywhere whose paths match `pattern`, like `"*.zip"` or
+    /// `"**/*.ron"`, sorted. `*` doesn't match across directories.
+    pub async fn glob_async(&self, pattern: &str) -> anyhow::Result<Vec<PathBuf>> {
+        let pattern =
+            glob::Pattern::new(pattern).with_context(|| format!("Invalid pattern {pattern:?}"))?;
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        let mut files = self.files().await?;
+        files.retain(|file| pattern.matches_path_with(file, options));
+        Ok(files)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn list(&self, dir: impl AsRef<Path>) -> anyhow::Result<Vec<PathBuf>> {
+        pollster::block_on(self.list_async(dir))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn glob(&self, pattern: &str) -> anyhow::Result<Vec<PathBuf>> {
+        pollster::block_on(self.glob_async(pattern))
+    }
+
+    /// Every file in the mounted archives and the files under them, sorted.
+    async fn files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        let mut files = self.source_files().await?;
+        for mount in &self.mounts {
+            files.extend(mount.file_names().map(PathBuf::from));
         }
+        files.retain(|file| file != Path::new(MANIFEST));
+        files.sort();
+        files.dedup();
+        Ok(files)
     }
 
+    async fn source_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        #[cfg(feature = "embedded-assets")]
+        if let Some(embedded) = self.embedded {
+            let mut files = Vec::new();
+            embedded_files(embedded, &mut files);
+            return Ok(files);
+        }
+        #[cfg(any(feature = "http", target_arch = "wasm32"))]
+        if let Some(base_url) = &self.base_url {
+            let url = join_url(base_url, Path::new(MANIFEST));
+            return parse_manifest(&fetch(&url, |_| {}).await?, &url);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut files = Vec::new();
+            dir_files(&self.base_dir, Path::new(""), &mut files)?;
+            Ok(files)
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let url = self.base_dir.join(MANIFEST).to_string_lossy().into_owned();
+            parse_manifest(&fetch(&url, |_| {}).await?, &url)
+        }
+    }
+
+    /// Lets [Resources::load_asset_async] load the files `loader` handles
+    /// as its type of asset. Fonts, images and documents can be loaded to
+    /// begin with.
+    pub fn add_loader(&mut self, loader: impl Loader) {
+        self.loaders.add(loader);
+    }
+
+    /// Loads the file at `path` as a `T`, with the [Loader] added for its
+    /// extension and `T`.
+    pub async fn load_asset_async<T: Any>(
+        &self,
+        path: impl AsRef<Path>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<T> {
+        let path = path.as_ref();
+        let data = self.load_binary_async(path).await?;
+        self.loaders.load(
+            data,
+            &LoadContext {
+                path,
+                device,
+                queue,
+            },
+        )
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_asset<T: Any>(
+        &self,
+        path: impl AsRef<Path>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<T> {
+        pollster::block_on(self.load_asset_async(path, device, queue))
+    }
+
+    /// How far loading has got, for drawing a progress bar.
+    pub fn progress(&self) -> Progress {
+        self.tracker().progress()
+    }
+
+    /// Receives the [Progress] each time a file starts loading, is read
+    /// from or finishes.
+    pub fn watch_progress(&self) -> futures_channel::mpsc::UnboundedReceiver<Progress> {
+        self.tracker().listen()
+    }
+
+    /// Counts `files` in the total before they start loading, so the total
+    /// doesn't grow as loads that depend on others start.
+    pub fn expect(&self, files: usize) {
+        self.tracker().expect(files);
+    }
+
+    /// Starts counting from nothing, like before loading the next level.
+    /// Files still loading will be counted as completed without having
+    /// started.
+    pub fn reset_progress(&self) {
+        self.tracker().reset();
+    }
+
+    fn tracker(&self) -> std::sync::MutexGuard<'_, ProgressTracker> {
+        lock_tracker(&self.progress)
+    }
+
+    /// Counts bytes read towards [Resources::progress], from any thread.
+    #[cfg(any(feature = "http", target_arch = "wasm32"))]
+    fn count_read(&self) -> impl Fn(usize) + Send + 'static {
+        let progress = self.progress.clone();
+        move |bytes| lock_tracker(&progress).read(bytes)
+    }
+
+    /// Reads the file at `path` under the base directory, or fetches it
+    /// relative to the page on the web. Files under a [URL](Resources::url)
+    /// are fetched from there, and embedded ones are served from memory.
+    /// Files in [mounted](Resources::mount_zip_async) archives are read
+    /// from them instead. Counts towards [Resources::progress].
+    pub async fn load_binary_async(&self, path: impl AsRef<Path>) -> anyhow::Result<Vec<u8>> {
+        self.tracker().start();
+        let data = self.read(path.as_ref()).await;
+        self.tracker().complete();
+        data
+    }
+
+    async fn read(&self, path: &Path) -> anyhow::Result<Vec<u8>> {
+        for mount in self.mounts.iter().rev() {
+            if let Some(data) = mount.read(path) {
+                let data = data?;
+                self.tracker().read(data.len());
+                return Ok(data);
+            }
+        }
+        #[cfg(feature = "embedded-assets")]
+        if let Some(embedded) = self.embedded {
+            let data = embedded
+                .get_file(path)
+                .map(|file| file.contents().to_vec())
+                .ok_or_else(|| anyhow::anyhow!("No embedded file at {}", path.display()))?;
+            self.tracker().read(data.len());
+            return Ok(data);
+        }
+        #[cfg(any(feature = "http", target_arch = "wasm32"))]
+        if let Some(base_url) = &self.base_url {
+            let url = join_url(base_url, path);
+            return fetch(&url, self.count_read()).await;
+        }
+        let path = self.base_dir.join(path);
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let data =
+                fs::read(&path).with_context(|| format!("Unable to read {}", path.display()))?;
+            self.tracker().read(data.len());
+            Ok(data)
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            fetch(&path.to_string_lossy(), self.count_read()).await
+        }
+    }
+
+    pub async fn load_string_async(&self, path: impl AsRef<Path>) -> anyhow::Result<String> {
+        let path = path.as_ref();
+        let data = self.load_binary_async(path).await?;
+        String::from_utf8(data).with_context(|| format!("{} isn't UTF-8", path.display()))
+    }
+
+    /// Loads WGSL and [composes](crate::wgsl::compose) it. Includes are
+    /// looked for next to it first, then among the built in snippets.
+    pub async fn load_shader_async(&self, path: impl AsRef<Path>) -> anyhow::Result<String> {
+        let path = path.as_ref();
+        let source = self.load_string_async(path).await?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        // Composing is synchronous, so everything it could include is
+        // loaded first.
+        let mut snippets = HashMap::new();
+        let mut pending: Vec<String> = wgsl::includes(&source).map(str::to_owned).collect();
+        while let Some(name) = pending.pop() {
+            if snippets.contains_key(&name) {
+                continue;
+            }
+            let snippet = match self.load_string_async(dir.join(&name)).await {
+                Ok(snippet) => snippet,
+                Err(_) => match wgsl::snippet(&name) {
+                    Some(snippet) => snippet,
+                    // Left for composing to report.
+                    None => continue,
+                },
+            };
+            pending.extend(wgsl::includes(&snippet).map(str::to_owned));
+            snippets.insert(name, snippet);
+        }
+        wgsl::compose(&source, |name| snippets.get(name).cloned())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn load_binary(&self, path: impl AsRef<Path>) -> anyhow::Result<Vec<u8>> {
-        // TODO: WASM
-        Ok(fs::read(self.base_dir.join(path))?)
+        pollster::block_on(self.load_binary_async(path))
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn load_string(&self, path: impl AsRef<Path>) -> anyhow::Result<String> {
-        // TODO: WASM
-        Ok(fs::read_to_string(self.base_dir.join(path))?)
+        pollster::block_on(self.load_string_async(path))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_shader(&self, path: impl AsRef<Path>) -> anyhow::Result<String> {
+        pollster::block_on(self.load_shader_async(path))
+    }
+}
+
+/// Adds the paths of the files under `dir` in `base`, relative to `base`,
+/// to `files`.
+#[cfg(not(target_arch = "wasm32"))]
+fn dir_files(base: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let full = base.join(dir);
+    let entries =
+        fs::read_dir(&full).with_context(|| format!("Unable to list {}", full.display()))?;
+    for entry in entries {
+        let path = dir.join(entry?.file_name());
+        if base.join(&path).is_dir() {
+            dir_files(base, &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "embedded-assets")]
+fn embedded_files(dir: &include_dir::Dir, files: &mut Vec<PathBuf>) {
+    files.extend(dir.files().map(|file| file.path().to_owned()));
+    for dir in dir.dirs() {
+        embedded_files(dir, files);
+    }
+}
+
+#[cfg(any(feature = "http", target_arch = "wasm32"))]
+fn parse_manifest(data: &[u8], url: &str) -> anyhow::Result<Vec<PathBuf>> {
+    serde_json::from_slice(data).with_context(|| format!("Invalid manifest at {url}"))
+}
+
+fn lock_tracker(progress: &Mutex<ProgressTracker>) -> std::sync::MutexGuard<'_, ProgressTracker> {
+    progress
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// `path` under `base_url`, with its separators made forward slashes.
+#[cfg(any(feature = "http", target_arch = "wasm32"))]
+fn join_url(base_url: &str, path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    format!(
+        "{}/{}",
+        base_url.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+/// Downloads `url`, calling `on_read` with the size of each chunk of the
+/// body as it arrives. ureq blocks, so the download gets a thread of its
+/// own and awaiting it doesn't hold up the executor.
+#[cfg(all(feature = "http", not(target_arch = "wasm32")))]
+async fn fetch(url: &str, on_read: impl Fn(usize) + Send + 'static) -> anyhow::Result<Vec<u8>> {
+    let (sender, receiver) = futures_channel::oneshot::channel();
+    let thread_url = url.to_string();
+    std::thread::spawn(move || {
+        // Nobody's waiting if the receiver is gone.
+        let _ = sender.send(fetch_blocking(&thread_url, on_read));
+    });
+    // The sender is only dropped without sending if the thread panicked.
+    receiver
+        .await
+        .map_err(|_| anyhow::anyhow!("Fetching {url} panicked"))?
+}
+
+#[cfg(all(feature = "http", not(target_arch = "wasm32")))]
+fn fetch_blocking(url: &str, on_read: impl Fn(usize)) -> anyhow::Result<Vec<u8>> {
+    use std::io::Read;
+
+    // ureq's errors already say which URL they're about.
+    let response = ureq::get(url)
+        .call()
+        .map_err(|e| anyhow::anyhow!("Unable to fetch {e}"))?;
+    let mut body = response.into_reader();
+    let mut data = Vec::new();
+    let mut chunk = vec![0; 64 * 1024];
+    loop {
+        let read = match body.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e).with_context(|| format!("Unable to read {url}")),
+        };
+        data.extend_from_slice(&chunk[..read]);
+        on_read(read);
+    }
+    Ok(data)
+}
+
+/// Fetches `url`, calling `on_read` with the size of each chunk of the
+/// body as it arrives.
+#[cfg(target_arch = "wasm32")]
+async fn fetch(url: &str, on_read: impl Fn(usize)) -> anyhow::Result<Vec<u8>> {
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    let window = web_sys::window().ok_or_else(|| anyhow::anyhow!("No window"))?;
+    let response: web_sys::Response = JsFuture::from(window.fetch_with_str(url))
+        .await
+        .and_then(|response| response.dyn_into())
+        .map_err(|e| anyhow::anyhow!("Unable to fetch {url}: {e:?}"))?;
+    anyhow::ensure!(
+        response.ok(),
+        "Unable to fetch {url}: {} {}",
+        response.status(),
+        response.status_text()
+    );
+    let Some(body) = response.body() else {
+        return Ok(Vec::new());
+    };
+    let reader: web_sys::ReadableStreamDefaultReader = body
+        .get_reader()
+        .dyn_into()
+        .map_err(|e| anyhow::anyhow!("Unable to read {url}: {e:?}"))?;
+    let field = |chunk: &wasm_bindgen::JsValue, name: &str| {
+        js_sys::Reflect::get(chunk, &name.into())
+            .map_err(|e| anyhow::anyhow!("Unable to read {url}: {e:?}"))
+    };
+    let mut data = Vec::new();
+    loop {
+        let chunk = JsFuture::from(reader.read())
+            .await
+            .map_err(|e| anyhow::anyhow!("Unable to read {url}: {e:?}"))?;
+        if field(&chunk, "done")?.as_bool().unwrap_or(true) {
+            break;
+        }
+        let bytes = js_sys::Uint8Array::new(&field(&chunk, "value")?);
+        let start = data.len();
+        data.resize(start + bytes.length() as usize, 0);
+        bytes.copy_to(&mut data[start..]);
+        on_read(bytes.length() as usize);
     }
+    Ok(data)
 }
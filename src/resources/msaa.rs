@@ -0,0 +1,67 @@
+/// Color target that's drawn into with several samples per pixel and then
+/// resolved into the surface, smoothing the edges of shapes.
+pub struct MultisampleBuffer {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+impl MultisampleBuffer {
+    pub fn new(
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        device: &wgpu::Device,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("MultisampleBuffer::texture"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+        Self { texture, view }
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.texture.sample_count()
+    }
+
+    /// Recreates the texture if it doesn't match the new size.
+    pub fn resize(&mut self, width: u32, height: u32, device: &wgpu::Device) {
+        if self.texture.width() != width.max(1) || self.texture.height() != height.max(1) {
+            *self = Self::new(
+                width,
+                height,
+                self.texture.format(),
+                self.sample_count(),
+                device,
+            );
+        }
+    }
+
+    /// Attachment that resolves into `target` at the end of the pass. The
+    /// samples are kept so later passes can load them.
+    pub fn attachment<'a>(
+        &'a self,
+        target: &'a wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'a> {
+        wgpu::RenderPassColorAttachment {
+            view: &self.view,
+            resolve_target: Some(target),
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use futures_channel::mpsc;
+
+/// How far the files loaded through a [Resources](super::Resources) have
+/// got since its progress was last reset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Bytes read or fetched so far, counting files that are still loading.
+    pub bytes: u64,
+    /// Files that have finished loading, whether they could be or not.
+    pub completed: usize,
+    /// Files that have started loading, or are [expected](super::Resources::expect) to.
+    pub total: usize,
+}
+
+impl Progress {
+    /// How much of the loading is done, from 0 to 1. Done when nothing's
+    /// loading.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.completed as f32 / self.total as f32
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.completed >= self.total
+    }
+}
+
+/// Counts loads and tells whoever's listening when they progress.
+#[derive(Debug, Default)]
+pub(crate) struct ProgressTracker {
+    progress: Progress,
+    /// Files counted in the total before they've started.
+    expected: usize,
+    listeners: Vec<mpsc::UnboundedSender<Progress>>,
+}
+
+impl ProgressTracker {
+    pub fn progress(&self) -> Progress {
+        self.progress
+    }
+
+    pub fn expect(&mut self, files: usize) {
+        self.expected += files;
+        self.update(|progress| progress.total += files);
+    }
+
+    pub fn start(&mut self) {
+        if self.expected > 0 {
+            self.expected -= 1;
+        } else {
+            self.update(|progress| progress.total += 1);
+        }
+    }
+
+    pub fn read(&mut self, bytes: usize) {
+        self.update(|progress| progress.bytes += bytes as u64);
+    }
+
+    pub fn complete(&mut self) {
+        self.update(|progress| progress.completed += 1);
+    }
+
+    pub fn reset(&mut self) {
+        self.expected = 0;
+        self.update(|progress| *progress = Progress::default());
+    }
+
+    pub fn listen(&mut self) -> mpsc::UnboundedReceiver<Progress> {
+        let (sender, receiver) = mpsc::unbounded();
+        self.listeners.push(sender);
+        receiver
+    }
+
+    fn update(&mut self, f: impl FnOnce(&mut Progress)) {
+        f(&mut self.progress);
+        let progress = self.progress;
+        // Listeners that have gone away are forgotten.
+        self.listeners
+            .retain(|listener| listener.unbounded_send(progress).is_ok());
+    }
+}
@@ -0,0 +1,133 @@
+use super::{depth::DepthBuffer, msaa::MultisampleBuffer};
+
+/// A texture to draw into instead of the window, like for thumbnails or
+/// effects that read the drawing back. With more than one sample per pixel
+/// it's drawn into through a [MultisampleBuffer] that resolves into it.
+pub struct RenderTarget {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    msaa: Option<MultisampleBuffer>,
+    depth: Option<DepthBuffer>,
+}
+
+impl RenderTarget {
+    /// The texture can be drawn into, sampled and copied from. Pipelines
+    /// drawing into it need the same `format` and `sample_count`.
+    pub fn new(
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        device: &wgpu::Device,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("RenderTarget::texture"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+        let msaa = (sample_count > 1)
+            .then(|| MultisampleBuffer::new(width, height, format, sample_count, device));
+        Self {
+            texture,
+            view,
+            msaa,
+            depth: None,
+        }
+    }
+
+    /// Adds a depth buffer for passes that test against one.
+    pub fn with_depth(mut self, device: &wgpu::Device) -> Self {
+        self.depth = Some(DepthBuffer::new(
+            self.texture.width(),
+            self.texture.height(),
+            self.sample_count(),
+            device,
+        ));
+        self
+    }
+
+    pub fn size(&self) -> glam::UVec2 {
+        glam::uvec2(self.texture.width(), self.texture.height())
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.texture.format()
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.msaa
+            .as_ref()
+            .map_or(1, MultisampleBuffer::sample_count)
+    }
+
+    /// The resolved image.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn depth(&self) -> Option<&DepthBuffer> {
+        self.depth.as_ref()
+    }
+
+    /// Recreates the textures if they don't match the new size. Anything
+    /// drawn is lost.
+    pub fn resize(&mut self, width: u32, height: u32, device: &wgpu::Device) {
+        if self.size() == glam::uvec2(width.max(1), height.max(1)) {
+            return;
+        }
+        let resized = Self::new(width, height, self.format(), self.sample_count(), device);
+        *self = match self.depth {
+            Some(_) => resized.with_depth(device),
+            None => resized,
+        };
+    }
+
+    pub fn color_attachment(
+        &self,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'_> {
+        match &self.msaa {
+            Some(msaa) => msaa.attachment(&self.view, load),
+            None => wgpu::RenderPassColorAttachment {
+                view: &self.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            },
+        }
+    }
+
+    /// Starts a pass that draws into the target, clearing it to `clear`
+    /// first if it's given.
+    pub fn begin_pass<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        clear: Option<wgpu::Color>,
+    ) -> wgpu::RenderPass<'a> {
+        let load = clear.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear);
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("RenderTarget::pass"),
+            color_attachments: &[Some(self.color_attachment(load))],
+            depth_stencil_attachment: self.depth.as_ref().map(DepthBuffer::attachment),
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,671 @@
+use std::{borrow::Cow, io::Read, path::Path};
+
+use anyhow::Context;
+
+use super::{decompress::decompress, target::RenderTarget, Resources};
+
+/// What every KTX2 file starts with.
+const KTX2_MAGIC: [u8; 12] = [
+    0xab, b'K', b'T', b'X', b' ', b'2', b'0', 0xbb, b'\r', b'\n', 0x1a, b'\n',
+];
+
+/// A texture with a view of the whole of it and a sampler, ready to be
+/// bound. Images are uploaded as sRGB, while KTX2 files keep the format
+/// they were stored in.
+pub struct Texture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    /// Linear and clamped to the edges.
+    pub sampler: wgpu::Sampler,
+    pub size: glam::UVec2,
+}
+
+impl Texture {
+    /// Loads a PNG, JPEG, WebP or KTX2 image from `path` in `resources`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(
+        resources: &Resources,
+        path: impl AsRef<Path>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let data = resources.load_binary(path)?;
+        Self::from_bytes(&data, &path.to_string_lossy(), device, queue)
+    }
+
+    pub async fn load_async(
+        resources: &Resources,
+        path: impl AsRef<Path>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let data = resources.load_binary_async(path).await?;
+        Self::from_bytes(&data, &path.to_string_lossy(), device, queue)
+    }
+
+    /// Decodes an encoded image, working out its format from the data.
+    pub fn from_bytes(
+        data: &[u8],
+        label: &str,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Self> {
+        if data.starts_with(&KTX2_MAGIC) {
+            return Self::from_ktx2(data, label, device, queue);
+        }
+        let img = image::load_from_memory(data)
+            .with_context(|| format!("Unable to decode {label}"))?
+            .to_rgba8();
+        Self::from_image(&img, label, device, queue)
+    }
+
+    /// Fails if the image encoded in `data` is wider or taller than
+    /// `max_size`, reading only as much of it as it takes to tell.
+    pub fn ensure_fits(data: &[u8], label: &str, max_size: u32) -> anyhow::Result<()> {
+        let (width, height) = if data.starts_with(&KTX2_MAGIC) {
+            let reader =
+                ktx2::Reader::new(data).with_context(|| format!("Unable to read {label}"))?;
+            let header = reader.header();
+            (header.pixel_width, header.pixel_height)
+        } else {
+            image::ImageReader::new(std::io::Cursor::new(data))
+                .with_guessed_format()?
+                .into_dimensions()
+                .with_context(|| format!("Unable to decode {label}"))?
+        };
+        anyhow::ensure!(
+            width <= max_size && height <= max_size,
+            "{label} is {width} x {height}, but images can't be larger than {max_size} x {max_size}"
+        );
+        Ok(())
+    }
+
+    /// Uploads `img`. An empty image makes a transparent 1×1 texture. Fails
+    /// if it's bigger than the device's textures can be.
+    pub fn from_image(
+        img: &image::RgbaImage,
+        label: &str,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Self> {
+        let dimensions = img.dimensions();
+        check_size(dimensions.0, dimensions.1, label, device)?;
+        let texture_size = wgpu::Extent3d {
+            width: dimensions.0.max(1),
+            height: dimensions.1.max(1),
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label: Some(label),
+            view_formats: &[],
+        });
+        if !img.is_empty() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                img,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * dimensions.0),
+                    rows_per_image: Some(dimensions.1),
+                },
+                texture_size,
+            );
+        }
+        Ok(Self::with_texture(texture, label, device))
+    }
+
+    /// Uploads the 2D image in a KTX2 file along with its mipmaps.
+    /// Block-compressed images are uploaded as they are when the device has
+    /// the feature for their format, and decompressed otherwise, which
+    /// BC1 to BC5, ETC2 and EAC can be. Zstandard supercompression is
+    /// undone first.
+    pub fn from_ktx2(
+        data: &[u8],
+        label: &str,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<Self> {
+        let reader = ktx2::Reader::new(data).with_context(|| format!("Unable to read {label}"))?;
+        let header = reader.header();
+        anyhow::ensure!(
+            header.pixel_depth <= 1 && header.layer_count <= 1 && header.face_count == 1,
+            "{label} isn't a 2D texture"
+        );
+        let format = header
+            .format
+            .and_then(ktx2_format)
+            .ok_or_else(|| anyhow::anyhow!("{label} is in an unsupported format"))?;
+        let mut levels = reader
+            .levels()
+            .map(|level| match header.supercompression_scheme {
+                None => Ok(Cow::Borrowed(level.data)),
+                Some(ktx2::SupercompressionScheme::Zstandard) => {
+                    let mut data = Vec::new();
+                    ruzstd::StreamingDecoder::new(level.data)?.read_to_end(&mut data)?;
+                    Ok(Cow::Owned(data))
+                }
+                Some(scheme) => Err(anyhow::anyhow!(
+                    "{label} is supercompressed with {scheme:?}, which isn't supported"
+                )),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        check_size(header.pixel_width, header.pixel_height, label, device)?;
+        let size = wgpu::Extent3d {
+            width: header.pixel_width.max(1),
+            height: header.pixel_height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let max_levels = size.max_mips(wgpu::TextureDimension::D2);
+        anyhow::ensure!(
+            (1..=max_levels as usize).contains(&levels.len()),
+            "{label} has {} mip levels, but its size allows 1 to {max_levels}",
+            levels.len()
+        );
+        let (block_width, block_height) = format.block_dimensions();
+        let sampleable = device.features().contains(format.required_features())
+            && size.width.is_multiple_of(block_width)
+            && size.height.is_multiple_of(block_height);
+        let format = if sampleable {
+            format
+        } else {
+            for (i, level) in levels.iter_mut().enumerate() {
+                let mip = size.mip_level_size(i as u32, wgpu::TextureDimension::D2);
+                let rgba = decompress(format, level, mip.width, mip.height).ok_or_else(|| {
+                    anyhow::anyhow!("{label} is {format:?}, which the GPU can't sample")
+                })?;
+                *level = Cow::Owned(rgba);
+            }
+            if format.is_srgb() {
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            } else {
+                wgpu::TextureFormat::Rgba8Unorm
+            }
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size,
+            mip_level_count: levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label: Some(label),
+            view_formats: &[],
+        });
+        let (block_width, block_height) = format.block_dimensions();
+        let block_size = format
+            .block_copy_size(None)
+            .ok_or_else(|| anyhow::anyhow!("{label} is {format:?}, which can't be uploaded"))?;
+        for (i, level) in levels.iter().enumerate() {
+            let mip = size
+                .mip_level_size(i as u32, wgpu::TextureDimension::D2)
+                .physical_size(format);
+            let bytes_per_row = mip.width / block_width * block_size;
+            let rows = mip.height / block_height;
+            anyhow::ensure!(
+                level.len() >= (bytes_per_row * rows) as usize,
+                "Mip level {i} of {label} is cut short"
+            );
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: i as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                level,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(rows),
+                },
+                mip,
+            );
+        }
+        Ok(Self::with_texture(texture, label, device))
+    }
+
+    fn with_texture(texture: wgpu::Texture, label: &str, device: &wgpu::Device) -> Self {
+        let view = texture.create_view(&Default::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
+            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Self {
+            size: glam::uvec2(texture.width(), texture.height()),
+            texture,
+            view,
+            sampler,
+        }
+    }
+}
+
+/// Fails if a `width`×`height` texture is bigger than `device` allows.
+fn check_size(width: u32, height: u32, label: &str, device: &wgpu::Device) -> anyhow::Result<()> {
+    let max = device.limits().max_texture_dimension_2d;
+    anyhow::ensure!(
+        width <= max && height <= max,
+        "{label} is {width} x {height}, but textures can't be larger than {max} x {max}"
+    );
+    Ok(())
+}
+
+/// The texture format KTX2's `format`, a Vulkan one, is uploaded as.
+fn source_hash(data: &[u8]) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn ktx2_format(format: ktx2::Format) -> Option<wgpu::TextureFormat> {
+    use ktx2::Format as K;
+    use wgpu::{AstcBlock, AstcChannel, TextureFormat as F};
+
+    const ASTC_BLOCKS: [AstcBlock; 14] = [
+        AstcBlock::B4x4,
+        AstcBlock::B5x4,
+        AstcBlock::B5x5,
+        AstcBlock::B6x5,
+        AstcBlock::B6x6,
+        AstcBlock::B8x5,
+        AstcBlock::B8x6,
+        AstcBlock::B8x8,
+        AstcBlock::B10x5,
+        AstcBlock::B10x6,
+        AstcBlock::B10x8,
+        AstcBlock::B10x10,
+        AstcBlock::B12x10,
+        AstcBlock::B12x12,
+    ];
+    // Each ASTC block size has an unorm and then an sRGB format, with the
+    // HDR ones numbered separately.
+    let astc = |block: u32, channel| {
+        let block = *ASTC_BLOCKS.get(block as usize)?;
+        Some(F::Astc { block, channel })
+    };
+    let value = format.value();
+    match format {
+        K::R8_UNORM => Some(F::R8Unorm),
+        K::R8G8_UNORM => Some(F::Rg8Unorm),
+        K::R8G8B8A8_UNORM => Some(F::Rgba8Unorm),
+        K::R8G8B8A8_SRGB => Some(F::Rgba8UnormSrgb),
+        K::B8G8R8A8_UNORM => Some(F::Bgra8Unorm),
+        K::B8G8R8A8_SRGB => Some(F::Bgra8UnormSrgb),
+        K::R16G16B16A16_SFLOAT => Some(F::Rgba16Float),
+        K::BC1_RGB_UNORM_BLOCK | K::BC1_RGBA_UNORM_BLOCK => Some(F::Bc1RgbaUnorm),
+        K::BC1_RGB_SRGB_BLOCK | K::BC1_RGBA_SRGB_BLOCK => Some(F::Bc1RgbaUnormSrgb),
+        K::BC2_UNORM_BLOCK => Some(F::Bc2RgbaUnorm),
+        K::BC2_SRGB_BLOCK => Some(F::Bc2RgbaUnormSrgb),
+        K::BC3_UNORM_BLOCK => Some(F::Bc3RgbaUnorm),
+        K::BC3_SRGB_BLOCK => Some(F::Bc3RgbaUnormSrgb),
+        K::BC4_UNORM_BLOCK => Some(F::Bc4RUnorm),
+        K::BC4_SNORM_BLOCK => Some(F::Bc4RSnorm),
+        K::BC5_UNORM_BLOCK => Some(F::Bc5RgUnorm),
+        K::BC5_SNORM_BLOCK => Some(F::Bc5RgSnorm),
+        K::BC6H_UFLOAT_BLOCK => Some(F::Bc6hRgbUfloat),
+        K::BC6H_SFLOAT_BLOCK => Some(F::Bc6hRgbFloat),
+        K::BC7_UNORM_BLOCK => Some(F::Bc7RgbaUnorm),
+        K::BC7_SRGB_BLOCK => Some(F::Bc7RgbaUnormSrgb),
+        K::ETC2_R8G8B8_UNORM_BLOCK => Some(F::Etc2Rgb8Unorm),
+        K::ETC2_R8G8B8_SRGB_BLOCK => Some(F::Etc2Rgb8UnormSrgb),
+        K::ETC2_R8G8B8A1_UNORM_BLOCK => Some(F::Etc2Rgb8A1Unorm),
+        K::ETC2_R8G8B8A1_SRGB_BLOCK => Some(F::Etc2Rgb8A1UnormSrgb),
+        K::ETC2_R8G8B8A8_UNORM_BLOCK => Some(F::Etc2Rgba8Unorm),
+        K::ETC2_R8G8B8A8_SRGB_BLOCK => Some(F::Etc2Rgba8UnormSrgb),
+        K::EAC_R11_UNORM_BLOCK => Some(F::EacR11Unorm),
+        K::EAC_R11_SNORM_BLOCK => Some(F::EacR11Snorm),
+        K::EAC_R11G11_UNORM_BLOCK => Some(F::EacRg11Unorm),
+        K::EAC_R11G11_SNORM_BLOCK => Some(F::EacRg11Snorm),
+        _ if (K::ASTC_4x4_UNORM_BLOCK.value()..=K::ASTC_12x12_SRGB_BLOCK.value())
+            .contains(&value) =>
+        {
+            let offset = value - K::ASTC_4x4_UNORM_BLOCK.value();
+            let channel = if offset.is_multiple_of(2) {
+                AstcChannel::Unorm
+            } else {
+                AstcChannel::UnormSrgb
+            };
+            astc(offset / 2, channel)
+        }
+        _ if value >= K::ASTC_4x4_SFLOAT_BLOCK.value() => {
+            astc(value - K::ASTC_4x4_SFLOAT_BLOCK.value(), AstcChannel::Hdr)
+        }
+        _ => None,
+    }
+}
+
+/// Handle to a texture in a [TextureRegistry].
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+pub struct TextureId(u32);
+
+impl TextureId {
+    pub fn index(&self) -> u32 {
+        self.0
+    }
+}
+
+pub struct RegisteredTexture {
+    name: String,
+    /// The encoded image the texture was loaded from, kept so documents can
+    /// embed it.
+    source: Option<Vec<u8>>,
+    /// Hash of [RegisteredTexture::source], for finding textures by their
+    /// contents.
+    source_hash: Option<u64>,
+    size: glam::UVec2,
+    /// `None` for render targets, which keep their own texture.
+    texture: Option<wgpu::Texture>,
+    bind_group: wgpu::BindGroup,
+}
+
+impl RegisteredTexture {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> Option<&[u8]> {
+        self.source.as_deref()
+    }
+
+    fn set_source(&mut self, data: &[u8]) {
+        self.source = Some(data.to_vec());
+        self.source_hash = Some(source_hash(data));
+    }
+
+    pub fn size(&self) -> glam::UVec2 {
+        self.size
+    }
+
+    /// The texture, unless it belongs to a [RenderTarget].
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        self.texture.as_ref()
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
+
+/// Images that shapes can be filled with. Every texture is bound with the
+/// same layout as the font atlas, using a repeating sampler.
+pub struct TextureRegistry {
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    textures: Vec<Option<RegisteredTexture>>,
+}
+
+impl TextureRegistry {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("texture_bindgroup_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("TextureRegistry::sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Self {
+            layout,
+            sampler,
+            textures: Vec::new(),
+        }
+    }
+
+    /// Layout of the bind group holding a texture and its sampler.
+    pub fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+
+    /// Decodes an image and registers it under `name`.
+    pub fn load(
+        &mut self,
+        name: impl Into<String>,
+        data: &[u8],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<TextureId> {
+        let name = name.into();
+        let texture = Texture::from_bytes(data, &name, device, queue)?;
+        let id = self.insert_texture(name, texture, device);
+        if let Some(Some(texture)) = self.textures.get_mut(id.0 as usize) {
+            texture.set_source(data);
+        }
+        Ok(id)
+    }
+
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        img: &image::RgbaImage,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<TextureId> {
+        let name = name.into();
+        let texture = Texture::from_image(img, &name, device, queue)?;
+        Ok(self.insert_texture(name, texture, device))
+    }
+
+    fn insert_texture(
+        &mut self,
+        name: String,
+        texture: Texture,
+        device: &wgpu::Device,
+    ) -> TextureId {
+        let bind_group = self.bind(&name, &texture.view, device);
+        self.register(RegisteredTexture {
+            name,
+            source: None,
+            source_hash: None,
+            size: texture.size,
+            texture: Some(texture.texture),
+            bind_group,
+        })
+    }
+
+    /// Decodes an image into the texture `id`, keeping its name. Shapes
+    /// filled with it show the new image. Returns `false` if there's no such
+    /// texture.
+    pub fn replace(
+        &mut self,
+        id: TextureId,
+        data: &[u8],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<bool> {
+        let Some(Some(entry)) = self.textures.get(id.0 as usize) else {
+            return Ok(false);
+        };
+        let texture = Texture::from_bytes(data, &entry.name, device, queue)?;
+        let bind_group = self.bind(&entry.name, &texture.view, device);
+        let Some(Some(entry)) = self.textures.get_mut(id.0 as usize) else {
+            return Ok(false);
+        };
+        entry.set_source(data);
+        entry.size = texture.size;
+        entry.texture = Some(texture.texture);
+        entry.bind_group = bind_group;
+        Ok(true)
+    }
+
+    /// Registers what's been drawn into `target`, so shapes can be filled
+    /// with it. The target keeps its texture, so it can go on being drawn
+    /// into. Resizing it recreates the texture, after which it has to be
+    /// registered again with [TextureRegistry::rebind_target].
+    pub fn insert_target(
+        &mut self,
+        name: impl Into<String>,
+        target: &RenderTarget,
+        device: &wgpu::Device,
+    ) -> TextureId {
+        let name = name.into();
+        let bind_group = self.bind(&name, target.view(), device);
+        self.register(RegisteredTexture {
+            name,
+            source: None,
+            source_hash: None,
+            size: target.size(),
+            texture: None,
+            bind_group,
+        })
+    }
+
+    /// Points a texture registered with [TextureRegistry::insert_target] at
+    /// the target's current texture. Returns `false` if there's no such
+    /// texture.
+    pub fn rebind_target(
+        &mut self,
+        id: TextureId,
+        target: &RenderTarget,
+        device: &wgpu::Device,
+    ) -> bool {
+        let Some(Some(entry)) = self.textures.get(id.0 as usize) else {
+            return false;
+        };
+        let bind_group = self.bind(&entry.name, target.view(), device);
+        let Some(Some(entry)) = self.textures.get_mut(id.0 as usize) else {
+            return false;
+        };
+        entry.size = target.size();
+        entry.bind_group = bind_group;
+        true
+    }
+
+    fn bind(
+        &self,
+        label: &str,
+        view: &wgpu::TextureView,
+        device: &wgpu::Device,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        })
+    }
+
+    fn register(&mut self, entry: RegisteredTexture) -> TextureId {
+        // Reuse the slot of a removed texture if there is one.
+        let index = match self.textures.iter().position(Option::is_none) {
+            Some(index) => {
+                self.textures[index] = Some(entry);
+                index
+            }
+            None => {
+                self.textures.push(Some(entry));
+                self.textures.len() - 1
+            }
+        };
+        TextureId(index as u32)
+    }
+
+    /// Unregisters a texture. Shapes that still use it aren't filled.
+    pub fn remove(&mut self, id: TextureId) -> Option<RegisteredTexture> {
+        self.textures.get_mut(id.0 as usize)?.take()
+    }
+
+    pub fn get(&self, id: TextureId) -> Option<&RegisteredTexture> {
+        self.textures.get(id.0 as usize)?.as_ref()
+    }
+
+    /// Looks a texture up by the name it was registered with.
+    pub fn find(&self, name: &str) -> Option<TextureId> {
+        self.iter()
+            .find(|(_, texture)| texture.name == name)
+            .map(|(id, _)| id)
+    }
+
+    /// Looks a texture up by the encoded image it was loaded from.
+    pub fn find_source(&self, data: &[u8]) -> Option<TextureId> {
+        let hash = source_hash(data);
+        self.iter()
+            .find(|(_, texture)| {
+                texture.source_hash == Some(hash) && texture.source() == Some(data)
+            })
+            .map(|(id, _)| id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (TextureId, &RegisteredTexture)> {
+        self.textures
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| Some((TextureId(i as u32), t.as_ref()?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        image::RgbaImage::new(width, height)
+            .write_to(
+                &mut std::io::Cursor::new(&mut data),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn oversized_images_dont_fit() {
+        assert!(Texture::ensure_fits(&png(64, 64), "square.png", 64).is_ok());
+        let error = Texture::ensure_fits(&png(65, 1), "wide.png", 64).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "wide.png is 65 x 1, but images can't be larger than 64 x 64"
+        );
+        assert!(Texture::ensure_fits(&png(1, 65), "tall.png", 64).is_err());
+        assert!(Texture::ensure_fits(b"not an image", "junk.png", 64).is_err());
+    }
+}
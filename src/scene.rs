@@ -0,0 +1,504 @@
+use glam::{Affine2, Vec2};
+
+use crate::shape::{Bounds, Shape};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    /// Slot index of the node. This doubles as the node's instance index on
+    /// the GPU.
+    pub fn index(self) -> u32 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Transform2D {
+    pub translation: Vec2,
+    /// Rotation in radians.
+    pub rotation: f32,
+    pub scale: Vec2,
+}
+
+impl Default for Transform2D {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform2D {
+    pub const IDENTITY: Self = Self {
+        translation: Vec2::ZERO,
+        rotation: 0.0,
+        scale: Vec2::ONE,
+    };
+
+    pub fn from_translation(translation: Vec2) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn to_affine(&self) -> Affine2 {
+        Affine2::from_scale_angle_translation(self.scale, self.rotation, self.translation)
+    }
+
+    /// Decomposes an affine transform. Any shear is lost.
+    pub fn from_affine(affine: Affine2) -> Self {
+        let (scale, rotation, translation) = affine.to_scale_angle_translation();
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum NodeKind {
+    /// A node that has no content of its own and only exists to transform
+    /// its children.
+    Group,
+    Shape(Shape),
+    Text(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub name: String,
+    pub kind: NodeKind,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    local: Transform2D,
+    world: Affine2,
+    /// The cached world transform is stale.
+    dirty: bool,
+    /// The world transform changed since the last
+    /// [Scene::update_transforms] call.
+    pending_upload: bool,
+}
+
+impl Node {
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    pub fn transform(&self) -> &Transform2D {
+        &self.local
+    }
+
+    /// World transform as of the last [Scene::update_transforms] or
+    /// [Scene::world_transform] call.
+    pub fn world(&self) -> Affine2 {
+        self.world
+    }
+
+    pub fn shape(&self) -> Option<&Shape> {
+        match &self.kind {
+            NodeKind::Shape(shape) => Some(shape),
+            _ => None,
+        }
+    }
+
+    pub fn shape_mut(&mut self) -> Option<&mut Shape> {
+        match &mut self.kind {
+            NodeKind::Shape(shape) => Some(shape),
+            _ => None,
+        }
+    }
+}
+
+/// Nodes removed from a [Scene], kept around so they can be restored.
+#[derive(Debug, Clone)]
+pub struct Subtree {
+    /// Parents come before their children.
+    nodes: Vec<(NodeId, Node)>,
+    parent: Option<NodeId>,
+    position: usize,
+}
+
+impl Subtree {
+    pub fn root(&self) -> Option<NodeId> {
+        self.nodes.first().map(|(id, _)| *id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &Node)> {
+        self.nodes.iter().map(|(id, n)| (*id, n))
+    }
+}
+
+/// A tree of nodes with hierarchical transforms. World transforms are only
+/// recomputed when a node or one of its ancestors changed.
+#[derive(Debug, Clone, Default)]
+pub struct Scene {
+    nodes: Vec<Option<Node>>,
+    free: Vec<u32>,
+    roots: Vec<NodeId>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        kind: NodeKind,
+        transform: Transform2D,
+        parent: Option<NodeId>,
+    ) -> NodeId {
+        let node = Node {
+            name: name.into(),
+            kind,
+            parent: None,
+            children: Vec::new(),
+            local: transform,
+            world: transform.to_affine(),
+            dirty: true,
+            pending_upload: true,
+        };
+
+        let id = match self.free.pop() {
+            Some(i) => {
+                self.nodes[i as usize] = Some(node);
+                NodeId(i)
+            }
+            None => {
+                self.nodes.push(Some(node));
+                NodeId(self.nodes.len() as u32 - 1)
+            }
+        };
+
+        self.attach(id, parent, usize::MAX);
+        id
+    }
+
+    /// Removes a node and all of its descendants. The returned [Subtree] can
+    /// be passed to [Scene::restore] to put them back with the same ids.
+    pub fn remove(&mut self, id: NodeId) -> Option<Subtree> {
+        let parent = self.get(id)?.parent;
+        let position = self.detach(id);
+
+        let mut nodes = Vec::new();
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if let Some(node) = self.nodes[id.0 as usize].take() {
+                stack.extend(node.children.iter().rev());
+                self.free.push(id.0);
+                nodes.push((id, node));
+            }
+        }
+
+        Some(Subtree {
+            nodes,
+            parent,
+            position,
+        })
+    }
+
+    /// Puts a removed subtree back where it was. Fails if one of its ids has
+    /// been reused in the mean time or its parent no longer exists.
+    pub fn restore(&mut self, subtree: Subtree) -> Result<(), Subtree> {
+        let slots_free = subtree.nodes.iter().all(|(id, _)| !self.contains(*id));
+        let parent_ok = subtree.parent.is_none_or(|p| self.contains(p));
+        if !slots_free || !parent_ok || subtree.nodes.is_empty() {
+            return Err(subtree);
+        }
+
+        let root = subtree.nodes[0].0;
+        for (id, node) in subtree.nodes {
+            let index = id.0 as usize;
+            if index >= self.nodes.len() {
+                self.nodes.resize_with(index + 1, || None);
+            }
+            self.free.retain(|&f| f != id.0);
+            self.nodes[index] = Some(node);
+        }
+
+        let siblings = match subtree.parent {
+            Some(p) => &mut self.get_mut(p).unwrap().children,
+            None => &mut self.roots,
+        };
+        siblings.insert(subtree.position.min(siblings.len()), root);
+        self.mark_dirty(root);
+        Ok(())
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.0 as usize).and_then(|n| n.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes.get_mut(id.0 as usize).and_then(|n| n.as_mut())
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// Number of node slots, including free ones. Instance buffers need to
+    /// be at least this long.
+    pub fn capacity(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Re-parents `id`. Passing `None` makes it a root node. Returns false if
+    /// the change would create a cycle.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> bool {
+        self.move_to(id, parent, usize::MAX)
+    }
+
+    /// Re-parents `id`, inserting it at `position` among its new siblings.
+    /// Positions past the end append it.
+    pub fn move_to(&mut self, id: NodeId, parent: Option<NodeId>, position: usize) -> bool {
+        if let Some(parent) = parent {
+            if !self.contains(parent) || self.is_ancestor_of(id, parent) {
+                return false;
+            }
+        }
+        if !self.contains(id) {
+            return false;
+        }
+        self.detach(id);
+        self.attach(id, parent, position);
+        true
+    }
+
+    /// Index of `id` among its siblings.
+    pub fn position(&self, id: NodeId) -> Option<usize> {
+        let siblings = match self.get(id)?.parent {
+            Some(p) => &self.get(p)?.children,
+            None => &self.roots,
+        };
+        siblings.iter().position(|&c| c == id)
+    }
+
+    /// The root node `id` belongs to.
+    pub fn root_of(&self, id: NodeId) -> NodeId {
+        let mut current = id;
+        while let Some(parent) = self.get(current).and_then(|n| n.parent) {
+            current = parent;
+        }
+        current
+    }
+
+    /// Whether `ancestor` is `id` or one of its parents.
+    pub fn is_ancestor_of(&self, ancestor: NodeId, id: NodeId) -> bool {
+        let mut current = Some(id);
+        while let Some(c) = current {
+            if c == ancestor {
+                return true;
+            }
+            current = self.get(c).and_then(|n| n.parent);
+        }
+        false
+    }
+
+    pub fn set_transform(&mut self, id: NodeId, transform: Transform2D) {
+        if let Some(node) = self.get_mut(id) {
+            node.local = transform;
+            self.mark_dirty(id);
+        }
+    }
+
+    pub fn translate(&mut self, id: NodeId, delta: Vec2) {
+        if let Some(node) = self.get(id) {
+            let mut t = node.local;
+            t.translation += delta;
+            self.set_transform(id, t);
+        }
+    }
+
+    /// Returns the world transform of `id`, recomputing it and any dirty
+    /// ancestors first.
+    pub fn world_transform(&mut self, id: NodeId) -> Affine2 {
+        let Some(node) = self.get(id) else {
+            return Affine2::IDENTITY;
+        };
+        if !node.dirty {
+            return node.world;
+        }
+
+        let local = node.local.to_affine();
+        let world = match node.parent {
+            Some(parent) => self.world_transform(parent) * local,
+            None => local,
+        };
+        let node = self.get_mut(id).unwrap();
+        node.world = world;
+        node.dirty = false;
+        world
+    }
+
+    /// World space bounds of a node including all of its descendants.
+    pub fn world_bounds(&mut self, id: NodeId) -> Option<Bounds> {
+        let mut bounds: Option<Bounds> = None;
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let world = self.world_transform(id);
+            let Some(node) = self.get(id) else {
+                continue;
+            };
+            stack.extend(node.children.iter().copied());
+            let Some(outline) = node.shape().map(|s| s.outline()) else {
+                continue;
+            };
+            let b = Bounds::from_points(outline.iter().map(|&p| world.transform_point2(p)));
+            bounds = match (bounds, b) {
+                (Some(a), Some(b)) => Some(a.union(&b)),
+                (a, b) => a.or(b),
+            };
+        }
+        bounds
+    }
+
+    /// Recomputes every dirty world transform, calling `f` for each node that
+    /// changed so the new value can be pushed to the GPU.
+    pub fn update_transforms(&mut self, mut f: impl FnMut(NodeId, Affine2)) {
+        let mut stack: Vec<(NodeId, Affine2)> = self
+            .roots
+            .iter()
+            .rev()
+            .map(|&id| (id, Affine2::IDENTITY))
+            .collect();
+
+        while let Some((id, parent_world)) = stack.pop() {
+            let node = self.nodes[id.0 as usize].as_mut().unwrap();
+            if node.dirty {
+                node.world = parent_world * node.local.to_affine();
+                node.dirty = false;
+            }
+            if node.pending_upload {
+                node.pending_upload = false;
+                f(id, node.world);
+            }
+            let world = node.world;
+            stack.extend(node.children.iter().rev().map(|&c| (c, world)));
+        }
+    }
+
+    /// Iterates nodes depth first, parents before children, which is also
+    /// the order they are drawn in.
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &Node)> {
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        std::iter::from_fn(move || {
+            let id = stack.pop()?;
+            let node = self.get(id)?;
+            stack.extend(node.children.iter().rev());
+            Some((id, node))
+        })
+    }
+
+    fn attach(&mut self, id: NodeId, parent: Option<NodeId>, position: usize) {
+        let parent = parent.filter(|&p| self.contains(p));
+        let siblings = match parent {
+            Some(p) => &mut self.get_mut(p).unwrap().children,
+            None => &mut self.roots,
+        };
+        siblings.insert(position.min(siblings.len()), id);
+        self.get_mut(id).unwrap().parent = parent;
+        self.mark_dirty(id);
+    }
+
+    /// Unlinks `id` from its parent, returning the position it had among
+    /// its siblings.
+    fn detach(&mut self, id: NodeId) -> usize {
+        let parent = self.get(id).and_then(|n| n.parent);
+        let siblings = match parent {
+            Some(p) => match self.get_mut(p) {
+                Some(p) => &mut p.children,
+                None => return 0,
+            },
+            None => &mut self.roots,
+        };
+        match siblings.iter().position(|&c| c == id) {
+            Some(position) => {
+                siblings.remove(position);
+                position
+            }
+            None => 0,
+        }
+    }
+
+    fn mark_dirty(&mut self, id: NodeId) {
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if let Some(node) = self.get_mut(id) {
+                node.dirty = true;
+                node.pending_upload = true;
+                stack.extend(node.children.iter().copied());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec2;
+
+    use super::*;
+
+    fn group(scene: &mut Scene, x: f32, y: f32, parent: Option<NodeId>) -> NodeId {
+        let transform = Transform2D::from_translation(vec2(x, y));
+        scene.insert("group", NodeKind::Group, transform, parent)
+    }
+
+    #[test]
+    fn reparenting_keeps_the_local_transform() {
+        let mut scene = Scene::new();
+        let a = group(&mut scene, 10.0, 0.0, None);
+        let b = group(&mut scene, 0.0, 5.0, None);
+        let child = group(&mut scene, 1.0, 1.0, Some(a));
+        assert_eq!(scene.world_transform(child).translation, vec2(11.0, 1.0));
+
+        assert!(scene.set_parent(child, Some(b)));
+        assert_eq!(scene.get(child).unwrap().parent(), Some(b));
+        assert!(scene.get(a).unwrap().children().is_empty());
+        assert_eq!(scene.get(b).unwrap().children(), &[child]);
+        assert_eq!(scene.world_transform(child).translation, vec2(1.0, 6.0));
+
+        assert!(scene.set_parent(child, None));
+        assert_eq!(scene.roots(), &[a, b, child]);
+        assert_eq!(scene.world_transform(child).translation, vec2(1.0, 1.0));
+    }
+
+    #[test]
+    fn reparenting_refuses_cycles() {
+        let mut scene = Scene::new();
+        let root = group(&mut scene, 0.0, 0.0, None);
+        let child = group(&mut scene, 0.0, 0.0, Some(root));
+        let grandchild = group(&mut scene, 0.0, 0.0, Some(child));
+        assert!(!scene.set_parent(root, Some(grandchild)));
+        assert!(!scene.set_parent(child, Some(child)));
+        assert_eq!(scene.get(root).unwrap().parent(), None);
+        assert_eq!(scene.get(grandchild).unwrap().parent(), Some(child));
+    }
+
+    #[test]
+    fn moving_places_nodes_among_their_siblings() {
+        let mut scene = Scene::new();
+        let parent = group(&mut scene, 0.0, 0.0, None);
+        let first = group(&mut scene, 0.0, 0.0, Some(parent));
+        let second = group(&mut scene, 0.0, 0.0, Some(parent));
+        let root = group(&mut scene, 0.0, 0.0, None);
+        assert!(scene.move_to(root, Some(parent), 1));
+        assert_eq!(
+            scene.get(parent).unwrap().children(),
+            &[first, root, second]
+        );
+        assert_eq!(scene.position(root), Some(1));
+        assert_eq!(scene.roots(), &[parent]);
+        assert_eq!(scene.root_of(root), parent);
+    }
+}
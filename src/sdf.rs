@@ -0,0 +1,392 @@
+use glam::{Affine2, Vec2, Vec4};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::{
+    layers::LayerStack,
+    resources::{
+        buffer::BackedBuffer,
+        camera::{CameraBinder, CameraBinding},
+    },
+    shape::{Fill, Geometry, SdfOp, Shape},
+    stats,
+    utils::{self, RenderPipelineBuilder},
+    wgsl,
+};
+
+pub const DEFAULT_BLEND_RADIUS: f32 = 16.0;
+/// How many shapes are drawn on devices without storage buffers, like
+/// WebGL2, where they have to fit in a uniform buffer.
+pub const MAX_UNIFORM_SHAPES: usize = 64;
+/// How many polyline points those shapes can have between them.
+pub const MAX_UNIFORM_POINTS: usize = 1024;
+
+const SHAPE_CIRCLE: u32 = 0;
+const SHAPE_BOX: u32 = 1;
+const SHAPE_POLYGON: u32 = 2;
+const SHAPE_POLYLINE: u32 = 3;
+
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct SdfUniform {
+    count: u32,
+    blend_radius: f32,
+    _padding: [u32; 2],
+}
+
+/// GPU layout of a shape for [SdfPipeline].
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct SdfShape {
+    inverse: [f32; 4],
+    inverse_translation: [f32; 2],
+    scale: f32,
+    kind: u32,
+    params: [f32; 4],
+    stroke: [f32; 4],
+    fill: [f32; 4],
+    op: u32,
+    _padding: [u32; 3],
+}
+
+impl SdfShape {
+    /// Describes `shape` drawn with the world transform `world`. Polyline
+    /// points are appended to `points`. Returns `None` for degenerate
+    /// shapes.
+    pub fn new(
+        shape: &Shape,
+        world: Affine2,
+        opacity: f32,
+        points: &mut Vec<Vec2>,
+    ) -> Option<Self> {
+        let det = world.matrix2.determinant();
+        if det.abs() <= f32::EPSILON {
+            return None;
+        }
+        let (kind, params) = match &shape.geometry {
+            Geometry::Circle { center, radius } => {
+                (SHAPE_CIRCLE, [center.x, center.y, *radius, 0.0])
+            }
+            Geometry::Rect { min, max } => (SHAPE_BOX, [min.x, min.y, max.x, max.y]),
+            Geometry::Polyline { points: p, closed } => {
+                if p.len() < 2 {
+                    return None;
+                }
+                let offset = points.len() as f32;
+                points.extend_from_slice(p);
+                let kind = if *closed {
+                    SHAPE_POLYGON
+                } else {
+                    SHAPE_POLYLINE
+                };
+                (kind, [offset, p.len() as f32, 0.0, 0.0])
+            }
+        };
+        // Gradients and textures are approximated with a flat color.
+        let fill = match shape.fill.as_ref().filter(|_| shape.is_closed()) {
+            Some(Fill::Solid(color)) => *color,
+            Some(Fill::Gradient(gradient)) => gradient.color_at(0.0),
+            Some(Fill::Texture(fill)) => fill.tint,
+            None => Vec4::ZERO,
+        };
+        let opacity = Vec4::new(1.0, 1.0, 1.0, opacity);
+        let inverse = world.inverse();
+        Some(Self {
+            inverse: inverse.matrix2.to_cols_array(),
+            inverse_translation: inverse.translation.to_array(),
+            scale: det.abs().sqrt(),
+            kind,
+            params,
+            stroke: (shape.color * opacity).to_array(),
+            fill: (fill * opacity).to_array(),
+            op: shape.sdf_op.index(),
+            _padding: [0; 3],
+        })
+    }
+}
+
+/// Draws every shape from its distance function in one fullscreen pass.
+/// This is what [DrawMode::Sdf](crate::resources::geometry::DrawMode::Sdf)
+/// uses. Text isn't included.
+pub struct SdfPipeline {
+    layout: wgpu::BindGroupLayout,
+    #[cfg(not(target_arch = "wasm32"))]
+    pipeline_layout: wgpu::PipelineLayout,
+    #[cfg(not(target_arch = "wasm32"))]
+    surface_format: wgpu::TextureFormat,
+    #[cfg(not(target_arch = "wasm32"))]
+    sample_count: u32,
+    pipeline: wgpu::RenderPipeline,
+    uniform: wgpu::Buffer,
+    shapes: BackedBuffer<SdfShape>,
+    points: BackedBuffer<Vec2>,
+    /// Shapes and points that fit in `shapes` and `points`. Only limited
+    /// without storage buffers.
+    max_shapes: usize,
+    max_points: usize,
+    /// How far apart shapes start to blend when using
+    /// [SdfOp::SmoothUnion] or [SdfOp::SmoothSubtract].
+    blend_radius: f32,
+    bind_group: wgpu::BindGroup,
+    /// Buffer versions the bind group was created with.
+    versions: (u32, u32),
+}
+
+impl SdfPipeline {
+    pub fn new(
+        camera_binder: &CameraBinder,
+        surface_format: wgpu::TextureFormat,
+        sample_count: u32,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<Self> {
+        let (max_shapes, max_points) = if utils::has_storage_buffers(device) {
+            (usize::MAX, usize::MAX)
+        } else {
+            (MAX_UNIFORM_SHAPES, MAX_UNIFORM_POINTS)
+        };
+
+        let storage = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: utils::array_binding_type(device),
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("SdfPipeline::layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1),
+                storage(2),
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("SdfPipeline::pipeline_layout"),
+            bind_group_layouts: &[camera_binder.layout(), &layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = Self::build(
+            &wgsl::compose_built_in(include_str!("sdf.wgsl"))?,
+            &pipeline_layout,
+            surface_format,
+            sample_count,
+            device,
+        )?;
+
+        let uniform = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("SdfPipeline::uniform"),
+            contents: bytemuck::bytes_of(&SdfUniform {
+                count: 0,
+                blend_radius: DEFAULT_BLEND_RADIUS,
+                _padding: [0; 2],
+            }),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+        });
+        let usage = utils::array_buffer_usage(device);
+        let shapes = BackedBuffer::with_capacity(device, MAX_UNIFORM_SHAPES as _, usage);
+        let points = BackedBuffer::with_capacity(device, MAX_UNIFORM_POINTS as _, usage);
+        let bind_group = Self::bind(&layout, &uniform, &shapes, &points, device);
+
+        Ok(Self {
+            versions: (shapes.version(), points.version()),
+            layout,
+            #[cfg(not(target_arch = "wasm32"))]
+            pipeline_layout,
+            #[cfg(not(target_arch = "wasm32"))]
+            surface_format,
+            #[cfg(not(target_arch = "wasm32"))]
+            sample_count,
+            pipeline,
+            uniform,
+            shapes,
+            points,
+            max_shapes,
+            max_points,
+            blend_radius: DEFAULT_BLEND_RADIUS,
+            bind_group,
+        })
+    }
+
+    fn build(
+        source: &str,
+        pipeline_layout: &wgpu::PipelineLayout,
+        surface_format: wgpu::TextureFormat,
+        sample_count: u32,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        // Points are packed two to an element.
+        let shader = utils::array_shader(
+            device,
+            "sdf.wgsl",
+            source,
+            &[
+                ("shapes", MAX_UNIFORM_SHAPES),
+                ("points", MAX_UNIFORM_POINTS / 2),
+            ],
+        );
+        RenderPipelineBuilder::new()
+            .label("SdfPipeline::pipeline")
+            .layout(pipeline_layout)
+            .vertex(wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("fullscreen_quad"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            })
+            .fragment(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("sdf_shapes"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            })
+            .sample_count(sample_count)
+            .build(device)
+    }
+
+    /// Recompiles the shader from `source` and rebuilds the pipeline with
+    /// it. Keeps the old one if that fails.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload(&mut self, source: &str, device: &wgpu::Device) -> anyhow::Result<()> {
+        self.pipeline = utils::validated(device, || {
+            Self::build(
+                source,
+                &self.pipeline_layout,
+                self.surface_format,
+                self.sample_count,
+                device,
+            )
+        })??;
+        Ok(())
+    }
+
+    fn bind(
+        layout: &wgpu::BindGroupLayout,
+        uniform: &wgpu::Buffer,
+        shapes: &BackedBuffer<SdfShape>,
+        points: &BackedBuffer<Vec2>,
+        device: &wgpu::Device,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("SdfPipeline::bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: shapes.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: points.buffer().as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Uploads the shapes of every visible layer. World transforms need to
+    /// be up to date, so call this after [Layer::prepare](crate::layers::Layer::prepare).
+    /// Without storage buffers, shapes past [MAX_UNIFORM_SHAPES] or
+    /// [MAX_UNIFORM_POINTS] aren't drawn.
+    pub fn prepare(&mut self, layers: &LayerStack, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let mut shapes = Vec::new();
+        let mut points = Vec::new();
+        for layer in layers.visible() {
+            let first = shapes.len();
+            for (_, node) in layer.scene().iter() {
+                let Some(shape) = node.shape() else {
+                    continue;
+                };
+                let count = points.len();
+                let Some(sdf) = SdfShape::new(
+                    &layers.styles().resolve(shape),
+                    node.world(),
+                    layer.opacity(),
+                    &mut points,
+                ) else {
+                    continue;
+                };
+                if shapes.len() == self.max_shapes || points.len() > self.max_points {
+                    points.truncate(count);
+                    continue;
+                }
+                shapes.push(sdf);
+            }
+            // Shapes don't merge with ones on other layers.
+            if let Some(shape) = shapes.get_mut(first) {
+                shape.op = SdfOp::None.index();
+            }
+        }
+
+        self.shapes.clear();
+        let mut batch = self.shapes.batch(device, queue);
+        for shape in &shapes {
+            batch.push(*shape);
+        }
+        drop(batch);
+        self.points.clear();
+        let mut batch = self.points.batch(device, queue);
+        for point in points {
+            batch.push(point);
+        }
+        drop(batch);
+        stats::write_buffer(
+            queue,
+            &self.uniform,
+            0,
+            bytemuck::bytes_of(&SdfUniform {
+                count: shapes.len() as u32,
+                blend_radius: self.blend_radius,
+                _padding: [0; 2],
+            }),
+        );
+
+        let versions = (self.shapes.version(), self.points.version());
+        if versions != self.versions {
+            self.versions = versions;
+            self.bind_group = Self::bind(
+                &self.layout,
+                &self.uniform,
+                &self.shapes,
+                &self.points,
+                device,
+            );
+        }
+    }
+
+    pub fn blend_radius(&self) -> f32 {
+        self.blend_radius
+    }
+
+    /// Sets the blend radius in world units. Takes effect on the next
+    /// [SdfPipeline::prepare].
+    pub fn set_blend_radius(&mut self, radius: f32) {
+        self.blend_radius = radius.max(0.0);
+    }
+
+    pub fn draw(&self, pass: &mut wgpu::RenderPass<'_>, camera_binding: &CameraBinding) {
+        pass.set_pipeline(&self.pipeline);
+        camera_binding.set(pass, 0);
+        pass.set_bind_group(1, &self.bind_group, &[]);
+        stats::draw(pass, 0..3, 0..1);
+    }
+}
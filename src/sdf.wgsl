@@ -0,0 +1,154 @@
+#include "camera.wgsl"
+#include "distance.wgsl"
+#include "fullscreen.wgsl"
+
+@group(0)
+@binding(0)
+var<uniform> camera: CameraUniform;
+
+struct SdfUniform {
+    count: u32,
+    blend_radius: f32,
+}
+
+struct SdfShape {
+    // World to local transform.
+    inverse: vec4<f32>,
+    inverse_translation: vec2<f32>,
+    // Converts local distances to world units.
+    scale: f32,
+    kind: u32,
+    // Circle: center.xy, radius. Box: min.xy, max.xy. Polylines: offset and
+    // number of points in `points`.
+    params: vec4<f32>,
+    stroke: vec4<f32>,
+    fill: vec4<f32>,
+    op: u32,
+}
+
+@group(1)
+@binding(0)
+var<uniform> sdf: SdfUniform;
+@group(1)
+@binding(1)
+var<storage, read> shapes: array<SdfShape>;
+@group(1)
+@binding(2)
+var<storage, read> points: array<vec4<f32>>;
+
+// Points are packed two to an element, which keeps the stride uniform
+// buffers need when there are no storage buffers.
+fn point(i: u32) -> vec2<f32> {
+    let pair = points[i / 2u];
+    return select(pair.xy, pair.zw, i % 2u == 1u);
+}
+
+const SHAPE_CIRCLE: u32 = 0u;
+const SHAPE_BOX: u32 = 1u;
+const SHAPE_POLYGON: u32 = 2u;
+const SHAPE_POLYLINE: u32 = 3u;
+
+const OP_NONE: u32 = 0u;
+const OP_SMOOTH_UNION: u32 = 1u;
+const OP_SMOOTH_SUBTRACT: u32 = 2u;
+
+struct VsOut {
+    @builtin(position)
+    frag_position: vec4<f32>,
+    @location(0)
+    ndc: vec2<f32>,
+}
+
+@vertex
+fn fullscreen_quad(@builtin(vertex_index) i: u32) -> VsOut {
+    let ndc = fullscreen_uv(i) * 2.0 - 1.0;
+    return VsOut(vec4(ndc, 0.0, 1.0), ndc);
+}
+
+// Distance to a run of points. Closed runs are treated as polygons and are
+// negative inside.
+fn sd_points(p: vec2<f32>, offset: u32, count: u32, closed: bool) -> f32 {
+    var d = 1e20;
+    var s = 1.0;
+    let segments = select(count - 1u, count, closed);
+    for (var i = 0u; i < segments; i++) {
+        let a = point(offset + i);
+        let b = point(offset + (i + 1u) % count);
+        let e = b - a;
+        let w = p - a;
+        let q = w - e * clamp(dot(w, e) / max(dot(e, e), 1e-12), 0.0, 1.0);
+        d = min(d, dot(q, q));
+        let c = vec3(p.y >= a.y, p.y < b.y, e.x * w.y > e.y * w.x);
+        if closed && (all(c) || !any(c)) {
+            s = -s;
+        }
+    }
+    return s * sqrt(d);
+}
+
+fn shape_distance(shape: SdfShape, p: vec2<f32>) -> f32 {
+    switch shape.kind {
+        case SHAPE_CIRCLE: {
+            return sd_circle(p - shape.params.xy, shape.params.z);
+        }
+        case SHAPE_BOX: {
+            let center = (shape.params.xy + shape.params.zw) * 0.5;
+            return sd_box(p - center, (shape.params.zw - shape.params.xy) * 0.5);
+        }
+        default: {
+            let closed = shape.kind == SHAPE_POLYGON;
+            return sd_points(p, u32(shape.params.x), u32(shape.params.y), closed);
+        }
+    }
+}
+
+// Composites a straight alpha color over a premultiplied one.
+fn over(dst: vec4<f32>, color: vec3<f32>, alpha: f32) -> vec4<f32> {
+    return vec4(color * alpha, alpha) + dst * (1.0 - alpha);
+}
+
+// A run of shapes merged by smooth operations.
+struct Blob {
+    d: f32,
+    fill: vec4<f32>,
+    stroke: vec4<f32>,
+}
+
+fn draw_blob(dst: vec4<f32>, blob: Blob, pixel: f32) -> vec4<f32> {
+    let fill = blob.fill.a * (1.0 - smoothstep(-0.5 * pixel, 0.5 * pixel, blob.d));
+    let color = over(dst, blob.fill.rgb, fill);
+    let stroke = blob.stroke.a * (1.0 - smoothstep(0.0, pixel, abs(blob.d) - 0.5 * pixel));
+    return over(color, blob.stroke.rgb, stroke);
+}
+
+@fragment
+fn sdf_shapes(vs: VsOut) -> @location(0) vec4<f32> {
+    let world = (camera.inv_view_proj * vec4(vs.ndc, 0.0, 1.0)).xy;
+    let pixel = length(fwidth(world)) * 0.7071;
+    let k = max(sdf.blend_radius, 1e-4);
+
+    var color = vec4(0.0);
+    var blob = Blob(1e20, vec4(0.0), vec4(0.0));
+    for (var i = 0u; i < sdf.count; i++) {
+        let shape = shapes[i];
+        let local = mat2x2(shape.inverse.xy, shape.inverse.zw) * world + shape.inverse_translation;
+        let d = shape_distance(shape, local) * shape.scale;
+        switch shape.op {
+            case OP_SMOOTH_UNION: {
+                let h = clamp(0.5 + 0.5 * (d - blob.d) / k, 0.0, 1.0);
+                blob.d = mix(d, blob.d, h) - k * h * (1.0 - h);
+                blob.fill = mix(shape.fill, blob.fill, h);
+                blob.stroke = mix(shape.stroke, blob.stroke, h);
+            }
+            case OP_SMOOTH_SUBTRACT: {
+                let h = clamp(0.5 - 0.5 * (blob.d + d) / k, 0.0, 1.0);
+                blob.d = mix(blob.d, -d, h) + k * h * (1.0 - h);
+            }
+            case OP_NONE, default: {
+                color = draw_blob(color, blob, pixel);
+                blob = Blob(d, shape.fill, shape.stroke);
+            }
+        }
+    }
+    return draw_blob(color, blob, pixel);
+}
@@ -1,3 +1,8 @@
+#include "camera.wgsl"
+#include "fullscreen.wgsl"
+#include "msdf.wgsl"
+#include "transform.wgsl"
+
 struct VsOut {
     @builtin(position)
     frag_position: vec4<f32>,
@@ -7,11 +12,7 @@ struct VsOut {
 
 @vertex
 fn fullscreen_quad(@builtin(vertex_index) i: u32) -> VsOut {
-    let uv = vec2(
-        f32(i % 2u) * 2.0,
-        f32(i > 1u) * 2.0,
-    );
-
+    let uv = fullscreen_uv(i);
     return VsOut(vec4(uv * 2.0 - 1.0, 0.0, 1.0), uv);
 }
 
@@ -22,17 +23,14 @@ struct TexturedVertex {
     uv: vec2<f32>,
 }
 
-struct CameraUniform {
-    view_proj: mat4x4<f32>,
-}
-
 @group(1)
 @binding(0)
 var<uniform> camera: CameraUniform;
 
 @vertex
-fn textured(in: TexturedVertex) -> VsOut {
-    return VsOut(camera.view_proj * vec4(in.position, 0.0, 1.0), in.uv);
+fn textured(in: TexturedVertex, instance: InstanceTransform) -> VsOut {
+    let position = apply_transform(instance, in.position);
+    return VsOut(camera.view_proj * vec4(position, 0.0, 1.0), in.uv);
 }
 
 @group(0)
@@ -50,6 +48,39 @@ fn canvas(vs: VsOut) -> @location(0) vec4<f32> {
     // return vec4(vs.uv, 0.0, 1.0);
 }
 
+struct GridUniform {
+    minor_color: vec4<f32>,
+    major_color: vec4<f32>,
+    spacing: f32,
+    major_spacing: f32,
+}
+
+@group(0)
+@binding(2)
+var<uniform> grid_uniforms: GridUniform;
+
+// Coverage of lines every `spacing` units, about a pixel wide. Fades out as
+// the lines get closer than a few pixels apart.
+fn grid_lines(world: vec2<f32>, spacing: f32) -> f32 {
+    let cell = world / spacing;
+    let width = fwidth(cell);
+    let distance = abs(fract(cell - 0.5) - 0.5) / max(width, vec2(1e-6));
+    let line = 1.0 - min(min(distance.x, distance.y), 1.0);
+    let cell_pixels = 1.0 / max(width.x, width.y);
+    return line * smoothstep(4.0, 12.0, cell_pixels);
+}
+
+@fragment
+fn grid(vs: VsOut) -> @location(0) vec4<f32> {
+    let ndc = vs.uv * 2.0 - 1.0;
+    let world = (camera.inv_view_proj * vec4(ndc, 0.0, 1.0)).xy;
+
+    let minor = grid_uniforms.minor_color.a * grid_lines(world, grid_uniforms.spacing);
+    let major = grid_uniforms.major_color.a * grid_lines(world, grid_uniforms.major_spacing);
+    let rgb = mix(grid_uniforms.minor_color.rgb, grid_uniforms.major_color.rgb, major);
+    return vec4(rgb, max(minor, major));
+}
+
 struct FontUniforms {
     unit_range: vec2<f32>,
     in_bias: f32,
@@ -63,10 +94,6 @@ struct FontUniforms {
 @binding(0)
 var<uniform> uniforms: FontUniforms;
 
-fn median(msd: vec3<f32>) -> f32 {
-    return max(min(msd.r, msd.g), min(max(msd.r, msd.g), msd.b));
-}
-
 fn screen_px_range(uv: vec2<f32>) -> f32 {
     let screen_tex_size = vec2(1.0) / fwidth(uv);
     return max(0.5 * dot(uniforms.unit_range, screen_tex_size), 1.0);
@@ -106,4 +133,158 @@ fn msdf_text(vs: VsOut) -> @location(0) vec4<f32> {
     let col = vec3(1.0);
 
     return vec4(col, opacity);
-}
\ No newline at end of file
+}
+const VIEWPORT_BACKGROUND: vec4<f32> = vec4(0.0, 0.0, 0.0, 1.0);
+const VIEWPORT_BORDER: vec4<f32> = vec4(0.3, 0.3, 0.3, 1.0);
+
+@fragment
+fn viewport_background(vs: VsOut) -> @location(0) vec4<f32> {
+    return VIEWPORT_BACKGROUND;
+}
+
+// One pixel border around the edge of a viewport.
+@fragment
+fn viewport_border(vs: VsOut) -> @location(0) vec4<f32> {
+    let edge = min(vs.uv, 1.0 - vs.uv) / max(fwidth(vs.uv), vec2(1e-6));
+    if min(edge.x, edge.y) < 1.0 {
+        return VIEWPORT_BORDER;
+    }
+    return vec4(0.0);
+}
+
+struct PostUniform {
+    texel_size: vec2<f32>,
+    strength: f32,
+    radius: f32,
+    threshold: f32,
+}
+
+// Bindings 3 to 5 keep clear of the font texture and the grid uniforms,
+// which share the group.
+@group(0)
+@binding(3)
+var post_input: texture_2d<f32>;
+@group(0)
+@binding(4)
+var post_sampler: sampler;
+@group(0)
+@binding(5)
+var<uniform> post: PostUniform;
+
+const FXAA_SPAN_MAX: f32 = 8.0;
+const FXAA_REDUCE_MUL: f32 = 0.125;
+const FXAA_REDUCE_MIN: f32 = 0.0078125;
+
+// The quad's uv goes up the screen, but textures go down.
+fn post_uv(uv: vec2<f32>) -> vec2<f32> {
+    return vec2(uv.x, 1.0 - uv.y);
+}
+
+fn post_sample(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(post_input, post_sampler, uv, 0.0);
+}
+
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+// Blurs along edges where the brightness changes sharply.
+@fragment
+fn post_fxaa(vs: VsOut) -> @location(0) vec4<f32> {
+    let uv = post_uv(vs.uv);
+    let t = post.texel_size;
+    let center = post_sample(uv);
+    let m = luma(center.rgb);
+    let nw = luma(post_sample(uv + vec2(-1.0, -1.0) * t).rgb);
+    let ne = luma(post_sample(uv + vec2(1.0, -1.0) * t).rgb);
+    let sw = luma(post_sample(uv + vec2(-1.0, 1.0) * t).rgb);
+    let se = luma(post_sample(uv + vec2(1.0, 1.0) * t).rgb);
+    let luma_min = min(m, min(min(nw, ne), min(sw, se)));
+    let luma_max = max(m, max(max(nw, ne), max(sw, se)));
+
+    var dir = vec2(-((nw + ne) - (sw + se)), (nw + sw) - (ne + se));
+    let reduce = max((nw + ne + sw + se) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+    let scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+    dir = clamp(dir * scale, vec2(-FXAA_SPAN_MAX), vec2(FXAA_SPAN_MAX)) * t;
+
+    let near = 0.5 * (post_sample(uv + dir * (1.0 / 3.0 - 0.5)).rgb
+        + post_sample(uv + dir * (2.0 / 3.0 - 0.5)).rgb);
+    let far = near * 0.5 + 0.25 * (post_sample(uv - dir * 0.5).rgb
+        + post_sample(uv + dir * 0.5).rgb);
+    let far_luma = luma(far);
+    if far_luma < luma_min || far_luma > luma_max {
+        return vec4(near, center.a);
+    }
+    return vec4(far, center.a);
+}
+
+// Darkens towards the corners, starting `radius` of the way out.
+@fragment
+fn post_vignette(vs: VsOut) -> @location(0) vec4<f32> {
+    let uv = post_uv(vs.uv);
+    let color = post_sample(uv);
+    let distance = length(uv - 0.5) * 1.41421356;
+    let fade = smoothstep(post.radius, 1.0, distance) * post.strength;
+    return vec4(color.rgb * (1.0 - fade), color.a);
+}
+
+// Spreads the parts brighter than `threshold` out over `radius` pixels.
+@fragment
+fn post_bloom(vs: VsOut) -> @location(0) vec4<f32> {
+    let uv = post_uv(vs.uv);
+    let color = post_sample(uv);
+    var glow = vec3(0.0);
+    var total = 0.0;
+    for (var ring = 1; ring <= 3; ring++) {
+        let weight = 1.0 / f32(ring);
+        for (var i = 0; i < 8; i++) {
+            // Each ring is turned a little so the taps don't line up.
+            let angle = (f32(i) + f32(ring) * 0.5) * 0.78539816;
+            let offset = vec2(cos(angle), sin(angle)) * f32(ring) / 3.0 * post.radius;
+            let tap = post_sample(uv + offset * post.texel_size).rgb;
+            glow += max(tap - vec3(post.threshold), vec3(0.0)) * weight;
+            total += weight;
+        }
+    }
+    return vec4(color.rgb + glow / total * post.strength, color.a);
+}
+
+const SCRGB_WHITE_NITS: f32 = 80.0;
+const PQ_MAX_NITS: f32 = 10000.0;
+const PQ_M1: f32 = 0.1593017578125;
+const PQ_M2: f32 = 78.84375;
+const PQ_C1: f32 = 0.8359375;
+const PQ_C2: f32 = 18.8515625;
+const PQ_C3: f32 = 18.6875;
+
+// From sRGB primaries to the wider ones HDR10 uses.
+const REC709_TO_REC2020: mat3x3<f32> = mat3x3(
+    vec3(0.6274, 0.0691, 0.0164),
+    vec3(0.3293, 0.9195, 0.0880),
+    vec3(0.0433, 0.0114, 0.8956),
+);
+
+// Leaves everything up to paper white alone, and rolls anything brighter
+// off towards the display's peak.
+fn compress_highlights(nits: vec3<f32>, white: f32, peak: f32) -> vec3<f32> {
+    let over = max(nits - white, vec3(0.0));
+    let room = max(peak - white, 1e-3);
+    return min(nits, vec3(white)) + room * (1.0 - exp(-over / room));
+}
+
+fn pq_encode(nits: vec3<f32>) -> vec3<f32> {
+    let y = pow(clamp(nits / PQ_MAX_NITS, vec3(0.0), vec3(1.0)), vec3(PQ_M1));
+    return pow((PQ_C1 + PQ_C2 * y) / (1.0 + PQ_C3 * y), vec3(PQ_M2));
+}
+
+// Maps SDR white to `strength` nits with highlights up to `radius` nits,
+// written as scRGB, or as HDR10 when `threshold` is set.
+@fragment
+fn post_tonemap(vs: VsOut) -> @location(0) vec4<f32> {
+    let color = post_sample(post_uv(vs.uv));
+    let nits = compress_highlights(max(color.rgb, vec3(0.0)) * post.strength, post.strength, post.radius);
+    if post.threshold > 0.5 {
+        return vec4(pq_encode(REC709_TO_REC2020 * nits), color.a);
+    }
+    return vec4(nits / SCRGB_WHITE_NITS, color.a);
+}
@@ -0,0 +1,7 @@
+// Matches `CameraUniform` in resources/camera.rs. Shaders bind it
+// themselves, as it's in a different group in each.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    viewport_size: vec2<f32>,
+}
@@ -0,0 +1,8 @@
+fn sd_circle(p: vec2<f32>, radius: f32) -> f32 {
+    return length(p) - radius;
+}
+
+fn sd_box(p: vec2<f32>, half_extents: vec2<f32>) -> f32 {
+    let d = abs(p) - half_extents;
+    return length(max(d, vec2(0.0))) + min(max(d.x, d.y), 0.0);
+}
@@ -0,0 +1,8 @@
+// Corners of a triangle that covers the screen, from 0 to 2 so the visible
+// part goes from 0 to 1.
+fn fullscreen_uv(i: u32) -> vec2<f32> {
+    return vec2(
+        f32(i % 2u) * 2.0,
+        f32(i > 1u) * 2.0,
+    );
+}
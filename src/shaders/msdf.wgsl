@@ -0,0 +1,4 @@
+// The distance a multi-channel SDF texel stores.
+fn median(msd: vec3<f32>) -> f32 {
+    return max(min(msd.r, msd.g), min(max(msd.r, msd.g), msd.b));
+}
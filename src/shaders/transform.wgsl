@@ -0,0 +1,11 @@
+// Matches `InstanceTransform` in resources/instance.rs.
+struct InstanceTransform {
+    @location(5)
+    matrix: vec4<f32>,
+    @location(6)
+    translation: vec2<f32>,
+}
+
+fn apply_transform(t: InstanceTransform, p: vec2<f32>) -> vec2<f32> {
+    return mat2x2(t.matrix.xy, t.matrix.zw) * p + t.translation;
+}
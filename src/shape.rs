@@ -0,0 +1,692 @@
+use glam::{Vec2, Vec4};
+
+use crate::{
+    resources::{
+        buffer::IndexedBatch,
+        geometry::{ColoredVertex, FillVertex, PatternVertex, StrokeVertex},
+        texture::TextureId,
+    },
+    scene::Transform2D,
+    style::StyleId,
+};
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Geometry {
+    /// Connected line segments. Freehand strokes end up as these.
+    Polyline { points: Vec<Vec2>, closed: bool },
+    /// Circle described by a signed distance function.
+    Circle { center: Vec2, radius: f32 },
+    /// Axis aligned box described by a signed distance function.
+    Rect { min: Vec2, max: Vec2 },
+}
+
+/// Axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Bounds {
+    pub fn new(a: Vec2, b: Vec2) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Vec2>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Self::new(first, first), |b, p| Self {
+            min: b.min.min(p),
+            max: b.max.max(p),
+        }))
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn center(&self) -> Vec2 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    pub fn contains(&self, p: Vec2) -> bool {
+        p.cmpge(self.min).all() && p.cmple(self.max).all()
+    }
+
+    pub fn expand(&self, amount: f32) -> Self {
+        Self {
+            min: self.min - amount,
+            max: self.max + amount,
+        }
+    }
+
+    /// Corners in clockwise order (in screen space) starting at `min`.
+    pub fn corners(&self) -> [Vec2; 4] {
+        [
+            self.min,
+            Vec2::new(self.max.x, self.min.y),
+            self.max,
+            Vec2::new(self.min.x, self.max.y),
+        ]
+    }
+}
+
+/// Number of segments used when drawing a circle outline.
+const CIRCLE_SEGMENTS: usize = 48;
+/// How far a stroke's corners can stick out, relative to half its width,
+/// before they're cut short.
+const MITER_LIMIT: f32 = 4.0;
+/// Most stops a gradient can have. This matches the shader.
+pub const MAX_GRADIENT_STOPS: usize = 8;
+
+/// How the inside of a closed shape is painted.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Fill {
+    Solid(Vec4),
+    Gradient(Gradient),
+    Texture(TextureFill),
+}
+
+/// How a texture repeats outside of its first tile.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Tiling {
+    #[default]
+    Repeat,
+    /// Every other tile is flipped so the edges line up.
+    Mirror,
+    /// The texture is drawn once and its edge pixels are stretched outwards.
+    Clamp,
+}
+
+impl Tiling {
+    /// Value the shader uses to pick a tiling mode.
+    pub fn index(&self) -> u32 {
+        match self {
+            Self::Repeat => 0,
+            Self::Mirror => 1,
+            Self::Clamp => 2,
+        }
+    }
+}
+
+/// Fills a shape with a texture from the [TextureRegistry](crate::resources::texture::TextureRegistry).
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TextureFill {
+    pub texture: TextureId,
+    /// Places a single tile, which covers the unit square before this is
+    /// applied, in the shape's local space.
+    pub transform: Transform2D,
+    pub tiling: Tiling,
+    /// Multiplied with the texture's color.
+    pub tint: Vec4,
+}
+
+impl TextureFill {
+    /// Repeats the texture every `tile_size` units starting at the shape's
+    /// origin.
+    pub fn new(texture: TextureId, tile_size: Vec2) -> Self {
+        Self {
+            texture,
+            transform: Transform2D {
+                scale: tile_size,
+                ..Transform2D::IDENTITY
+            },
+            tiling: Tiling::Repeat,
+            tint: Vec4::ONE,
+        }
+    }
+
+    /// Texture coordinates of a point in the shape's local space.
+    pub fn uv(&self, p: Vec2) -> Vec2 {
+        let affine = self.transform.to_affine();
+        if affine.matrix2.determinant().abs() <= f32::EPSILON {
+            return Vec2::ZERO;
+        }
+        affine.inverse().transform_point2(p)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum GradientKind {
+    /// Blends from `start` to `end` along the line between them.
+    Linear { start: Vec2, end: Vec2 },
+    /// Blends outwards from `center`, reaching the last stop at `radius`.
+    Radial { center: Vec2, radius: f32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct GradientStop {
+    /// Position along the gradient from 0 to 1.
+    pub offset: f32,
+    pub color: Vec4,
+}
+
+/// A gradient in the local space of the shape it fills. Stops are kept
+/// sorted by offset.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Gradient {
+    kind: GradientKind,
+    stops: Vec<GradientStop>,
+}
+
+impl Gradient {
+    pub fn linear(start: Vec2, end: Vec2, from: Vec4, to: Vec4) -> Self {
+        Self::new(GradientKind::Linear { start, end }, from, to)
+    }
+
+    pub fn radial(center: Vec2, radius: f32, from: Vec4, to: Vec4) -> Self {
+        Self::new(GradientKind::Radial { center, radius }, from, to)
+    }
+
+    fn new(kind: GradientKind, from: Vec4, to: Vec4) -> Self {
+        Self {
+            kind,
+            stops: vec![
+                GradientStop {
+                    offset: 0.0,
+                    color: from,
+                },
+                GradientStop {
+                    offset: 1.0,
+                    color: to,
+                },
+            ],
+        }
+    }
+
+    pub fn kind(&self) -> GradientKind {
+        self.kind
+    }
+
+    /// Changes the direction of the gradient, or switches between linear
+    /// and radial. Stops are kept.
+    pub fn set_kind(&mut self, kind: GradientKind) {
+        self.kind = kind;
+    }
+
+    pub fn stops(&self) -> &[GradientStop] {
+        &self.stops
+    }
+
+    /// Adds a stop, returning its index. Fails if the gradient already has
+    /// [MAX_GRADIENT_STOPS] stops.
+    pub fn add_stop(&mut self, offset: f32, color: Vec4) -> Option<usize> {
+        if self.stops.len() >= MAX_GRADIENT_STOPS {
+            return None;
+        }
+        let offset = offset.clamp(0.0, 1.0);
+        let index = self.stops.partition_point(|s| s.offset <= offset);
+        self.stops.insert(index, GradientStop { offset, color });
+        Some(index)
+    }
+
+    /// Moves or recolors a stop, returning its new index.
+    pub fn set_stop(&mut self, index: usize, offset: f32, color: Vec4) -> Option<usize> {
+        if index >= self.stops.len() {
+            return None;
+        }
+        self.stops.remove(index);
+        self.add_stop(offset, color)
+    }
+
+    /// Removes a stop. A gradient always keeps at least one stop.
+    pub fn remove_stop(&mut self, index: usize) -> Option<GradientStop> {
+        if self.stops.len() <= 1 || index >= self.stops.len() {
+            return None;
+        }
+        Some(self.stops.remove(index))
+    }
+
+    /// Color of the gradient at `p` (in shape local space).
+    pub fn sample(&self, p: Vec2) -> Vec4 {
+        let t = match self.kind {
+            GradientKind::Linear { start, end } => {
+                let d = end - start;
+                (p - start).dot(d) / d.length_squared().max(f32::EPSILON)
+            }
+            GradientKind::Radial { center, radius } => {
+                p.distance(center) / radius.max(f32::EPSILON)
+            }
+        };
+        self.color_at(t)
+    }
+
+    /// Color at offset `t` along the gradient.
+    pub fn color_at(&self, t: f32) -> Vec4 {
+        let Some(first) = self.stops.first() else {
+            return Vec4::ONE;
+        };
+        let mut color = first.color;
+        for pair in self.stops.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if t >= a.offset {
+                let f = ((t - a.offset) / (b.offset - a.offset).max(f32::EPSILON)).clamp(0.0, 1.0);
+                color = a.color.lerp(b.color, f);
+            }
+        }
+        color
+    }
+}
+
+/// A drawable shape in node local coordinates.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Shape {
+    pub geometry: Geometry,
+    /// Color of the outline.
+    pub color: Vec4,
+    /// Only closed shapes are filled.
+    #[serde(default)]
+    pub fill: Option<Fill>,
+    /// How the shape combines with the one drawn before it in
+    /// [DrawMode::Sdf](crate::resources::geometry::DrawMode::Sdf).
+    #[serde(default)]
+    pub sdf_op: SdfOp,
+    /// Shared style that replaces `color` and `fill` when set.
+    #[serde(default)]
+    pub style: Option<StyleId>,
+}
+
+/// Operations that merge a shape's distance field with the shapes before it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum SdfOp {
+    /// Drawn on top of the shapes before it.
+    #[default]
+    None,
+    /// Blends into the previous shape, forming a single blob.
+    SmoothUnion,
+    /// Carves itself out of the previous shape. The shape itself isn't
+    /// drawn.
+    SmoothSubtract,
+}
+
+impl SdfOp {
+    /// Value the shader uses to pick an operation.
+    pub fn index(&self) -> u32 {
+        match self {
+            Self::None => 0,
+            Self::SmoothUnion => 1,
+            Self::SmoothSubtract => 2,
+        }
+    }
+}
+
+impl Shape {
+    pub fn polyline(points: Vec<Vec2>, closed: bool, color: Vec4) -> Self {
+        Self {
+            geometry: Geometry::Polyline { points, closed },
+            color,
+            fill: None,
+            sdf_op: SdfOp::None,
+            style: None,
+        }
+    }
+
+    pub fn circle(center: Vec2, radius: f32, color: Vec4) -> Self {
+        Self {
+            geometry: Geometry::Circle { center, radius },
+            color,
+            fill: None,
+            sdf_op: SdfOp::None,
+            style: None,
+        }
+    }
+
+    pub fn rect(a: Vec2, b: Vec2, color: Vec4) -> Self {
+        Self {
+            geometry: Geometry::Rect {
+                min: a.min(b),
+                max: a.max(b),
+            },
+            color,
+            fill: None,
+            sdf_op: SdfOp::None,
+            style: None,
+        }
+    }
+
+    /// A rectangle showing a whole texture of `size` pixels, centered on the
+    /// origin. The outline is transparent.
+    pub fn image(texture: TextureId, size: Vec2) -> Self {
+        let half = size * 0.5;
+        let fill = TextureFill {
+            texture,
+            transform: Transform2D {
+                translation: -half,
+                scale: size,
+                ..Transform2D::IDENTITY
+            },
+            tiling: Tiling::Clamp,
+            tint: Vec4::ONE,
+        };
+        Self::rect(-half, half, Vec4::ZERO).with_fill(Fill::Texture(fill))
+    }
+
+    pub fn with_fill(mut self, fill: Fill) -> Self {
+        self.fill = Some(fill);
+        self
+    }
+
+    pub fn with_style(mut self, style: StyleId) -> Self {
+        self.style = Some(style);
+        self
+    }
+
+    /// Appends the shape's line segments to `batch`. Styled shapes are
+    /// outlined by [Shape::tessellate_stroke] instead.
+    pub fn tessellate(&self, batch: &mut IndexedBatch<'_, ColoredVertex>) {
+        if self.style.is_some() {
+            return;
+        }
+        match &self.geometry {
+            Geometry::Polyline { points, closed } => {
+                tessellate_polyline(points, *closed, self.color, batch)
+            }
+            Geometry::Circle { .. } | Geometry::Rect { .. } => {
+                tessellate_polyline(&self.outline(), true, self.color, batch)
+            }
+        }
+    }
+
+    /// Appends triangles covering the inside of the shape to `batch`, if it
+    /// has a fill. `gradient` is the slot the shape's gradient was uploaded
+    /// to. Gradients that didn't get a slot are drawn with their first stop.
+    /// Texture fills are handled by [Shape::tessellate_pattern] instead.
+    pub fn tessellate_fill(&self, gradient: Option<u32>, batch: &mut IndexedBatch<'_, FillVertex>) {
+        // Closed styled shapes are always covered. Whether anything is drawn
+        // is up to the style.
+        if let (Some(style), true) = (self.style, self.is_closed()) {
+            let outline = self.outline();
+            let vertices: Vec<FillVertex> = outline
+                .iter()
+                .map(|&p| FillVertex::styled(p, style.index()))
+                .collect();
+            batch.mesh(&vertices, &triangulate(&outline));
+            return;
+        }
+        let Some(fill) = self.fill.as_ref().filter(|_| self.is_closed()) else {
+            return;
+        };
+        let (color, gradient) = match (fill, gradient) {
+            (Fill::Solid(color), _) => (*color, FillVertex::NO_GRADIENT),
+            (Fill::Gradient(_), Some(slot)) => (Vec4::ONE, slot),
+            (Fill::Gradient(g), None) => (g.color_at(0.0), FillVertex::NO_GRADIENT),
+            (Fill::Texture(_), _) => return,
+        };
+        let outline = self.outline();
+        let indices = triangulate(&outline);
+        if indices.is_empty() {
+            return;
+        }
+        let vertices: Vec<FillVertex> = outline
+            .iter()
+            .map(|&p| FillVertex::new(p, color, gradient))
+            .collect();
+        batch.mesh(&vertices, &indices);
+    }
+
+    /// Appends textured triangles covering the shape to `batch`, if it has a
+    /// texture fill.
+    pub fn tessellate_pattern(&self, batch: &mut IndexedBatch<'_, PatternVertex>) {
+        let Some(Fill::Texture(fill)) = self.fill.as_ref().filter(|_| self.is_closed()) else {
+            return;
+        };
+        if self.style.is_some() {
+            return;
+        }
+        let outline = self.outline();
+        let indices = triangulate(&outline);
+        if indices.is_empty() {
+            return;
+        }
+        let vertices: Vec<PatternVertex> = outline
+            .iter()
+            .map(|&p| PatternVertex::new(p, fill.uv(p), fill.tint, fill.tiling.index()))
+            .collect();
+        batch.mesh(&vertices, &indices);
+    }
+
+    /// Appends the outline of a styled shape to `batch`.
+    pub fn tessellate_stroke(&self, batch: &mut IndexedBatch<'_, StrokeVertex>) {
+        if let Some(style) = self.style {
+            tessellate_stroke(&self.outline(), self.is_closed(), style.index(), batch);
+        }
+    }
+
+    /// Whether [Shape::tessellate_fill] produces anything.
+    pub fn has_fill(&self) -> bool {
+        self.is_closed()
+            && match &self.fill {
+                _ if self.style.is_some() => true,
+                Some(Fill::Solid(_) | Fill::Gradient(_)) => true,
+                Some(Fill::Texture(_)) | None => false,
+            }
+    }
+
+    /// Points along the edge of the shape. Circles are approximated.
+    pub fn outline(&self) -> Vec<Vec2> {
+        match &self.geometry {
+            Geometry::Polyline { points, .. } => points.clone(),
+            Geometry::Circle { center, radius } => (0..CIRCLE_SEGMENTS)
+                .map(|i| {
+                    let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+                    *center + Vec2::from_angle(angle) * *radius
+                })
+                .collect(),
+            Geometry::Rect { min, max } => {
+                vec![*min, Vec2::new(max.x, min.y), *max, Vec2::new(min.x, max.y)]
+            }
+        }
+    }
+
+    /// Whether the outline should be treated as a closed loop.
+    pub fn is_closed(&self) -> bool {
+        match &self.geometry {
+            Geometry::Polyline { closed, .. } => *closed,
+            Geometry::Circle { .. } | Geometry::Rect { .. } => true,
+        }
+    }
+
+    /// Signed distance from `p` to the shape for shapes that have a distance
+    /// function. Negative values are inside.
+    pub fn sdf(&self, p: Vec2) -> Option<f32> {
+        match &self.geometry {
+            Geometry::Polyline { .. } => None,
+            Geometry::Circle { center, radius } => Some(sd_circle(p - *center, *radius)),
+            Geometry::Rect { min, max } => {
+                let center = (*min + *max) * 0.5;
+                Some(sd_box(p - center, (*max - *min) * 0.5))
+            }
+        }
+    }
+}
+
+pub fn sd_circle(p: Vec2, radius: f32) -> f32 {
+    p.length() - radius
+}
+
+pub fn sd_box(p: Vec2, half_extents: Vec2) -> f32 {
+    let d = p.abs() - half_extents;
+    d.max(Vec2::ZERO).length() + d.x.max(d.y).min(0.0)
+}
+
+pub fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let len2 = ab.length_squared();
+    if len2 <= f32::EPSILON {
+        return p.distance(a);
+    }
+    let t = ((p - a).dot(ab) / len2).clamp(0.0, 1.0);
+    p.distance(a + ab * t)
+}
+
+/// Even-odd point in polygon test.
+pub fn point_in_polygon(p: Vec2, polygon: &[Vec2]) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for (i, &a) in polygon.iter().enumerate() {
+        let b = polygon[j];
+        if (a.y > p.y) != (b.y > p.y) && p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// Splits a simple polygon into triangles by ear clipping, returning
+/// indices into `polygon`. Works with either winding order.
+pub fn triangulate(polygon: &[Vec2]) -> Vec<u32> {
+    let n = polygon.len();
+    if n < 3 {
+        return Vec::new();
+    }
+    let signed_area: f32 = (0..n)
+        .map(|i| polygon[i].perp_dot(polygon[(i + 1) % n]))
+        .sum();
+    let winding = signed_area.signum();
+    if winding == 0.0 {
+        return Vec::new();
+    }
+
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut indices = Vec::with_capacity((n - 2) * 3);
+    let mut misses = 0;
+    let mut i = 0;
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let (ia, ib, ic) = (
+            remaining[(i + len - 1) % len],
+            remaining[i % len],
+            remaining[(i + 1) % len],
+        );
+        let (a, b, c) = (polygon[ia], polygon[ib], polygon[ic]);
+        let convex = (b - a).perp_dot(c - b) * winding > 0.0;
+        let is_ear = convex
+            && remaining
+                .iter()
+                .all(|&j| j == ia || j == ib || j == ic || !point_in_triangle(polygon[j], a, b, c));
+        if is_ear {
+            indices.extend_from_slice(&[ia as u32, ib as u32, ic as u32]);
+            remaining.remove(i % len);
+            misses = 0;
+        } else {
+            i += 1;
+            misses += 1;
+            // Self intersecting polygons can run out of ears, so fall back
+            // to a fan for whatever is left.
+            if misses > len {
+                break;
+            }
+        }
+    }
+    for k in 1..remaining.len() - 1 {
+        indices.extend_from_slice(&[
+            remaining[0] as u32,
+            remaining[k] as u32,
+            remaining[k + 1] as u32,
+        ]);
+    }
+    indices
+}
+
+fn point_in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let d1 = (b - a).perp_dot(p - a);
+    let d2 = (c - b).perp_dot(p - b);
+    let d3 = (a - c).perp_dot(p - c);
+    let has_neg = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+    let has_pos = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+    !(has_neg && has_pos)
+}
+
+pub fn tessellate_polyline(
+    points: &[Vec2],
+    closed: bool,
+    color: Vec4,
+    batch: &mut IndexedBatch<'_, ColoredVertex>,
+) {
+    for segment in points.windows(2) {
+        batch.line(
+            ColoredVertex::new(segment[0], color),
+            ColoredVertex::new(segment[1], color),
+        );
+    }
+    if closed && points.len() > 2 {
+        batch.line(
+            ColoredVertex::new(points[points.len() - 1], color),
+            ColoredVertex::new(points[0], color),
+        );
+    }
+}
+
+/// Appends quads along `points` that the stroke shader pushes out to the
+/// width of style `style`. Corners are mitered.
+pub fn tessellate_stroke(
+    points: &[Vec2],
+    closed: bool,
+    style: u32,
+    batch: &mut IndexedBatch<'_, StrokeVertex>,
+) {
+    // Repeated points have no direction to extrude along.
+    let mut points = points.to_vec();
+    points.dedup_by(|a, b| a.distance_squared(*b) <= f32::EPSILON);
+    if closed
+        && points.len() > 2
+        && points[0].distance_squared(points[points.len() - 1]) <= f32::EPSILON
+    {
+        points.pop();
+    }
+    let n = points.len();
+    if n < 2 {
+        return;
+    }
+    let closed = closed && n > 2;
+    let segments = if closed { n } else { n - 1 };
+    let normal = |segment: usize| {
+        (points[(segment + 1) % n] - points[segment])
+            .normalize()
+            .perp()
+    };
+    let miter = |i: usize| {
+        let before = match i {
+            0 if closed => Some(n - 1),
+            0 => None,
+            i => Some(i - 1),
+        };
+        let after = (i < segments).then_some(i);
+        match (before.map(normal), after.map(normal)) {
+            (Some(a), Some(b)) => {
+                let m = (a + b).normalize_or(b);
+                m / m.dot(b).max(1.0 / MITER_LIMIT)
+            }
+            (Some(a), None) => a,
+            (None, Some(b)) => b,
+            (None, None) => Vec2::ZERO,
+        }
+    };
+
+    let mut vertices = Vec::with_capacity((segments + 1) * 2);
+    let mut indices = Vec::with_capacity(segments * 6);
+    let mut distance = 0.0;
+    for k in 0..=segments {
+        let i = k % n;
+        let m = miter(i);
+        vertices.push(StrokeVertex::new(points[i], m, distance, style));
+        vertices.push(StrokeVertex::new(points[i], -m, distance, style));
+        if k < segments {
+            distance += points[i].distance(points[(i + 1) % n]);
+            let base = k as u32 * 2;
+            indices.extend_from_slice(&[base, base + 1, base + 2, base + 1, base + 3, base + 2]);
+        }
+    }
+    batch.mesh(&vertices, &indices);
+}
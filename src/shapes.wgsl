@@ -0,0 +1,267 @@
+#include "camera.wgsl"
+#include "transform.wgsl"
+
+@group(0)
+@binding(0)
+var<uniform> camera: CameraUniform;
+
+struct ColoredVertex {
+    @location(0)
+    position: vec2<f32>,
+    @location(1)
+    color: vec4<f32>,
+}
+
+struct ColoredVsOut {
+    @builtin(position)
+    frag_position: vec4<f32>,
+    @location(0)
+    color: vec4<f32>,
+}
+
+@vertex
+fn colored(in: ColoredVertex, instance: InstanceTransform) -> ColoredVsOut {
+    let position = apply_transform(instance, in.position);
+    return ColoredVsOut(camera.view_proj * vec4(position, -layer.depth, 1.0), in.color);
+}
+
+struct LayerUniform {
+    opacity: f32,
+    // Distance from the camera, for ordering layers with a depth buffer.
+    depth: f32,
+}
+
+@group(1)
+@binding(0)
+var<uniform> layer: LayerUniform;
+
+const DRAW_MODE_WIREFRAME: u32 = 2u;
+
+struct Globals {
+    draw_mode: u32,
+}
+
+@group(1)
+@binding(2)
+var<uniform> globals: Globals;
+
+// Fills are faded in wireframe mode so the triangles on top stand out.
+fn fill_opacity() -> f32 {
+    return select(1.0, 0.25, globals.draw_mode == DRAW_MODE_WIREFRAME) * layer.opacity;
+}
+
+@fragment
+fn flat_color(vs: ColoredVsOut) -> @location(0) vec4<f32> {
+    return vec4(vs.color.rgb, vs.color.a * layer.opacity);
+}
+
+struct FillVertex {
+    @location(0)
+    position: vec2<f32>,
+    @location(1)
+    color: vec4<f32>,
+    @location(2)
+    gradient: u32,
+    @location(3)
+    style: u32,
+}
+
+struct FillVsOut {
+    @builtin(position)
+    frag_position: vec4<f32>,
+    @location(0)
+    color: vec4<f32>,
+    @location(1)
+    local_position: vec2<f32>,
+    @location(2) @interpolate(flat)
+    gradient: u32,
+    @location(3) @interpolate(flat)
+    style: u32,
+}
+
+@vertex
+fn filled(in: FillVertex, instance: InstanceTransform) -> FillVsOut {
+    let position = apply_transform(instance, in.position);
+    return FillVsOut(
+        camera.view_proj * vec4(position, -layer.depth, 1.0),
+        in.color,
+        in.position,
+        in.gradient,
+        in.style,
+    );
+}
+
+const MAX_GRADIENTS: u32 = 64u;
+const MAX_GRADIENT_STOPS: u32 = 8u;
+
+struct Gradient {
+    // x: kind (0 linear, 1 radial), y: number of stops
+    info: vec4<f32>,
+    // Linear: start.xy, end.xy. Radial: center.xy, radius.
+    geometry: vec4<f32>,
+    offsets: array<vec4<f32>, 2>,
+    colors: array<vec4<f32>, MAX_GRADIENT_STOPS>,
+}
+
+@group(1)
+@binding(1)
+var<uniform> gradients: array<Gradient, MAX_GRADIENTS>;
+
+const NO_STYLE: u32 = 0xffffffffu;
+const STYLE_FILL_SOLID: u32 = 1u;
+const STYLE_FILL_GRADIENT: u32 = 2u;
+
+struct Style {
+    stroke: vec4<f32>,
+    fill: vec4<f32>,
+    // x: stroke width, y: dash length, z: dash gap, w: fill kind (0 none,
+    // 1 solid, 2 gradient)
+    params: vec4<f32>,
+    gradient: Gradient,
+}
+
+@group(1)
+@binding(3)
+var<storage, read> styles: array<Style>;
+
+fn sample_gradient(g: Gradient, p: vec2<f32>) -> vec4<f32> {
+    var t: f32;
+    if g.info.x < 0.5 {
+        let d = g.geometry.zw - g.geometry.xy;
+        t = dot(p - g.geometry.xy, d) / max(dot(d, d), 1e-6);
+    } else {
+        t = length(p - g.geometry.xy) / max(g.geometry.z, 1e-6);
+    }
+
+    let count = min(u32(g.info.y), MAX_GRADIENT_STOPS);
+    var color = g.colors[0];
+    for (var i = 1u; i < count; i++) {
+        let a = g.offsets[(i - 1u) / 4u][(i - 1u) % 4u];
+        let b = g.offsets[i / 4u][i % 4u];
+        if t >= a {
+            let f = clamp((t - a) / max(b - a, 1e-6), 0.0, 1.0);
+            color = mix(g.colors[i - 1u], g.colors[i], f);
+        }
+    }
+    return color;
+}
+
+@fragment
+fn fill_color(vs: FillVsOut) -> @location(0) vec4<f32> {
+    var color = vs.color;
+    if vs.style != NO_STYLE {
+        let style = styles[vs.style];
+        switch u32(style.params.w) {
+            case STYLE_FILL_SOLID: {
+                color = style.fill;
+            }
+            case STYLE_FILL_GRADIENT: {
+                color = sample_gradient(style.gradient, vs.local_position);
+            }
+            default: {
+                discard;
+            }
+        }
+    } else if vs.gradient < MAX_GRADIENTS {
+        color = sample_gradient(gradients[vs.gradient], vs.local_position);
+    }
+    return vec4(color.rgb, color.a * fill_opacity());
+}
+
+struct StrokeVertex {
+    @location(0)
+    position: vec2<f32>,
+    @location(1)
+    normal: vec2<f32>,
+    @location(2)
+    distance: f32,
+    @location(3)
+    style: u32,
+}
+
+struct StrokeVsOut {
+    @builtin(position)
+    frag_position: vec4<f32>,
+    @location(0)
+    distance: f32,
+    @location(1) @interpolate(flat)
+    style: u32,
+}
+
+@vertex
+fn stroked(in: StrokeVertex, instance: InstanceTransform) -> StrokeVsOut {
+    let width = styles[in.style].params.x;
+    let position = apply_transform(instance, in.position + in.normal * width * 0.5);
+    return StrokeVsOut(
+        camera.view_proj * vec4(position, -layer.depth, 1.0),
+        in.distance,
+        in.style,
+    );
+}
+
+@fragment
+fn stroke_color(vs: StrokeVsOut) -> @location(0) vec4<f32> {
+    let style = styles[vs.style];
+    let dash = style.params.y;
+    let gap = style.params.z;
+    if dash > 0.0 && gap > 0.0 && vs.distance % (dash + gap) > dash {
+        discard;
+    }
+    return vec4(style.stroke.rgb, style.stroke.a * layer.opacity);
+}
+
+struct PatternVertex {
+    @location(0)
+    position: vec2<f32>,
+    @location(1)
+    uv: vec2<f32>,
+    @location(2)
+    tint: vec4<f32>,
+    @location(3)
+    tiling: u32,
+}
+
+struct PatternVsOut {
+    @builtin(position)
+    frag_position: vec4<f32>,
+    @location(0)
+    uv: vec2<f32>,
+    @location(1)
+    tint: vec4<f32>,
+    @location(2) @interpolate(flat)
+    tiling: u32,
+}
+
+@vertex
+fn patterned(in: PatternVertex, instance: InstanceTransform) -> PatternVsOut {
+    let position = apply_transform(instance, in.position);
+    return PatternVsOut(
+        camera.view_proj * vec4(position, -layer.depth, 1.0),
+        in.uv,
+        in.tint,
+        in.tiling,
+    );
+}
+
+@group(2)
+@binding(0)
+var pattern_texture: texture_2d<f32>;
+@group(2)
+@binding(1)
+var pattern_sampler: sampler;
+
+const TILING_MIRROR: u32 = 1u;
+const TILING_CLAMP: u32 = 2u;
+
+@fragment
+fn pattern_color(vs: PatternVsOut) -> @location(0) vec4<f32> {
+    // The sampler repeats, so only mirroring and clamping need adjusting.
+    // Branches are avoided so the sample stays in uniform control flow.
+    let mirrored = 1.0 - abs(fract(vs.uv * 0.5) * 2.0 - 1.0);
+    let half_texel = 0.5 / vec2<f32>(textureDimensions(pattern_texture));
+    let clamped = clamp(vs.uv, half_texel, 1.0 - half_texel);
+    var uv = select(vs.uv, mirrored, vs.tiling == TILING_MIRROR);
+    uv = select(uv, clamped, vs.tiling == TILING_CLAMP);
+    let color = textureSample(pattern_texture, pattern_sampler, uv) * vs.tint;
+    return vec4(color.rgb, color.a * fill_opacity());
+}
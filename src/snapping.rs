@@ -0,0 +1,205 @@
+use glam::{Vec2, Vec4};
+
+use crate::{
+    layers::LayerStack,
+    resources::{buffer::IndexedBatch, geometry::ColoredVertex},
+    shape::{tessellate_polyline, Geometry},
+};
+
+const INDICATOR_COLOR: Vec4 = Vec4::new(1.0, 0.4, 0.1, 1.0);
+const INDICATOR_SIZE: f32 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnapSettings {
+    /// Grid spacing in world units. `None` disables grid snapping.
+    pub grid: Option<f32>,
+    /// Snap to shape endpoints and midpoints.
+    pub points: bool,
+    /// How close (in pixels) the cursor needs to be to a point to snap to
+    /// it.
+    pub radius: f32,
+    /// Angle increment used when angle snapping is requested.
+    pub angle_step: f32,
+}
+
+impl Default for SnapSettings {
+    fn default() -> Self {
+        Self {
+            grid: Some(20.0),
+            points: true,
+            radius: 8.0,
+            angle_step: 15f32.to_radians(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapKind {
+    None,
+    Grid,
+    Endpoint,
+    Midpoint,
+    Angle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Snap {
+    pub point: Vec2,
+    pub kind: SnapKind,
+}
+
+/// Adjusts cursor positions for drawing tools.
+#[derive(Debug, Default)]
+pub struct Snapper {
+    pub settings: SnapSettings,
+}
+
+impl Snapper {
+    pub fn new(settings: SnapSettings) -> Self {
+        Self { settings }
+    }
+
+    /// Snaps `point` (in world space). When `constrain_angle` is set and an
+    /// `origin` is given, the point is locked to the nearest angle increment
+    /// around the origin instead. `world_per_pixel` converts the pixel snap
+    /// radius into world units.
+    pub fn snap(
+        &self,
+        layers: &mut LayerStack,
+        point: Vec2,
+        origin: Option<Vec2>,
+        constrain_angle: bool,
+        world_per_pixel: f32,
+    ) -> Snap {
+        if let (true, Some(origin)) = (constrain_angle, origin) {
+            return Snap {
+                point: snap_angle(origin, point, self.settings.angle_step),
+                kind: SnapKind::Angle,
+            };
+        }
+
+        if self.settings.points {
+            let radius = self.settings.radius * world_per_pixel;
+            let mut best: Option<(f32, Snap)> = None;
+            for (candidate, kind) in snap_points(layers) {
+                let d = candidate.distance(point);
+                if d <= radius && best.is_none_or(|(bd, _)| d < bd) {
+                    best = Some((
+                        d,
+                        Snap {
+                            point: candidate,
+                            kind,
+                        },
+                    ));
+                }
+            }
+            if let Some((_, snap)) = best {
+                return snap;
+            }
+        }
+
+        if let Some(spacing) = self.settings.grid.filter(|s| *s > 0.0) {
+            return Snap {
+                point: (point / spacing).round() * spacing,
+                kind: SnapKind::Grid,
+            };
+        }
+
+        Snap {
+            point,
+            kind: SnapKind::None,
+        }
+    }
+
+    /// Draws a marker at the snapped position so users can tell what the
+    /// cursor locked on to.
+    pub fn draw_indicator(&self, snap: &Snap, batch: &mut IndexedBatch<'_, ColoredVertex>) {
+        let p = snap.point;
+        let s = INDICATOR_SIZE;
+        match snap.kind {
+            SnapKind::None => {}
+            SnapKind::Grid | SnapKind::Angle => {
+                tessellate_polyline(
+                    &[p - Vec2::X * s, p + Vec2::X * s],
+                    false,
+                    INDICATOR_COLOR,
+                    batch,
+                );
+                tessellate_polyline(
+                    &[p - Vec2::Y * s, p + Vec2::Y * s],
+                    false,
+                    INDICATOR_COLOR,
+                    batch,
+                );
+            }
+            SnapKind::Endpoint => {
+                let square = [
+                    p + Vec2::new(-s, -s),
+                    p + Vec2::new(s, -s),
+                    p + Vec2::new(s, s),
+                    p + Vec2::new(-s, s),
+                ];
+                tessellate_polyline(&square, true, INDICATOR_COLOR, batch);
+            }
+            SnapKind::Midpoint => {
+                let triangle = [
+                    p + Vec2::new(0.0, -s),
+                    p + Vec2::new(s, s),
+                    p + Vec2::new(-s, s),
+                ];
+                tessellate_polyline(&triangle, true, INDICATOR_COLOR, batch);
+            }
+        }
+    }
+}
+
+/// Locks `point` to the closest multiple of `step` radians around `origin`,
+/// keeping its distance along that direction.
+pub fn snap_angle(origin: Vec2, point: Vec2, step: f32) -> Vec2 {
+    let delta = point - origin;
+    if delta.length_squared() <= f32::EPSILON || step <= 0.0 {
+        return point;
+    }
+    let angle = (delta.to_angle() / step).round() * step;
+    let dir = Vec2::from_angle(angle);
+    origin + dir * delta.dot(dir)
+}
+
+/// Endpoints and midpoints of every shape on visible layers in world space.
+fn snap_points(layers: &mut LayerStack) -> Vec<(Vec2, SnapKind)> {
+    let mut points = Vec::new();
+    for layer in layers.iter_mut().filter(|l| l.is_visible()) {
+        let scene = layer.scene_mut();
+        let ids: Vec<_> = scene.iter().map(|(id, _)| id).collect();
+        for id in ids {
+            let world = scene.world_transform(id);
+            let Some(shape) = scene.get(id).and_then(|n| n.shape()) else {
+                continue;
+            };
+            let mut push = |p: Vec2, kind| points.push((world.transform_point2(p), kind));
+            match &shape.geometry {
+                // Freehand strokes have lots of points so only their ends
+                // are interesting. Straight lines also get a midpoint.
+                Geometry::Polyline { points, closed } => {
+                    if let (Some(&first), Some(&last)) = (points.first(), points.last()) {
+                        push(first, SnapKind::Endpoint);
+                        push(last, SnapKind::Endpoint);
+                        if points.len() == 2 && !closed {
+                            push((first + last) * 0.5, SnapKind::Midpoint);
+                        }
+                    }
+                }
+                Geometry::Circle { center, .. } => push(*center, SnapKind::Midpoint),
+                Geometry::Rect { .. } => {
+                    let outline = shape.outline();
+                    for (i, &corner) in outline.iter().enumerate() {
+                        let next = outline[(i + 1) % outline.len()];
+                        push(corner, SnapKind::Endpoint);
+                        push((corner + next) * 0.5, SnapKind::Midpoint);
+                    }
+                }
+            }
+        }
+    }
+    points
+}
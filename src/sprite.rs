@@ -0,0 +1,272 @@
+use glam::{UVec2, Vec2, Vec4};
+
+use crate::{
+    draw_list::{Draw, DrawList},
+    resources::{
+        buffer::BackedBuffer,
+        camera::{CameraBinder, CameraBinding},
+    },
+    utils::RenderPipelineBuilder,
+    wgsl,
+};
+
+/// A textured quad drawn by [SpriteRenderer]. `uv_min` is sampled at the
+/// quad's minimum corner before it's rotated.
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Sprite {
+    /// Center of the quad in world units.
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+    /// Multiplies the texture's color.
+    pub color: [f32; 4],
+    /// Counter clockwise in radians, around `position`.
+    pub rotation: f32,
+}
+
+impl Sprite {
+    pub const VB_DESC: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<Sprite>() as _,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &wgpu::vertex_attr_array![
+            0 => Float32x2,
+            1 => Float32x2,
+            2 => Float32x2,
+            3 => Float32x2,
+            4 => Float32x4,
+            5 => Float32,
+        ],
+    };
+
+    /// The whole texture, untinted and unrotated.
+    pub fn new(position: Vec2, size: Vec2) -> Self {
+        Self {
+            position: position.to_array(),
+            size: size.to_array(),
+            uv_min: [0.0, 0.0],
+            uv_max: [1.0, 1.0],
+            color: [1.0; 4],
+            rotation: 0.0,
+        }
+    }
+
+    /// Draws the part of the texture between `min` and `max`, like one
+    /// icon out of an atlas.
+    pub fn uv(mut self, min: Vec2, max: Vec2) -> Self {
+        self.uv_min = min.to_array();
+        self.uv_max = max.to_array();
+        self
+    }
+
+    pub fn color(mut self, color: Vec4) -> Self {
+        self.color = color.to_array();
+        self
+    }
+
+    pub fn rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+}
+
+/// Cuts a texture into a 3×3 grid for drawing panels and buttons of any
+/// size. The corners keep their size, the edges stretch along the panel's
+/// sides and the middle stretches to fill it. Insets are in texels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NineSlice {
+    pub texture_size: UVec2,
+    /// Border widths along the minimum edges of the texture.
+    pub min_inset: Vec2,
+    /// Border widths along the maximum edges.
+    pub max_inset: Vec2,
+    /// World units per texel in the borders.
+    pub scale: f32,
+}
+
+impl NineSlice {
+    /// Borders `inset` texels wide all the way around.
+    pub fn new(texture_size: UVec2, inset: f32) -> Self {
+        Self {
+            texture_size,
+            min_inset: Vec2::splat(inset),
+            max_inset: Vec2::splat(inset),
+            scale: 1.0,
+        }
+    }
+
+    pub fn insets(mut self, min: Vec2, max: Vec2) -> Self {
+        self.min_inset = min;
+        self.max_inset = max;
+        self
+    }
+
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// The nine sprites covering `min` to `max`, row by row from the
+    /// minimum corner. Borders shrink to fit panels smaller than them.
+    pub fn sprites(&self, min: Vec2, max: Vec2, color: Vec4) -> [Sprite; 9] {
+        let size = (max - min).max(Vec2::ZERO);
+        let mut near = self.min_inset * self.scale;
+        let mut far = self.max_inset * self.scale;
+        let fit = (size / (near + far).max(Vec2::splat(f32::EPSILON))).min(Vec2::ONE);
+        near *= fit;
+        far *= fit;
+        let xs = [min.x, min.x + near.x, max.x - far.x, max.x];
+        let ys = [min.y, min.y + near.y, max.y - far.y, max.y];
+
+        let texture_size = self.texture_size.max(UVec2::ONE).as_vec2();
+        let uv_near = self.min_inset / texture_size;
+        let uv_far = Vec2::ONE - self.max_inset / texture_size;
+        let us = [0.0, uv_near.x, uv_far.x, 1.0];
+        let vs = [0.0, uv_near.y, uv_far.y, 1.0];
+
+        std::array::from_fn(|i| {
+            let (column, row) = (i % 3, i / 3);
+            let a = Vec2::new(xs[column], ys[row]);
+            let b = Vec2::new(xs[column + 1], ys[row + 1]);
+            Sprite::new((a + b) * 0.5, b - a)
+                .uv(
+                    Vec2::new(us[column], vs[row]),
+                    Vec2::new(us[column + 1], vs[row + 1]),
+                )
+                .color(color)
+        })
+    }
+}
+
+/// Draws many [Sprite]s sharing a texture in one instanced draw call, like
+/// icons, handles or brush stamps. Textures are bound with the
+/// [TextureRegistry](crate::resources::texture::TextureRegistry) layout.
+/// [NineSlice] panels are batched as sprites too.
+pub struct SpriteRenderer {
+    #[cfg(not(target_arch = "wasm32"))]
+    pipeline_layout: wgpu::PipelineLayout,
+    #[cfg(not(target_arch = "wasm32"))]
+    surface_format: wgpu::TextureFormat,
+    #[cfg(not(target_arch = "wasm32"))]
+    sample_count: u32,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl SpriteRenderer {
+    pub fn new(
+        camera_binder: &CameraBinder,
+        texture_layout: &wgpu::BindGroupLayout,
+        surface_format: wgpu::TextureFormat,
+        sample_count: u32,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<Self> {
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("SpriteRenderer::pipeline_layout"),
+            bind_group_layouts: &[camera_binder.layout(), texture_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = Self::build(
+            &wgsl::compose_built_in(include_str!("sprite.wgsl"))?,
+            &pipeline_layout,
+            surface_format,
+            sample_count,
+            device,
+        )?;
+        Ok(Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            pipeline_layout,
+            #[cfg(not(target_arch = "wasm32"))]
+            surface_format,
+            #[cfg(not(target_arch = "wasm32"))]
+            sample_count,
+            pipeline,
+        })
+    }
+
+    fn build(
+        source: &str,
+        pipeline_layout: &wgpu::PipelineLayout,
+        surface_format: wgpu::TextureFormat,
+        sample_count: u32,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("sprite.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        RenderPipelineBuilder::new()
+            .label("SpriteRenderer::pipeline")
+            .layout(pipeline_layout)
+            .topology(wgpu::PrimitiveTopology::TriangleStrip)
+            .vertex(wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("sprite_quad"),
+                compilation_options: Default::default(),
+                buffers: &[Sprite::VB_DESC],
+            })
+            .fragment(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("sprite_color"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            })
+            .sample_count(sample_count)
+            .build(device)
+    }
+
+    /// Recompiles the shader from `source` and rebuilds the pipeline with
+    /// it. Keeps the old one if that fails.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload(&mut self, source: &str, device: &wgpu::Device) -> anyhow::Result<()> {
+        self.pipeline = crate::utils::validated(device, || {
+            Self::build(
+                source,
+                &self.pipeline_layout,
+                self.surface_format,
+                self.sample_count,
+                device,
+            )
+        })??;
+        Ok(())
+    }
+
+    /// An empty buffer to [batch](BackedBuffer::batch) sprites into.
+    pub fn buffer(&self, capacity: u64, device: &wgpu::Device) -> BackedBuffer<Sprite> {
+        BackedBuffer::with_capacity(device, capacity.max(1), wgpu::BufferUsages::VERTEX)
+    }
+
+    /// Draws every sprite in `sprites` with `texture`, later ones on top.
+    pub fn draw(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        sprites: &BackedBuffer<Sprite>,
+        texture: &wgpu::BindGroup,
+        camera_binding: &CameraBinding,
+    ) {
+        let mut list = DrawList::new();
+        self.submit(&mut list, sprites, texture, camera_binding);
+        list.execute(pass);
+    }
+
+    /// Adds a draw of `sprites` to `list`. See [SpriteRenderer::draw].
+    pub fn submit<'a>(
+        &'a self,
+        list: &mut DrawList<'a>,
+        sprites: &'a BackedBuffer<Sprite>,
+        texture: &'a wgpu::BindGroup,
+        camera_binding: &'a CameraBinding,
+    ) {
+        list.push(
+            Draw::new(&self.pipeline)
+                .camera(0, camera_binding)
+                .texture(1, texture)
+                .vertex_buffer(0, sprites.buffer())
+                .vertices(0..4, 0..sprites.len()),
+        );
+    }
+}
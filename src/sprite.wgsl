@@ -0,0 +1,53 @@
+#include "camera.wgsl"
+
+@group(0)
+@binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1)
+@binding(0)
+var sprite_texture: texture_2d<f32>;
+@group(1)
+@binding(1)
+var sprite_sampler: sampler;
+
+struct Sprite {
+    @location(0)
+    position: vec2<f32>,
+    @location(1)
+    size: vec2<f32>,
+    @location(2)
+    uv_min: vec2<f32>,
+    @location(3)
+    uv_max: vec2<f32>,
+    @location(4)
+    color: vec4<f32>,
+    @location(5)
+    rotation: f32,
+}
+
+struct VsOut {
+    @builtin(position)
+    frag_position: vec4<f32>,
+    @location(0)
+    uv: vec2<f32>,
+    @location(1)
+    color: vec4<f32>,
+}
+
+// Corners of the quad as a triangle strip, from 0 to 1.
+@vertex
+fn sprite_quad(@builtin(vertex_index) i: u32, sprite: Sprite) -> VsOut {
+    let corner = vec2(f32(i % 2u), f32(i / 2u));
+    let c = cos(sprite.rotation);
+    let s = sin(sprite.rotation);
+    let local = (corner - 0.5) * sprite.size;
+    let position = mat2x2(c, s, -s, c) * local + sprite.position;
+    let uv = mix(sprite.uv_min, sprite.uv_max, corner);
+    return VsOut(camera.view_proj * vec4(position, 0.0, 1.0), uv, sprite.color);
+}
+
+@fragment
+fn sprite_color(vs: VsOut) -> @location(0) vec4<f32> {
+    return textureSample(sprite_texture, sprite_sampler, vs.uv) * vs.color;
+}
@@ -0,0 +1,822 @@
+use std::{
+    fmt::{Debug, Write},
+    path::Path,
+    time::Duration,
+};
+
+use winit::{
+    dpi::{LogicalPosition, LogicalSize},
+    event::{ElementState, Ime, MouseButton, WindowEvent},
+    keyboard::{Key, KeyCode, NamedKey, PhysicalKey},
+};
+
+use crate::{
+    bindings::{Action, Chord, KeyBindings},
+    diagram::StateDiagram,
+    input::KeyInput,
+    machine::StateMachine,
+    overlay::Overlay,
+    resources::font::TextBlock,
+    tools::{select::Selection, Interaction},
+    Canvas, TextEdit, ViewDrag, PNG_EXPORT_PATH, VIEW_ROTATION_STEP,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    document::Document, recording::RecordingFormat, replay::InputRecording, DOCUMENT_PATH,
+    GIF_RECORDING_FRAMES, GIF_RECORDING_PATH, INPUT_RECORDING_PATH, SVG_EXPORT_PATH,
+};
+
+/// What an [AppState] wants done to the [StateStack] after it's handled
+/// something.
+pub enum StateChange {
+    None,
+    /// Passes the event on to the state below this one. Anything that state
+    /// wants done still happens to the top of the stack.
+    Unhandled,
+    /// Puts a state on top of this one.
+    Push(Box<dyn AppState>),
+    /// Removes this state. Popping the last state exits the app.
+    Pop,
+    /// Swaps this state for another one.
+    Replace(Box<dyn AppState>),
+    Exit,
+}
+
+/// A mode the app can be in, like drawing or looking at a menu. Only the
+/// state on top of the stack gets input and updates, but every state is
+/// drawn, bottom to top.
+pub trait AppState {
+    /// Handles input. Resizing, redraws and modifier changes are taken care
+    /// of before states see any events, and keys go to
+    /// [AppState::handle_key] instead.
+    fn handle_event(&mut self, canvas: &mut Canvas, event: &WindowEvent) -> StateChange;
+
+    /// Handles a key going up or down, whether it was typed or replayed.
+    fn handle_key(&mut self, _canvas: &mut Canvas, _key: &KeyInput) -> StateChange {
+        StateChange::Unhandled
+    }
+
+    /// Called every [crate::clock::FixedTimestep::step] of time before
+    /// [AppState::update], zero or more times a frame. Simulations that have
+    /// to behave the same at any frame rate go here. Unhandled steps go to
+    /// the state below, so what it's animating keeps moving.
+    fn fixed_update(&mut self, _canvas: &mut Canvas, _step: Duration) -> StateChange {
+        StateChange::Unhandled
+    }
+
+    /// Called once a frame before the canvas is drawn.
+    fn update(&mut self, _canvas: &mut Canvas) -> StateChange {
+        StateChange::None
+    }
+
+    /// Draws on top of the canvas and the states below this one. `alpha`
+    /// is how far the frame is between the last [AppState::fixed_update]
+    /// and the next, for drawing what they move in between.
+    fn render(&self, _canvas: &Canvas, _pass: &mut wgpu::RenderPass<'_>, _alpha: f32) {}
+
+    /// Called when the file at `path` in the resource directory changed on
+    /// disk, after the canvas has reloaded whatever of its own uses it.
+    fn asset_changed(&mut self, _canvas: &mut Canvas, _path: &Path) -> StateChange {
+        StateChange::Unhandled
+    }
+}
+
+pub struct StateStack {
+    states: Vec<Box<dyn AppState>>,
+}
+
+impl StateStack {
+    pub fn new(base: Box<dyn AppState>) -> Self {
+        Self { states: vec![base] }
+    }
+
+    pub fn push(&mut self, state: Box<dyn AppState>) {
+        self.states.push(state);
+    }
+
+    pub fn pop(&mut self) -> Option<Box<dyn AppState>> {
+        self.states.pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// Sends `event` to the top state. Returns `false` once the app should
+    /// exit.
+    pub fn handle_event(&mut self, canvas: &mut Canvas, event: &WindowEvent) -> bool {
+        if let WindowEvent::KeyboardInput { event, .. } = event {
+            return self.handle_key(canvas, &KeyInput::from_event(event));
+        }
+        self.dispatch(|state| state.handle_event(canvas, event))
+    }
+
+    /// Sends `key` to the top state. Returns `false` once the app should
+    /// exit.
+    pub fn handle_key(&mut self, canvas: &mut Canvas, key: &KeyInput) -> bool {
+        self.dispatch(|state| state.handle_key(canvas, key))
+    }
+
+    /// Tells the states from the top down that a resource changed, until
+    /// one of them handles it. Returns `false` once the app should exit.
+    pub fn asset_changed(&mut self, canvas: &mut Canvas, path: &Path) -> bool {
+        self.dispatch(|state| state.asset_changed(canvas, path))
+    }
+
+    /// Hands something to the states from the top down until one of them
+    /// handles it.
+    fn dispatch(&mut self, mut handle: impl FnMut(&mut dyn AppState) -> StateChange) -> bool {
+        if self.states.is_empty() {
+            return false;
+        }
+        let mut change = StateChange::Unhandled;
+        for state in self.states.iter_mut().rev() {
+            change = handle(state.as_mut());
+            if !matches!(change, StateChange::Unhandled) {
+                break;
+            }
+        }
+        self.apply(change)
+    }
+
+    /// Steps the states from the top down, until one of them handles it.
+    /// Returns `false` once the app should exit.
+    pub fn fixed_update(&mut self, canvas: &mut Canvas, step: Duration) -> bool {
+        self.dispatch(|state| state.fixed_update(canvas, step))
+    }
+
+    /// Updates the top state. Returns `false` once the app should exit.
+    pub fn update(&mut self, canvas: &mut Canvas) -> bool {
+        let Some(top) = self.states.last_mut() else {
+            return false;
+        };
+        let change = top.update(canvas);
+        self.apply(change)
+    }
+
+    pub fn render(&self, canvas: &Canvas, pass: &mut wgpu::RenderPass<'_>, alpha: f32) {
+        for state in &self.states {
+            state.render(canvas, pass, alpha);
+        }
+    }
+
+    fn apply(&mut self, change: StateChange) -> bool {
+        match change {
+            StateChange::None | StateChange::Unhandled => {}
+            StateChange::Push(state) => self.states.push(state),
+            StateChange::Pop => {
+                self.states.pop();
+            }
+            StateChange::Replace(state) => {
+                self.states.pop();
+                self.states.push(state);
+            }
+            StateChange::Exit => self.states.clear(),
+        }
+        !self.states.is_empty()
+    }
+}
+
+/// Editing the drawing with the tools. This is the bottom of the stack.
+#[derive(Default)]
+pub struct DrawingState {
+    bindings: KeyBindings,
+}
+
+impl DrawingState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_bindings(bindings: KeyBindings) -> Self {
+        Self { bindings }
+    }
+
+    pub fn bindings(&self) -> &KeyBindings {
+        &self.bindings
+    }
+
+    pub fn bindings_mut(&mut self) -> &mut KeyBindings {
+        &mut self.bindings
+    }
+
+    fn key_pressed(&mut self, canvas: &mut Canvas, code: KeyCode) -> StateChange {
+        let modifiers = canvas.modifiers();
+        let chord = Chord {
+            key: code,
+            command: canvas.command_modifier(),
+            shift: modifiers.shift_key(),
+            alt: modifiers.alt_key(),
+        };
+        match self.bindings.action(chord) {
+            Some(action) => self.perform(canvas, action),
+            None => StateChange::None,
+        }
+    }
+
+    fn perform(&mut self, canvas: &mut Canvas, action: Action) -> StateChange {
+        match action {
+            Action::Exit => return StateChange::Exit,
+            Action::Help => match HelpState::new(canvas, &self.bindings) {
+                Ok(help) => return StateChange::Push(Box::new(help)),
+                Err(e) => log::error!("Unable to show help: {e}"),
+            },
+            Action::CycleDrawMode => canvas.cycle_draw_mode(),
+            Action::Undo => canvas.undo(),
+            Action::Redo => canvas.redo(),
+            #[cfg(not(target_arch = "wasm32"))]
+            Action::Save => {
+                if let Err(e) = canvas.document().save(DOCUMENT_PATH) {
+                    log::error!("Unable to save {DOCUMENT_PATH}: {e}");
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            Action::Open => {
+                if let Err(e) = Document::load(DOCUMENT_PATH)
+                    .and_then(|document| canvas.open_document(&document))
+                {
+                    log::error!("Unable to open {DOCUMENT_PATH}: {e}");
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            Action::ExportSvg => {
+                if let Err(e) = std::fs::write(SVG_EXPORT_PATH, canvas.export_svg()) {
+                    log::error!("Unable to export {SVG_EXPORT_PATH}: {e}");
+                }
+            }
+            #[cfg(target_arch = "wasm32")]
+            Action::Save | Action::Open | Action::ExportSvg => {}
+            Action::ExportPng => {
+                let size = canvas.window().inner_size();
+                if let Err(e) = canvas.export_png(size.width, size.height, PNG_EXPORT_PATH) {
+                    log::error!("Unable to export {PNG_EXPORT_PATH}: {e}");
+                }
+            }
+            Action::Copy => {
+                if let Err(e) = canvas.copy_selection() {
+                    log::error!("Unable to copy selection: {e}");
+                }
+            }
+            Action::Cut => {
+                if let Err(e) = canvas.cut_selection() {
+                    log::error!("Unable to cut selection: {e}");
+                }
+            }
+            Action::Paste => canvas.paste(),
+            Action::Delete => canvas.delete_selection(),
+            Action::Tool(tool) => canvas.set_tool(tool),
+            Action::ToggleSelectMode => canvas.toggle_select_mode(),
+            Action::Group => canvas.group_selection(),
+            Action::Ungroup => canvas.ungroup_selection(),
+            Action::ToggleGrid => canvas.toggle_grid(),
+            Action::ZoomToFit => canvas.zoom_to_fit(),
+            Action::ToggleDetailView => canvas.toggle_detail_view(),
+            Action::ToggleMinimap => canvas.toggle_minimap(),
+            Action::ToggleFollow => canvas.toggle_follow_selection(),
+            Action::RotateViewLeft => canvas.rotate_view(-VIEW_ROTATION_STEP),
+            Action::RotateViewRight => canvas.rotate_view(VIEW_ROTATION_STEP),
+            Action::ResetViewRotation => canvas.set_view_rotation(0.0),
+            Action::Constrain(kind) => canvas.constrain_selection(kind),
+            Action::ShowStateDiagram => return StateChange::Push(Box::new(DiagramState::new())),
+            Action::ShowStateDebug => return StateChange::Push(Box::new(MachineDebugState::new())),
+            Action::TogglePerfOverlay => canvas.toggle_perf_overlay(),
+            Action::ToggleRedrawPolicy => canvas.toggle_redraw_policy(),
+            Action::CyclePresentMode => {
+                canvas.cycle_present_mode();
+            }
+            Action::AddText => return StateChange::Push(Box::new(TextEntryState::new(canvas))),
+            Action::ToggleTouchDrawing => canvas.set_touch_drawing(!canvas.touch_drawing()),
+            Action::ToggleInputRecording => match canvas.stop_recording() {
+                #[cfg(not(target_arch = "wasm32"))]
+                Some(recording) => {
+                    if let Err(e) = recording.save(INPUT_RECORDING_PATH) {
+                        log::error!("Unable to save {INPUT_RECORDING_PATH}: {e}");
+                    }
+                }
+                #[cfg(target_arch = "wasm32")]
+                Some(_) => {}
+                None => canvas.start_recording(),
+            },
+            #[cfg(not(target_arch = "wasm32"))]
+            Action::ReplayInput => match InputRecording::load(INPUT_RECORDING_PATH) {
+                Ok(recording) => canvas.start_replay(recording),
+                Err(e) => log::error!("Unable to load {INPUT_RECORDING_PATH}: {e}"),
+            },
+            #[cfg(target_arch = "wasm32")]
+            Action::ReplayInput => {}
+            #[cfg(not(target_arch = "wasm32"))]
+            Action::ToggleFrameRecording => {
+                if canvas.is_recording_frames() {
+                    canvas.stop_frame_recording();
+                } else if let Err(e) = canvas.start_frame_recording(
+                    GIF_RECORDING_PATH,
+                    RecordingFormat::Gif,
+                    GIF_RECORDING_FRAMES,
+                    // Plays back at about the speed it was drawn.
+                    (canvas.clock().stats().fps().round() as u32).max(1),
+                ) {
+                    log::error!("Unable to record {GIF_RECORDING_PATH}: {e}");
+                }
+            }
+            #[cfg(target_arch = "wasm32")]
+            Action::ToggleFrameRecording => {}
+            #[cfg(feature = "debug-ui")]
+            Action::ToggleDebugUi => canvas.toggle_debug_ui(),
+            #[cfg(not(feature = "debug-ui"))]
+            Action::ToggleDebugUi => log::warn!("The debug panel needs the debug-ui feature"),
+        }
+        StateChange::None
+    }
+}
+
+impl AppState for DrawingState {
+    fn handle_event(&mut self, canvas: &mut Canvas, event: &WindowEvent) -> StateChange {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = position.to_logical::<f32>(canvas.scale_factor() as f64);
+                canvas.cursor_moved(position.x, position.y)
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => match state {
+                ElementState::Pressed => canvas.mouse_pressed(),
+                ElementState::Released => canvas.mouse_released(),
+            },
+            // Middle dragging pans the view, or turns it with Shift held.
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Middle,
+                ..
+            } => match state {
+                ElementState::Pressed if canvas.modifiers().shift_key() => {
+                    canvas.begin_view_drag(ViewDrag::Rotate)
+                }
+                ElementState::Pressed => canvas.begin_view_drag(ViewDrag::Pan),
+                ElementState::Released => canvas.end_view_drag(),
+            },
+            WindowEvent::Focused(false) => canvas.end_view_drag(),
+            WindowEvent::Touch(touch) => {
+                let position = touch
+                    .location
+                    .to_logical::<f32>(canvas.scale_factor() as f64);
+                canvas.touch(
+                    touch.id,
+                    touch.phase,
+                    glam::Vec2::new(position.x, position.y),
+                );
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            WindowEvent::DroppedFile(path) => {
+                if let Err(e) = canvas.open_file(path) {
+                    log::error!("Unable to open {}: {e}", path.display());
+                }
+            }
+            _ => {}
+        }
+        StateChange::None
+    }
+
+    fn handle_key(&mut self, canvas: &mut Canvas, key: &KeyInput) -> StateChange {
+        match key.pressed_code() {
+            Some(code) => self.key_pressed(canvas, code),
+            None => StateChange::None,
+        }
+    }
+
+    fn fixed_update(&mut self, canvas: &mut Canvas, step: Duration) -> StateChange {
+        canvas.step_camera(step);
+        StateChange::None
+    }
+
+    fn update(&mut self, canvas: &mut Canvas) -> StateChange {
+        canvas.poll_paste();
+        canvas.zoom_with_wheel();
+        canvas.update_view_drag();
+        match canvas.take_text_edit() {
+            Some(edit) => StateChange::Push(Box::new(TextEntryState::edit(canvas, edit))),
+            None => StateChange::None,
+        }
+    }
+}
+
+/// Where the help text starts, in logical pixels from the top left corner.
+const HELP_POSITION: glam::Vec2 = glam::Vec2::new(20.0, 60.0);
+const HELP_TEXT_SIZE: f32 = 16.0;
+const HELP_ENTRIES_PER_LINE: usize = 4;
+
+/// Lists the key bindings over the drawing. Input doesn't reach the drawing
+/// while it's open.
+pub struct HelpState {
+    text: TextBlock,
+}
+
+impl HelpState {
+    pub fn new(canvas: &Canvas, bindings: &KeyBindings) -> anyhow::Result<Self> {
+        Ok(Self {
+            text: canvas.screen_text(&help_text(bindings), HELP_POSITION, HELP_TEXT_SIZE)?,
+        })
+    }
+}
+
+/// Lists what each bound action does, a few to a line.
+fn help_text(bindings: &KeyBindings) -> String {
+    let mut actions = Vec::new();
+    for binding in bindings.bindings() {
+        if !actions.contains(&binding.action) {
+            actions.push(binding.action);
+        }
+    }
+    let entries: Vec<_> = actions
+        .into_iter()
+        .map(|action| {
+            let chords: Vec<_> = bindings.chords(action).map(|c| c.to_string()).collect();
+            format!("{}  {action}", chords.join(" / "))
+        })
+        .collect();
+    let mut text = String::from("Esc / F1  Close help");
+    for line in entries.chunks(HELP_ENTRIES_PER_LINE) {
+        text.push('\n');
+        text.push_str(&line.join("    "));
+    }
+    text
+}
+
+impl AppState for HelpState {
+    fn handle_event(&mut self, _canvas: &mut Canvas, _event: &WindowEvent) -> StateChange {
+        StateChange::None
+    }
+
+    fn handle_key(&mut self, _canvas: &mut Canvas, key: &KeyInput) -> StateChange {
+        match key.pressed_code() {
+            Some(KeyCode::Escape | KeyCode::F1) => StateChange::Pop,
+            _ => StateChange::None,
+        }
+    }
+
+    fn render(&self, canvas: &Canvas, pass: &mut wgpu::RenderPass<'_>, _alpha: f32) {
+        canvas.draw_screen_text(pass, &self.text);
+    }
+}
+
+/// Where the tool state diagram starts, in logical pixels from the top left
+/// corner.
+const DIAGRAM_POSITION: glam::Vec2 = glam::Vec2::new(20.0, 100.0);
+
+/// Shows the tool [Interaction] state machine as a diagram over the
+/// drawing. Input still reaches the drawing, so the diagram follows along
+/// as the tools are used.
+#[derive(Default)]
+pub struct DiagramState {
+    /// The state the diagram was laid out for.
+    shown: Option<Interaction>,
+    diagram: Option<StateDiagram>,
+}
+
+impl DiagramState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl AppState for DiagramState {
+    fn handle_event(&mut self, _canvas: &mut Canvas, _event: &WindowEvent) -> StateChange {
+        StateChange::Unhandled
+    }
+
+    fn handle_key(&mut self, _canvas: &mut Canvas, key: &KeyInput) -> StateChange {
+        match key.pressed_code() {
+            Some(KeyCode::Escape | KeyCode::F3) => StateChange::Pop,
+            _ => StateChange::Unhandled,
+        }
+    }
+
+    fn update(&mut self, canvas: &mut Canvas) -> StateChange {
+        let state = *canvas.interaction().state();
+        if self.shown != Some(state) {
+            match canvas.screen_diagram(canvas.interaction(), DIAGRAM_POSITION) {
+                Ok(diagram) => self.diagram = Some(diagram),
+                Err(e) => log::error!("Unable to draw the state diagram: {e}"),
+            }
+            self.shown = Some(state);
+        }
+        StateChange::None
+    }
+
+    fn render(&self, canvas: &Canvas, pass: &mut wgpu::RenderPass<'_>, _alpha: f32) {
+        if let Some(diagram) = &self.diagram {
+            canvas.draw_screen_diagram(pass, diagram);
+        }
+    }
+}
+
+/// Where the state machine debug info starts, in logical pixels from the
+/// top left corner.
+const MACHINE_DEBUG_POSITION: glam::Vec2 = glam::Vec2::new(20.0, 260.0);
+const MACHINE_DEBUG_TEXT_SIZE: f32 = 14.0;
+
+/// Shows what the tool [Interaction] state machine is doing: the state it's
+/// in, the transitions it took last and the events waiting to be handled.
+/// Like [DiagramState], input still reaches the drawing.
+#[derive(Default)]
+pub struct MachineDebugState {
+    shown: String,
+    text: Option<TextBlock>,
+}
+
+impl MachineDebugState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl AppState for MachineDebugState {
+    fn handle_event(&mut self, _canvas: &mut Canvas, _event: &WindowEvent) -> StateChange {
+        StateChange::Unhandled
+    }
+
+    fn handle_key(&mut self, _canvas: &mut Canvas, key: &KeyInput) -> StateChange {
+        match key.pressed_code() {
+            Some(KeyCode::Escape | KeyCode::F4) => StateChange::Pop,
+            _ => StateChange::Unhandled,
+        }
+    }
+
+    fn update(&mut self, canvas: &mut Canvas) -> StateChange {
+        let text = describe_machine(canvas.interaction());
+        if text != self.shown {
+            match canvas.screen_text(&text, MACHINE_DEBUG_POSITION, MACHINE_DEBUG_TEXT_SIZE) {
+                Ok(block) => self.text = Some(block),
+                Err(e) => log::error!("Unable to show the tool state: {e}"),
+            }
+            self.shown = text;
+        }
+        StateChange::None
+    }
+
+    fn render(&self, canvas: &Canvas, pass: &mut wgpu::RenderPass<'_>, _alpha: f32) {
+        if let Some(text) = &self.text {
+            canvas.draw_screen_text(pass, text);
+        }
+    }
+}
+
+/// The state `machine` is in along with its parents, the events waiting to
+/// be handled and the transitions it took, newest first.
+fn describe_machine<S, E, C>(machine: &StateMachine<S, E, C>) -> String
+where
+    S: Clone + Eq + std::hash::Hash + Debug,
+    E: Clone + Eq + std::hash::Hash + Debug,
+{
+    let mut text = format!("State: {:?}", machine.state());
+    let mut state = machine.state();
+    while let Some(parent) = machine.parent(state) {
+        let _ = write!(text, " in {parent:?}");
+        state = parent;
+    }
+    let pending = machine.pending();
+    if pending.is_empty() {
+        text.push_str("\nPending: none");
+    } else {
+        let pending: Vec<_> = pending.iter().map(|e| format!("{e:?}")).collect();
+        let _ = write!(text, "\nPending: {}", pending.join(", "));
+    }
+    text.push_str("\nTransitions:");
+    for record in machine.history().iter().rev() {
+        let _ = write!(
+            text,
+            "\n{:>8.2}s  {:?} -> {:?} on {:?}",
+            record.time.as_secs_f32(),
+            record.from,
+            record.to,
+            record.event
+        );
+    }
+    text
+}
+
+/// How high text typed with [TextEntryState] looks when it's added.
+const TEXT_ENTRY_SIZE: f32 = 24.0;
+const PREEDIT_UNDERLINE_COLOR: glam::Vec4 = glam::Vec4::new(0.9, 0.9, 0.9, 1.0);
+
+/// Types a text node into the drawing where the cursor was. Text that an
+/// input method is still composing is shown underlined until it's
+/// committed. Enter adds the text and Escape drops it. Double clicking a
+/// text node edits it in place instead. Ctrl+C copies what's
+/// been typed and Ctrl+V pastes text from the system clipboard.
+pub struct TextEntryState {
+    /// Top left corner of the text in logical window pixels.
+    screen: glam::Vec2,
+    world: glam::Vec2,
+    /// How high the text looks in pixels.
+    size: f32,
+    /// The text node being edited, if this isn't adding a new one.
+    editing: Option<Selection>,
+    text: String,
+    /// What the input method is composing.
+    preedit: String,
+    /// Some platforms send typed text both as a key press and as an input
+    /// method commit. Whichever comes second is dropped.
+    last_key_text: Option<String>,
+    last_commit: Option<String>,
+    /// Whether a paste is waiting for the system clipboard to be read.
+    paste_pending: bool,
+    changed: bool,
+    block: Option<TextBlock>,
+    underline: Option<Overlay>,
+}
+
+impl TextEntryState {
+    pub fn new(canvas: &Canvas) -> Self {
+        Self::open(
+            canvas,
+            canvas.cursor(),
+            TEXT_ENTRY_SIZE,
+            None,
+            String::new(),
+        )
+    }
+
+    /// Edits the text node in `edit`, starting with what it says now.
+    pub fn edit(canvas: &Canvas, edit: TextEdit) -> Self {
+        Self::open(
+            canvas,
+            edit.position,
+            edit.size,
+            Some(edit.selection),
+            edit.text,
+        )
+    }
+
+    fn open(
+        canvas: &Canvas,
+        world: glam::Vec2,
+        size: f32,
+        editing: Option<Selection>,
+        text: String,
+    ) -> Self {
+        let screen = canvas.world_to_screen(world);
+        let window = canvas.window();
+        window.set_ime_allowed(true);
+        window.set_ime_cursor_area(
+            LogicalPosition::new(screen.x, screen.y),
+            LogicalSize::new(1.0, size),
+        );
+        Self {
+            screen,
+            world,
+            size,
+            editing,
+            changed: !text.is_empty(),
+            text,
+            preedit: String::new(),
+            last_key_text: None,
+            last_commit: None,
+            paste_pending: false,
+            block: None,
+            underline: None,
+        }
+    }
+
+    fn close(&self, canvas: &Canvas) -> StateChange {
+        canvas.window().set_ime_allowed(false);
+        StateChange::Pop
+    }
+
+    fn key_pressed(&mut self, canvas: &mut Canvas, event: &KeyInput) -> StateChange {
+        // Keys go to the input method while it's composing.
+        if !self.preedit.is_empty() {
+            return StateChange::None;
+        }
+        if canvas.command_modifier() {
+            match event.physical_key {
+                PhysicalKey::Code(KeyCode::KeyC) => canvas.copy_text(&self.text),
+                PhysicalKey::Code(KeyCode::KeyV) => match canvas.clipboard_text() {
+                    Some(text) => self.paste(&text),
+                    None => self.paste_pending = true,
+                },
+                _ => {}
+            }
+            return StateChange::None;
+        }
+        match &event.logical_key {
+            Key::Named(NamedKey::Escape) => return self.close(canvas),
+            Key::Named(NamedKey::Enter) => {
+                let result = match self.editing {
+                    _ if self.text.is_empty() => Ok(()),
+                    Some(selection) => canvas.edit_text(selection, &self.text),
+                    None => canvas.add_text(&self.text, self.world, self.size),
+                };
+                if let Err(e) = result {
+                    log::error!("Unable to save text: {e}");
+                }
+                return self.close(canvas);
+            }
+            Key::Named(NamedKey::Backspace) => {
+                self.changed |= self.text.pop().is_some();
+            }
+            _ => {
+                let Some(text) = event.text.as_ref().filter(|t| !t.is_empty()) else {
+                    return StateChange::None;
+                };
+                if text.chars().any(char::is_control) {
+                    return StateChange::None;
+                }
+                if self.last_commit.take().as_deref() != Some(text.as_str()) {
+                    self.text.push_str(text);
+                    self.last_key_text = Some(text.to_string());
+                    self.changed = true;
+                }
+            }
+        }
+        StateChange::None
+    }
+
+    /// Adds the first line of `text`, without control characters.
+    fn paste(&mut self, text: &str) {
+        let line = text.lines().next().unwrap_or_default();
+        let len = self.text.len();
+        self.text.extend(line.chars().filter(|c| !c.is_control()));
+        self.changed |= self.text.len() != len;
+    }
+}
+
+impl AppState for TextEntryState {
+    fn handle_event(&mut self, _canvas: &mut Canvas, event: &WindowEvent) -> StateChange {
+        match event {
+            WindowEvent::Ime(Ime::Preedit(text, _)) => {
+                self.preedit = text.clone();
+                self.changed = true;
+            }
+            WindowEvent::Ime(Ime::Commit(text)) => {
+                if self.last_key_text.take().as_deref() != Some(text.as_str()) {
+                    self.text.push_str(text);
+                    self.last_commit = Some(text.clone());
+                }
+                self.preedit.clear();
+                self.changed = true;
+            }
+            WindowEvent::Ime(Ime::Disabled) => {
+                self.preedit.clear();
+                self.changed = true;
+            }
+            _ => {}
+        }
+        StateChange::None
+    }
+
+    fn handle_key(&mut self, canvas: &mut Canvas, key: &KeyInput) -> StateChange {
+        if key.is_pressed() {
+            self.key_pressed(canvas, key)
+        } else {
+            StateChange::None
+        }
+    }
+
+    fn update(&mut self, canvas: &mut Canvas) -> StateChange {
+        if self.paste_pending {
+            if let Some(text) = canvas.poll_clipboard_text() {
+                self.paste_pending = false;
+                self.paste(&text);
+            } else {
+                canvas.request_redraw();
+            }
+        }
+        if !std::mem::take(&mut self.changed) {
+            return StateChange::None;
+        }
+        let shown = format!("{}{}", self.text, self.preedit);
+        self.block = match canvas.screen_text(&shown, self.screen, self.size) {
+            Ok(block) => Some(block),
+            Err(e) => {
+                log::error!("Unable to show the text being typed: {e}");
+                None
+            }
+        };
+        self.underline = (!self.preedit.is_empty()).then(|| {
+            let (start, baseline) = canvas.text_metrics(&self.text, self.size);
+            let (width, _) = canvas.text_metrics(&self.preedit, self.size);
+            let y = self.screen.y + baseline + 2.0;
+            let start = self.screen.x + start;
+            canvas.screen_lines(
+                &[[glam::Vec2::new(start, y), glam::Vec2::new(start + width, y)]],
+                PREEDIT_UNDERLINE_COLOR,
+            )
+        });
+        StateChange::None
+    }
+
+    fn render(&self, canvas: &Canvas, pass: &mut wgpu::RenderPass<'_>, _alpha: f32) {
+        if let Some(block) = &self.block {
+            canvas.draw_screen_text(pass, block);
+        }
+        if let Some(underline) = &self.underline {
+            canvas.draw_screen_lines(pass, underline);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::{cell::Cell, ops::Range};
+
+/// What the GPU was asked to do, counted as it's recorded. Counts pile up
+/// on the thread that records them until they're taken with [take].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    pub instances: u32,
+    /// Vertices processed, which for indexed draws is the indices drawn.
+    pub vertices: u32,
+    /// Writes to buffers through the queue.
+    pub buffer_uploads: u32,
+    pub upload_bytes: u64,
+    /// Texture bind groups set, other than ones only holding uniforms.
+    pub texture_binds: u32,
+}
+
+impl std::ops::AddAssign for RenderStats {
+    fn add_assign(&mut self, other: Self) {
+        self.draw_calls += other.draw_calls;
+        self.instances += other.instances;
+        self.vertices += other.vertices;
+        self.buffer_uploads += other.buffer_uploads;
+        self.upload_bytes += other.upload_bytes;
+        self.texture_binds += other.texture_binds;
+    }
+}
+
+thread_local! {
+    static STATS: Cell<RenderStats> = Cell::new(RenderStats::default());
+}
+
+fn record(f: impl FnOnce(&mut RenderStats)) {
+    STATS.with(|stats| {
+        let mut current = stats.get();
+        f(&mut current);
+        stats.set(current);
+    });
+}
+
+/// Returns what's been counted since the last call, and starts over.
+pub fn take() -> RenderStats {
+    STATS.with(|stats| stats.take())
+}
+
+pub fn draw(pass: &mut wgpu::RenderPass<'_>, vertices: Range<u32>, instances: Range<u32>) {
+    record(|stats| {
+        stats.draw_calls += 1;
+        stats.instances += instances.len() as u32;
+        stats.vertices += vertices.len() as u32 * instances.len() as u32;
+    });
+    pass.draw(vertices, instances);
+}
+
+pub fn draw_indexed(
+    pass: &mut wgpu::RenderPass<'_>,
+    indices: Range<u32>,
+    base_vertex: i32,
+    instances: Range<u32>,
+) {
+    record(|stats| {
+        stats.draw_calls += 1;
+        stats.instances += instances.len() as u32;
+        stats.vertices += indices.len() as u32 * instances.len() as u32;
+    });
+    pass.draw_indexed(indices, base_vertex, instances);
+}
+
+pub fn write_buffer(queue: &wgpu::Queue, buffer: &wgpu::Buffer, offset: u64, data: &[u8]) {
+    record(|stats| {
+        stats.buffer_uploads += 1;
+        stats.upload_bytes += data.len() as u64;
+    });
+    queue.write_buffer(buffer, offset, data);
+}
+
+pub fn bind_texture(pass: &mut wgpu::RenderPass<'_>, index: u32, bind_group: &wgpu::BindGroup) {
+    record(|stats| stats.texture_binds += 1);
+    pass.set_bind_group(index, bind_group, &[]);
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use super::*;
+    use crate::{
+        layers::LayerStack,
+        resources::{
+            camera::{CameraBinder, OrthoCamera},
+            geometry::test_pipeline,
+            texture::TextureRegistry,
+        },
+        scene::Transform2D,
+        shape::Shape,
+        utils::test_device,
+    };
+
+    #[test]
+    #[ignore = "needs a GPU adapter"]
+    fn layers_sharing_a_texture_bind_it_once() {
+        let (device, queue) = test_device();
+        let pipeline = test_pipeline(&device);
+        let mut textures = TextureRegistry::new(&device);
+        let texture = textures
+            .insert("Tile", &image::RgbaImage::new(1, 1), &device, &queue)
+            .unwrap();
+        let mut layers = LayerStack::new(&pipeline, &device);
+        layers.add("Top", &pipeline, &device);
+        for i in 0..layers.len() {
+            let layer = layers.get_mut(i).unwrap();
+            layer.add_shape(
+                "Image",
+                Shape::image(texture, Vec2::ONE),
+                Transform2D::IDENTITY,
+                None,
+                &device,
+                &queue,
+            );
+            layer.prepare(&device, &queue);
+        }
+        let camera =
+            CameraBinder::new(&device).bind(&device, &OrthoCamera::new(0.0, 4.0, 4.0, 0.0));
+
+        let target = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: 4,
+                    height: 4,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&Default::default());
+        take();
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target,
+                    resolve_target: None,
+                    ops: Default::default(),
+                })],
+                ..Default::default()
+            });
+            let mut list = crate::draw_list::DrawList::new();
+            for i in 0..layers.len() {
+                layers.get(i).unwrap().submit_shapes(
+                    &mut list,
+                    pipeline.shapes(),
+                    &textures,
+                    &camera,
+                );
+            }
+            list.execute(&mut pass);
+        }
+        queue.submit([encoder.finish()]);
+
+        // The image and its outline on each layer, with the texture left
+        // bound from the first layer to the second.
+        let stats = take();
+        assert_eq!(stats.draw_calls, 4);
+        assert_eq!(stats.texture_binds, 1);
+    }
+}
@@ -0,0 +1,161 @@
+use std::borrow::Cow;
+
+use glam::Vec4;
+use serde::{Deserialize, Serialize};
+
+use crate::shape::{Fill, Gradient, Shape};
+
+/// Most styles a [StyleSheet] can hold. This matches the size of the style
+/// buffer in [GeometryPipeline](crate::resources::geometry::GeometryPipeline).
+pub const MAX_STYLES: usize = 256;
+
+/// Handle to a style in a [StyleSheet].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct StyleId(u32);
+
+impl StyleId {
+    pub fn index(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Splits a stroke into dashes. Lengths are in the shape's local units.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Dash {
+    pub length: f32,
+    pub gap: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StyleFill {
+    Solid(Vec4),
+    Gradient(Gradient),
+}
+
+/// Stroke and fill shared by every shape that references it. Shapes with a
+/// style are drawn with it instead of their own color and fill.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Style {
+    pub name: String,
+    pub stroke: Vec4,
+    /// Width of the stroke in the shape's local units.
+    pub stroke_width: f32,
+    pub dash: Option<Dash>,
+    /// Only closed shapes are filled.
+    pub fill: Option<StyleFill>,
+}
+
+impl Style {
+    pub fn new(name: impl Into<String>, stroke: Vec4, stroke_width: f32) -> Self {
+        Self {
+            name: name.into(),
+            stroke,
+            stroke_width,
+            dash: None,
+            fill: None,
+        }
+    }
+
+    pub fn with_fill(mut self, fill: StyleFill) -> Self {
+        self.fill = Some(fill);
+        self
+    }
+
+    pub fn with_dash(mut self, length: f32, gap: f32) -> Self {
+        self.dash = Some(Dash { length, gap });
+        self
+    }
+}
+
+impl From<&StyleFill> for Fill {
+    fn from(fill: &StyleFill) -> Self {
+        match fill {
+            StyleFill::Solid(color) => Fill::Solid(*color),
+            StyleFill::Gradient(gradient) => Fill::Gradient(gradient.clone()),
+        }
+    }
+}
+
+/// The styles of a drawing. Styles can be edited but not removed, so ids
+/// stay valid.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StyleSheet {
+    styles: Vec<Style>,
+    /// Styles changed since they were last uploaded.
+    #[serde(skip, default = "dirty")]
+    dirty: bool,
+}
+
+fn dirty() -> bool {
+    true
+}
+
+impl Default for StyleSheet {
+    fn default() -> Self {
+        Self {
+            styles: Vec::new(),
+            dirty: true,
+        }
+    }
+}
+
+impl StyleSheet {
+    /// Adds a style. Fails if the sheet already has [MAX_STYLES] styles.
+    pub fn add(&mut self, style: Style) -> Option<StyleId> {
+        if self.styles.len() >= MAX_STYLES {
+            return None;
+        }
+        self.styles.push(style);
+        self.dirty = true;
+        Some(StyleId(self.styles.len() as u32 - 1))
+    }
+
+    pub fn get(&self, id: StyleId) -> Option<&Style> {
+        self.styles.get(id.0 as usize)
+    }
+
+    /// Replaces a style, returning the old one.
+    pub fn set(&mut self, id: StyleId, style: Style) -> Option<Style> {
+        let slot = self.styles.get_mut(id.0 as usize)?;
+        self.dirty = true;
+        Some(std::mem::replace(slot, style))
+    }
+
+    pub fn find(&self, name: &str) -> Option<StyleId> {
+        self.iter()
+            .find(|(_, style)| style.name == name)
+            .map(|(id, _)| id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (StyleId, &Style)> {
+        self.styles
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (StyleId(i as u32), s))
+    }
+
+    pub fn len(&self) -> usize {
+        self.styles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.styles.is_empty()
+    }
+
+    /// Whether the styles changed since the last call.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    /// Returns the shape with its style's stroke color and fill applied, for
+    /// code that doesn't read styles from the GPU.
+    pub fn resolve<'a>(&self, shape: &'a Shape) -> Cow<'a, Shape> {
+        let Some(style) = shape.style.and_then(|id| self.get(id)) else {
+            return Cow::Borrowed(shape);
+        };
+        let mut shape = shape.clone();
+        shape.color = style.stroke;
+        shape.fill = style.fill.as_ref().map(Fill::from);
+        Cow::Owned(shape)
+    }
+}
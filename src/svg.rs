@@ -0,0 +1,582 @@
+use std::{collections::HashMap, fmt::Write};
+
+use base64::Engine;
+use glam::{Affine2, Vec2, Vec4};
+use usvg::tiny_skia_path::PathSegment;
+
+use crate::{
+    layers::LayerStack,
+    resources::{
+        font::FontData,
+        texture::{TextureId, TextureRegistry},
+    },
+    scene::{NodeId, NodeKind, Scene, Transform2D},
+    shape::{Bounds, Fill, Geometry, Gradient, GradientKind, Shape, TextureFill, Tiling},
+    style::{Style, StyleSheet},
+};
+
+/// Curves are flattened into segments of roughly this length.
+const CURVE_TOLERANCE: f32 = 4.0;
+const MAX_CURVE_SEGMENTS: usize = 32;
+
+/// A shape read from an SVG file along with the id it had there.
+#[derive(Debug, Clone)]
+pub struct ImportedShape {
+    pub name: String,
+    pub shape: Shape,
+}
+
+/// Converts the paths in an SVG document into polyline shapes in document
+/// coordinates. Curves get flattened and transforms are baked into the
+/// points since they may contain skew. Images are skipped.
+pub fn import(data: &[u8]) -> anyhow::Result<Vec<ImportedShape>> {
+    let tree = usvg::Tree::from_data(data, &usvg::Options::default())?;
+    let mut shapes = Vec::new();
+    import_group(tree.root(), &mut shapes);
+    Ok(shapes)
+}
+
+fn import_group(group: &usvg::Group, shapes: &mut Vec<ImportedShape>) {
+    for node in group.children() {
+        match node {
+            usvg::Node::Group(group) => import_group(group, shapes),
+            usvg::Node::Path(path) => import_path(path, shapes),
+            usvg::Node::Text(text) => import_group(text.flattened(), shapes),
+            usvg::Node::Image(image) => {
+                log::warn!("Skipping image \"{}\" in SVG", image.id());
+            }
+        }
+    }
+}
+
+fn import_path(path: &usvg::Path, shapes: &mut Vec<ImportedShape>) {
+    if !path.is_visible() {
+        return;
+    }
+    let transform = to_affine(path.abs_transform());
+    let fill = path
+        .fill()
+        .map(|f| import_fill(f.paint(), f.opacity().get(), transform));
+    // Outlines of shapes without a stroke use the fill color so their edges
+    // blend in.
+    let color = match (path.stroke(), &fill) {
+        (Some(stroke), _) => paint_color(stroke.paint(), stroke.opacity().get()),
+        (None, Some(Fill::Solid(color))) => *color,
+        (None, Some(Fill::Gradient(gradient))) => gradient.color_at(0.0),
+        (None, Some(Fill::Texture(fill))) => fill.tint,
+        (None, None) => return,
+    };
+    let filled = fill.is_some();
+    let name = if path.id().is_empty() {
+        "Path".to_string()
+    } else {
+        path.id().to_string()
+    };
+
+    let point = |p: usvg::tiny_skia_path::Point| transform.transform_point2(Vec2::new(p.x, p.y));
+
+    let mut points: Vec<Vec2> = Vec::new();
+    let mut closed = false;
+    let mut finish = |points: &mut Vec<Vec2>, closed: &mut bool| {
+        // Each subpath becomes its own shape, so holes end up filled.
+        if points.len() > 1 {
+            let mut shape = Shape::polyline(std::mem::take(points), *closed || filled, color);
+            shape.fill = fill.clone();
+            shapes.push(ImportedShape {
+                name: name.clone(),
+                shape,
+            });
+        }
+        points.clear();
+        *closed = false;
+    };
+
+    for segment in path.data().segments() {
+        match segment {
+            PathSegment::MoveTo(p) => {
+                finish(&mut points, &mut closed);
+                points.push(point(p));
+            }
+            PathSegment::LineTo(p) => points.push(point(p)),
+            PathSegment::QuadTo(c, p) => {
+                let start = points.last().copied().unwrap_or(point(c));
+                let (c, end) = (point(c), point(p));
+                let n = curve_segments(start.distance(c) + c.distance(end));
+                points.extend((1..=n).map(|i| {
+                    let t = i as f32 / n as f32;
+                    let u = 1.0 - t;
+                    start * (u * u) + c * (2.0 * u * t) + end * (t * t)
+                }));
+            }
+            PathSegment::CubicTo(c1, c2, p) => {
+                let start = points.last().copied().unwrap_or(point(c1));
+                let (c1, c2, end) = (point(c1), point(c2), point(p));
+                let n = curve_segments(start.distance(c1) + c1.distance(c2) + c2.distance(end));
+                points.extend((1..=n).map(|i| {
+                    let t = i as f32 / n as f32;
+                    let u = 1.0 - t;
+                    start * (u * u * u)
+                        + c1 * (3.0 * u * u * t)
+                        + c2 * (3.0 * u * t * t)
+                        + end * (t * t * t)
+                }));
+            }
+            PathSegment::Close => {
+                // The closing segment is implied, so drop a duplicate end
+                // point if the path spelled it out.
+                if points.len() > 2 && points.first() == points.last() {
+                    points.pop();
+                }
+                closed = true;
+                let start = points.first().copied();
+                finish(&mut points, &mut closed);
+                // Segments after a close without a move continue from the
+                // start of the closed subpath.
+                points.extend(start);
+            }
+        }
+    }
+    finish(&mut points, &mut closed);
+}
+
+fn to_affine(t: usvg::Transform) -> Affine2 {
+    Affine2::from_cols_array(&[t.sx, t.ky, t.kx, t.sy, t.tx, t.ty])
+}
+
+/// Converts a fill, moving gradients into the same space as the path's
+/// points. Stops past [crate::shape::MAX_GRADIENT_STOPS] are dropped.
+fn import_fill(paint: &usvg::Paint, opacity: f32, transform: Affine2) -> Fill {
+    let (kind, stops) = match paint {
+        usvg::Paint::LinearGradient(g) => {
+            let t = transform * to_affine(g.transform());
+            let kind = GradientKind::Linear {
+                start: t.transform_point2(Vec2::new(g.x1(), g.y1())),
+                end: t.transform_point2(Vec2::new(g.x2(), g.y2())),
+            };
+            (kind, g.stops())
+        }
+        usvg::Paint::RadialGradient(g) => {
+            let t = transform * to_affine(g.transform());
+            // Skewed or non-uniformly scaled gradients become circles.
+            let kind = GradientKind::Radial {
+                center: t.transform_point2(Vec2::new(g.cx(), g.cy())),
+                radius: g.r().get() * t.matrix2.determinant().abs().sqrt(),
+            };
+            (kind, g.stops())
+        }
+        _ => return Fill::Solid(paint_color(paint, opacity)),
+    };
+    let stop = |s: &usvg::Stop| {
+        let color = paint_color(&usvg::Paint::Color(s.color()), opacity * s.opacity().get());
+        (s.offset().get(), color)
+    };
+    let (Some(first), Some(last)) = (stops.first().map(stop), stops.last().map(stop)) else {
+        return Fill::Solid(Vec4::ZERO);
+    };
+    let mut gradient = Gradient::linear(Vec2::ZERO, Vec2::X, first.1, last.1);
+    gradient.set_kind(kind);
+    gradient.set_stop(1, last.0, last.1);
+    gradient.set_stop(0, first.0, first.1);
+    for s in &stops[1..stops.len().saturating_sub(1)] {
+        let (offset, color) = stop(s);
+        if gradient.add_stop(offset, color).is_none() {
+            break;
+        }
+    }
+    Fill::Gradient(gradient)
+}
+
+fn curve_segments(control_length: f32) -> usize {
+    ((control_length / CURVE_TOLERANCE).ceil() as usize).clamp(1, MAX_CURVE_SEGMENTS)
+}
+
+/// Writes the drawing out as an SVG document. The scene hierarchy is kept
+/// as nested groups and each layer becomes a top level group. `font` is
+/// used to size and position text nodes. Images in `textures` that fills
+/// use are embedded as data URIs, if browsers can show them.
+pub fn export(layers: &LayerStack, font: &FontData, textures: &TextureRegistry) -> String {
+    let mut body = String::new();
+    let mut defs = String::new();
+    let mut gradients = 0;
+    let mut images = HashMap::new();
+    let mut bounds: Option<Bounds> = None;
+    for (i, layer) in layers.iter().enumerate() {
+        let mut attributes = format!(r#" id="layer{}""#, i + 1);
+        if !layer.is_visible() {
+            attributes.push_str(r#" display="none""#);
+        }
+        if layer.opacity() < 1.0 {
+            let _ = write!(attributes, r#" opacity="{}""#, layer.opacity());
+        }
+        let _ = writeln!(body, "  <g{attributes}>");
+        let _ = writeln!(body, "    <title>{}</title>", escape(layer.name()));
+        let mut exporter = Exporter {
+            scene: layer.scene(),
+            styles: layers.styles(),
+            font,
+            out: &mut body,
+            textures,
+            defs: &mut defs,
+            gradients: &mut gradients,
+            images: &mut images,
+            bounds: &mut bounds,
+        };
+        for &root in layer.scene().roots() {
+            exporter.node(root, Affine2::IDENTITY, 2);
+        }
+        let _ = writeln!(body, "  </g>");
+    }
+
+    let bounds = bounds
+        .unwrap_or(Bounds::new(Vec2::ZERO, Vec2::ONE))
+        .expand(1.0);
+    let size = bounds.size();
+    if !defs.is_empty() {
+        defs = format!("  <defs>\n{defs}  </defs>\n");
+    }
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            "\n",
+            r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{w}" height="{h}" viewBox="{x} {y} {w} {h}">"#,
+            "\n{defs}{body}</svg>\n",
+        ),
+        x = bounds.min.x,
+        y = bounds.min.y,
+        w = size.x,
+        h = size.y,
+        defs = defs,
+        body = body,
+    )
+}
+
+struct Exporter<'a> {
+    scene: &'a Scene,
+    styles: &'a StyleSheet,
+    font: &'a FontData,
+    textures: &'a TextureRegistry,
+    out: &'a mut String,
+    /// Gradient, pattern and image definitions referenced by fills.
+    defs: &'a mut String,
+    /// Fills defined so far, which number their ids.
+    gradients: &'a mut usize,
+    /// The id of each texture's embedded image, or `None` if it couldn't
+    /// be embedded.
+    images: &'a mut HashMap<TextureId, Option<String>>,
+    /// World space bounds of everything written so far.
+    bounds: &'a mut Option<Bounds>,
+}
+
+impl Exporter<'_> {
+    fn node(&mut self, id: NodeId, parent_world: Affine2, depth: usize) {
+        let Some(node) = self.scene.get(id) else {
+            return;
+        };
+        let world = parent_world * node.transform().to_affine();
+        let indent = "  ".repeat(depth);
+        let _ = writeln!(
+            self.out,
+            r#"{indent}<g id="node{}"{}>"#,
+            id.index(),
+            transform_attribute(node.transform())
+        );
+        let _ = writeln!(self.out, "{indent}  <title>{}</title>", escape(&node.name));
+
+        match &node.kind {
+            NodeKind::Group => {}
+            NodeKind::Shape(shape) => {
+                let style = shape.style.and_then(|id| self.styles.get(id));
+                let shape = self.styles.resolve(shape);
+                let fill = match &shape.fill {
+                    Some(fill) if shape.is_closed() => self.fill(fill),
+                    _ => r#"fill="none""#.to_string(),
+                };
+                let paint = format!("{fill} {}", stroke_attributes(shape.color, style));
+                let element = shape_element(&shape, &paint);
+                let _ = writeln!(self.out, "{indent}  {element}");
+                self.include(Bounds::from_points(
+                    shape.outline().iter().map(|&p| world.transform_point2(p)),
+                ));
+            }
+            NodeKind::Text(text) => {
+                let info = &self.font.info;
+                let _ = writeln!(
+                    self.out,
+                    r#"{indent}  <text x="0" y="{}" font-family="{}" font-size="{}" fill="white">{}</text>"#,
+                    self.font.common.base,
+                    escape(&info.face),
+                    info.size,
+                    escape(text)
+                );
+                let height = self.font.common.line_height as f32;
+                let width = text.chars().count() as f32 * info.size as f32 * 0.6;
+                self.include(Bounds::from_points(
+                    Bounds::new(Vec2::ZERO, Vec2::new(width, height))
+                        .corners()
+                        .map(|p| world.transform_point2(p)),
+                ));
+            }
+        }
+
+        for &child in node.children() {
+            self.node(child, world, depth + 1);
+        }
+        let _ = writeln!(self.out, "{indent}</g>");
+    }
+
+    /// Returns the fill attributes for `fill`, adding a gradient or pattern
+    /// definition if needed.
+    fn fill(&mut self, fill: &Fill) -> String {
+        let gradient = match fill {
+            Fill::Solid(color) => {
+                return format!(r#"fill="{}" fill-opacity="{}""#, hex_color(*color), color.w)
+            }
+            Fill::Gradient(gradient) => gradient,
+            Fill::Texture(fill) => return self.pattern(fill),
+        };
+        *self.gradients += 1;
+        let id = format!("gradient{}", self.gradients);
+        let (element, geometry) = match gradient.kind() {
+            GradientKind::Linear { start, end } => (
+                "linearGradient",
+                format!(
+                    r#"x1="{}" y1="{}" x2="{}" y2="{}""#,
+                    start.x, start.y, end.x, end.y
+                ),
+            ),
+            GradientKind::Radial { center, radius } => (
+                "radialGradient",
+                format!(r#"cx="{}" cy="{}" r="{radius}""#, center.x, center.y),
+            ),
+        };
+        let _ = writeln!(
+            self.defs,
+            r#"    <{element} id="{id}" gradientUnits="userSpaceOnUse" {geometry}>"#
+        );
+        for stop in gradient.stops() {
+            let _ = writeln!(
+                self.defs,
+                r#"      <stop offset="{}" stop-color="{}" stop-opacity="{}"/>"#,
+                stop.offset,
+                hex_color(stop.color),
+                stop.color.w
+            );
+        }
+        let _ = writeln!(self.defs, "    </{element}>");
+        format!(r#"fill="url(#{id})""#)
+    }
+
+    /// Returns the fill attributes for a texture fill, adding a pattern
+    /// that tiles its image. Mirrored tiles are flipped like they are when
+    /// drawn, but clamped ones repeat, as SVG can't stretch their edges.
+    /// Only the tint's alpha is kept.
+    fn pattern(&mut self, fill: &TextureFill) -> String {
+        let Some(image) = self.image(fill.texture) else {
+            return r#"fill="none""#.to_string();
+        };
+        *self.gradients += 1;
+        let id = format!("pattern{}", self.gradients);
+        let size = if fill.tiling == Tiling::Mirror { 2 } else { 1 };
+        let _ = writeln!(
+            self.defs,
+            r#"    <pattern id="{id}" patternUnits="userSpaceOnUse" width="{size}" height="{size}" patternTransform="{}">"#,
+            transform_list(&fill.transform)
+        );
+        let _ = writeln!(self.defs, r##"      <use href="#{image}"/>"##);
+        if fill.tiling == Tiling::Mirror {
+            for flip in [
+                "scale(-1 1) translate(-2 0)",
+                "scale(1 -1) translate(0 -2)",
+                "scale(-1 -1) translate(-2 -2)",
+            ] {
+                let _ = writeln!(
+                    self.defs,
+                    r##"      <use href="#{image}" transform="{flip}"/>"##
+                );
+            }
+        }
+        let _ = writeln!(self.defs, "    </pattern>");
+        format!(r#"fill="url(#{id})" fill-opacity="{}""#, fill.tint.w)
+    }
+
+    /// The id of the image definition for `texture`, embedding it the first
+    /// time it's used. `None` if there's nothing a browser could show.
+    fn image(&mut self, texture: TextureId) -> Option<String> {
+        if let Some(id) = self.images.get(&texture) {
+            return id.clone();
+        }
+        let id = self.embed(texture);
+        self.images.insert(texture, id.clone());
+        id
+    }
+
+    fn embed(&mut self, texture: TextureId) -> Option<String> {
+        let Some(registered) = self.textures.get(texture) else {
+            log::warn!(
+                "Leaving texture {} out of the SVG, as it isn't loaded",
+                texture.index()
+            );
+            return None;
+        };
+        let source = registered.source().and_then(|data| {
+            use image::ImageFormat::*;
+            match image::guess_format(data).ok()? {
+                format @ (Png | Jpeg | WebP | Gif | Bmp) => Some((data, format.to_mime_type())),
+                _ => None,
+            }
+        });
+        let Some((data, mime)) = source else {
+            log::warn!(
+                "Leaving \"{}\" out of the SVG, as it isn't an image browsers can show",
+                registered.name()
+            );
+            return None;
+        };
+        let id = format!("image{}", texture.index());
+        let _ = writeln!(
+            self.defs,
+            r#"    <image id="{id}" width="1" height="1" preserveAspectRatio="none" href="data:{mime};base64,{}"/>"#,
+            base64::engine::general_purpose::STANDARD.encode(data)
+        );
+        Some(id)
+    }
+
+    fn include(&mut self, bounds: Option<Bounds>) {
+        *self.bounds = match (*self.bounds, bounds) {
+            (Some(a), Some(b)) => Some(a.union(&b)),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+fn transform_attribute(t: &Transform2D) -> String {
+    if *t == Transform2D::IDENTITY {
+        return String::new();
+    }
+    format!(r#" transform="{}""#, transform_list(t))
+}
+
+fn transform_list(t: &Transform2D) -> String {
+    // SVG applies the rightmost transform first, which matches
+    // Transform2D::to_affine.
+    format!(
+        "translate({} {}) rotate({}) scale({} {})",
+        t.translation.x,
+        t.translation.y,
+        t.rotation.to_degrees(),
+        t.scale.x,
+        t.scale.y
+    )
+}
+
+fn stroke_attributes(color: Vec4, style: Option<&Style>) -> String {
+    let paint = format!(
+        r#"stroke="{}" stroke-opacity="{}""#,
+        hex_color(color),
+        color.w
+    );
+    let Some(style) = style else {
+        // Outlines are drawn as hairlines, so keep the stroke one pixel wide
+        // no matter how they're scaled.
+        return format!(r#"{paint} stroke-width="1" vector-effect="non-scaling-stroke""#);
+    };
+    let mut attributes = format!(
+        r#"{paint} stroke-width="{}" stroke-linejoin="miter""#,
+        style.stroke_width
+    );
+    if let Some(dash) = style.dash {
+        let _ = write!(
+            attributes,
+            r#" stroke-dasharray="{} {}""#,
+            dash.length, dash.gap
+        );
+    }
+    attributes
+}
+
+fn shape_element(shape: &Shape, style: &str) -> String {
+    match &shape.geometry {
+        Geometry::Polyline { points, closed } => {
+            let points = points
+                .iter()
+                .map(|p| format!("{},{}", p.x, p.y))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let element = if *closed { "polygon" } else { "polyline" };
+            format!(r#"<{element} points="{points}" {style}/>"#)
+        }
+        Geometry::Circle { center, radius } => format!(
+            r#"<circle cx="{}" cy="{}" r="{radius}" {style}/>"#,
+            center.x, center.y
+        ),
+        Geometry::Rect { min, max } => {
+            let size = *max - *min;
+            format!(
+                r#"<rect x="{}" y="{}" width="{}" height="{}" {style}/>"#,
+                min.x, min.y, size.x, size.y
+            )
+        }
+    }
+}
+
+fn hex_color(color: Vec4) -> String {
+    let channel = |c: f32| (linear_to_srgb(c.clamp(0.0, 1.0)) * 255.0).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(color.x),
+        channel(color.y),
+        channel(color.z)
+    )
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Shape colors are linear since they're drawn to an sRGB view, but SVG
+/// colors are sRGB.
+fn paint_color(paint: &usvg::Paint, opacity: f32) -> Vec4 {
+    let srgb = |c: usvg::Color| {
+        Vec4::new(
+            srgb_to_linear(c.red as f32 / 255.0),
+            srgb_to_linear(c.green as f32 / 255.0),
+            srgb_to_linear(c.blue as f32 / 255.0),
+            1.0,
+        )
+    };
+    let color = match paint {
+        usvg::Paint::Color(c) => srgb(*c),
+        // Gradients are approximated with their first stop.
+        usvg::Paint::LinearGradient(g) => g.stops().first().map_or(Vec4::ONE, |s| srgb(s.color())),
+        usvg::Paint::RadialGradient(g) => g.stops().first().map_or(Vec4::ONE, |s| srgb(s.color())),
+        usvg::Paint::Pattern(_) => Vec4::ONE,
+    };
+    color * Vec4::new(1.0, 1.0, 1.0, opacity)
+}
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
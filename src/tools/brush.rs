@@ -0,0 +1,270 @@
+use glam::Vec2;
+
+/// How raw cursor samples are turned into stroke points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    None,
+    /// Exponential moving average. Lower `alpha` means smoother but laggier
+    /// strokes.
+    Ema {
+        alpha: f32,
+    },
+    /// Uniform Catmull-Rom spline through the decimated samples.
+    /// Each segment is emitted once the following control point is known.
+    CatmullRom {
+        subdivisions: u32,
+    },
+}
+
+impl Default for Smoothing {
+    fn default() -> Self {
+        Self::CatmullRom { subdivisions: 4 }
+    }
+}
+
+/// Freehand drawing tool. Feed it cursor positions with [BrushTool::begin],
+/// [BrushTool::drag] and [BrushTool::end], and pull the newly produced stroke
+/// points out with [BrushTool::drain_new] so they can be appended to a
+/// geometry buffer while the user is still dragging.
+#[derive(Debug, Clone)]
+pub struct BrushTool {
+    smoothing: Smoothing,
+    min_distance: f32,
+    drawing: bool,
+    /// Where the cursor last was, before smoothing or dropping it.
+    cursor: Vec2,
+    controls: Vec<Vec2>,
+    ema: Vec2,
+    points: Vec<Vec2>,
+    emitted: usize,
+}
+
+impl Default for BrushTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BrushTool {
+    pub fn new() -> Self {
+        Self {
+            smoothing: Smoothing::default(),
+            min_distance: 2.0,
+            drawing: false,
+            cursor: Vec2::ZERO,
+            controls: Vec::new(),
+            ema: Vec2::ZERO,
+            points: Vec::new(),
+            emitted: 0,
+        }
+    }
+
+    pub fn with_smoothing(mut self, smoothing: Smoothing) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// Samples closer than `distance` to the previous one are dropped.
+    pub fn with_min_distance(mut self, distance: f32) -> Self {
+        self.min_distance = distance.max(0.0);
+        self
+    }
+
+    pub fn smoothing(&self) -> Smoothing {
+        self.smoothing
+    }
+
+    pub fn is_drawing(&self) -> bool {
+        self.drawing
+    }
+
+    pub fn begin(&mut self, p: Vec2) {
+        self.drawing = true;
+        self.cursor = p;
+        self.controls.clear();
+        self.controls.push(p);
+        self.ema = p;
+        self.points.clear();
+        self.points.push(p);
+        self.emitted = 0;
+    }
+
+    pub fn drag(&mut self, p: Vec2) {
+        if !self.drawing {
+            return;
+        }
+        self.cursor = p;
+
+        match self.smoothing {
+            Smoothing::None => self.commit(p),
+            Smoothing::Ema { alpha } => {
+                self.ema += (p - self.ema) * alpha.clamp(0.0, 1.0);
+                self.commit(self.ema);
+            }
+            Smoothing::CatmullRom { subdivisions } => {
+                let last = *self.controls.last().unwrap();
+                if last.distance(p) < self.min_distance {
+                    return;
+                }
+                self.add_control(p, subdivisions);
+            }
+        }
+    }
+
+    /// Finishes the stroke and returns all of its points. Call
+    /// [BrushTool::drain_new] first to get the tail that was produced by
+    /// closing the stroke.
+    pub fn end(&mut self) -> Vec<Vec2> {
+        if !self.drawing {
+            return Vec::new();
+        }
+
+        match self.smoothing {
+            Smoothing::None | Smoothing::Ema { .. } => {
+                // Make sure the stroke ends where the cursor was released,
+                // not where smoothing had got to.
+                self.push_point(self.cursor);
+            }
+            Smoothing::CatmullRom { subdivisions } => {
+                // The release point may have been too close to the last
+                // control point to be added while dragging.
+                if self.controls.last() != Some(&self.cursor) {
+                    self.add_control(self.cursor, subdivisions);
+                }
+                let n = self.controls.len();
+                if n >= 2 {
+                    let p0 = self.controls[n.saturating_sub(3)];
+                    let p1 = self.controls[n - 2];
+                    let p2 = self.controls[n - 1];
+                    self.emit_spline(p0, p1, p2, p2, subdivisions);
+                }
+            }
+        }
+
+        self.drawing = false;
+        self.points.clone()
+    }
+
+    /// Returns the points produced since the last call.
+    pub fn drain_new(&mut self) -> &[Vec2] {
+        let start = self.emitted;
+        self.emitted = self.points.len();
+        &self.points[start..]
+    }
+
+    fn commit(&mut self, p: Vec2) {
+        self.controls.push(p);
+        let last = *self.points.last().unwrap();
+        if last.distance(p) >= self.min_distance {
+            self.points.push(p);
+        }
+    }
+
+    /// Adds a control point and emits the segment it completes.
+    fn add_control(&mut self, p: Vec2, subdivisions: u32) {
+        self.controls.push(p);
+        let n = self.controls.len();
+        if n >= 3 {
+            let p0 = self.controls[if n >= 4 { n - 4 } else { n - 3 }];
+            let p1 = self.controls[n - 3];
+            let p2 = self.controls[n - 2];
+            let p3 = self.controls[n - 1];
+            self.emit_spline(p0, p1, p2, p3, subdivisions);
+        }
+    }
+
+    fn push_point(&mut self, p: Vec2) {
+        if self.points.last() != Some(&p) {
+            self.points.push(p);
+        }
+    }
+
+    fn emit_spline(&mut self, p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2, subdivisions: u32) {
+        let subdivisions = subdivisions.max(1);
+        for i in 1..=subdivisions {
+            let t = i as f32 / subdivisions as f32;
+            self.push_point(catmull_rom(p0, p1, p2, p3, t));
+        }
+    }
+}
+
+/// Uniform Catmull-Rom interpolation between `p1` and `p2`.
+pub fn catmull_rom(p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2, t: f32) -> Vec2 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * ((2.0 * p1)
+        + (-p0 + p2) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (-p0 + 3.0 * p1 - 3.0 * p2 + p3) * t3)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec2;
+
+    use super::*;
+
+    #[test]
+    fn unsmoothed_strokes_drop_close_samples_but_end_at_the_release() {
+        let mut brush = BrushTool::new()
+            .with_smoothing(Smoothing::None)
+            .with_min_distance(2.0);
+        brush.begin(vec2(0.0, 0.0));
+        brush.drag(vec2(1.0, 0.0));
+        brush.drag(vec2(3.0, 0.0));
+        assert_eq!(brush.drain_new(), &[vec2(0.0, 0.0), vec2(3.0, 0.0)]);
+        brush.drag(vec2(4.0, 0.0));
+        assert_eq!(
+            brush.end(),
+            vec![vec2(0.0, 0.0), vec2(3.0, 0.0), vec2(4.0, 0.0)]
+        );
+        assert!(!brush.is_drawing());
+    }
+
+    #[test]
+    fn ema_strokes_lag_behind_and_end_at_the_release() {
+        let mut brush = BrushTool::new()
+            .with_smoothing(Smoothing::Ema { alpha: 0.5 })
+            .with_min_distance(0.0);
+        brush.begin(vec2(0.0, 0.0));
+        brush.drag(vec2(8.0, 0.0));
+        brush.drag(vec2(8.0, 0.0));
+        assert_eq!(
+            brush.end(),
+            vec![
+                vec2(0.0, 0.0),
+                vec2(4.0, 0.0),
+                vec2(6.0, 0.0),
+                vec2(8.0, 0.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn catmull_rom_strokes_pass_through_the_samples() {
+        let mut brush = BrushTool::new()
+            .with_smoothing(Smoothing::CatmullRom { subdivisions: 4 })
+            .with_min_distance(0.0);
+        brush.begin(vec2(0.0, 0.0));
+        brush.drag(vec2(10.0, 0.0));
+        // A segment is only drawn once the sample after it is known.
+        assert_eq!(brush.drain_new(), &[vec2(0.0, 0.0)]);
+        brush.drag(vec2(10.0, 10.0));
+        assert_eq!(brush.drain_new().last(), Some(&vec2(10.0, 0.0)));
+        let points = brush.end();
+        assert_eq!(points.len(), 9);
+        assert_eq!(points[4], vec2(10.0, 0.0));
+        assert_eq!(points.last(), Some(&vec2(10.0, 10.0)));
+    }
+
+    #[test]
+    fn catmull_rom_strokes_end_at_a_release_too_close_to_add() {
+        let mut brush = BrushTool::new()
+            .with_smoothing(Smoothing::CatmullRom { subdivisions: 4 })
+            .with_min_distance(5.0);
+        brush.begin(vec2(0.0, 0.0));
+        brush.drag(vec2(10.0, 0.0));
+        brush.drag(vec2(12.0, 0.0));
+        assert_eq!(brush.end().last(), Some(&vec2(12.0, 0.0)));
+    }
+}
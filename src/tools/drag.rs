@@ -0,0 +1,155 @@
+use glam::Vec2;
+
+/// How far in logical pixels the cursor has to move with the button down
+/// before a press counts as a drag.
+pub const DRAG_THRESHOLD: f32 = 4.0;
+
+/// A cursor position in logical window pixels and in the world.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DragPoint {
+    pub screen: Vec2,
+    pub world: Vec2,
+}
+
+impl DragPoint {
+    pub fn new(screen: Vec2, world: Vec2) -> Self {
+        Self { screen, world }
+    }
+}
+
+/// Where a [Drag] is in its gesture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DragPhase {
+    /// The button is down but the cursor hasn't gone past the threshold.
+    Pressed,
+    /// The cursor just went past the threshold.
+    Started,
+    Moved,
+    Released,
+}
+
+/// A press of the mouse and what the cursor has done since.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Drag {
+    pub phase: DragPhase,
+    pub start: DragPoint,
+    pub current: DragPoint,
+    /// Where the cursor was the last time the drag was updated.
+    pub previous: DragPoint,
+    /// Whether the cursor ever went past the threshold. Releases without it
+    /// are clicks.
+    pub dragged: bool,
+}
+
+impl Drag {
+    /// How far the cursor moved in logical pixels since the last update.
+    pub fn screen_delta(&self) -> Vec2 {
+        self.current.screen - self.previous.screen
+    }
+
+    /// How far the cursor moved in the world since the last update.
+    pub fn world_delta(&self) -> Vec2 {
+        self.current.world - self.previous.world
+    }
+
+    /// How far the cursor is from where the drag started in logical
+    /// pixels.
+    pub fn screen_offset(&self) -> Vec2 {
+        self.current.screen - self.start.screen
+    }
+
+    /// How far the cursor is from where the drag started in the world.
+    pub fn world_offset(&self) -> Vec2 {
+        self.current.world - self.start.world
+    }
+
+    pub fn is_click(&self) -> bool {
+        self.phase == DragPhase::Released && !self.dragged
+    }
+}
+
+/// Turns presses, cursor moves and releases into [Drag]s, so tools can
+/// share the bookkeeping. Small wobbles while clicking stay under the
+/// threshold and don't count as drags.
+#[derive(Debug, Clone)]
+pub struct DragTracker {
+    threshold: f32,
+    drag: Option<Drag>,
+}
+
+impl Default for DragTracker {
+    fn default() -> Self {
+        Self::new(DRAG_THRESHOLD)
+    }
+}
+
+impl DragTracker {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold: threshold.max(0.0),
+            drag: None,
+        }
+    }
+
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold.max(0.0);
+    }
+
+    /// The drag in progress, if the button is down.
+    pub fn drag(&self) -> Option<&Drag> {
+        self.drag.as_ref()
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// Starts a drag at `point`, dropping any that was in progress.
+    pub fn press(&mut self, point: DragPoint) -> Drag {
+        let drag = Drag {
+            phase: DragPhase::Pressed,
+            start: point,
+            current: point,
+            previous: point,
+            dragged: false,
+        };
+        self.drag = Some(drag);
+        drag
+    }
+
+    /// Moves the drag in progress to `point`. Returns `None` if the button
+    /// isn't down.
+    pub fn moved(&mut self, point: DragPoint) -> Option<Drag> {
+        let drag = self.drag.as_mut()?;
+        drag.previous = drag.current;
+        drag.current = point;
+        drag.phase = if drag.dragged {
+            DragPhase::Moved
+        } else if drag.start.screen.distance(point.screen) > self.threshold {
+            drag.dragged = true;
+            DragPhase::Started
+        } else {
+            DragPhase::Pressed
+        };
+        Some(*drag)
+    }
+
+    /// Finishes the drag in progress at `point`. Returns `None` if the
+    /// button wasn't down.
+    pub fn release(&mut self, point: DragPoint) -> Option<Drag> {
+        let mut drag = self.drag.take()?;
+        drag.previous = drag.current;
+        drag.current = point;
+        drag.phase = DragPhase::Released;
+        Some(drag)
+    }
+
+    /// Drops the drag in progress without releasing it.
+    pub fn cancel(&mut self) {
+        self.drag = None;
+    }
+}
@@ -0,0 +1,60 @@
+use glam::{UVec2, Vec4};
+
+use crate::{export::FrameCapture, svg::srgb_to_linear};
+
+/// What a picked color is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorTarget {
+    /// The color new strokes are drawn with.
+    Stroke,
+    /// The fill of the selected shape.
+    Fill,
+}
+
+/// Picks colors off the frame drawn to the window. The frame is read back
+/// from the GPU, so the color shows up a frame or so after the click.
+#[derive(Default)]
+pub struct EyedropperTool {
+    pending: Option<(FrameCapture, UVec2, ColorTarget)>,
+    color: Option<Vec4>,
+}
+
+impl EyedropperTool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Waits for `frame` and picks the color at `pixel`, in physical
+    /// pixels. Replaces any pick that hasn't finished yet.
+    pub fn begin(&mut self, frame: FrameCapture, pixel: UVec2, target: ColorTarget) {
+        self.pending = Some((frame, pixel, target));
+    }
+
+    pub fn is_picking(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Returns the picked color, in linear space, once the pixel has been
+    /// read back.
+    pub fn poll(&mut self) -> Option<anyhow::Result<(Vec4, ColorTarget)>> {
+        let (frame, pixel, target) = self.pending.as_mut()?;
+        let (pixel, target) = (*pixel, *target);
+        let result = frame.try_image()?;
+        self.pending = None;
+        Some(result.and_then(|image| {
+            let [r, g, b, a] = image
+                .get_pixel_checked(pixel.x, pixel.y)
+                .ok_or_else(|| anyhow::anyhow!("{pixel} is outside the frame"))?
+                .0;
+            let channel = |c: u8| srgb_to_linear(c as f32 / 255.0);
+            let color = Vec4::new(channel(r), channel(g), channel(b), a as f32 / 255.0);
+            self.color = Some(color);
+            Ok((color, target))
+        }))
+    }
+
+    /// The last color that was picked.
+    pub fn color(&self) -> Option<Vec4> {
+        self.color
+    }
+}
@@ -0,0 +1,75 @@
+use glam::{Vec2, Vec4};
+
+use crate::{
+    resources::{buffer::IndexedBatch, geometry::ColoredVertex},
+    shape::tessellate_polyline,
+};
+
+/// Draws straight lines by press, drag and release. While dragging, the
+/// line is only a preview and is drawn dashed in the overlay. Releasing
+/// hands back the finished segment.
+#[derive(Debug, Default)]
+pub struct LineTool {
+    start: Option<Vec2>,
+    end: Vec2,
+}
+
+impl LineTool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_drawing(&self) -> bool {
+        self.start.is_some()
+    }
+
+    pub fn start(&self) -> Option<Vec2> {
+        self.start
+    }
+
+    pub fn begin(&mut self, p: Vec2) {
+        self.start = Some(p);
+        self.end = p;
+    }
+
+    pub fn drag(&mut self, p: Vec2) {
+        if self.start.is_some() {
+            self.end = p;
+        }
+    }
+
+    /// Returns the line being dragged out, if any.
+    pub fn preview(&self) -> Option<[Vec2; 2]> {
+        Some([self.start?, self.end])
+    }
+
+    /// Finishes the line. Returns `None` if nothing was being drawn or the
+    /// line has no length.
+    pub fn end(&mut self) -> Option<[Vec2; 2]> {
+        let start = self.start.take()?;
+        (start.distance_squared(self.end) > f32::EPSILON).then_some([start, self.end])
+    }
+
+    /// Draws the preview as dashes `dash` world units long.
+    pub fn draw_preview(
+        &self,
+        color: Vec4,
+        dash: f32,
+        batch: &mut IndexedBatch<'_, ColoredVertex>,
+    ) {
+        let Some([a, b]) = self.preview() else {
+            return;
+        };
+        let length = a.distance(b);
+        if length <= f32::EPSILON || dash <= 0.0 {
+            return;
+        }
+        let dir = (b - a) / length;
+        let mut t = 0.0;
+        while t < length {
+            let end = (t + dash).min(length);
+            tessellate_polyline(&[a + dir * t, a + dir * end], false, color, batch);
+            t += dash * 2.0;
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+use crate::machine::StateMachine;
+
+pub mod brush;
+pub mod drag;
+pub mod eyedropper;
+pub mod line;
+pub mod select;
+
+/// The tool that left mouse input is routed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum ToolKind {
+    #[default]
+    Brush,
+    /// Picks colors off the drawing.
+    Eyedropper,
+    Line,
+    /// Drags out dimension lines.
+    Measure,
+    Select,
+}
+
+impl ToolKind {
+    pub const ALL: [Self; 5] = [
+        Self::Brush,
+        Self::Eyedropper,
+        Self::Line,
+        Self::Measure,
+        Self::Select,
+    ];
+}
+
+/// What the user is doing with the tools, tracked by [interaction_machine].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Interaction {
+    /// The tool is picked but the mouse isn't down.
+    Tool(ToolKind),
+    /// The mouse is down with the tool. A substate of [Interaction::Tool].
+    Pressing(ToolKind),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InteractionEvent {
+    SetTool(ToolKind),
+    Press,
+    Release,
+}
+
+/// Where the app looks for the [Interaction] machine, relative to the
+/// resource directory.
+pub const INTERACTION_MACHINE_PATH: &str = "machines/interaction.ron";
+
+/// A state machine that follows tool changes and mouse presses, starting
+/// with `tool`. This is what the app uses when
+/// [INTERACTION_MACHINE_PATH] can't be loaded.
+pub fn interaction_machine(tool: ToolKind) -> StateMachine<Interaction, InteractionEvent> {
+    let mut builder = StateMachine::builder(Interaction::Tool(tool));
+    for tool in ToolKind::ALL {
+        builder = builder
+            .substate(Interaction::Tool(tool), Interaction::Pressing(tool))
+            .transition(
+                Interaction::Tool(tool),
+                InteractionEvent::Press,
+                Interaction::Pressing(tool),
+            )
+            .transition(
+                Interaction::Pressing(tool),
+                InteractionEvent::Release,
+                Interaction::Tool(tool),
+            )
+            .from_any(InteractionEvent::SetTool(tool), Interaction::Tool(tool));
+    }
+    builder.build()
+}
@@ -0,0 +1,515 @@
+use glam::{Affine2, Vec2, Vec4};
+
+use crate::{
+    constraints,
+    history::{constrained_geometry, Command, Compound, SetGeometry, SetTransform},
+    layers::{Layer, LayerStack},
+    picking::{self, Region},
+    resources::{buffer::IndexedBatch, geometry::ColoredVertex},
+    scene::{NodeId, Scene, Transform2D},
+    shape::{tessellate_polyline, Bounds, Geometry, Shape},
+};
+
+/// Size of the square scale handles in world units.
+const HANDLE_SIZE: f32 = 8.0;
+/// How far above the selection the rotate handle sits.
+const ROTATE_OFFSET: f32 = 24.0;
+const HANDLE_COLOR: Vec4 = Vec4::new(0.2, 0.6, 1.0, 1.0);
+/// Minimum distance between lasso points in world units.
+const LASSO_SPACING: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    pub layer: usize,
+    pub node: NodeId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Handle {
+    Move,
+    /// Scale from the corner at this index of [Bounds::corners], anchored at
+    /// the opposite corner.
+    Scale(usize),
+    Rotate,
+    /// Move the point at this index of a line.
+    Endpoint(usize),
+}
+
+/// What dragging over empty space selects with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SelectMode {
+    /// A rectangle between the press and the cursor.
+    #[default]
+    Marquee,
+    /// A freeform outline following the cursor.
+    Lasso,
+}
+
+/// A region being dragged out over empty space.
+#[derive(Debug)]
+struct RegionDrag {
+    points: Vec<Vec2>,
+    /// Whether to add to the selection rather than replace it.
+    additive: bool,
+}
+
+#[derive(Debug)]
+struct Drag {
+    handle: Handle,
+    start: Vec2,
+    start_transform: Transform2D,
+    start_bounds: Bounds,
+    /// Geometry of the dragged node and every constrained line, for undo.
+    start_geometry: Vec<(NodeId, Geometry)>,
+}
+
+/// Click to select a shape, then drag the shape or its handles to move,
+/// scale or rotate it. Shift-click adds or removes shapes from the
+/// selection. Handles are shown for the most recently selected one.
+/// Dragging over empty space selects everything inside a marquee or lasso,
+/// adding to the selection if Shift is held.
+#[derive(Debug, Default)]
+pub struct SelectTool {
+    /// The last entry is the primary selection.
+    selected: Vec<Selection>,
+    drag: Option<Drag>,
+    mode: SelectMode,
+    region: Option<RegionDrag>,
+}
+
+impl SelectTool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The primary selection, which is the one that has handles.
+    pub fn selection(&self) -> Option<Selection> {
+        self.selected.last().copied()
+    }
+
+    /// Everything that's selected, ending with the primary selection.
+    pub fn selections(&self) -> &[Selection] {
+        &self.selected
+    }
+
+    pub fn select(&mut self, selection: Option<Selection>) {
+        self.select_all(selection);
+    }
+
+    /// Replaces the selection. The last item becomes the primary selection.
+    pub fn select_all(&mut self, selections: impl IntoIterator<Item = Selection>) {
+        self.selected.clear();
+        for selection in selections {
+            self.add(selection);
+        }
+        self.drag = None;
+    }
+
+    /// Adds to the selection, making `selection` the primary one.
+    fn add(&mut self, selection: Selection) {
+        self.selected.retain(|&s| s != selection);
+        self.selected.push(selection);
+    }
+
+    pub fn mode(&self) -> SelectMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: SelectMode) {
+        self.mode = mode;
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some() || self.region.is_some()
+    }
+
+    /// Where the current drag started, if there is one.
+    pub fn drag_origin(&self) -> Option<Vec2> {
+        self.drag.as_ref().map(|d| d.start)
+    }
+
+    /// Picks whatever is under the cursor. Picking a shape in a group
+    /// selects the whole group. With `toggle`, the picked node is added to
+    /// or removed from the selection instead of replacing it. Pressing on
+    /// empty space starts a marquee or lasso.
+    pub fn press(&mut self, layers: &mut LayerStack, cursor: Vec2, tolerance: f32, toggle: bool) {
+        self.drag = None;
+        self.region = None;
+
+        let picked = picking::pick(layers, cursor, tolerance).map(|hit| Selection {
+            layer: hit.layer,
+            node: layers
+                .get(hit.layer)
+                .map_or(hit.node, |layer| layer.scene().root_of(hit.node)),
+        });
+        if toggle {
+            match picked {
+                Some(picked) if self.selected.contains(&picked) => {
+                    self.selected.retain(|&s| s != picked);
+                }
+                Some(picked) => self.add(picked),
+                None => self.begin_region(cursor, true),
+            }
+            return;
+        }
+
+        // Handles of the current selection take priority over picking.
+        if let Some((selection, bounds)) = self.selected_bounds(layers) {
+            let endpoint = selected_line(layers, selection).and_then(|points| {
+                points
+                    .iter()
+                    .position(|&p| p.distance(cursor) <= HANDLE_SIZE)
+                    .map(Handle::Endpoint)
+            });
+            if let Some(handle) = endpoint.or_else(|| handle_at(&bounds, cursor)) {
+                self.begin_drag(layers, selection, handle, cursor, bounds);
+                return;
+            }
+        }
+
+        self.select(picked);
+        match self.selected_bounds(layers) {
+            Some((selection, bounds)) => {
+                self.begin_drag(layers, selection, Handle::Move, cursor, bounds)
+            }
+            None => self.begin_region(cursor, false),
+        }
+    }
+
+    fn begin_region(&mut self, cursor: Vec2, additive: bool) {
+        self.region = Some(RegionDrag {
+            points: vec![cursor],
+            additive,
+        });
+    }
+
+    /// The region being dragged out, if any.
+    pub fn region(&self) -> Option<Region> {
+        let region = self.region.as_ref()?;
+        Some(match self.mode {
+            SelectMode::Marquee => {
+                let start = region.points[0];
+                let end = *region.points.last().unwrap();
+                Region::Rect(Bounds::new(start, end))
+            }
+            SelectMode::Lasso => Region::Lasso(region.points.clone()),
+        })
+    }
+
+    /// Drags the selection or its region. Lines constrained to the dragged
+    /// node are kept in line as it moves.
+    pub fn drag(
+        &mut self,
+        layers: &mut LayerStack,
+        cursor: Vec2,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        if let Some(region) = &mut self.region {
+            match self.mode {
+                SelectMode::Marquee => region.points.truncate(1),
+                SelectMode::Lasso => {
+                    let last = *region.points.last().unwrap();
+                    if last.distance(cursor) < LASSO_SPACING {
+                        return;
+                    }
+                }
+            }
+            region.points.push(cursor);
+            return;
+        }
+
+        let (Some(selection), Some(drag)) = (self.selection(), &self.drag) else {
+            return;
+        };
+        let Some(layer) = layers.get_mut(selection.layer) else {
+            return;
+        };
+        let pinned = pinned_lines(layer, selection.node);
+        if let Handle::Endpoint(i) = drag.handle {
+            let world = layer.scene_mut().world_transform(selection.node);
+            let local = world.inverse().transform_point2(cursor);
+            let move_point = |shape: &mut Shape| {
+                if let Geometry::Polyline { points, .. } = &mut shape.geometry {
+                    if let Some(p) = points.get_mut(i) {
+                        *p = local;
+                    }
+                }
+            };
+            layer.update_shape(selection.node, move_point, device, queue);
+            layer.solve_constraints(&pinned, device, queue);
+            return;
+        }
+
+        let scene = layer.scene_mut();
+        let parent_world = parent_world(scene, selection.node);
+        let start = drag.start_transform;
+
+        let transform = match drag.handle {
+            Handle::Move => {
+                let delta = parent_world
+                    .inverse()
+                    .transform_vector2(cursor - drag.start);
+                Transform2D {
+                    translation: start.translation + delta,
+                    ..start
+                }
+            }
+            Handle::Scale(corner) => {
+                let corners = drag.start_bounds.corners();
+                let pivot = corners[(corner + 2) % 4];
+                let from = drag.start - pivot;
+                let to = cursor - pivot;
+                let factor = if start.rotation == 0.0 {
+                    Vec2::new(ratio(to.x, from.x), ratio(to.y, from.y))
+                } else {
+                    // Per axis scaling doesn't line up with the bounds once
+                    // the shape is rotated, so fall back to uniform scaling.
+                    Vec2::splat(ratio(to.length(), from.length()))
+                };
+                transform_about(
+                    start,
+                    parent_world,
+                    pivot,
+                    start.scale * factor,
+                    start.rotation,
+                )
+            }
+            Handle::Rotate => {
+                let center = drag.start_bounds.center();
+                let angle = (drag.start - center).angle_to(cursor - center);
+                transform_about(
+                    start,
+                    parent_world,
+                    center,
+                    start.scale,
+                    start.rotation + angle,
+                )
+            }
+            Handle::Endpoint(_) => return,
+        };
+
+        scene.set_transform(selection.node, transform);
+        layer.solve_constraints(&pinned, device, queue);
+    }
+
+    /// Ends the current drag, returning the change it made so it can be
+    /// recorded in the history. Releasing a marquee or lasso selects what's
+    /// inside it.
+    pub fn release(&mut self, layers: &mut LayerStack) -> Option<Compound> {
+        if let Some(region) = self.region() {
+            let additive = self.region.take().is_some_and(|r| r.additive);
+            let picked = picking::pick_region(layers, &region)
+                .into_iter()
+                .map(|(layer, node)| Selection { layer, node });
+            if additive {
+                for selection in picked {
+                    self.add(selection);
+                }
+            } else {
+                self.select_all(picked);
+            }
+            return None;
+        }
+
+        let drag = self.drag.take()?;
+        let selection = self.selection()?;
+        let layer = layers.get(selection.layer)?;
+        let after = *layer.scene().get(selection.node)?.transform();
+
+        let mut commands: Vec<Box<dyn Command>> = Vec::new();
+        if after != drag.start_transform {
+            commands.push(Box::new(SetTransform {
+                layer: selection.layer,
+                node: selection.node,
+                before: drag.start_transform,
+                after,
+            }));
+        }
+        for (node, before) in drag.start_geometry {
+            let Some(shape) = layer.scene().get(node).and_then(|n| n.shape()) else {
+                continue;
+            };
+            if shape.geometry != before {
+                commands.push(Box::new(SetGeometry {
+                    layer: selection.layer,
+                    node,
+                    before,
+                    after: shape.geometry.clone(),
+                }));
+            }
+        }
+
+        let name = match drag.handle {
+            Handle::Endpoint(_) => "Move point",
+            _ => "Transform",
+        };
+        let command = Compound::new(name, commands);
+        (!command.is_empty()).then_some(command)
+    }
+
+    /// Draws the bounds of the selection along with its handles.
+    pub fn draw_overlay(
+        &self,
+        layers: &mut LayerStack,
+        batch: &mut IndexedBatch<'_, ColoredVertex>,
+    ) {
+        match self.region() {
+            Some(Region::Rect(rect)) => {
+                tessellate_polyline(&rect.corners(), true, HANDLE_COLOR, batch)
+            }
+            Some(Region::Lasso(points)) => tessellate_polyline(&points, true, HANDLE_COLOR, batch),
+            None => {}
+        }
+
+        // Everything but the primary selection only gets an outline.
+        for selection in &self.selected[..self.selected.len().saturating_sub(1)] {
+            let bounds = layers
+                .get_mut(selection.layer)
+                .and_then(|layer| layer.scene_mut().world_bounds(selection.node));
+            if let Some(bounds) = bounds {
+                tessellate_polyline(&bounds.corners(), true, HANDLE_COLOR, batch);
+            }
+        }
+
+        let Some((selection, bounds)) = self.selected_bounds(layers) else {
+            return;
+        };
+        for point in selected_line(layers, selection).into_iter().flatten() {
+            let handle = Bounds::new(point, point).expand(HANDLE_SIZE * 0.5);
+            tessellate_polyline(&handle.corners(), true, HANDLE_COLOR, batch);
+        }
+
+        tessellate_polyline(&bounds.corners(), true, HANDLE_COLOR, batch);
+        for corner in bounds.corners() {
+            let handle = Bounds::new(corner, corner).expand(HANDLE_SIZE * 0.5);
+            tessellate_polyline(&handle.corners(), true, HANDLE_COLOR, batch);
+        }
+
+        let top = Vec2::new(bounds.center().x, bounds.min.y);
+        let rotate = rotate_handle_position(&bounds);
+        tessellate_polyline(&[top, rotate], false, HANDLE_COLOR, batch);
+        let handle = Bounds::new(rotate, rotate).expand(HANDLE_SIZE * 0.5);
+        tessellate_polyline(&handle.corners(), true, HANDLE_COLOR, batch);
+    }
+
+    fn selected_bounds(&self, layers: &mut LayerStack) -> Option<(Selection, Bounds)> {
+        let selection = self.selection()?;
+        let layer = layers.get_mut(selection.layer)?;
+        let bounds = layer.scene_mut().world_bounds(selection.node)?;
+        Some((selection, bounds))
+    }
+
+    fn begin_drag(
+        &mut self,
+        layers: &mut LayerStack,
+        selection: Selection,
+        handle: Handle,
+        cursor: Vec2,
+        bounds: Bounds,
+    ) {
+        let Some(layer) = layers.get(selection.layer) else {
+            return;
+        };
+        if !layer.is_editable() {
+            return;
+        }
+        let Some(node) = layer.scene().get(selection.node) else {
+            return;
+        };
+        let mut start_geometry = constrained_geometry(layer);
+        if let Some(shape) = node.shape() {
+            if !start_geometry.iter().any(|(id, _)| *id == selection.node) {
+                start_geometry.push((selection.node, shape.geometry.clone()));
+            }
+        }
+        self.drag = Some(Drag {
+            handle,
+            start: cursor,
+            start_transform: *node.transform(),
+            start_bounds: bounds,
+            start_geometry,
+        });
+    }
+}
+
+/// World space endpoints of the selection if it's a line.
+fn selected_line(layers: &mut LayerStack, selection: Selection) -> Option<[Vec2; 2]> {
+    let scene = layers.get_mut(selection.layer)?.scene_mut();
+    let (world, points) = constraints::line(scene, selection.node)?;
+    Some(points.map(|p| world.transform_point2(p)))
+}
+
+/// Constrained lines that move along with `id`, which stay put while the
+/// constraints are solved.
+fn pinned_lines(layer: &Layer, id: NodeId) -> Vec<NodeId> {
+    layer
+        .constraints()
+        .iter()
+        .flat_map(|c| [c.a, c.b])
+        .filter(|&line| layer.scene().is_ancestor_of(id, line))
+        .collect()
+}
+
+fn rotate_handle_position(bounds: &Bounds) -> Vec2 {
+    Vec2::new(bounds.center().x, bounds.min.y - ROTATE_OFFSET)
+}
+
+fn handle_at(bounds: &Bounds, cursor: Vec2) -> Option<Handle> {
+    let hit = |p: Vec2| Bounds::new(p, p).expand(HANDLE_SIZE).contains(cursor);
+
+    if hit(rotate_handle_position(bounds)) {
+        return Some(Handle::Rotate);
+    }
+    if let Some(i) = bounds.corners().into_iter().position(hit) {
+        return Some(Handle::Scale(i));
+    }
+    if bounds.contains(cursor) {
+        return Some(Handle::Move);
+    }
+    None
+}
+
+fn parent_world(scene: &mut Scene, id: NodeId) -> Affine2 {
+    match scene.get(id).and_then(|n| n.parent()) {
+        Some(parent) => scene.world_transform(parent),
+        None => Affine2::IDENTITY,
+    }
+}
+
+fn ratio(to: f32, from: f32) -> f32 {
+    if from.abs() < f32::EPSILON {
+        1.0
+    } else {
+        let r = to / from;
+        // Keep the shape from collapsing, which would make it impossible to
+        // grab again.
+        if r.abs() < 0.01 {
+            0.01f32.copysign(r)
+        } else {
+            r
+        }
+    }
+}
+
+/// Builds a transform with the new scale and rotation, translated so that
+/// `anchor` (in world space) stays where it was under `start`.
+fn transform_about(
+    start: Transform2D,
+    parent_world: Affine2,
+    anchor: Vec2,
+    scale: Vec2,
+    rotation: f32,
+) -> Transform2D {
+    let anchor_parent = parent_world.inverse().transform_point2(anchor);
+    let anchor_local = start.to_affine().inverse().transform_point2(anchor_parent);
+    let linear = Transform2D {
+        translation: Vec2::ZERO,
+        rotation,
+        scale,
+    };
+    Transform2D {
+        translation: anchor_parent - linear.to_affine().transform_point2(anchor_local),
+        ..linear
+    }
+}
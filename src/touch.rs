@@ -0,0 +1,140 @@
+use glam::Vec2;
+use winit::event::TouchPhase;
+
+/// What a [TouchTracker] makes of a touch event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TouchGesture {
+    /// The first finger went down, like pressing the mouse there.
+    Press(Vec2),
+    Move(Vec2),
+    /// The finger that was acting as the mouse went up, or a second finger
+    /// came down and turned the touch into a gesture.
+    Release(Vec2),
+    /// Two fingers moved. The point that was under `from` should end up
+    /// under `to`, with the view zoomed in by `scale`.
+    PanZoom {
+        from: Vec2,
+        to: Vec2,
+        scale: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Idle,
+    /// One finger is acting as the mouse.
+    Pointer(u64),
+    /// The first two fingers down pan and zoom.
+    Gesture,
+    /// A gesture lost a finger. The rest are ignored until they're all
+    /// lifted, so the one left behind doesn't start drawing.
+    Ignored,
+}
+
+/// Follows fingers by their touch ids. One finger acts as the mouse, and
+/// two fingers pan and pinch zoom the view. Positions are in logical window
+/// pixels.
+#[derive(Debug, Clone)]
+pub struct TouchTracker {
+    /// Fingers that are down in the order they came down.
+    touches: Vec<(u64, Vec2)>,
+    mode: Mode,
+}
+
+impl Default for TouchTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TouchTracker {
+    pub fn new() -> Self {
+        Self {
+            touches: Vec::new(),
+            mode: Mode::Idle,
+        }
+    }
+
+    /// How many fingers are down.
+    pub fn len(&self) -> usize {
+        self.touches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.touches.is_empty()
+    }
+
+    pub fn handle(&mut self, id: u64, phase: TouchPhase, position: Vec2) -> Option<TouchGesture> {
+        match phase {
+            TouchPhase::Started => {
+                self.touches.retain(|&(touch, _)| touch != id);
+                self.touches.push((id, position));
+                match self.mode {
+                    Mode::Idle => {
+                        self.mode = Mode::Pointer(id);
+                        Some(TouchGesture::Press(position))
+                    }
+                    Mode::Pointer(pointer) => {
+                        self.mode = Mode::Gesture;
+                        let (_, at) = self.touches.iter().find(|&&(t, _)| t == pointer)?;
+                        Some(TouchGesture::Release(*at))
+                    }
+                    Mode::Gesture | Mode::Ignored => {
+                        self.mode = Mode::Gesture;
+                        None
+                    }
+                }
+            }
+            TouchPhase::Moved => {
+                let before = self.pair();
+                let (_, at) = self.touches.iter_mut().find(|(t, _)| *t == id)?;
+                *at = position;
+                match self.mode {
+                    Mode::Pointer(pointer) if pointer == id => Some(TouchGesture::Move(position)),
+                    Mode::Gesture => {
+                        let ((a0, b0), (a1, b1)) = (before?, self.pair()?);
+                        let from_distance = a0.distance(b0);
+                        let scale = if from_distance > f32::EPSILON {
+                            a1.distance(b1) / from_distance
+                        } else {
+                            1.0
+                        };
+                        Some(TouchGesture::PanZoom {
+                            from: (a0 + b0) * 0.5,
+                            to: (a1 + b1) * 0.5,
+                            scale,
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                let index = self.touches.iter().position(|&(t, _)| t == id)?;
+                self.touches.remove(index);
+                let gesture = match self.mode {
+                    Mode::Pointer(pointer) if pointer == id => {
+                        self.mode = Mode::Idle;
+                        Some(TouchGesture::Release(position))
+                    }
+                    Mode::Gesture if self.touches.len() < 2 => {
+                        self.mode = Mode::Ignored;
+                        None
+                    }
+                    _ => None,
+                };
+                if self.touches.is_empty() {
+                    self.mode = Mode::Idle;
+                }
+                gesture
+            }
+        }
+    }
+
+    /// Where the first two fingers are.
+    fn pair(&self) -> Option<(Vec2, Vec2)> {
+        match self.touches.as_slice() {
+            [(_, a), (_, b), ..] => Some((*a, *b)),
+            _ => None,
+        }
+    }
+}
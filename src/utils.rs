@@ -15,6 +15,12 @@ pub struct RenderPipelineBuilder<'a> {
     cache: Option<&'a wgpu::PipelineCache>,
 }
 
+impl Default for RenderPipelineBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<'a> RenderPipelineBuilder<'a> {
     pub fn new() -> Self {
         Self {
@@ -74,6 +80,12 @@ impl<'a> RenderPipelineBuilder<'a> {
         self
     }
 
+    /// Samples per pixel of the targets the pipeline draws into.
+    pub fn sample_count(mut self, count: u32) -> Self {
+        self.multisample.count = count;
+        self
+    }
+
     #[allow(unused)]
     pub fn topology(mut self, value: wgpu::PrimitiveTopology) -> Self {
         self.primitive.topology = value;
@@ -96,3 +108,136 @@ impl<'a> RenderPipelineBuilder<'a> {
         )
     }
 }
+
+/// `Send` on native, where [spawn] runs futures on another thread, and
+/// nothing on the web, where everything runs on the main thread.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSend: Send {}
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send> MaybeSend for T {}
+
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSend {}
+#[cfg(target_arch = "wasm32")]
+impl<T> MaybeSend for T {}
+
+/// Runs `future` in the background. On native it gets a thread of its own,
+/// and on the web it's handed to the browser's event loop.
+pub fn spawn(future: impl std::future::Future<Output = ()> + MaybeSend + 'static) {
+    #[cfg(not(target_arch = "wasm32"))]
+    std::thread::spawn(move || pollster::block_on(future));
+    #[cfg(target_arch = "wasm32")]
+    wasm_bindgen_futures::spawn_local(future);
+}
+
+/// Where the platform keeps apps' data for the current user, like
+/// `~/.local/share` on Linux. Apps have to add a directory of their own.
+/// `None` on Android, where only the activity knows its data directory,
+/// and wherever the environment doesn't say.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn data_dir() -> Option<std::path::PathBuf> {
+    use std::{env, path::PathBuf};
+    let home = || {
+        env::var_os("HOME")
+            .filter(|home| !home.is_empty())
+            .map(PathBuf::from)
+    };
+    if cfg!(target_os = "android") {
+        None
+    } else if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(any(target_os = "macos", target_os = "ios")) {
+        Some(home()?.join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| Some(home()?.join(".local/share")))
+    }
+}
+
+/// Runs `f`, returning the validation errors it causes instead of letting
+/// wgpu panic over them. For rebuilding things from sources that may be
+/// broken, like shaders that were just edited.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn validated<T>(device: &wgpu::Device, f: impl FnOnce() -> T) -> anyhow::Result<T> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = f();
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(error) => Err(anyhow::anyhow!("{error}")),
+        None => Ok(value),
+    }
+}
+
+/// A device on any adapter, software ones included, for tests that need
+/// one. Panics if there's no adapter, so those tests are `#[ignore]`d and
+/// run with `cargo test -- --ignored` where there is.
+#[cfg(test)]
+pub(crate) fn test_device() -> (wgpu::Device, wgpu::Queue) {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    let adapter = pollster::block_on(instance.request_adapter(&Default::default()))
+        .expect("No adapter to test with");
+    let descriptor = wgpu::DeviceDescriptor {
+        required_limits: adapter.limits(),
+        ..Default::default()
+    };
+    pollster::block_on(adapter.request_device(&descriptor, None)).expect("No device to test with")
+}
+
+/// Whether `device` can read storage buffers from shaders. WebGL2 can't,
+/// so arrays that would be in one go in a fixed size uniform buffer.
+pub fn has_storage_buffers(device: &wgpu::Device) -> bool {
+    device.limits().max_storage_buffers_per_shader_stage > 0
+}
+
+/// How to bind a read only array of the shader made with [array_shader].
+pub fn array_binding_type(device: &wgpu::Device) -> wgpu::BufferBindingType {
+    if has_storage_buffers(device) {
+        wgpu::BufferBindingType::Storage { read_only: true }
+    } else {
+        wgpu::BufferBindingType::Uniform
+    }
+}
+
+/// What a buffer bound with [array_binding_type] needs to be usable for.
+pub fn array_buffer_usage(device: &wgpu::Device) -> wgpu::BufferUsages {
+    if has_storage_buffers(device) {
+        wgpu::BufferUsages::STORAGE
+    } else {
+        wgpu::BufferUsages::UNIFORM
+    }
+}
+
+/// Compiles WGSL that reads arrays from storage buffers. Without storage
+/// buffers each `var<storage, read> name: array<T>;` named in `arrays` is
+/// declared as a uniform `array<T, len>` instead, so buffers bound to it
+/// need to hold at least `len` elements, and elements need a stride that's
+/// a multiple of 16 bytes.
+pub fn array_shader(
+    device: &wgpu::Device,
+    label: &str,
+    source: &str,
+    arrays: &[(&str, usize)],
+) -> wgpu::ShaderModule {
+    let source = if has_storage_buffers(device) {
+        source.to_owned()
+    } else {
+        source
+            .lines()
+            .map(|line| uniform_array(line, arrays).unwrap_or_else(|| line.to_owned()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    })
+}
+
+fn uniform_array(line: &str, arrays: &[(&str, usize)]) -> Option<String> {
+    let declaration = line.trim().strip_prefix("var<storage, read> ")?;
+    let (name, ty) = declaration.split_once(": ")?;
+    let len = arrays.iter().find(|(n, _)| *n == name)?.1;
+    let element = ty.strip_prefix("array<")?.strip_suffix(">;")?;
+    Some(format!("var<uniform> {name}: array<{element}, {len}>;"))
+}
@@ -0,0 +1,179 @@
+use glam::Vec2;
+
+use crate::{
+    resources::camera::{CameraBinding, OrthoCamera},
+    stats,
+    utils::RenderPipelineBuilder,
+};
+
+/// Part of the window in logical pixels, measured from its top left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewRect {
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+impl ViewRect {
+    pub fn new(position: Vec2, size: Vec2) -> Self {
+        Self { position, size }
+    }
+
+    pub fn contains(&self, screen: Vec2) -> bool {
+        let p = screen - self.position;
+        p.cmpge(Vec2::ZERO).all() && p.cmplt(self.size).all()
+    }
+}
+
+/// An extra view of the drawing with its own camera, drawn over a part of
+/// the window on top of the main view.
+pub struct Viewport {
+    rect: ViewRect,
+    camera: OrthoCamera,
+    binding: CameraBinding,
+}
+
+impl Viewport {
+    /// Creates a viewport that draws with `binding`, which doesn't need to
+    /// hold any particular camera yet.
+    pub fn new(rect: ViewRect, mut binding: CameraBinding, queue: &wgpu::Queue) -> Self {
+        let camera = OrthoCamera::new(0.0, rect.size.x, rect.size.y, 0.0);
+        binding.update(&camera, queue);
+        Self {
+            rect,
+            camera,
+            binding,
+        }
+    }
+
+    pub fn rect(&self) -> ViewRect {
+        self.rect
+    }
+
+    pub fn set_rect(&mut self, rect: ViewRect, queue: &wgpu::Queue) {
+        self.rect = rect;
+        self.camera.resize(rect.size.x, rect.size.y);
+        self.binding.update(&self.camera, queue);
+    }
+
+    pub fn camera(&self) -> &OrthoCamera {
+        &self.camera
+    }
+
+    /// Changes the camera and uploads it.
+    pub fn update_camera(&mut self, queue: &wgpu::Queue, f: impl FnOnce(&mut OrthoCamera)) {
+        f(&mut self.camera);
+        self.binding.update(&self.camera, queue);
+    }
+
+    pub fn binding(&self) -> &CameraBinding {
+        &self.binding
+    }
+
+    /// Converts a position in logical window pixels to world space.
+    pub fn screen_to_world(&self, screen: Vec2) -> Vec2 {
+        self.camera.screen_to_world(screen - self.rect.position)
+    }
+
+    /// Limits drawing to the viewport. `surface_size` is in physical pixels.
+    /// Returns `false` if the viewport doesn't fit on the surface, in which
+    /// case it shouldn't be drawn.
+    pub fn begin(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        scale_factor: f32,
+        surface_size: glam::UVec2,
+    ) -> bool {
+        let min = (self.rect.position * scale_factor).round();
+        let max = ((self.rect.position + self.rect.size) * scale_factor).round();
+        if min.cmplt(Vec2::ZERO).any()
+            || max.cmpgt(surface_size.as_vec2()).any()
+            || min.cmpge(max).any()
+        {
+            return false;
+        }
+        let size = max - min;
+        pass.set_viewport(min.x, min.y, size.x, size.y, 0.0, 1.0);
+        let (min, size) = (min.as_uvec2(), size.as_uvec2());
+        pass.set_scissor_rect(min.x, min.y, size.x, size.y);
+        true
+    }
+}
+
+/// Clears the area of a [Viewport] and draws a border around it.
+// The format and sample count are only kept for reloading the pipelines,
+// which wasm can't do.
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+pub struct ViewportFrame {
+    surface_format: wgpu::TextureFormat,
+    sample_count: u32,
+    background: wgpu::RenderPipeline,
+    border: wgpu::RenderPipeline,
+}
+
+impl ViewportFrame {
+    pub fn new(
+        shader: &wgpu::ShaderModule,
+        surface_format: wgpu::TextureFormat,
+        sample_count: u32,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<Self> {
+        let pipeline = |label, entry_point, blend| {
+            RenderPipelineBuilder::new()
+                .label(label)
+                .vertex(wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some("fullscreen_quad"),
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                })
+                .fragment(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some(entry_point),
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: surface_format,
+                        blend,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                })
+                .sample_count(sample_count)
+                .build(device)
+        };
+        Ok(Self {
+            surface_format,
+            sample_count,
+            background: pipeline("ViewportFrame::background", "viewport_background", None)?,
+            border: pipeline(
+                "ViewportFrame::border",
+                "viewport_border",
+                Some(wgpu::BlendState::ALPHA_BLENDING),
+            )?,
+        })
+    }
+
+    /// Rebuilds the pipelines from an edited `shader`. Keeps the old ones
+    /// if that fails.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload(
+        &mut self,
+        shader: &wgpu::ShaderModule,
+        device: &wgpu::Device,
+    ) -> anyhow::Result<()> {
+        *self = crate::utils::validated(device, || {
+            Self::new(shader, self.surface_format, self.sample_count, device)
+        })??;
+        Ok(())
+    }
+
+    /// Clears the viewport. Call before drawing into it.
+    pub fn draw_background(&self, pass: &mut wgpu::RenderPass<'_>) {
+        pass.set_pipeline(&self.background);
+        stats::draw(pass, 0..3, 0..1);
+    }
+
+    /// Outlines the viewport. Call after drawing into it.
+    pub fn draw_border(&self, pass: &mut wgpu::RenderPass<'_>) {
+        pass.set_pipeline(&self.border);
+        stats::draw(pass, 0..3, 0..1);
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::HashSet;
+
+/// Snippets the built in shaders share, by the name they're included with.
+pub const SNIPPETS: &[(&str, &str)] = &[
+    ("camera.wgsl", include_str!("shaders/camera.wgsl")),
+    ("distance.wgsl", include_str!("shaders/distance.wgsl")),
+    ("fullscreen.wgsl", include_str!("shaders/fullscreen.wgsl")),
+    ("msdf.wgsl", include_str!("shaders/msdf.wgsl")),
+    ("transform.wgsl", include_str!("shaders/transform.wgsl")),
+];
+
+/// The built in snippet called `name`.
+pub fn snippet(name: &str) -> Option<String> {
+    SNIPPETS
+        .iter()
+        .find(|(snippet, _)| *snippet == name)
+        .map(|(_, source)| source.to_string())
+}
+
+/// Replaces each `#include "name"` line in `source` with what `snippets`
+/// returns for `name`, which can include more. Each snippet is only
+/// included the first time, so snippets can include what they use without
+/// it being declared twice.
+pub fn compose(source: &str, snippets: impl Fn(&str) -> Option<String>) -> anyhow::Result<String> {
+    let mut composed = String::with_capacity(source.len());
+    compose_into(&mut composed, source, &snippets, &mut HashSet::new())?;
+    Ok(composed)
+}
+
+/// Composes `source` from the built in [SNIPPETS].
+pub fn compose_built_in(source: &str) -> anyhow::Result<String> {
+    compose(source, snippet)
+}
+
+fn compose_into(
+    composed: &mut String,
+    source: &str,
+    snippets: &dyn Fn(&str) -> Option<String>,
+    included: &mut HashSet<String>,
+) -> anyhow::Result<()> {
+    for line in source.lines() {
+        let Some(name) = include_name(line) else {
+            composed.push_str(line);
+            composed.push('\n');
+            continue;
+        };
+        if !included.insert(name.to_owned()) {
+            continue;
+        }
+        let snippet =
+            snippets(name).ok_or_else(|| anyhow::anyhow!("No shader snippet named {name:?}"))?;
+        compose_into(composed, &snippet, snippets, included)?;
+    }
+    Ok(())
+}
+
+/// The names `source` includes directly, in order.
+pub fn includes(source: &str) -> impl Iterator<Item = &str> {
+    source.lines().filter_map(include_name)
+}
+
+fn include_name(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix("#include")?
+        .trim()
+        .strip_prefix('"')?
+        .strip_suffix('"')
+}
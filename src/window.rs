@@ -0,0 +1,192 @@
+#[cfg(target_arch = "wasm32")]
+use std::sync::Arc;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::{closure::Closure, JsCast};
+use winit::{dpi::LogicalSize, window::Window};
+#[cfg(target_arch = "wasm32")]
+use winit::{dpi::PhysicalSize, platform::web::WindowExtWebSys};
+
+/// The id of the `<canvas>` the app draws into on the web, unless
+/// [WindowConfig::canvas_id] says otherwise.
+pub const CANVAS_ID: &str = "canvas";
+
+/// How the window is created. Sizes are in logical pixels.
+#[derive(Debug, Clone)]
+pub struct WindowConfig {
+    pub title: String,
+    /// Left to the platform if `None`.
+    pub size: Option<LogicalSize<f64>>,
+    pub min_size: Option<LogicalSize<f64>>,
+    pub resizable: bool,
+    pub decorations: bool,
+    /// Lets whatever's behind the window show through where nothing's drawn.
+    pub transparent: bool,
+    pub maximized: bool,
+    /// The `<canvas>` to draw into on the web. Ignored elsewhere.
+    pub canvas_id: String,
+    /// Which GPU to prefer. Low power by default, as drawing in 2D doesn't
+    /// need a discrete GPU.
+    pub power_preference: wgpu::PowerPreference,
+    /// Picks the first adapter whose name contains this, ignoring case,
+    /// over [WindowConfig::power_preference]. Ignored on the web.
+    pub adapter_name: Option<String>,
+    /// Draws into an HDR surface where the window has one. See
+    /// [select_format](crate::gpu::select_format).
+    pub hdr: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "State Machine".into(),
+            size: None,
+            min_size: None,
+            resizable: true,
+            decorations: true,
+            transparent: false,
+            maximized: false,
+            canvas_id: CANVAS_ID.into(),
+            power_preference: wgpu::PowerPreference::LowPower,
+            adapter_name: None,
+            hdr: false,
+        }
+    }
+}
+
+impl WindowConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn size(mut self, width: f64, height: f64) -> Self {
+        self.size = Some(LogicalSize::new(width, height));
+        self
+    }
+
+    pub fn min_size(mut self, width: f64, height: f64) -> Self {
+        self.min_size = Some(LogicalSize::new(width, height));
+        self
+    }
+
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn decorations(mut self, decorations: bool) -> Self {
+        self.decorations = decorations;
+        self
+    }
+
+    pub fn transparent(mut self, transparent: bool) -> Self {
+        self.transparent = transparent;
+        self
+    }
+
+    pub fn maximized(mut self, maximized: bool) -> Self {
+        self.maximized = maximized;
+        self
+    }
+
+    pub fn canvas_id(mut self, canvas_id: impl Into<String>) -> Self {
+        self.canvas_id = canvas_id.into();
+        self
+    }
+
+    pub fn power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    pub fn adapter_name(mut self, name: impl Into<String>) -> Self {
+        self.adapter_name = Some(name.into());
+        self
+    }
+
+    pub fn hdr(mut self, hdr: bool) -> Self {
+        self.hdr = hdr;
+        self
+    }
+
+    /// The attributes to create the window with. Fails on the web if there's
+    /// no `<canvas>` with the configured id.
+    pub fn attributes(&self) -> anyhow::Result<winit::window::WindowAttributes> {
+        let mut attributes = Window::default_attributes()
+            .with_title(&self.title)
+            .with_resizable(self.resizable)
+            .with_decorations(self.decorations)
+            .with_transparent(self.transparent)
+            .with_maximized(self.maximized);
+        if let Some(size) = self.size {
+            attributes = attributes.with_inner_size(size);
+        }
+        if let Some(min_size) = self.min_size {
+            attributes = attributes.with_min_inner_size(min_size);
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            use winit::platform::web::WindowAttributesExtWebSys;
+
+            let canvas = wgpu::web_sys::window()
+                .and_then(|window| window.document())
+                .and_then(|document| document.get_element_by_id(&self.canvas_id))
+                .ok_or_else(|| anyhow::anyhow!("No canvas with the id {:?}", self.canvas_id))?;
+            attributes = attributes.with_canvas(Some(canvas.unchecked_into()));
+        }
+
+        Ok(attributes)
+    }
+}
+
+/// Redraws the window whenever CSS resizes its `<canvas>`, so the surface
+/// can follow it with [canvas_size]. Stops watching when dropped.
+#[cfg(target_arch = "wasm32")]
+pub struct CanvasObserver {
+    observer: web_sys::ResizeObserver,
+    _callback: Closure<dyn FnMut(js_sys::Array)>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl CanvasObserver {
+    /// Returns `None` if the browser can't observe the canvas.
+    pub fn new(window: Arc<Window>) -> Option<Self> {
+        let canvas = window.canvas()?;
+        let callback = Closure::<dyn FnMut(js_sys::Array)>::new(move |_| window.request_redraw());
+        let observer = web_sys::ResizeObserver::new(callback.as_ref().unchecked_ref()).ok()?;
+        observer.observe(&canvas);
+        Some(Self {
+            observer,
+            _callback: callback,
+        })
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Drop for CanvasObserver {
+    fn drop(&mut self) {
+        self.observer.disconnect();
+    }
+}
+
+/// The size in physical pixels that `window`'s `<canvas>` is laid out at,
+/// and the page's device pixel ratio.
+#[cfg(target_arch = "wasm32")]
+pub fn canvas_size(window: &Window) -> Option<(PhysicalSize<u32>, f64)> {
+    let canvas = window.canvas()?;
+    let scale_factor = web_sys::window()?.device_pixel_ratio();
+    let physical = |css: i32| (css as f64 * scale_factor).round() as u32;
+    Some((
+        PhysicalSize::new(
+            physical(canvas.client_width()),
+            physical(canvas.client_height()),
+        ),
+        scale_factor,
+    ))
+}